@@ -0,0 +1,258 @@
+//! Adapter selection at initialization.
+//! `AdapterPolicy` filters adapters by required features and memory and scores the rest,
+//! so discrete GPUs are preferred over integrated and software ones unless a scoring callback says otherwise.
+//! `AdapterReport` tells why each adapter was chosen or rejected.
+//!
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use hal::{Backend, Features, Limits, MemoryProperties};
+use hal::adapter::{Adapter, AdapterInfo, PhysicalDevice};
+use hal::memory::Properties;
+
+/// Kind of the adapter.
+/// gfx-hal doesn't report device type, so it is deduced from memory topology.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AdapterKind {
+    /// Device has local memory that host can't access directly.
+    Discrete,
+
+    /// All device-local memory is also host-visible.
+    Integrated,
+
+    /// Software implementation or device without device-local memory.
+    Software,
+}
+
+/// Adapter considered for selection.
+#[derive(Clone, Debug)]
+pub struct AdapterCandidate {
+    /// Index of the adapter in order of enumeration.
+    pub index: usize,
+
+    /// Information reported by the backend.
+    pub info: AdapterInfo,
+
+    /// Deduced kind of the adapter.
+    pub kind: AdapterKind,
+
+    /// Features of the adapter.
+    pub features: Features,
+
+    /// Limits of the adapter.
+    pub limits: Limits,
+
+    /// Total size of memory heaps of device-local memory types in bytes.
+    pub device_local_memory: u64,
+}
+
+impl AdapterCandidate {
+    /// Describe the adapter.
+    pub fn new<B>(index: usize, adapter: &Adapter<B>) -> Self
+    where
+        B: Backend,
+    {
+        let properties = adapter.physical_device.memory_properties();
+        let kind = if adapter.info.software_rendering {
+            AdapterKind::Software
+        } else {
+            memory_kind(&properties)
+        };
+        AdapterCandidate {
+            index,
+            info: adapter.info.clone(),
+            kind,
+            features: adapter.physical_device.features(),
+            limits: adapter.physical_device.limits(),
+            device_local_memory: device_local_memory(&properties),
+        }
+    }
+}
+
+/// Score the adapter higher than others if it is discrete, then by amount of device-local memory.
+pub fn default_adapter_score(candidate: &AdapterCandidate) -> Result<u64, String> {
+    let kind = match candidate.kind {
+        AdapterKind::Discrete => 2,
+        AdapterKind::Integrated => 1,
+        AdapterKind::Software => 0,
+    };
+    Ok((kind << 48) | (candidate.device_local_memory >> 20).min((1 << 48) - 1))
+}
+
+/// Callback scoring adapters. Adapter with the highest score is chosen.
+/// Error rejects the adapter with the reason.
+pub type AdapterScorer = Arc<Fn(&AdapterCandidate) -> Result<u64, String> + Send + Sync>;
+
+/// Policy of picking adapter at initialization.
+#[derive(Clone)]
+pub struct AdapterPolicy {
+    /// Features adapter must support.
+    pub required_features: Features,
+
+    /// Minimal total size of device-local memory in bytes.
+    pub min_device_local_memory: u64,
+
+    /// Callback scoring adapters that satisfy requirements above.
+    /// `default_adapter_score` is used if `None`.
+    pub scorer: Option<AdapterScorer>,
+}
+
+impl AdapterPolicy {
+    /// Pick adapter with `default_adapter_score` without requirements.
+    pub fn new() -> Self {
+        AdapterPolicy {
+            required_features: Features::empty(),
+            min_device_local_memory: 0,
+            scorer: None,
+        }
+    }
+
+    /// Score adapters with the callback.
+    pub fn with_scorer<F>(mut self, scorer: F) -> Self
+    where
+        F: Fn(&AdapterCandidate) -> Result<u64, String> + Send + Sync + 'static,
+    {
+        self.scorer = Some(Arc::new(scorer));
+        self
+    }
+
+    /// Score the adapter or tell why it is rejected.
+    pub fn score(&self, candidate: &AdapterCandidate) -> Result<u64, String> {
+        let missing = self.required_features - candidate.features;
+        if !missing.is_empty() {
+            return Err(format!("missing features {:?}", missing));
+        }
+        if candidate.device_local_memory < self.min_device_local_memory {
+            return Err(format!(
+                "{} bytes of device-local memory is less than required {}",
+                candidate.device_local_memory, self.min_device_local_memory
+            ));
+        }
+        match self.scorer {
+            Some(ref scorer) => scorer(candidate),
+            None => default_adapter_score(candidate),
+        }
+    }
+
+    /// Score all adapters and pick the one with the highest score.
+    /// Adapter enumerated first wins among equally scored ones.
+    pub fn select<B>(&self, adapters: &[Adapter<B>]) -> AdapterReport
+    where
+        B: Backend,
+    {
+        let verdicts = adapters
+            .iter()
+            .enumerate()
+            .map(|(index, adapter)| {
+                let candidate = AdapterCandidate::new(index, adapter);
+                let score = self.score(&candidate);
+                (candidate, score)
+            })
+            .collect::<Vec<_>>();
+        let chosen = verdicts
+            .iter()
+            .filter_map(|&(ref candidate, ref score)| score.as_ref().ok().map(|&score| (score, candidate.index)))
+            .fold(None, |best: Option<(u64, usize)>, (score, index)| match best {
+                Some((best_score, _)) if best_score >= score => best,
+                _ => Some((score, index)),
+            })
+            .map(|(_, index)| index);
+        AdapterReport { verdicts, chosen }
+    }
+}
+
+impl Default for AdapterPolicy {
+    fn default() -> Self {
+        AdapterPolicy::new()
+    }
+}
+
+impl fmt::Debug for AdapterPolicy {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AdapterPolicy")
+            .field("required_features", &self.required_features)
+            .field("min_device_local_memory", &self.min_device_local_memory)
+            .field("scorer", &self.scorer.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// Outcome of adapter selection.
+/// `Display` implementation lists all adapters with their scores or reasons of rejection.
+#[derive(Clone, Debug)]
+pub struct AdapterReport {
+    verdicts: Vec<(AdapterCandidate, Result<u64, String>)>,
+    chosen: Option<usize>,
+}
+
+impl AdapterReport {
+    /// Get all adapters with their scores or reasons of rejection.
+    pub fn verdicts(&self) -> &[(AdapterCandidate, Result<u64, String>)] {
+        &self.verdicts
+    }
+
+    /// Get index of the chosen adapter.
+    pub fn chosen(&self) -> Option<usize> {
+        self.chosen
+    }
+
+    /// Get the chosen adapter.
+    pub fn chosen_candidate(&self) -> Option<&AdapterCandidate> {
+        self.chosen.map(|index| &self.verdicts[index].0)
+    }
+}
+
+impl fmt::Display for AdapterReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.verdicts.is_empty() {
+            return write!(fmt, "No adapters found");
+        }
+        for &(ref candidate, ref score) in &self.verdicts {
+            write!(
+                fmt,
+                "{} #{} \"{}\" ({:?}, {} MiB device-local): ",
+                if self.chosen == Some(candidate.index) { "*" } else { " " },
+                candidate.index,
+                candidate.info.name,
+                candidate.kind,
+                candidate.device_local_memory >> 20,
+            )?;
+            match *score {
+                Ok(score) if self.chosen == Some(candidate.index) => writeln!(fmt, "chosen with score {}", score)?,
+                Ok(score) => writeln!(fmt, "outscored with score {}", score)?,
+                Err(ref reason) => writeln!(fmt, "rejected, {}", reason)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn memory_kind(properties: &MemoryProperties) -> AdapterKind {
+    let mut device_local = properties
+        .memory_types
+        .iter()
+        .filter(|ty| ty.properties.contains(Properties::DEVICE_LOCAL))
+        .peekable();
+    if device_local.peek().is_none() {
+        return AdapterKind::Software;
+    }
+    if device_local.all(|ty| ty.properties.contains(Properties::CPU_VISIBLE)) {
+        AdapterKind::Integrated
+    } else {
+        AdapterKind::Discrete
+    }
+}
+
+fn device_local_memory(properties: &MemoryProperties) -> u64 {
+    properties
+        .memory_types
+        .iter()
+        .filter(|ty| ty.properties.contains(Properties::DEVICE_LOCAL))
+        .map(|ty| ty.heap_index)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|heap| properties.memory_heaps[heap])
+        .sum()
+}
@@ -0,0 +1,466 @@
+//! Aliasing of transient resources which lifetimes within a frame don't overlap.
+//! 
+
+use std::ops::Range;
+
+use hal::{Backend, Device};
+use hal::buffer::{Access as BufferAccess, Usage as BufferUsage};
+use hal::command::RawCommandBuffer;
+use hal::format::Format;
+use hal::image::{Access as ImageAccess, ImageLayout, Kind, Level, SubresourceRange, Usage as ImageUsage};
+use hal::memory::{Barrier, Properties, Requirements};
+use hal::pso::PipelineStage;
+
+use mem::{Block, SmartBlock};
+
+use Error;
+use budget::MemoryTag;
+use factory::Factory;
+
+/// Identifier of transient resource declared in `AliasingBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TransientId(usize);
+
+#[derive(Clone, Debug)]
+enum TransientDesc {
+    Buffer {
+        size: u64,
+        usage: BufferUsage,
+        access: BufferAccess,
+    },
+    Image {
+        kind: Kind,
+        levels: Level,
+        format: Format,
+        usage: ImageUsage,
+        range: SubresourceRange,
+        state: (ImageAccess, ImageLayout),
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Transient {
+    desc: TransientDesc,
+    passes: Range<usize>,
+    stages: PipelineStage,
+}
+
+impl Transient {
+    fn is_image(&self) -> bool {
+        match self.desc {
+            TransientDesc::Image { .. } => true,
+            TransientDesc::Buffer { .. } => false,
+        }
+    }
+}
+
+/// Collects transient resources with their lifetimes expressed as ranges of pass indices.
+/// Resources which lifetimes don't overlap may share memory.
+#[derive(Clone, Debug, Default)]
+pub struct AliasingBuilder {
+    transients: Vec<Transient>,
+}
+
+impl AliasingBuilder {
+    /// Create empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare transient buffer.
+    /// 
+    /// # Parameters
+    /// 
+    /// `size`      - size of the buffer.
+    /// `usage`     - usage of the buffer.
+    /// `access`    - access of the first pass using the buffer.
+    /// `stages`    - stages of all passes using the buffer.
+    /// `passes`    - passes using the buffer.
+    /// 
+    pub fn buffer(
+        &mut self,
+        size: u64,
+        usage: BufferUsage,
+        access: BufferAccess,
+        stages: PipelineStage,
+        passes: Range<usize>,
+    ) -> TransientId {
+        self.push(TransientDesc::Buffer { size, usage, access }, stages, passes)
+    }
+
+    /// Declare transient image.
+    /// 
+    /// # Parameters
+    /// 
+    /// `kind`      - dimensions of the image.
+    /// `levels`    - number of mip-levels.
+    /// `format`    - format of the image.
+    /// `usage`     - usage of the image.
+    /// `range`     - subresources to transition when the image starts its life.
+    /// `state`     - access and layout of the first pass using the image.
+    /// `stages`    - stages of all passes using the image.
+    /// `passes`    - passes using the image.
+    /// 
+    pub fn image(
+        &mut self,
+        kind: Kind,
+        levels: Level,
+        format: Format,
+        usage: ImageUsage,
+        range: SubresourceRange,
+        state: (ImageAccess, ImageLayout),
+        stages: PipelineStage,
+        passes: Range<usize>,
+    ) -> TransientId {
+        self.push(
+            TransientDesc::Image {
+                kind,
+                levels,
+                format,
+                usage,
+                range,
+                state,
+            },
+            stages,
+            passes,
+        )
+    }
+
+    /// Create resources and bind them to single block of device-local memory allocated by the `Factory`.
+    /// Resources with overlapping lifetimes get disjoint memory ranges.
+    /// Block is accounted in the memory budget with `MemoryTag::Transient`.
+    pub fn build<B>(&self, factory: &Factory<B>) -> Result<AliasedResources<B>, Error>
+    where
+        B: Backend,
+    {
+        let device: &B::Device = &**factory;
+        let mut unbound = Vec::with_capacity(self.transients.len());
+        for transient in &self.transients {
+            let resource = match transient.desc {
+                TransientDesc::Buffer { size, usage, .. } => {
+                    let buffer = device
+                        .create_buffer(size, usage)
+                        .map_err(|err| Error::creation("transient buffer", err))?;
+                    let requirements = device.get_buffer_requirements(&buffer);
+                    (Unbound::Buffer(buffer), requirements)
+                }
+                TransientDesc::Image {
+                    kind,
+                    levels,
+                    format,
+                    usage,
+                    ..
+                } => {
+                    let image = device
+                        .create_image(kind, levels, format, usage)
+                        .map_err(|err| Error::creation("transient image", err))?;
+                    let requirements = device.get_image_requirements(&image);
+                    (Unbound::Image(image), requirements)
+                }
+            };
+            unbound.push(resource);
+        }
+
+        let requirements = unbound.iter().map(|&(_, ref r)| r.clone()).collect::<Vec<_>>();
+        let granularity = factory.limits().buffer_image_granularity;
+        let offsets = self.place(&requirements, granularity);
+        let size = offsets
+            .iter()
+            .zip(&requirements)
+            .map(|(offset, requirements)| offset + requirements.size)
+            .max()
+            .unwrap_or(0);
+        // Block must be aligned for every resource and to granularity page as offsets are relative to it.
+        let alignment = requirements
+            .iter()
+            .fold(granularity.max(1), |alignment, requirements| alignment.max(requirements.alignment));
+        let type_mask = requirements
+            .iter()
+            .fold(!0, |mask, requirements| mask & requirements.type_mask);
+        let block = factory.allocate_block(
+            MemoryTag::Transient,
+            Properties::DEVICE_LOCAL,
+            Requirements {
+                size: size.max(1),
+                alignment,
+                type_mask,
+            },
+        )?;
+        let base = block.range().start;
+
+        let mut resources = Vec::with_capacity(unbound.len());
+        for ((resource, requirements), &offset) in unbound.into_iter().zip(&offsets) {
+            let bound = match resource {
+                Unbound::Buffer(buffer) => device
+                    .bind_buffer_memory(block.memory(), base + offset, buffer)
+                    .map(Bound::Buffer)
+                    .map_err(|err| Error::creation("transient buffer", err)),
+                Unbound::Image(image) => device
+                    .bind_image_memory(block.memory(), base + offset, image)
+                    .map(Bound::Image)
+                    .map_err(|err| Error::creation("transient image", err)),
+            }?;
+            resources.push((bound, offset..offset + requirements.size));
+        }
+
+        info!("Transient resources aliased into {} bytes at {:?}", size, block.range());
+        Ok(AliasedResources {
+            transients: self.transients.clone(),
+            resources,
+            block,
+            size,
+        })
+    }
+
+    fn push(&mut self, desc: TransientDesc, stages: PipelineStage, passes: Range<usize>) -> TransientId {
+        assert!(passes.start < passes.end, "Transient resource must be used by at least one pass");
+        self.transients.push(Transient {
+            desc,
+            passes,
+            stages,
+        });
+        TransientId(self.transients.len() - 1)
+    }
+
+    /// Place resources largest first at lowest offset not used by resources with overlapping lifetime.
+    /// Buffers and images with overlapping lifetime never share a page of `granularity` bytes.
+    fn place(&self, requirements: &[Requirements], granularity: u64) -> Vec<u64> {
+        let mut order = (0..requirements.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| !requirements[index].size);
+
+        let mut placed: Vec<(usize, Range<u64>)> = Vec::new();
+        let mut offsets = vec![0; requirements.len()];
+        for index in order {
+            let ref transient = self.transients[index];
+            let mut conflicts = placed
+                .iter()
+                .filter(|&&(other, _)| overlaps(&transient.passes, &self.transients[other].passes))
+                .map(|&(other, ref range)| {
+                    if transient.is_image() == self.transients[other].is_image() {
+                        range.clone()
+                    } else {
+                        // Pad to pages of granularity so linear and optimal resources are never adjacent.
+                        align_down(range.start, granularity)..align(range.end, granularity)
+                    }
+                })
+                .collect::<Vec<_>>();
+            conflicts.sort_by_key(|range| range.start);
+
+            let Requirements { size, alignment, .. } = requirements[index];
+            let mut offset = 0;
+            for range in conflicts {
+                if offset + size <= range.start {
+                    break;
+                }
+                offset = align(offset.max(range.end), alignment);
+            }
+            offsets[index] = offset;
+            placed.push((index, offset..offset + size));
+        }
+        offsets
+    }
+}
+
+enum Unbound<B: Backend> {
+    Buffer(B::UnboundBuffer),
+    Image(B::UnboundImage),
+}
+
+#[derive(Debug)]
+enum Bound<B: Backend> {
+    Buffer(B::Buffer),
+    Image(B::Image),
+}
+
+/// Transient resources sharing single memory object.
+#[derive(Debug)]
+pub struct AliasedResources<B: Backend> {
+    transients: Vec<Transient>,
+    resources: Vec<(Bound<B>, Range<u64>)>,
+    block: SmartBlock<B::Memory>,
+    size: u64,
+}
+
+impl<B> AliasedResources<B>
+where
+    B: Backend,
+{
+    /// Get transient buffer.
+    pub fn buffer(&self, id: TransientId) -> Option<&B::Buffer> {
+        match self.resources[id.0].0 {
+            Bound::Buffer(ref buffer) => Some(buffer),
+            _ => None,
+        }
+    }
+
+    /// Get transient image.
+    pub fn image(&self, id: TransientId) -> Option<&B::Image> {
+        match self.resources[id.0].0 {
+            Bound::Image(ref image) => Some(image),
+            _ => None,
+        }
+    }
+
+    /// Get size of memory shared by all resources.
+    pub fn memory_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get range of memory bound to the resource.
+    pub(crate) fn memory_range(&self, id: TransientId) -> Range<u64> {
+        self.resources[id.0].1.clone()
+    }
+
+    /// Get stages of other resources which memory overlaps with memory of the resource.
+    pub(crate) fn aliased_stages(&self, id: TransientId) -> PipelineStage {
+        let ref range = self.resources[id.0].1;
+        self.transients
+            .iter()
+            .zip(&self.resources)
+            .enumerate()
+            .filter(|&(index, (_, &(_, ref other_range)))| index != id.0 && overlaps(range, other_range))
+            .fold(PipelineStage::empty(), |stages, (_, (other, _))| stages | other.stages)
+    }
+
+    /// Record barriers for resources starting their life at `pass`
+    /// which memory was used by resources of earlier passes.
+    /// Images are transitioned from `Undefined` layout to the state of their first pass.
+    /// Must be recorded before `pass` begins.
+    pub fn aliasing_barriers(&self, pass: usize, cbuf: &mut B::CommandBuffer) {
+        for (index, transient) in self.transients.iter().enumerate() {
+            if transient.passes.start != pass {
+                continue;
+            }
+            let ref range = self.resources[index].1;
+            let previous = self.transients
+                .iter()
+                .zip(&self.resources)
+                .filter(|&(other, &(_, ref other_range))| {
+                    other.passes.end <= pass && overlaps(range, other_range)
+                })
+                .fold(PipelineStage::empty(), |stages, (other, _)| stages | other.stages);
+            let src = if previous.is_empty() {
+                PipelineStage::TOP_OF_PIPE
+            } else {
+                previous
+            };
+
+            match (&transient.desc, &self.resources[index].0) {
+                (&TransientDesc::Buffer { access, .. }, &Bound::Buffer(ref buffer)) => {
+                    if previous.is_empty() {
+                        continue;
+                    }
+                    cbuf.pipeline_barrier(
+                        src..transient.stages,
+                        Some(Barrier::Buffer {
+                            states: BufferAccess::empty()..access,
+                            target: buffer,
+                        }),
+                    );
+                }
+                (&TransientDesc::Image { ref range, state, .. }, &Bound::Image(ref image)) => {
+                    cbuf.pipeline_barrier(
+                        src..transient.stages,
+                        Some(Barrier::Image {
+                            states: (ImageAccess::empty(), ImageLayout::Undefined)..state,
+                            target: image,
+                            range: range.clone(),
+                        }),
+                    );
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    /// Destroy resources and free memory block.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        for (resource, _) in self.resources {
+            match resource {
+                Bound::Buffer(buffer) => factory.destroy_raw_buffer(buffer),
+                Bound::Image(image) => factory.destroy_raw_image(image),
+            }
+        }
+        factory.free_block(MemoryTag::Transient, self.block);
+    }
+}
+
+fn overlaps<T: PartialOrd>(a: &Range<T>, b: &Range<T>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    let alignment = alignment.max(1);
+    (value + alignment - 1) / alignment * alignment
+}
+
+fn align_down(value: u64, alignment: u64) -> u64 {
+    let alignment = alignment.max(1);
+    value / alignment * alignment
+}
+
+#[test]
+fn resources_with_disjoint_lifetimes_share_memory() {
+    let mut builder = AliasingBuilder::new();
+    for passes in vec![0..2, 1..3, 2..4] {
+        builder.buffer(1, BufferUsage::STORAGE, BufferAccess::SHADER_WRITE, PipelineStage::COMPUTE_SHADER, passes);
+    }
+    let requirements = |size| Requirements {
+        size,
+        alignment: 64,
+        type_mask: !0,
+    };
+    let offsets = builder.place(&[requirements(256), requirements(128), requirements(256)], 1);
+    assert_eq!(offsets, vec![0, 256, 0]);
+}
+
+#[test]
+fn placed_resources_are_aligned() {
+    let mut builder = AliasingBuilder::new();
+    for _ in 0..3 {
+        builder.buffer(1, BufferUsage::STORAGE, BufferAccess::SHADER_WRITE, PipelineStage::COMPUTE_SHADER, 0..1);
+    }
+    let requirements = |size, alignment| Requirements {
+        size,
+        alignment,
+        type_mask: !0,
+    };
+    let offsets = builder.place(&[requirements(100, 4), requirements(60, 64), requirements(50, 256)], 1);
+    assert_eq!(offsets, vec![0, 128, 256]);
+}
+
+#[test]
+fn buffers_and_images_are_padded_to_granularity() {
+    use hal::format::Aspects;
+    use hal::image::AaMode;
+
+    let mut builder = AliasingBuilder::new();
+    builder.image(
+        Kind::D2(16, 16, AaMode::Single),
+        1,
+        Format::Rgba8Unorm,
+        ImageUsage::COLOR_ATTACHMENT,
+        SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..1,
+        },
+        (ImageAccess::COLOR_ATTACHMENT_WRITE, ImageLayout::ColorAttachmentOptimal),
+        PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+        0..2,
+    );
+    for passes in vec![1..2, 2..3] {
+        builder.buffer(1, BufferUsage::STORAGE, BufferAccess::SHADER_WRITE, PipelineStage::COMPUTE_SHADER, passes);
+    }
+    let requirements = |size, alignment| Requirements {
+        size,
+        alignment,
+        type_mask: !0,
+    };
+    let ref placed = [requirements(1000, 256), requirements(100, 4), requirements(100, 4)];
+
+    // Buffer used with the image starts at the next page, buffer used after it may alias the image.
+    assert_eq!(builder.place(placed, 1024), vec![0, 1024, 0]);
+    assert_eq!(builder.place(placed, 1), vec![0, 1000, 0]);
+}
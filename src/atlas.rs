@@ -0,0 +1,315 @@
+//! `TextureAtlas` packs many small images into few large textures.
+//! Edges of inserted images are extruded into padding around them
+//! so filtering near the edges never samples neighbouring images or uninitialized texels.
+//! Block-compressed formats are supported as long as sizes are multiples of the block.
+//! 
+
+use std::cmp::min;
+
+use hal::Backend;
+use hal::device::Extent;
+use hal::format::Format;
+use hal::image::{AaMode, Kind, Offset, SamplerInfo};
+
+use Error;
+use factory::Factory;
+use texture::Texture;
+
+/// Region of the atlas occupied by inserted image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// Index of the page texture.
+    pub page: usize,
+
+    /// Offset of the region in texels.
+    pub x: u32,
+
+    /// Offset of the region in texels.
+    pub y: u32,
+
+    /// Width of the region in texels.
+    pub width: u32,
+
+    /// Height of the region in texels.
+    pub height: u32,
+
+    /// Normalized texture coordinates of the region: `[u_min, v_min, u_max, v_max]`.
+    pub uv: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+/// Shelf rectangle packer.
+#[derive(Debug)]
+struct ShelfPacker {
+    size: u32,
+    shelves: Vec<Shelf>,
+    top: u32,
+}
+
+impl ShelfPacker {
+    fn new(size: u32) -> Self {
+        ShelfPacker {
+            size,
+            shelves: Vec::new(),
+            top: 0,
+        }
+    }
+
+    /// Find place for `width` x `height` rectangle.
+    /// Picks shelf with least wasted height or opens new one.
+    fn pack(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let size = self.size;
+        let best = self.shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.x + width <= size)
+            .min_by_key(|shelf| shelf.height - height);
+        if let Some(shelf) = best {
+            let x = shelf.x;
+            shelf.x += width;
+            return Some((x, shelf.y));
+        }
+        if self.top + height > size || width > size {
+            return None;
+        }
+        let y = self.top;
+        self.top += height;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some((0, y))
+    }
+}
+
+#[derive(Debug)]
+struct AtlasPage<B: Backend> {
+    texture: Texture<B>,
+    packer: ShelfPacker,
+}
+
+/// Atlas of 2D textures of the same format.
+/// New pages are created when existing ones are full.
+/// Each inserted image is surrounded by `padding` texels filled with its edges to avoid bleeding when filtering.
+#[derive(Debug)]
+pub struct TextureAtlas<B: Backend> {
+    format: Format,
+    size: u32,
+    padding: u32,
+    block: (u32, u32),
+    block_size: u32,
+    sampler: Option<SamplerInfo>,
+    pages: Vec<AtlasPage<B>>,
+}
+
+impl<B> TextureAtlas<B>
+where
+    B: Backend,
+{
+    /// Create empty atlas.
+    /// 
+    /// # Parameters
+    /// 
+    /// `format`    - format of page textures and inserted images.
+    /// `size`      - width and height of page textures.
+    /// `padding`   - space between images.
+    /// `sampler`   - parameters of sampler created with each page.
+    /// 
+    /// Fails if `size` can't be size of an image
+    /// or `size` and `padding` aren't multiples of the block of compressed `format`.
+    pub fn new(format: Format, size: u32, padding: u32, sampler: Option<SamplerInfo>) -> Result<Self, Error> {
+        if size == 0 || size > u16::max_value() as u32 {
+            return Err(Error::InvalidArgument("Atlas page size is out of range 1..65535"));
+        }
+        let desc = format.surface_desc();
+        let block = (desc.dim.0 as u32, desc.dim.1 as u32);
+        if size % block.0 != 0 || size % block.1 != 0 || padding % block.0 != 0 || padding % block.1 != 0 {
+            return Err(Error::InvalidArgument("Atlas page size and padding must be multiples of format blocks"));
+        }
+        Ok(TextureAtlas {
+            format,
+            size,
+            padding,
+            block,
+            block_size: desc.bits as u32 / 8,
+            sampler,
+            pages: Vec::new(),
+        })
+    }
+
+    /// Get page textures.
+    pub fn pages(&self) -> Vec<&Texture<B>> {
+        self.pages.iter().map(|page| &page.texture).collect()
+    }
+
+    /// Get page texture.
+    pub fn page(&self, index: usize) -> &Texture<B> {
+        &self.pages[index].texture
+    }
+
+    /// Insert image into the atlas and upload its texels.
+    /// `data` must contain `width * height` tightly packed texels.
+    /// For block-compressed formats `width` and `height` must be multiples of the block
+    /// and `data` must contain tightly packed blocks.
+    pub fn insert(
+        &mut self,
+        factory: &mut Factory<B>,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<AtlasRegion, Error> {
+        if width == 0 || height == 0 || width % self.block.0 != 0 || height % self.block.1 != 0 {
+            return Err(Error::InvalidArgument("Image is empty or isn't made of whole format blocks"));
+        }
+        let blocks = (width / self.block.0, height / self.block.1);
+        let expected = blocks.0 as usize * blocks.1 as usize * self.block_size as usize;
+        if data.len() != expected {
+            return Err(Error::SizeMismatch {
+                what: "image data bytes",
+                expected: expected as u64,
+                actual: data.len() as u64,
+            });
+        }
+
+        let padded = (width + self.padding * 2, height + self.padding * 2);
+        let mut placed = None;
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(position) = page.packer.pack(padded.0, padded.1) {
+                placed = Some((index, position));
+                break;
+            }
+        }
+        let (page, (x, y)) = match placed {
+            Some(placed) => placed,
+            None => {
+                let mut packer = ShelfPacker::new(self.size);
+                let position = packer
+                    .pack(padded.0, padded.1)
+                    .ok_or(Error::InvalidArgument("Image doesn't fit into atlas page"))?;
+                let texture = factory.create_empty_texture(
+                    Kind::D2(self.size as u16, self.size as u16, AaMode::Single),
+                    self.format,
+                    self.sampler.clone(),
+                )?;
+                self.pages.push(AtlasPage { texture, packer });
+                (self.pages.len() - 1, position)
+            }
+        };
+
+        let padding = (
+            (self.padding / self.block.0) as usize,
+            (self.padding / self.block.1) as usize,
+        );
+        let extruded = extrude(
+            data,
+            blocks.0 as usize,
+            blocks.1 as usize,
+            padding,
+            self.block_size as usize,
+        );
+        factory.upload_texture_region(
+            &mut self.pages[page].texture,
+            0..1,
+            Offset {
+                x: x as i32,
+                y: y as i32,
+                z: 0,
+            },
+            Extent {
+                width: padded.0,
+                height: padded.1,
+                depth: 1,
+            },
+            &extruded,
+        )?;
+
+        let x = x + self.padding;
+        let y = y + self.padding;
+
+        let size = self.size as f32;
+        Ok(AtlasRegion {
+            page,
+            x,
+            y,
+            width,
+            height,
+            uv: [
+                x as f32 / size,
+                y as f32 / size,
+                (x + width) as f32 / size,
+                (y + height) as f32 / size,
+            ],
+        })
+    }
+
+    /// Destroy all pages.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        for page in self.pages {
+            factory.destroy_texture(page.texture);
+        }
+    }
+}
+
+/// Surround `width` x `height` grid of texels or blocks of `element` bytes with `padding` copies of its edges.
+fn extrude(data: &[u8], width: usize, height: usize, padding: (usize, usize), element: usize) -> Vec<u8> {
+    let padded_width = width + padding.0 * 2;
+    let padded_height = height + padding.1 * 2;
+    let mut extruded = Vec::with_capacity(padded_width * padded_height * element);
+    for y in 0..padded_height {
+        let src_y = min(y.saturating_sub(padding.1), height - 1);
+        for x in 0..padded_width {
+            let src_x = min(x.saturating_sub(padding.0), width - 1);
+            let start = (src_y * width + src_x) * element;
+            extruded.extend_from_slice(&data[start..start + element]);
+        }
+    }
+    extruded
+}
+
+#[test]
+fn packer_fills_shelves_with_best_fit() {
+    let mut packer = ShelfPacker::new(16);
+    assert_eq!(packer.pack(8, 4), Some((0, 0)));
+    assert_eq!(packer.pack(8, 8), Some((0, 4)));
+    // Shorter rectangle goes to the shelf wasting least height.
+    assert_eq!(packer.pack(4, 3), Some((8, 0)));
+    assert_eq!(packer.pack(4, 6), Some((8, 4)));
+    // Nothing fits into existing shelves so new one is opened below them.
+    assert_eq!(packer.pack(16, 4), Some((0, 12)));
+}
+
+#[test]
+fn packer_rejects_what_does_not_fit() {
+    let mut packer = ShelfPacker::new(16);
+    assert_eq!(packer.pack(17, 1), None);
+    assert_eq!(packer.pack(1, 17), None);
+    assert_eq!(packer.pack(16, 10), Some((0, 0)));
+    assert_eq!(packer.pack(16, 7), None);
+    assert_eq!(packer.pack(16, 6), Some((0, 10)));
+    assert_eq!(packer.pack(1, 1), None);
+}
+
+#[test]
+fn edges_are_extruded_into_padding() {
+    let data = [1u8, 2, 3, 4];
+    assert_eq!(
+        extrude(&data, 2, 2, (1, 1), 1),
+        vec![
+            1, 1, 2, 2,
+            1, 1, 2, 2,
+            3, 3, 4, 4,
+            3, 3, 4, 4,
+        ]
+    );
+    // Elements are copied whole and padding may differ between axes.
+    let data = [1u8, 10, 2, 20];
+    assert_eq!(extrude(&data, 2, 1, (0, 1), 2), vec![1, 10, 2, 20, 1, 10, 2, 20, 1, 10, 2, 20]);
+    assert_eq!(extrude(&data, 2, 1, (0, 0), 2), data.to_vec());
+}
@@ -0,0 +1,354 @@
+//! Attachments `Renderer` creates for each target and recreates when target is resized.
+//! 
+
+use hal::Backend;
+use hal::command::{ImageCopy, ImageResolve, RawCommandBuffer};
+use hal::device::Extent;
+use hal::format::{Aspects, Format, Swizzle};
+use hal::image::{Access, AaMode, ImageLayout, Kind, NumSamples, Offset, SubresourceLayers, SubresourceRange,
+                 Usage as ImageUsage};
+use hal::memory::Barrier;
+use hal::pso::PipelineStage;
+
+use Error;
+use factory::{Factory, Image};
+use texture::kind_extent;
+
+/// Image with view created for the target.
+#[derive(Debug)]
+pub struct ManagedAttachment<B: Backend> {
+    image: Image<B>,
+    view: B::ImageView,
+    format: Format,
+}
+
+impl<B> ManagedAttachment<B>
+where
+    B: Backend,
+{
+    /// Get image of the attachment.
+    pub fn image(&self) -> &Image<B> {
+        &self.image
+    }
+
+    /// Get view of the whole image.
+    pub fn view(&self) -> &B::ImageView {
+        &self.view
+    }
+
+    /// Get format picked for the attachment.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    fn dispose(self, factory: &mut Factory<B>) {
+        factory.destroy_image_view(self.view);
+        factory.destroy_image(self.image);
+    }
+}
+
+/// Attachments managed by `Renderer` for the target.
+/// Passed to `Render::render` along with the backbuffer.
+///
+/// With multisampling enabled renders draw into multisampled color attachment
+/// instead of the backbuffer and resolve it with `Attachments::resolve`.
+/// Depth-stencil attachment gets the same number of samples.
+///
+/// With multiple views, e.g. for stereo rendering, attachments have a layer per view
+/// and renders draw into color attachment as well. `Attachments::resolve` places views
+/// side by side in the backbuffer, so each view gets an equal part of the backbuffer width.
+#[derive(Debug)]
+pub struct Attachments<B: Backend> {
+    depth_formats: Vec<Format>,
+    depth: Option<ManagedAttachment<B>>,
+    samples: NumSamples,
+    views: u32,
+    color_format: Option<Format>,
+    color: Option<ManagedAttachment<B>>,
+}
+
+impl<B> Attachments<B>
+where
+    B: Backend,
+{
+    pub(crate) fn new() -> Self {
+        Attachments {
+            depth_formats: Vec::new(),
+            depth: None,
+            samples: 1,
+            views: 1,
+            color_format: None,
+            color: None,
+        }
+    }
+
+    /// Get number of samples of attachments.
+    pub fn samples(&self) -> NumSamples {
+        self.samples
+    }
+
+    /// Get number of views. Attachments have a layer per view.
+    pub fn views(&self) -> u32 {
+        self.views
+    }
+
+    /// Get extent of single view for the target of `kind`.
+    /// Depth is number of views, so it can be used to create framebuffers with all layers of attachments.
+    pub fn extent(&self, kind: Kind) -> Extent {
+        view_extent(kind, self.views)
+    }
+
+    /// Get color attachment if multisampling or multiple views are enabled for the target.
+    pub fn color(&self) -> Option<&ManagedAttachment<B>> {
+        self.color.as_ref()
+    }
+
+    /// Record resolve of color attachment into `target` image.
+    /// Multisampled views are resolved, otherwise views are copied. View `i` is placed
+    /// at `i`-th part of the `target` width.
+    /// Color attachment must be in `ColorAttachmentOptimal` layout. It's left in `TransferSrcOptimal` layout,
+    /// so render passes should use `Undefined` initial layout for it.
+    /// `target` is transitioned from `Undefined` to `layout`.
+    /// Does nothing if neither multisampling nor multiple views are enabled.
+    pub fn resolve(&self, cbuf: &mut B::CommandBuffer, target: &B::Image, kind: Kind, layout: ImageLayout) {
+        let color = match self.color {
+            Some(ref color) => color,
+            None => return,
+        };
+        let range = SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..1,
+        };
+        cbuf.pipeline_barrier(
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::TRANSFER,
+            vec![
+                Barrier::Image {
+                    states: (Access::COLOR_ATTACHMENT_WRITE, ImageLayout::ColorAttachmentOptimal)
+                        ..(Access::TRANSFER_READ, ImageLayout::TransferSrcOptimal),
+                    target: color.image.raw(),
+                    range: SubresourceRange {
+                        layers: 0..self.views as u16,
+                        ..range.clone()
+                    },
+                },
+                Barrier::Image {
+                    states: (Access::empty(), ImageLayout::Undefined)..(Access::TRANSFER_WRITE, ImageLayout::TransferDstOptimal),
+                    target,
+                    range: range.clone(),
+                },
+            ],
+        );
+        let extent = view_extent(kind, self.views);
+        let layers = |layer| SubresourceLayers {
+            aspects: Aspects::COLOR,
+            level: 0,
+            layers: layer..layer + 1,
+        };
+        let offset = |view| Offset {
+            x: (view * extent.width) as i32,
+            y: 0,
+            z: 0,
+        };
+        let extent = Extent { depth: 1, ..extent };
+        if self.samples > 1 {
+            cbuf.resolve_image(
+                color.image.raw(),
+                ImageLayout::TransferSrcOptimal,
+                target,
+                ImageLayout::TransferDstOptimal,
+                (0..self.views).map(|view| ImageResolve {
+                    src_subresource: layers(view as u16),
+                    src_offset: Offset { x: 0, y: 0, z: 0 },
+                    dst_subresource: layers(0),
+                    dst_offset: offset(view),
+                    extent,
+                }),
+            );
+        } else {
+            cbuf.copy_image(
+                color.image.raw(),
+                ImageLayout::TransferSrcOptimal,
+                target,
+                ImageLayout::TransferDstOptimal,
+                (0..self.views).map(|view| ImageCopy {
+                    src_subresource: layers(view as u16),
+                    src_offset: Offset { x: 0, y: 0, z: 0 },
+                    dst_subresource: layers(0),
+                    dst_offset: offset(view),
+                    extent,
+                }),
+            );
+        }
+        cbuf.pipeline_barrier(
+            PipelineStage::TRANSFER..PipelineStage::BOTTOM_OF_PIPE,
+            Some(Barrier::Image {
+                states: (Access::TRANSFER_WRITE, ImageLayout::TransferDstOptimal)..(Access::empty(), layout),
+                target,
+                range,
+            }),
+        );
+    }
+
+    /// Enable multisampling with `samples` per texel for color attachment of `color_format`.
+    /// Single sample disables it.
+    pub(crate) fn set_samples(
+        &mut self,
+        samples: NumSamples,
+        color_format: Format,
+        kind: Kind,
+        factory: &mut Factory<B>,
+    ) -> Result<(), Error> {
+        self.samples = samples.max(1);
+        self.color_format = Some(color_format);
+        self.recreate(kind, factory)
+    }
+
+    /// Render `views` views with color attachment of `color_format`. Single view disables it.
+    pub(crate) fn set_views(
+        &mut self,
+        views: u32,
+        color_format: Format,
+        kind: Kind,
+        factory: &mut Factory<B>,
+    ) -> Result<(), Error> {
+        self.views = views.max(1);
+        self.color_format = Some(color_format);
+        self.recreate(kind, factory)
+    }
+
+    /// Get depth-stencil attachment if enabled for the target.
+    pub fn depth(&self) -> Option<&ManagedAttachment<B>> {
+        self.depth.as_ref()
+    }
+
+    /// Enable depth-stencil attachment with first of `formats` supported by the device.
+    /// Empty `formats` disable it.
+    pub(crate) fn set_depth_formats(
+        &mut self,
+        formats: &[Format],
+        kind: Kind,
+        factory: &mut Factory<B>,
+    ) -> Result<Option<Format>, Error> {
+        self.depth_formats = formats.to_vec();
+        self.recreate(kind, factory)?;
+        Ok(self.depth.as_ref().map(|depth| depth.format))
+    }
+
+    /// Recreate attachments to match the target of new size.
+    pub(crate) fn recreate(&mut self, kind: Kind, factory: &mut Factory<B>) -> Result<(), Error> {
+        if let Some(depth) = self.depth.take() {
+            depth.dispose(factory);
+        }
+        if let Some(color) = self.color.take() {
+            color.dispose(factory);
+        }
+        if !self.depth_formats.is_empty() {
+            self.depth = Some(create_attachment(
+                &self.depth_formats,
+                kind,
+                self.samples,
+                self.views,
+                true,
+                factory,
+            )?);
+        }
+        if let Some(color_format) = self.color_format {
+            if self.samples > 1 || self.views > 1 {
+                self.color = Some(create_attachment(
+                    &[color_format],
+                    kind,
+                    self.samples,
+                    self.views,
+                    false,
+                    factory,
+                )?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Destroy all attachments.
+    pub(crate) fn dispose(self, factory: &mut Factory<B>) {
+        if let Some(depth) = self.depth {
+            depth.dispose(factory);
+        }
+        if let Some(color) = self.color {
+            color.dispose(factory);
+        }
+    }
+}
+
+fn create_attachment<B>(
+    formats: &[Format],
+    kind: Kind,
+    samples: NumSamples,
+    views: u32,
+    depth: bool,
+    factory: &mut Factory<B>,
+) -> Result<ManagedAttachment<B>, Error>
+where
+    B: Backend,
+{
+    let extent = view_extent(kind, views);
+    let aa = if samples > 1 {
+        AaMode::Multi(samples)
+    } else {
+        AaMode::Single
+    };
+    let kind = if views > 1 {
+        Kind::D2Array(extent.width as u16, extent.height as u16, views as u16, aa)
+    } else {
+        Kind::D2(extent.width as u16, extent.height as u16, aa)
+    };
+    let builder = factory.image().kind(kind);
+    let mut builder = if depth {
+        builder.depth_stencil_attachment().name("target depth")
+    } else {
+        builder
+            .color_attachment()
+            .usage(ImageUsage::TRANSFER_SRC)
+            .name("target color")
+    };
+    for &format in formats {
+        builder = builder.fallback(format);
+    }
+    let (image, format) = builder.build()?;
+    let view = factory
+        .create_image_view(
+            image.raw(),
+            format,
+            Swizzle::NO,
+            SubresourceRange {
+                aspects: if depth {
+                    depth_stencil_aspects(format)
+                } else {
+                    Aspects::COLOR
+                },
+                levels: 0..1,
+                layers: 0..views as u16,
+            },
+        )
+        .map_err(|err| Error::creation("attachment view", err))?;
+    Ok(ManagedAttachment { image, view, format })
+}
+
+/// Get extent of single view when `views` are placed side by side in the target of `kind`.
+/// Depth is number of views.
+fn view_extent(kind: Kind, views: u32) -> Extent {
+    let extent = kind_extent(kind);
+    Extent {
+        width: extent.width / views,
+        height: extent.height,
+        depth: views,
+    }
+}
+
+/// Get aspects of depth-stencil format.
+pub(crate) fn depth_stencil_aspects(format: Format) -> Aspects {
+    match format {
+        Format::D16UnormS8Uint | Format::D24UnormS8Uint | Format::D32FloatS8Uint => Aspects::DEPTH | Aspects::STENCIL,
+        Format::S8Uint => Aspects::STENCIL,
+        _ => Aspects::DEPTH,
+    }
+}
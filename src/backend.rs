@@ -3,9 +3,19 @@
 //! Also empty backend implements `BackendEx` if no backend-features enabled.
 //! 
 
+#[cfg(feature = "gfx-backend-vulkan")]
+use std::panic::catch_unwind;
+
 use hal::{Backend, Instance};
+use raw_window_handle::RawWindowHandle;
+#[cfg(feature = "winit")]
 use winit::Window;
 
+use Error;
+
+use breadcrumb::BreadcrumbMarkers;
+use dynamic::BackendKind;
+
 #[cfg(feature = "gfx-backend-vulkan")]
 use vulkan;
 
@@ -21,50 +31,112 @@ use empty;
 /// Extend backend trait with initialization method and surface creation method.
 pub trait BackendEx: Backend {
     type Instance: Instance<Backend = Self> + Send + Sync;
-    fn init() -> Self::Instance;
+
+    /// Create instance of the backend.
+    /// Fails if the backend is unavailable, so `init_dynamic` can try the next one.
+    fn init() -> Result<Self::Instance, Error>;
+
+    /// Get kind of the backend.
+    fn kind() -> BackendKind;
+    #[cfg(feature = "winit")]
     fn create_surface(instance: &Self::Instance, window: &Window) -> Self::Surface;
+
+    /// Create surface from raw handle of the window of any windowing library.
+    /// Fails if backend can't present to windows of the platform.
+    fn create_surface_from_raw(_instance: &Self::Instance, _handle: RawWindowHandle) -> Result<Self::Surface, Error> {
+        Err(Error::Unsupported("Surface creation from raw window handle"))
+    }
+
+    /// Get breadcrumb marker capability if device supports checkpoint extensions.
+    fn breadcrumb_markers(_physical: &Self::PhysicalDevice, _device: &Self::Device) -> Option<Box<BreadcrumbMarkers<Self>>> {
+        None
+    }
 }
 
 #[cfg(feature = "gfx-backend-vulkan")]
 impl BackendEx for vulkan::Backend {
     type Instance = vulkan::Instance;
-    fn init() -> Self::Instance {
-        vulkan::Instance::create("amethyst", 1)
+    fn init() -> Result<Self::Instance, Error> {
+        // Instance creation panics if Vulkan loader or driver is missing.
+        catch_unwind(|| vulkan::Instance::create("amethyst", 1))
+            .map_err(|_| Error::Unsupported("Vulkan instance"))
+    }
+    fn kind() -> BackendKind {
+        BackendKind::Vulkan
     }
+    #[cfg(feature = "winit")]
     fn create_surface(instance: &Self::Instance, window: &Window) -> Self::Surface {
         instance.create_surface(window)
     }
+    fn create_surface_from_raw(instance: &Self::Instance, handle: RawWindowHandle) -> Result<Self::Surface, Error> {
+        match handle {
+            #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+            RawWindowHandle::Xlib(handle) => Ok(instance.create_surface_from_xlib(handle.display as *mut _, handle.window)),
+            #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+            RawWindowHandle::Wayland(handle) => Ok(instance.create_surface_from_wayland(handle.display, handle.surface)),
+            #[cfg(windows)]
+            RawWindowHandle::Windows(handle) => Ok(instance.create_surface_from_hwnd(handle.hinstance, handle.hwnd)),
+            _ => Err(Error::Unsupported("Window handle of other platform")),
+        }
+    }
 }
 
 #[cfg(feature = "gfx-backend-metal")]
 impl BackendEx for metal::Backend {
     type Instance = metal::Instance;
-    fn init() -> Self::Instance {
-        metal::Instance::create("amethyst", 1)
+    fn init() -> Result<Self::Instance, Error> {
+        Ok(metal::Instance::create("amethyst", 1))
     }
+    fn kind() -> BackendKind {
+        BackendKind::Metal
+    }
+    #[cfg(feature = "winit")]
     fn create_surface(instance: &Self::Instance, window: &Window) -> Self::Surface {
         instance.create_surface(window)
     }
+    fn create_surface_from_raw(instance: &Self::Instance, handle: RawWindowHandle) -> Result<Self::Surface, Error> {
+        match handle {
+            RawWindowHandle::MacOS(handle) => Ok(instance.create_surface_from_nsview(handle.ns_view)),
+            _ => Err(Error::Unsupported("Window handle of other platform")),
+        }
+    }
 }
 
 #[cfg(feature = "gfx-backend-dx12")]
 impl BackendEx for dx12::Backend {
     type Instance = dx12::Instance;
-    fn init() -> Self::Instance {
-        dx12::Instance::create("amethyst", 1)
+    fn init() -> Result<Self::Instance, Error> {
+        Ok(dx12::Instance::create("amethyst", 1))
     }
+    fn kind() -> BackendKind {
+        BackendKind::Dx12
+    }
+    #[cfg(feature = "winit")]
     fn create_surface(instance: &Self::Instance, window: &Window) -> Self::Surface {
         instance.create_surface(window)
     }
+    fn create_surface_from_raw(instance: &Self::Instance, handle: RawWindowHandle) -> Result<Self::Surface, Error> {
+        match handle {
+            RawWindowHandle::Windows(handle) => Ok(instance.create_surface_from_hwnd(handle.hwnd)),
+            _ => Err(Error::Unsupported("Window handle of other platform")),
+        }
+    }
 }
 
 #[cfg(not(any(feature = "gfx-backend-vulkan", feature = "gfx-backend-metal", feature = "gfx-backend-dx12")))]
 impl BackendEx for empty::Backend {
     type Instance = empty::Instance;
-    fn init() -> Self::Instance {
-        empty::Instance
+    fn init() -> Result<Self::Instance, Error> {
+        Ok(empty::Instance)
     }
+    fn kind() -> BackendKind {
+        BackendKind::Empty
+    }
+    #[cfg(feature = "winit")]
     fn create_surface(_: &Self::Instance, _: &Window) -> Self::Surface {
         empty::Surface
     }
+    fn create_surface_from_raw(_: &Self::Instance, _: RawWindowHandle) -> Result<Self::Surface, Error> {
+        Ok(empty::Surface)
+    }
 }
@@ -0,0 +1,237 @@
+//! Global table of sampled images addressed by stable `u32` indices from shaders.
+//! 
+//! gfx-hal doesn't expose descriptor indexing, so the table is a fixed-size descriptor array
+//! and update-after-bind is emulated: there is one descriptor set per frame in flight and
+//! registrations are written into each set right before it is used again.
+//! Slots without registered image hold view of the default image, so the whole array is always valid.
+//! 
+
+use std::collections::VecDeque;
+
+use hal::{Backend, DescriptorPool, Device};
+use hal::format::{Aspects, Format, Swizzle};
+use hal::image::{ImageLayout, SubresourceRange};
+use hal::pso::{DescriptorRangeDesc, DescriptorSetLayoutBinding, DescriptorSetWrite, DescriptorType,
+               DescriptorWrite, ShaderStageFlags};
+
+use Error;
+use factory::{Factory, Image};
+
+/// Table of sampled images bound as single descriptor array.
+/// Index returned by `register` stays valid until `unregister`.
+/// Indices are reused only after frames that could reference them are retired.
+#[derive(Debug)]
+pub struct BindlessTable<B: Backend> {
+    capacity: u32,
+    layout: B::DescriptorSetLayout,
+    pool: B::DescriptorPool,
+    sets: Vec<B::DescriptorSet>,
+    pending: Vec<Vec<u32>>,
+    default: B::ImageView,
+    views: Vec<Option<B::ImageView>>,
+    unregistered: Vec<(u32, B::ImageView, usize)>,
+    free: Vec<u32>,
+    retired: VecDeque<(u32, u64)>,
+}
+
+impl<B> BindlessTable<B>
+where
+    B: Backend,
+{
+    /// Create table.
+    /// 
+    /// # Parameters
+    /// 
+    /// `capacity`  - number of descriptors in the array.
+    /// `frames`    - number of frames that can be in flight simultaneously.
+    /// `stages`    - shader stages accessing the table.
+    /// `default`   - color image in `ShaderReadOnlyOptimal` layout sampled through unused indices.
+    ///               It must outlive the table.
+    /// `format`    - format of the default image.
+    /// 
+    pub fn new(
+        factory: &Factory<B>,
+        capacity: u32,
+        frames: usize,
+        stages: ShaderStageFlags,
+        default: &Image<B>,
+        format: Format,
+    ) -> Result<Self, Error> {
+        let default = create_view(factory, default, format)?;
+        let layout = factory.create_descriptor_set_layout(&[
+            DescriptorSetLayoutBinding {
+                binding: 0,
+                ty: DescriptorType::SampledImage,
+                count: capacity as usize,
+                stage_flags: stages,
+            },
+        ]);
+        let mut pool = factory.create_descriptor_pool(
+            frames,
+            &[
+                DescriptorRangeDesc {
+                    ty: DescriptorType::SampledImage,
+                    count: capacity as usize * frames,
+                },
+            ],
+        );
+        let mut sets = Vec::with_capacity(frames);
+        for _ in 0..frames {
+            sets.push(pool.allocate_set(&layout)
+                .map_err(|err| Error::descriptor_set("bindless descriptor set", err))?);
+        }
+        factory.write_descriptor_sets(sets.iter().map(|set| DescriptorSetWrite {
+            set,
+            binding: 0,
+            array_offset: 0,
+            write: DescriptorWrite::SampledImage(
+                (0..capacity)
+                    .map(|_| (&default, ImageLayout::ShaderReadOnlyOptimal))
+                    .collect(),
+            ),
+        }));
+
+        Ok(BindlessTable {
+            capacity,
+            layout,
+            pool,
+            sets,
+            pending: vec![Vec::new(); frames],
+            default,
+            views: Vec::new(),
+            unregistered: Vec::new(),
+            free: (0..capacity).rev().collect(),
+            retired: VecDeque::new(),
+        })
+    }
+
+    /// Get layout of the table descriptor set.
+    pub fn layout(&self) -> &B::DescriptorSetLayout {
+        &self.layout
+    }
+
+    /// Get capacity of the table.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Register color image in `ShaderReadOnlyOptimal` layout.
+    /// Table creates its own view of the image. Image must outlive registration.
+    pub fn register(&mut self, factory: &Factory<B>, image: &Image<B>, format: Format) -> Result<u32, Error> {
+        let index = self.free.pop().ok_or(Error::Exhausted("Bindless table"))?;
+        let view = match create_view(factory, image, format) {
+            Ok(view) => view,
+            Err(err) => {
+                self.free.push(index);
+                return Err(err);
+            }
+        };
+
+        while self.views.len() <= index as usize {
+            self.views.push(None);
+        }
+        self.views[index as usize] = Some(view);
+        for pending in &mut self.pending {
+            pending.push(index);
+        }
+        Ok(index)
+    }
+
+    /// Unregister image.
+    /// Index is pointed to the default image in each set before it is used again.
+    /// View is destroyed after all sets are rewritten and index is reused after commands using them complete.
+    pub fn unregister(&mut self, index: u32) {
+        if let Some(view) = self.views[index as usize].take() {
+            self.unregistered.push((index, view, self.sets.len()));
+            for pending in &mut self.pending {
+                pending.push(index);
+            }
+        }
+    }
+
+    /// Get descriptor set to bind for the `frame`, writing registrations made since it was used last.
+    /// Set of the `frame` must not be used by the device anymore.
+    pub fn set(&mut self, factory: &mut Factory<B>, frame: u64) -> &B::DescriptorSet {
+        let slot = (frame % self.sets.len() as u64) as usize;
+        let mut pending = self.pending[slot].drain(..).collect::<Vec<_>>();
+        pending.sort();
+        pending.dedup();
+        {
+            let ref set = self.sets[slot];
+            let ref views = self.views;
+            let ref default = self.default;
+            factory.write_descriptor_sets(pending.iter().map(|&index| DescriptorSetWrite {
+                set,
+                binding: 0,
+                array_offset: index as usize,
+                write: DescriptorWrite::SampledImage(vec![(
+                    views
+                        .get(index as usize)
+                        .and_then(Option::as_ref)
+                        .unwrap_or(default),
+                    ImageLayout::ShaderReadOnlyOptimal,
+                )]),
+            }));
+        }
+
+        // Views no set refers to anymore are destroyed after commands of the current frame complete.
+        let current = factory.current();
+        let mut index = 0;
+        while index < self.unregistered.len() {
+            if pending.contains(&self.unregistered[index].0) {
+                self.unregistered[index].2 -= 1;
+            }
+            if self.unregistered[index].2 == 0 {
+                let (unregistered, view, _) = self.unregistered.swap_remove(index);
+                factory.destroy_image_view(view);
+                self.retired.push_back((unregistered, current));
+            } else {
+                index += 1;
+            }
+        }
+        &self.sets[slot]
+    }
+
+    /// Make indices unregistered before `ongoing` frame available for reuse.
+    pub fn clear(&mut self, ongoing: u64) {
+        while let Some((index, frame)) = self.retired.pop_front() {
+            if frame >= ongoing {
+                self.retired.push_front((index, frame));
+                break;
+            }
+            self.free.push(index);
+        }
+    }
+
+    /// Destroy the table.
+    /// Sets of the table must not be used by the device anymore.
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        for view in self.views.into_iter().filter_map(|view| view) {
+            factory.destroy_image_view(view);
+        }
+        for (_, view, _) in self.unregistered {
+            factory.destroy_image_view(view);
+        }
+        factory.destroy_image_view(self.default);
+        factory.destroy_descriptor_pool(self.pool);
+        factory.destroy_descriptor_set_layout(self.layout);
+    }
+}
+
+fn create_view<B>(factory: &Factory<B>, image: &Image<B>, format: Format) -> Result<B::ImageView, Error>
+where
+    B: Backend,
+{
+    factory
+        .create_image_view(
+            image.raw(),
+            format,
+            Swizzle::NO,
+            SubresourceRange {
+                aspects: Aspects::COLOR,
+                levels: 0..1,
+                layers: 0..1,
+            },
+        )
+        .map_err(|err| Error::creation("image view", err))
+}
@@ -0,0 +1,236 @@
+//! Breadcrumb markers written by the device between passes and after upload batches.
+//! When device is lost the last marker each queue reached tells which work hung the GPU.
+//! Backends can write markers with checkpoint extensions through `BackendEx::breadcrumb_markers`.
+//! Otherwise markers are written into host-visible buffer with transfer commands.
+//! Enabled with `Config::breadcrumbs` or `Factory::enable_breadcrumbs`.
+//!
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Debug;
+use std::iter::empty;
+use std::slice::from_raw_parts;
+use std::sync::Arc;
+
+use hal::{Backend, Device};
+use hal::command::{CommandBufferFlags, RawCommandBuffer, RawLevel};
+use hal::memory::Barrier;
+use hal::pool::{CommandPoolCreateFlags, RawCommandPool};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+use Error;
+use factory::Buffer;
+
+/// Number of latest markers which labels are kept.
+const KEPT_LABELS: usize = 256;
+
+/// Checkpoint capability of the device.
+/// Backend records markers with extensions like `VK_NV_device_diagnostic_checkpoints`
+/// and reads back markers reached after device loss.
+pub trait BreadcrumbMarkers<B: Backend>: Debug + Send + Sync {
+    /// Record marker reached when all previous commands complete.
+    ///
+    /// # Safety
+    ///
+    /// Command buffer must be in recording state.
+    unsafe fn write(&self, cbuf: &mut B::CommandBuffer, marker: u32);
+
+    /// Get last marker reached by each queue.
+    fn last_completed(&self, device: &B::Device) -> Vec<u32>;
+}
+
+/// Work markers are written after.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BreadcrumbStream {
+    Uploads = 0,
+    Passes = 1,
+}
+
+/// Last markers reached by the device.
+#[derive(Clone, Debug, Default)]
+pub struct BreadcrumbReport {
+    /// Labels of last completed work of each queue.
+    /// Marker id is reported instead if its label isn't kept anymore.
+    pub completed: Vec<String>,
+}
+
+impl fmt::Display for BreadcrumbReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.completed.is_empty() {
+            return write!(fmt, "No breadcrumb markers were reached");
+        }
+        write!(fmt, "Last reached breadcrumb markers: {}", self.completed.join(", "))
+    }
+}
+
+/// Command pool with command buffers writing upload markers of single frame.
+#[derive(Debug)]
+struct MarkerPool<B: Backend> {
+    pool: B::CommandPool,
+    cbufs: Vec<B::CommandBuffer>,
+}
+
+/// Breadcrumb markers written through `BreadcrumbMarkers` or into the buffer.
+#[derive(Debug)]
+pub(crate) struct Breadcrumbs<B: Backend> {
+    markers: Option<Arc<BreadcrumbMarkers<B>>>,
+    buffer: Option<Buffer<B>>,
+    family: QueueFamilyId,
+    next: u32,
+    labels: VecDeque<(u32, String)>,
+    used: VecDeque<(MarkerPool<B>, u64)>,
+    free: Vec<MarkerPool<B>>,
+}
+
+impl<B> Breadcrumbs<B>
+where
+    B: Backend,
+{
+    /// Create breadcrumbs writing markers with `markers` if any or into `buffer` otherwise.
+    /// Buffer must be cpu-visible, coherent, zeroed and have `TRANSFER_DST` usage
+    /// and room for marker of each `BreadcrumbStream`.
+    pub(crate) fn new(
+        markers: Option<Arc<BreadcrumbMarkers<B>>>,
+        buffer: Option<Buffer<B>>,
+        family: QueueFamilyId,
+    ) -> Self {
+        Breadcrumbs {
+            markers,
+            buffer,
+            family,
+            next: 1,
+            labels: VecDeque::new(),
+            used: VecDeque::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Record marker reached after previous commands of the queue complete.
+    ///
+    /// # Safety
+    ///
+    /// Command buffer must be in recording state outside of render pass.
+    pub(crate) unsafe fn write(&mut self, cbuf: &mut B::CommandBuffer, stream: BreadcrumbStream, label: String) {
+        let marker = self.next;
+        self.next = self.next.wrapping_add(1).max(1);
+        if self.labels.len() == KEPT_LABELS {
+            self.labels.pop_front();
+        }
+        self.labels.push_back((marker, label));
+        match (self.markers.as_ref(), self.buffer.as_ref()) {
+            (Some(markers), _) => markers.write(cbuf, marker),
+            (None, Some(buffer)) => {
+                cbuf.pipeline_barrier(
+                    PipelineStage::BOTTOM_OF_PIPE..PipelineStage::TRANSFER,
+                    empty::<Barrier<B>>(),
+                );
+                let bytes = [
+                    marker as u8,
+                    (marker >> 8) as u8,
+                    (marker >> 16) as u8,
+                    (marker >> 24) as u8,
+                ];
+                cbuf.update_buffer(buffer.raw(), stream as u64 * 4, &bytes);
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Record `count` command buffers with marker after each upload batch of the `frame`.
+    /// They must be submitted right after corresponding batches.
+    pub(crate) fn upload_markers(&mut self, device: &B::Device, frame: u64, count: usize) -> Vec<&mut B::CommandBuffer> {
+        if count == 0 {
+            return Vec::new();
+        }
+        let family = self.family;
+        let mut pool = self.free.pop().unwrap_or_else(|| MarkerPool {
+            pool: device.create_command_pool(family, CommandPoolCreateFlags::TRANSIENT),
+            cbufs: Vec::new(),
+        });
+        if pool.cbufs.len() < count {
+            let more = count - pool.cbufs.len();
+            pool.cbufs.extend(pool.pool.allocate(more, RawLevel::Primary));
+        }
+        for index in 0..count {
+            let cbuf = &mut pool.cbufs[index];
+            cbuf.begin(CommandBufferFlags::ONE_TIME_SUBMIT);
+            unsafe {
+                // Command buffer is just started.
+                self.write(
+                    cbuf,
+                    BreadcrumbStream::Uploads,
+                    format!("upload batch {} of frame {}", index, frame),
+                );
+            }
+            cbuf.finish();
+        }
+        self.used.push_back((pool, frame));
+        self.used
+            .back_mut()
+            .map(|&mut (ref mut pool, _)| pool.cbufs[..count].iter_mut().collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Read last markers reached by the device.
+    pub(crate) fn report(&self, device: &B::Device) -> Result<BreadcrumbReport, Error> {
+        let reached = match (self.markers.as_ref(), self.buffer.as_ref()) {
+            (Some(markers), _) => markers.last_completed(device),
+            (None, Some(buffer)) => unsafe {
+                let range = buffer.range();
+                let ptr = device
+                    .map_memory(buffer.memory(), range.clone())
+                    .map_err(Error::MappingFailed)?;
+                let bytes = from_raw_parts(ptr, 8);
+                let reached: Vec<u32> = bytes
+                    .chunks(4)
+                    .map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+                    .collect();
+                device.unmap_memory(buffer.memory());
+                reached
+            },
+            (None, None) => Vec::new(),
+        };
+        let completed = reached
+            .into_iter()
+            .filter(|&marker| marker != 0)
+            .map(|marker| {
+                self.labels
+                    .iter()
+                    .find(|&&(id, _)| id == marker)
+                    .map(|&(_, ref label)| label.clone())
+                    .unwrap_or_else(|| format!("marker {}", marker))
+            })
+            .collect();
+        Ok(BreadcrumbReport { completed })
+    }
+
+    /// Reset command buffers of frames before `ongoing`.
+    pub(crate) fn clear(&mut self, ongoing: u64) {
+        while let Some((mut pool, frame)) = self.used.pop_front() {
+            if frame >= ongoing {
+                self.used.push_front((pool, frame));
+                break;
+            }
+            pool.pool.reset();
+            self.free.push(pool);
+        }
+    }
+
+    /// Take marker buffer to destroy it through the `Factory`.
+    pub(crate) fn take_buffer(&mut self) -> Option<Buffer<B>> {
+        self.buffer.take()
+    }
+
+    /// Destroy all command pools.
+    ///
+    /// # Safety
+    ///
+    /// Device must be idle or lost.
+    pub(crate) unsafe fn dispose(self, device: &B::Device) {
+        let pools = self.used.into_iter().map(|(pool, _)| pool).chain(self.free);
+        for pool in pools {
+            device.destroy_command_pool(pool.pool);
+        }
+    }
+}
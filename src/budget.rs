@@ -0,0 +1,386 @@
+//! Tracking of memory allocated by `Factory` per memory heap with optional soft budgets.
+//! 
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use hal::MemoryProperties;
+use hal::memory::Properties;
+
+use Error;
+
+/// Usage of single memory heap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct HeapUsage {
+    /// Size of the heap reported by the device.
+    pub size: u64,
+
+    /// Bytes allocated for resources created by `Factory`.
+    pub allocated: u64,
+
+    /// Soft budget set by the user.
+    pub budget: Option<u64>,
+}
+
+/// Kind of resource memory is allocated for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum MemoryTag {
+    /// Buffers created by `Factory`.
+    Buffer,
+
+    /// Images created by `Factory`.
+    Image,
+
+    /// Buffers with dedicated memory created by `Factory::create_dedicated_buffer`.
+    Dedicated,
+
+    /// Memory shared by aliased transient resources. See `AliasingBuilder::build`.
+    Transient,
+}
+
+/// Usage of memory by resources of one kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct TagUsage {
+    /// Number of live resources.
+    pub count: u64,
+
+    /// Bytes allocated for live resources.
+    pub bytes: u64,
+
+    /// Largest allocation made so far.
+    pub largest: u64,
+}
+
+/// Usage of single memory heap broken down by kinds of resources.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct HeapReport {
+    /// Index of the heap.
+    pub heap: usize,
+
+    /// Size, allocated bytes and budget of the heap.
+    pub usage: HeapUsage,
+
+    /// Bytes that can still be allocated before reaching size or budget of the heap, whichever is smaller.
+    pub available: u64,
+
+    /// Usage by each kind of resources allocated from the heap.
+    pub tags: Vec<(MemoryTag, TagUsage)>,
+}
+
+/// Snapshot of memory usage. Created by `Factory::memory_report`.
+/// Can be serialized with `serialize` feature to collect reports from production builds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct MemoryReport {
+    /// Usage of each heap.
+    pub heaps: Vec<HeapReport>,
+
+    /// Usage by buffers across all heaps.
+    pub buffers: TagUsage,
+
+    /// Usage by images across all heaps.
+    pub images: TagUsage,
+
+    /// Usage by staging buffers not yet retired.
+    pub staging: TagUsage,
+
+    /// Usage by resources which memory can't be attributed to single heap.
+    /// See `Factory::memory_usage`.
+    pub untracked: TagUsage,
+}
+
+/// Per-heap accounting of resources created by `Factory`.
+/// 
+/// Memory is accounted in the heap of memory type it was allocated from.
+/// The allocator reports properties of the picked type rather than its index,
+/// so candidate types are those with exactly these properties allowed by the type mask of the resource.
+/// If candidates belong to different heaps the allocation must fit budgets of all of them
+/// and is accounted as untracked, without heap.
+/// Staging buffers are not accounted.
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+    types: Vec<(Properties, usize)>,
+    heaps: Vec<HeapUsage>,
+    tags: HashMap<(MemoryTag, Option<usize>), TagUsage>,
+    blocks: HashMap<(usize, u64, u64), (MemoryTag, Option<usize>)>,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(properties: &MemoryProperties) -> Self {
+        MemoryBudget {
+            types: properties
+                .memory_types
+                .iter()
+                .map(|ty| (ty.properties, ty.heap_index))
+                .collect(),
+            heaps: properties
+                .memory_heaps
+                .iter()
+                .map(|&size| HeapUsage {
+                    size,
+                    allocated: 0,
+                    budget: None,
+                })
+                .collect(),
+            tags: HashMap::new(),
+            blocks: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn usage(&self) -> &[HeapUsage] {
+        &self.heaps
+    }
+
+    /// Get usage by resources of `tag` across all heaps, including untracked ones.
+    pub(crate) fn tag_usage(&self, tag: MemoryTag) -> TagUsage {
+        self.sum(|other, _| other == tag)
+    }
+
+    /// Get usage by resources which memory isn't attributed to single heap.
+    pub(crate) fn untracked(&self) -> TagUsage {
+        self.sum(|_, heap| heap.is_none())
+    }
+
+    fn sum<F>(&self, filter: F) -> TagUsage
+    where
+        F: Fn(MemoryTag, Option<usize>) -> bool,
+    {
+        self.tags
+            .iter()
+            .filter(|&(&(tag, heap), _)| filter(tag, heap))
+            .fold(TagUsage::default(), |total, (_, usage)| TagUsage {
+                count: total.count + usage.count,
+                bytes: total.bytes + usage.bytes,
+                largest: total.largest.max(usage.largest),
+            })
+    }
+
+    /// Get usage of each heap broken down by tags.
+    pub(crate) fn report(&self) -> Vec<HeapReport> {
+        self.heaps
+            .iter()
+            .enumerate()
+            .map(|(heap, &usage)| {
+                let mut tags = self.tags
+                    .iter()
+                    .filter(|&(&(_, other), _)| other == Some(heap))
+                    .map(|(&(tag, _), &usage)| (tag, usage))
+                    .collect::<Vec<_>>();
+                tags.sort_by_key(|&(tag, _)| tag as u8);
+                let limit = usage.budget.map_or(usage.size, |budget| budget.min(usage.size));
+                HeapReport {
+                    heap,
+                    usage,
+                    available: limit.saturating_sub(usage.allocated),
+                    tags,
+                }
+            })
+            .collect()
+    }
+
+    /// Set soft budget of the heap. Fails if there is no such heap.
+    pub(crate) fn set_budget(&mut self, heap: usize, budget: Option<u64>) -> Result<(), Error> {
+        let heaps = self.heaps.len();
+        let usage = self.heaps
+            .get_mut(heap)
+            .ok_or(Error::UnknownHeap { heap, heaps })?;
+        usage.budget = budget;
+        Ok(())
+    }
+
+    /// Get heaps of memory types with exactly `properties` allowed by `type_mask`.
+    /// Resource allocated from memory with these properties comes from one of them.
+    pub(crate) fn heaps(&self, type_mask: u64, properties: Properties) -> Vec<usize> {
+        let mut heaps = self.types
+            .iter()
+            .enumerate()
+            .filter(|&(index, &(props, _))| type_mask & (1 << index) != 0 && props == properties)
+            .map(|(_, &(_, heap))| heap)
+            .collect::<Vec<_>>();
+        heaps.sort();
+        heaps.dedup();
+        heaps
+    }
+
+    /// Account allocation of `size` bytes from one of `heaps`. See `MemoryBudget::heaps`.
+    /// Fails without accounting if allocation exceeds the budget of any of them.
+    /// Returns heap allocation is accounted in, `None` if it is untracked.
+    pub(crate) fn allocate(&mut self, tag: MemoryTag, heaps: &[usize], size: u64) -> Result<Option<usize>, Error> {
+        for &heap in heaps {
+            let usage = self.heaps[heap];
+            if let Some(budget) = usage.budget {
+                if usage.allocated + size > budget {
+                    return Err(Error::BudgetExceeded {
+                        heap,
+                        allocated: usage.allocated,
+                        requested: size,
+                        budget,
+                    });
+                }
+            }
+        }
+        let heap = if heaps.len() == 1 {
+            self.heaps[heaps[0]].allocated += size;
+            Some(heaps[0])
+        } else {
+            warn!(
+                "{:?} memory of {} bytes may come from any of heaps {:?} and is accounted as untracked",
+                tag, size, heaps
+            );
+            None
+        };
+        let tag_usage = self.tags.entry((tag, heap)).or_insert_with(TagUsage::default);
+        tag_usage.count += 1;
+        tag_usage.bytes += size;
+        tag_usage.largest = tag_usage.largest.max(size);
+        Ok(heap)
+    }
+
+    /// Account release of memory accounted by `allocate` with the same `tag` and `size` in `heap` it returned.
+    pub(crate) fn free(&mut self, tag: MemoryTag, heap: Option<usize>, size: u64) {
+        if let Some(heap) = heap {
+            self.heaps[heap].allocated -= size;
+        }
+        let tag_usage = self.tags.get_mut(&(tag, heap)).expect("Allocation was accounted");
+        tag_usage.count -= 1;
+        tag_usage.bytes -= size;
+    }
+
+    /// Account allocation of block at `range` of memory object at address `memory` from one of `heaps`.
+    /// Heap it is accounted in is remembered until `MemoryBudget::free_block`.
+    pub(crate) fn allocate_block(
+        &mut self,
+        tag: MemoryTag,
+        memory: usize,
+        heaps: &[usize],
+        range: Range<u64>,
+    ) -> Result<(), Error> {
+        let heap = self.allocate(tag, heaps, range.end - range.start)?;
+        self.blocks.insert((memory, range.start, range.end), (tag, heap));
+        Ok(())
+    }
+
+    /// Account release of block accounted by `allocate_block` with the same `tag`.
+    pub(crate) fn free_block(&mut self, tag: MemoryTag, memory: usize, range: Range<u64>) {
+        if let Some((allocated, heap)) = self.blocks.remove(&(memory, range.start, range.end)) {
+            debug_assert_eq!(allocated, tag);
+            self.free(tag, heap, range.end - range.start);
+        }
+    }
+
+    /// Get heap block accounted by `allocate_block` is accounted in.
+    pub(crate) fn block_heap(&self, memory: usize, range: Range<u64>) -> Option<usize> {
+        self.blocks
+            .get(&(memory, range.start, range.end))
+            .and_then(|&(_, heap)| heap)
+    }
+}
+
+#[test]
+fn usage_is_reported_per_heap_and_tag() {
+    use hal::MemoryType;
+
+    let mut budget = MemoryBudget::new(&MemoryProperties {
+        memory_types: vec![
+            MemoryType {
+                properties: Properties::DEVICE_LOCAL,
+                heap_index: 0,
+            },
+            MemoryType {
+                properties: Properties::CPU_VISIBLE | Properties::COHERENT,
+                heap_index: 1,
+            },
+        ],
+        memory_heaps: vec![1024, 512],
+    });
+    let local = budget.heaps(!0, Properties::DEVICE_LOCAL);
+    let visible = budget.heaps(!0, Properties::CPU_VISIBLE | Properties::COHERENT);
+    assert_eq!((&local[..], &visible[..]), (&[0][..], &[1][..]));
+
+    budget.set_budget(1, Some(256)).unwrap();
+    budget.allocate_block(MemoryTag::Buffer, 1, &local, 0..100).unwrap();
+    budget.allocate_block(MemoryTag::Image, 1, &local, 100..400).unwrap();
+    budget.allocate_block(MemoryTag::Buffer, 2, &visible, 0..200).unwrap();
+    assert!(budget.allocate_block(MemoryTag::Buffer, 2, &visible, 200..300).is_err());
+
+    let report = budget.report();
+    assert_eq!(report[0].available, 624);
+    assert_eq!(report[1].available, 56);
+    assert_eq!(report[0].tags.len(), 2);
+    assert_eq!(report[1].tags, vec![(MemoryTag::Buffer, TagUsage { count: 1, bytes: 200, largest: 200 })]);
+    assert_eq!(budget.tag_usage(MemoryTag::Buffer), TagUsage { count: 2, bytes: 300, largest: 200 });
+    assert_eq!(budget.block_heap(2, 0..200), Some(1));
+
+    budget.free_block(MemoryTag::Buffer, 2, 0..200);
+    assert_eq!(budget.report()[1].available, 256);
+    assert_eq!(budget.tag_usage(MemoryTag::Buffer).count, 1);
+    assert_eq!(budget.block_heap(2, 0..200), None);
+}
+
+#[test]
+fn memory_of_types_with_same_properties_in_different_heaps_is_untracked() {
+    use hal::MemoryType;
+
+    let mut budget = MemoryBudget::new(&MemoryProperties {
+        memory_types: vec![
+            MemoryType {
+                properties: Properties::DEVICE_LOCAL,
+                heap_index: 0,
+            },
+            MemoryType {
+                properties: Properties::DEVICE_LOCAL,
+                heap_index: 1,
+            },
+            MemoryType {
+                properties: Properties::DEVICE_LOCAL,
+                heap_index: 1,
+            },
+        ],
+        memory_heaps: vec![1024, 256],
+    });
+    // Type mask of the resource tells the heap apart.
+    assert_eq!(budget.heaps(0b001, Properties::DEVICE_LOCAL), vec![0]);
+    assert_eq!(budget.heaps(0b110, Properties::DEVICE_LOCAL), vec![1]);
+    let both = budget.heaps(!0, Properties::DEVICE_LOCAL);
+    assert_eq!(both, vec![0, 1]);
+
+    budget.allocate_block(MemoryTag::Buffer, 1, &both, 0..100).unwrap();
+    assert_eq!(budget.usage()[0].allocated, 0);
+    assert_eq!(budget.usage()[1].allocated, 0);
+    assert_eq!(budget.untracked(), TagUsage { count: 1, bytes: 100, largest: 100 });
+    assert_eq!(budget.block_heap(1, 0..100), None);
+
+    // Untracked allocation is still checked against budgets of all heaps it may come from.
+    budget.set_budget(1, Some(64)).unwrap();
+    match budget.allocate_block(MemoryTag::Buffer, 2, &both, 0..100) {
+        Err(Error::BudgetExceeded { heap, .. }) => assert_eq!(heap, 1),
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    // Same range of different memory objects is freed from the right heap.
+    budget.allocate_block(MemoryTag::Buffer, 3, &[0], 0..100).unwrap();
+    budget.free_block(MemoryTag::Buffer, 1, 0..100);
+    assert_eq!(budget.untracked(), TagUsage { count: 0, bytes: 0, largest: 100 });
+    assert_eq!(budget.usage()[0].allocated, 100);
+
+    // Allocations not bound to blocks are freed from heap `allocate` returned.
+    let heap = budget.allocate(MemoryTag::Dedicated, &[0], 100).unwrap();
+    assert_eq!(heap, Some(0));
+    assert_eq!(budget.usage()[0].allocated, 200);
+    budget.free(MemoryTag::Dedicated, heap, 100);
+    assert_eq!(budget.usage()[0].allocated, 100);
+}
+
+#[test]
+fn budget_of_missing_heap_is_rejected() {
+    let mut budget = MemoryBudget::new(&MemoryProperties {
+        memory_types: Vec::new(),
+        memory_heaps: vec![1024],
+    });
+    assert!(budget.set_budget(0, Some(512)).is_ok());
+    assert!(budget.set_budget(1, Some(512)).is_err());
+}
@@ -0,0 +1,307 @@
+//! Builders for resources created by `Factory`.
+//! Presets pick usage flags and memory properties for common cases.
+//! 
+
+use hal::Backend;
+use hal::buffer::Usage as BufferUsage;
+use hal::format::{Format, ImageFeature};
+use hal::image::{Kind, Level, Usage as ImageUsage};
+use hal::memory::Properties;
+
+use mem::Type;
+
+use Error;
+use debug::DebugObject;
+use factory::{Buffer, Factory, Image};
+
+/// Builder for `Buffer`. Created by `Factory::buffer`.
+/// 
+/// # Example
+/// 
+/// ```ignore
+/// let buffer = factory.buffer().vertex().device_local().size(1024).build()?;
+/// ```
+pub struct BufferBuilder<'a, B: Backend> {
+    factory: &'a mut Factory<B>,
+    size: u64,
+    usage: BufferUsage,
+    properties: Properties,
+    preferred: Properties,
+    ty: Type,
+    name: Option<&'a str>,
+}
+
+impl<'a, B> BufferBuilder<'a, B>
+where
+    B: Backend,
+{
+    pub(crate) fn new(factory: &'a mut Factory<B>) -> Self {
+        BufferBuilder {
+            factory,
+            size: 0,
+            usage: BufferUsage::empty(),
+            properties: Properties::empty(),
+            preferred: Properties::empty(),
+            ty: Type::General,
+            name: None,
+        }
+    }
+
+    /// Set debug name of the buffer.
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Set size of the buffer.
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Add usage flags.
+    pub fn usage(mut self, usage: BufferUsage) -> Self {
+        self.usage |= usage;
+        self
+    }
+
+    /// Add required memory properties.
+    pub fn properties(mut self, properties: Properties) -> Self {
+        self.properties |= properties;
+        self
+    }
+
+    /// Add memory properties that are used if device has memory type with them.
+    pub fn preferred(mut self, properties: Properties) -> Self {
+        self.preferred |= properties;
+        self
+    }
+
+    /// Buffer for vertex data.
+    pub fn vertex(self) -> Self {
+        self.usage(BufferUsage::VERTEX | BufferUsage::TRANSFER_DST)
+    }
+
+    /// Buffer for index data.
+    pub fn index(self) -> Self {
+        self.usage(BufferUsage::INDEX | BufferUsage::TRANSFER_DST)
+    }
+
+    /// Buffer for uniform data.
+    pub fn uniform(self) -> Self {
+        self.usage(BufferUsage::UNIFORM | BufferUsage::TRANSFER_DST)
+    }
+
+    /// Buffer for storage data.
+    pub fn storage(self) -> Self {
+        self.usage(BufferUsage::STORAGE | BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST)
+    }
+
+    /// Short-lived cpu-visible buffer to copy data from.
+    pub fn staging(mut self) -> Self {
+        self.ty = Type::ShortLived;
+        self.usage(BufferUsage::TRANSFER_SRC)
+            .properties(Properties::CPU_VISIBLE)
+    }
+
+    /// Cpu-visible buffer to copy data from the device into.
+    /// Cached memory is preferred but not all devices have cpu-visible cached memory.
+    /// Read it with `Factory::read_buffer` which invalidates non-coherent memory.
+    pub fn readback(self) -> Self {
+        self.usage(BufferUsage::TRANSFER_DST)
+            .properties(Properties::CPU_VISIBLE)
+            .preferred(Properties::CPU_CACHED)
+    }
+
+    /// Place buffer into device-local memory.
+    /// Data should be uploaded through `Factory::upload_buffer`.
+    pub fn device_local(self) -> Self {
+        self.properties(Properties::DEVICE_LOCAL)
+    }
+
+    /// Place buffer into cpu-visible memory to update it frequently from the host.
+    /// On devices with cpu-visible device-local memory allocator will prefer it.
+    pub fn dynamic(self) -> Self {
+        self.properties(Properties::CPU_VISIBLE)
+    }
+
+    /// Create the buffer.
+    pub fn build(self) -> Result<Buffer<B>, Error> {
+        if self.size == 0 {
+            return Err(Error::InvalidArgument("Buffer size is not set"));
+        }
+        if self.usage.is_empty() {
+            return Err(Error::InvalidArgument("Buffer usage is not set"));
+        }
+        let preferred = self.properties | self.preferred;
+        let buffer = match self.factory
+            .create_buffer_of_type(self.ty, self.size, preferred, self.usage)
+        {
+            Err(Error::Allocation { .. }) if preferred != self.properties => {
+                // No memory type with preferred properties.
+                self.factory
+                    .create_buffer_of_type(self.ty, self.size, self.properties, self.usage)?
+            }
+            result => result?,
+        };
+        if let Some(name) = self.name {
+            self.factory
+                .set_debug_name(DebugObject::Buffer(buffer.raw()), name);
+        }
+        Ok(buffer)
+    }
+}
+
+/// Builder for `Image`. Created by `Factory::image`.
+/// Checks that format supports requested usage and falls back along the chain of alternatives.
+/// 
+/// # Example
+/// 
+/// ```ignore
+/// let (image, format) = factory
+///     .image()
+///     .kind(kind)
+///     .format(Format::D24UnormS8Uint)
+///     .fallback(Format::D32FloatS8Uint)
+///     .depth_stencil_attachment()
+///     .build()?;
+/// ```
+pub struct ImageBuilder<'a, B: Backend> {
+    factory: &'a mut Factory<B>,
+    kind: Option<Kind>,
+    levels: Level,
+    formats: Vec<Format>,
+    usage: ImageUsage,
+    properties: Properties,
+    name: Option<&'a str>,
+}
+
+impl<'a, B> ImageBuilder<'a, B>
+where
+    B: Backend,
+{
+    pub(crate) fn new(factory: &'a mut Factory<B>) -> Self {
+        ImageBuilder {
+            factory,
+            kind: None,
+            levels: 1,
+            formats: Vec::new(),
+            usage: ImageUsage::empty(),
+            properties: Properties::DEVICE_LOCAL,
+            name: None,
+        }
+    }
+
+    /// Set debug name of the image.
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Set dimensions of the image.
+    pub fn kind(mut self, kind: Kind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Set number of mip-levels.
+    pub fn levels(mut self, levels: Level) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// Set preferred format.
+    pub fn format(mut self, format: Format) -> Self {
+        self.formats.insert(0, format);
+        self
+    }
+
+    /// Add format to try if previous ones don't support requested usage.
+    pub fn fallback(mut self, format: Format) -> Self {
+        self.formats.push(format);
+        self
+    }
+
+    /// Add usage flags.
+    pub fn usage(mut self, usage: ImageUsage) -> Self {
+        self.usage |= usage;
+        self
+    }
+
+    /// Set required memory properties. `DEVICE_LOCAL` by default.
+    /// Images are created with optimal tiling even in cpu-visible memory, so their texels can't be mapped.
+    pub fn properties(mut self, properties: Properties) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    /// Image to sample in shaders. Data should be uploaded through `Factory::upload_image`.
+    pub fn sampled(self) -> Self {
+        self.usage(ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST)
+    }
+
+    /// Image to use as storage in shaders.
+    pub fn storage(self) -> Self {
+        self.usage(ImageUsage::STORAGE)
+    }
+
+    /// Image to render color into.
+    pub fn color_attachment(self) -> Self {
+        self.usage(ImageUsage::COLOR_ATTACHMENT)
+    }
+
+    /// Image to use as depth-stencil attachment.
+    pub fn depth_stencil_attachment(self) -> Self {
+        self.usage(ImageUsage::DEPTH_STENCIL_ATTACHMENT)
+    }
+
+    /// Attachment used only within a render pass.
+    /// Backed by `LAZILY_ALLOCATED` memory when the device has it so tiled GPUs may never allocate it.
+    /// Contents must not be loaded or stored, see `transient_attachment`.
+    /// Combine with `color_attachment` or `depth_stencil_attachment`.
+    pub fn transient(mut self) -> Self {
+        self.properties = if self.factory.supports_lazily_allocated() {
+            Properties::DEVICE_LOCAL | Properties::LAZILY_ALLOCATED
+        } else {
+            Properties::DEVICE_LOCAL
+        };
+        self.usage(ImageUsage::TRANSIENT_ATTACHMENT)
+    }
+
+    /// Create the image with first format that supports requested usage.
+    /// Returns the image and chosen format.
+    pub fn build(self) -> Result<(Image<B>, Format), Error> {
+        let kind = self.kind.ok_or(Error::InvalidArgument("Image kind is not set"))?;
+        let format = self.factory
+            .pick_format(&self.formats, self.usage, false)
+            .ok_or_else(|| Error::NoSupportedFormat(self.formats.clone()))?;
+        if Some(&format) != self.formats.first() {
+            info!("Format {:?} is picked instead of {:?}", format, self.formats[0]);
+        }
+        let image = self.factory
+            .create_image(kind, self.levels, format, self.properties, self.usage)?;
+        if let Some(name) = self.name {
+            self.factory
+                .set_debug_name(DebugObject::Image(image.raw()), name);
+        }
+        Ok((image, format))
+    }
+}
+
+/// Features format must support to create image with specified usage.
+pub(crate) fn required_features(usage: ImageUsage) -> ImageFeature {
+    let mut features = ImageFeature::empty();
+    if usage.contains(ImageUsage::SAMPLED) {
+        features |= ImageFeature::SAMPLED;
+    }
+    if usage.contains(ImageUsage::STORAGE) {
+        features |= ImageFeature::STORAGE;
+    }
+    if usage.contains(ImageUsage::COLOR_ATTACHMENT) {
+        features |= ImageFeature::COLOR_ATTACHMENT;
+    }
+    if usage.contains(ImageUsage::DEPTH_STENCIL_ATTACHMENT) {
+        features |= ImageFeature::DEPTH_STENCIL_ATTACHMENT;
+    }
+    features
+}
@@ -0,0 +1,73 @@
+//! Programmatic RenderDoc captures.
+//! `Renderer::capture_next_frame` makes the next `Renderer::run` captured from its first submission to its last one,
+//! including uploads and computes recorded by the `Factory`.
+//! Application must be launched or injected by RenderDoc for captures to work.
+//!
+
+use std::ptr::null;
+
+use renderdoc::{RenderDoc, RenderDocV100, V110};
+
+use Error;
+
+/// Captures of single frames through RenderDoc in-application API.
+pub(crate) struct FrameCapture {
+    api: Option<RenderDoc<V110>>,
+    pending: bool,
+    capturing: bool,
+}
+
+// RenderDoc in-application API can be used from any thread.
+unsafe impl Send for FrameCapture {}
+unsafe impl Sync for FrameCapture {}
+
+impl FrameCapture {
+    /// Create capture trigger. RenderDoc API is loaded on first request.
+    pub(crate) fn new() -> Self {
+        FrameCapture {
+            api: None,
+            pending: false,
+            capturing: false,
+        }
+    }
+
+    /// Request capture of the next frame.
+    /// Fails if application isn't running under RenderDoc.
+    pub(crate) fn request(&mut self) -> Result<(), Error> {
+        if self.api.is_none() {
+            let api = RenderDoc::<V110>::new().map_err(|err| {
+                warn!("Failed to load RenderDoc API: {}", err);
+                Error::Unsupported("Frame capture without RenderDoc")
+            })?;
+            info!("RenderDoc API loaded");
+            self.api = Some(api);
+        }
+        self.pending = true;
+        Ok(())
+    }
+
+    /// Start capture if it was requested. Called before the first submission of the frame.
+    pub(crate) fn begin(&mut self) {
+        if !self.pending {
+            return;
+        }
+        self.pending = false;
+        if let Some(ref mut api) = self.api {
+            // Null device and window capture all of them.
+            api.start_frame_capture(null(), null());
+            self.capturing = true;
+        }
+    }
+
+    /// End capture started by `begin`. Called after the last submission of the frame.
+    pub(crate) fn end(&mut self) {
+        if !self.capturing {
+            return;
+        }
+        self.capturing = false;
+        if let Some(ref mut api) = self.api {
+            api.end_frame_capture(null(), null());
+            info!("Frame captured by RenderDoc");
+        }
+    }
+}
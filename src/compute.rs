@@ -0,0 +1,187 @@
+//! Recording of compute work submitted together with uploads each frame.
+//! Command buffers are retired with the frame they were submitted in,
+//! so buffers written by compute can be consumed by the frame and destroyed through `Factory` safely.
+//! 
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use hal::{Backend, Device};
+use hal::buffer::State as BufferState;
+use hal::command::{CommandBufferFlags, RawCommandBuffer, RawLevel};
+use hal::image::{State as ImageState, SubresourceRange};
+use hal::memory::Barrier;
+use hal::pool::{CommandPoolCreateFlags, RawCommandPool};
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+
+/// Command pool with single command buffer.
+#[derive(Debug)]
+struct ComputePool<B: Backend> {
+    pool: B::CommandPool,
+    cbuf: B::CommandBuffer,
+}
+
+/// Compute command buffers recorded for frames.
+#[derive(Debug)]
+pub(crate) struct Compute<B: Backend> {
+    family: QueueFamilyId,
+    recording: Option<ComputePool<B>>,
+    used: VecDeque<(ComputePool<B>, u64)>,
+    free: Vec<ComputePool<B>>,
+}
+
+impl<B> Compute<B>
+where
+    B: Backend,
+{
+    pub(crate) fn new(family: QueueFamilyId) -> Self {
+        Compute {
+            family,
+            recording: None,
+            used: VecDeque::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Get encoder recording into command buffer of the current frame.
+    pub(crate) fn encoder<'a>(&'a mut self, device: &B::Device) -> ComputeEncoder<'a, B> {
+        ComputeEncoder {
+            cbuf: self.command_buffer(device),
+        }
+    }
+
+    /// Get command buffer of the current frame in recording state.
+    pub(crate) fn command_buffer(&mut self, device: &B::Device) -> &mut B::CommandBuffer {
+        let family = self.family;
+        let ref mut free = self.free;
+        let pool = self.recording.get_or_insert_with(|| {
+            let mut pool = free.pop().unwrap_or_else(|| {
+                let mut pool = device.create_command_pool(family, CommandPoolCreateFlags::TRANSIENT);
+                let cbuf = pool.allocate(1, RawLevel::Primary).remove(0);
+                ComputePool { pool, cbuf }
+            });
+            pool.cbuf.begin(CommandBufferFlags::ONE_TIME_SUBMIT);
+            pool
+        });
+        &mut pool.cbuf
+    }
+
+    /// Finish recording and get command buffer to submit with the `frame`.
+    pub(crate) fn submit(&mut self, frame: u64) -> Option<&mut B::CommandBuffer> {
+        let mut pool = self.recording.take()?;
+        pool.cbuf.finish();
+        self.used.push_back((pool, frame));
+        self.used.back_mut().map(|&mut (ref mut pool, _)| &mut pool.cbuf)
+    }
+
+    /// Get frames of submitted command buffers that aren't reset.
+    pub(crate) fn outstanding_frames(&self) -> Vec<u64> {
+        self.used.iter().map(|&(_, frame)| frame).collect()
+    }
+
+    /// Destroy all command pools.
+    ///
+    /// # Safety
+    ///
+    /// Device must be idle or lost.
+    pub(crate) unsafe fn dispose(self, device: &B::Device) {
+        let pools = self.recording
+            .into_iter()
+            .chain(self.used.into_iter().map(|(pool, _)| pool))
+            .chain(self.free);
+        for pool in pools {
+            device.destroy_command_pool(pool.pool);
+        }
+    }
+
+    /// Reset command buffers of frames before `ongoing`.
+    pub(crate) fn clear(&mut self, ongoing: u64) {
+        while let Some((mut pool, frame)) = self.used.pop_front() {
+            if frame >= ongoing {
+                self.used.push_front((pool, frame));
+                break;
+            }
+            pool.pool.reset();
+            self.free.push(pool);
+        }
+    }
+}
+
+/// Helper to record compute commands. Created by `Factory::compute`.
+/// Commands are submitted after uploads of the same frame.
+pub struct ComputeEncoder<'a, B: Backend> {
+    cbuf: &'a mut B::CommandBuffer,
+}
+
+impl<'a, B> ComputeEncoder<'a, B>
+where
+    B: Backend,
+{
+    /// Bind compute pipeline.
+    pub fn bind_pipeline(&mut self, pipeline: &B::ComputePipeline) -> &mut Self {
+        self.cbuf.bind_compute_pipeline(pipeline);
+        self
+    }
+
+    /// Bind descriptor sets starting from `first` set of the `layout`.
+    pub fn bind_descriptor_sets(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first: usize,
+        sets: &[&B::DescriptorSet],
+    ) -> &mut Self {
+        self.cbuf
+            .bind_compute_descriptor_sets(layout, first, sets.iter().cloned());
+        self
+    }
+
+    /// Dispatch work groups.
+    pub fn dispatch(&mut self, x: u32, y: u32, z: u32) -> &mut Self {
+        self.cbuf.dispatch([x, y, z]);
+        self
+    }
+
+    /// Record barrier on the buffer.
+    /// For example from `COMPUTE_SHADER` writes to `VERTEX_INPUT` reads.
+    pub fn buffer_barrier(
+        &mut self,
+        buffer: &B::Buffer,
+        stages: Range<PipelineStage>,
+        states: Range<BufferState>,
+    ) -> &mut Self {
+        self.cbuf.pipeline_barrier(
+            stages,
+            Some(Barrier::Buffer {
+                states,
+                target: buffer,
+            }),
+        );
+        self
+    }
+
+    /// Record barrier on the image.
+    pub fn image_barrier(
+        &mut self,
+        image: &B::Image,
+        range: SubresourceRange,
+        stages: Range<PipelineStage>,
+        states: Range<ImageState>,
+    ) -> &mut Self {
+        self.cbuf.pipeline_barrier(
+            stages,
+            Some(Barrier::Image {
+                states,
+                target: image,
+                range,
+            }),
+        );
+        self
+    }
+
+    /// Get raw command buffer to record commands helper doesn't cover.
+    pub fn raw(&mut self) -> &mut B::CommandBuffer {
+        self.cbuf
+    }
+}
@@ -0,0 +1,350 @@
+//! Loaders of KTX2 and DDS texture containers, which pre-compressed texture pipelines emit.
+//! Containers are parsed into `TextureContainer` that lists regions of all mip-levels and layers,
+//! so the texture is uploaded from single staging buffer with `Factory::create_texture_from_container`.
+//! Supercompressed KTX2 files aren't supported.
+//!
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use hal::Backend;
+use hal::command::BufferImageCopy;
+use hal::device::Extent;
+use hal::format::{Aspects, Format};
+use hal::image::{AaMode, Kind, Layer, Level, Offset, SamplerInfo, SubresourceLayers};
+
+use Error;
+use factory::Factory;
+use texture::{level_extent, Texture, REGION_ALIGNMENT};
+
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const DDS_MAGIC: &[u8] = b"DDS ";
+
+/// Texture data parsed from container.
+#[derive(Clone, Debug)]
+pub struct TextureContainer {
+    /// Dimensions of the texture.
+    pub kind: Kind,
+
+    /// Number of mip-levels.
+    pub levels: Level,
+
+    /// Format of the texture followed by formats to fall back to.
+    /// sRGB formats fall back to their linear counterparts, which shaders must decode then.
+    pub formats: Vec<Format>,
+
+    /// Texels of all regions.
+    pub data: Vec<u8>,
+
+    /// Regions of mip-levels and layers with offsets of their texels in `data`.
+    pub regions: Vec<BufferImageCopy>,
+}
+
+impl TextureContainer {
+    /// Parse KTX2 or DDS container, whichever `bytes` start with identifier of.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.starts_with(&KTX2_IDENTIFIER) {
+            Self::parse_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            Self::parse_dds(bytes)
+        } else {
+            Err(Error::InvalidArgument("Data is neither KTX2 nor DDS container"))
+        }
+    }
+
+    /// Read and parse KTX2 or DDS file.
+    pub fn load<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(Error::Io)?;
+        Self::parse(&bytes)
+    }
+
+    /// Parse KTX2 container.
+    /// Level data is aligned by the container, so it is uploaded as is.
+    pub fn parse_ktx2(bytes: &[u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(&KTX2_IDENTIFIER) {
+            return Err(Error::InvalidArgument("Data is not KTX2 container"));
+        }
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)?;
+        let height = read_u32(bytes, 24)?;
+        let depth = read_u32(bytes, 28)?;
+        let layers = read_u32(bytes, 32)?;
+        let faces = read_u32(bytes, 36)?;
+        let levels = read_u32(bytes, 40)?.max(1);
+        let supercompression = read_u32(bytes, 44)?;
+        if supercompression != 0 {
+            return Err(Error::Unsupported("KTX2 supercompression"));
+        }
+        let format = match vk_format {
+            9 => Format::R8Unorm,
+            16 => Format::Rg8Unorm,
+            37 => Format::Rgba8Unorm,
+            43 => Format::Rgba8Srgb,
+            44 => Format::Bgra8Unorm,
+            50 => Format::Bgra8Srgb,
+            97 => Format::Rgba16Float,
+            109 => Format::Rgba32Float,
+            133 => Format::Bc1RgbaUnorm,
+            134 => Format::Bc1RgbaSrgb,
+            135 => Format::Bc2Unorm,
+            136 => Format::Bc2Srgb,
+            137 => Format::Bc3Unorm,
+            138 => Format::Bc3Srgb,
+            139 => Format::Bc4Unorm,
+            141 => Format::Bc5Unorm,
+            143 => Format::Bc6hUfloat,
+            145 => Format::Bc7Unorm,
+            146 => Format::Bc7Srgb,
+            0 => return Err(Error::Unsupported("Transcoding of KTX2 container without format")),
+            _ => return Err(Error::Unsupported("KTX2 format")),
+        };
+        let kind = container_kind(width, height, depth, layers, faces == 6)?;
+        let layer_count = layers.max(1) * faces.max(1);
+
+        // Level index follows the header.
+        let mut index = Vec::with_capacity(levels as usize);
+        for level in 0..levels as usize {
+            let offset = read_u64(bytes, 80 + level * 24)? as usize;
+            let length = read_u64(bytes, 88 + level * 24)? as usize;
+            if offset.checked_add(length).map_or(true, |end| end > bytes.len()) {
+                return Err(Error::InvalidArgument("KTX2 level is out of bounds of the container"));
+            }
+            index.push((offset, length));
+        }
+        let start = index.iter().map(|&(offset, _)| offset).min().unwrap();
+        let end = index.iter().map(|&(offset, length)| offset + length).max().unwrap();
+
+        let extent = Extent {
+            width,
+            height: height.max(1),
+            depth: depth.max(1),
+        };
+        let regions = index
+            .iter()
+            .enumerate()
+            .map(|(level, &(offset, _))| {
+                region(
+                    level as Level,
+                    0..layer_count as Layer,
+                    level_extent(extent, level as Level),
+                    offset - start,
+                )
+            })
+            .collect();
+
+        Ok(TextureContainer {
+            kind,
+            levels: levels as Level,
+            formats: fallbacks(format),
+            data: bytes[start..end].to_vec(),
+            regions,
+        })
+    }
+
+    /// Parse DDS container, either legacy or with DX10 header.
+    /// Data is stored layer by layer, so it is repacked with each region aligned.
+    pub fn parse_dds(bytes: &[u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(DDS_MAGIC) {
+            return Err(Error::InvalidArgument("Data is not DDS container"));
+        }
+        let height = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 16)?;
+        let depth = read_u32(bytes, 24)?;
+        let levels = read_u32(bytes, 28)?.max(1);
+        let pf_flags = read_u32(bytes, 80)?;
+        let caps2 = read_u32(bytes, 112)?;
+        let four_cc = &bytes[84..88];
+
+        let mut layers = 0;
+        let mut cube = caps2 & 0x200 != 0;
+        let volume = caps2 & 0x20_0000 != 0;
+        let mut offset = 128;
+
+        let format = if pf_flags & 0x4 != 0 {
+            match four_cc {
+                b"DXT1" => Format::Bc1RgbaUnorm,
+                b"DXT2" | b"DXT3" => Format::Bc2Unorm,
+                b"DXT4" | b"DXT5" => Format::Bc3Unorm,
+                b"ATI1" | b"BC4U" => Format::Bc4Unorm,
+                b"ATI2" | b"BC5U" => Format::Bc5Unorm,
+                b"DX10" => {
+                    let dxgi_format = read_u32(bytes, 128)?;
+                    cube = read_u32(bytes, 136)? & 0x4 != 0;
+                    layers = read_u32(bytes, 140)?;
+                    offset = 148;
+                    match dxgi_format {
+                        2 => Format::Rgba32Float,
+                        10 => Format::Rgba16Float,
+                        28 => Format::Rgba8Unorm,
+                        29 => Format::Rgba8Srgb,
+                        49 => Format::Rg8Unorm,
+                        61 => Format::R8Unorm,
+                        71 => Format::Bc1RgbaUnorm,
+                        72 => Format::Bc1RgbaSrgb,
+                        74 => Format::Bc2Unorm,
+                        75 => Format::Bc2Srgb,
+                        77 => Format::Bc3Unorm,
+                        78 => Format::Bc3Srgb,
+                        80 => Format::Bc4Unorm,
+                        83 => Format::Bc5Unorm,
+                        87 => Format::Bgra8Unorm,
+                        91 => Format::Bgra8Srgb,
+                        95 => Format::Bc6hUfloat,
+                        98 => Format::Bc7Unorm,
+                        99 => Format::Bc7Srgb,
+                        _ => return Err(Error::Unsupported("DXGI format")),
+                    }
+                }
+                _ => return Err(Error::Unsupported("DDS FourCC")),
+            }
+        } else {
+            let bits = read_u32(bytes, 88)?;
+            let masks = (read_u32(bytes, 92)?, read_u32(bytes, 96)?, read_u32(bytes, 100)?);
+            match (pf_flags & 0x2_0040 != 0, bits, masks) {
+                (true, 8, (0xff, _, _)) => Format::R8Unorm,
+                (true, 32, (0xff, 0xff00, 0xff_0000)) => Format::Rgba8Unorm,
+                (true, 32, (0xff_0000, 0xff00, 0xff)) => Format::Bgra8Unorm,
+                _ => return Err(Error::Unsupported("DDS pixel format")),
+            }
+        };
+        let faces = if cube { 6 } else { 1 };
+        let kind = container_kind(width, height, if volume { depth } else { 0 }, layers, cube)?;
+        let layer_count = layers.max(1) * faces;
+
+        let extent = Extent {
+            width,
+            height: height.max(1),
+            depth: if volume { depth.max(1) } else { 1 },
+        };
+        let (block_size, block_width, block_height) = block_info(format);
+        let mut data = Vec::new();
+        let mut regions = Vec::with_capacity((layer_count * levels) as usize);
+        for layer in 0..layer_count {
+            for level in 0..levels {
+                let extent = level_extent(extent, level as Level);
+                let size = ((extent.width + block_width - 1) / block_width) as usize
+                    * ((extent.height + block_height - 1) / block_height) as usize
+                    * extent.depth as usize * block_size;
+                let texels = bytes
+                    .get(offset..offset + size)
+                    .ok_or(Error::InvalidArgument("DDS level is out of bounds of the container"))?;
+                offset += size;
+                let packed = (data.len() + REGION_ALIGNMENT - 1) / REGION_ALIGNMENT * REGION_ALIGNMENT;
+                data.resize(packed, 0);
+                data.extend_from_slice(texels);
+                regions.push(region(level as Level, layer as Layer..layer as Layer + 1, extent, packed));
+            }
+        }
+
+        Ok(TextureContainer {
+            kind,
+            levels: levels as Level,
+            formats: fallbacks(format),
+            data,
+            regions,
+        })
+    }
+}
+
+impl<B> Factory<B>
+where
+    B: Backend,
+{
+    /// Create `Texture` from parsed container.
+    /// First format of the container that can be sampled is used.
+    /// All mip-levels and layers are uploaded from single staging buffer.
+    pub fn create_texture_from_container(
+        &mut self,
+        container: &TextureContainer,
+        sampler: Option<SamplerInfo>,
+    ) -> Result<Texture<B>, Error> {
+        self.create_texture_from_regions(
+            container.kind,
+            container.levels,
+            &container.formats,
+            &container.data,
+            &container.regions,
+            sampler,
+        )
+    }
+}
+
+/// Get kind of the texture from dimensions stored in container. Zero dimensions are absent.
+fn container_kind(width: u32, height: u32, depth: u32, layers: u32, cube: bool) -> Result<Kind, Error> {
+    if width == 0 || width > 0xFFFF || height > 0xFFFF || depth > 0xFFFF || layers > 0xFFFF {
+        return Err(Error::Unsupported("Texture size or layer count of the container"));
+    }
+    let kind = match (height, depth, layers, cube) {
+        (_, 0, 0, true) => Kind::Cube(width as _),
+        (_, 0, layers, true) => Kind::CubeArray(width as _, layers as _),
+        (0, 0, 0, false) => Kind::D1(width as _),
+        (0, 0, layers, false) => Kind::D1Array(width as _, layers as _),
+        (height, 0, 0, false) => Kind::D2(width as _, height as _, AaMode::Single),
+        (height, 0, layers, false) => Kind::D2Array(width as _, height as _, layers as _, AaMode::Single),
+        (height, depth, 0, false) => Kind::D3(width as _, height as _, depth as _),
+        _ => return Err(Error::Unsupported("Arrays of 3D textures")),
+    };
+    Ok(kind)
+}
+
+/// Get formats to try for the format stored in container.
+fn fallbacks(format: Format) -> Vec<Format> {
+    let linear = match format {
+        Format::Rgba8Srgb => Format::Rgba8Unorm,
+        Format::Bgra8Srgb => Format::Bgra8Unorm,
+        Format::Bc1RgbaSrgb => Format::Bc1RgbaUnorm,
+        Format::Bc2Srgb => Format::Bc2Unorm,
+        Format::Bc3Srgb => Format::Bc3Unorm,
+        Format::Bc7Srgb => Format::Bc7Unorm,
+        _ => return vec![format],
+    };
+    vec![format, linear]
+}
+
+/// Get size in bytes, width and height of texel block of the format.
+fn block_info(format: Format) -> (usize, u32, u32) {
+    match format {
+        Format::R8Unorm => (1, 1, 1),
+        Format::Rg8Unorm => (2, 1, 1),
+        Format::Rgba16Float => (8, 1, 1),
+        Format::Rgba32Float => (16, 1, 1),
+        Format::Bc1RgbaUnorm | Format::Bc1RgbaSrgb | Format::Bc4Unorm => (8, 4, 4),
+        Format::Bc2Unorm | Format::Bc2Srgb | Format::Bc3Unorm | Format::Bc3Srgb | Format::Bc5Unorm
+        | Format::Bc6hUfloat | Format::Bc7Unorm | Format::Bc7Srgb => (16, 4, 4),
+        _ => (4, 1, 1),
+    }
+}
+
+/// Get region of color texels of the mip-level.
+fn region(level: Level, layers: ::std::ops::Range<Layer>, extent: Extent, offset: usize) -> BufferImageCopy {
+    BufferImageCopy {
+        buffer_offset: offset as u64,
+        buffer_width: 0,
+        buffer_height: 0,
+        image_layers: SubresourceLayers {
+            aspects: Aspects::COLOR,
+            level,
+            layers,
+        },
+        image_offset: Offset { x: 0, y: 0, z: 0 },
+        image_extent: extent,
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    let bytes = bytes
+        .get(offset..offset + 4)
+        .ok_or(Error::InvalidArgument("Texture container is truncated"))?;
+    Ok(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    Ok(read_u32(bytes, offset)? as u64 | (read_u32(bytes, offset + 4)? as u64) << 32)
+}
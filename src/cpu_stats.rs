@@ -0,0 +1,144 @@
+//! CPU time spent by `Renderer::run` in each phase of the frame.
+//! Long recording means the application is CPU bound,
+//! while long fence waits, acquisitions or presents mean it waits for the GPU, driver or display.
+//!
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Default number of frames `CpuFrameStats` keeps.
+pub const DEFAULT_STATS_WINDOW: usize = 120;
+
+/// CPU time spent in phases of single frame. Times of all targets are summed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuFrameTimes {
+    /// Time spent in `Render::render`.
+    pub recording: Duration,
+
+    /// Time spent waiting for fences of earlier frames to reuse their resources.
+    pub fence_wait: Duration,
+
+    /// Time spent acquiring swapchain images.
+    pub acquire: Duration,
+
+    /// Time spent submitting presentation.
+    pub present: Duration,
+
+    /// Time of whole `Renderer::run` excluding sleep of `FramePacer`.
+    pub total: Duration,
+}
+
+/// Rolling window of CPU times of latest frames.
+#[derive(Clone, Debug)]
+pub struct CpuFrameStats {
+    window: usize,
+    frames: VecDeque<CpuFrameTimes>,
+}
+
+impl CpuFrameStats {
+    /// Create statistics of `window` latest frames.
+    pub fn new(window: usize) -> Self {
+        CpuFrameStats {
+            window: window.max(1),
+            frames: VecDeque::with_capacity(window.max(1)),
+        }
+    }
+
+    /// Get number of frames kept.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Set number of frames kept. Oldest frames beyond it are dropped.
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(1);
+        while self.frames.len() > self.window {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Get times of kept frames, oldest first.
+    pub fn frames(&self) -> &VecDeque<CpuFrameTimes> {
+        &self.frames
+    }
+
+    /// Get times of the latest frame.
+    pub fn latest(&self) -> Option<CpuFrameTimes> {
+        self.frames.back().cloned()
+    }
+
+    /// Get percentile of each phase over kept frames separately.
+    /// `percentile` is clamped to range `0.0 ..= 1.0`, so `0.5` gives median and `1.0` gives maximum.
+    pub fn percentile(&self, percentile: f32) -> Option<CpuFrameTimes> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let percentile = percentile.max(0.0).min(1.0);
+        let rank = ((self.frames.len() - 1) as f32 * percentile).round() as usize;
+        let pick = |phase: fn(&CpuFrameTimes) -> Duration| {
+            let mut times = self.frames.iter().map(phase).collect::<Vec<_>>();
+            times.sort();
+            times[rank]
+        };
+        Some(CpuFrameTimes {
+            recording: pick(|times| times.recording),
+            fence_wait: pick(|times| times.fence_wait),
+            acquire: pick(|times| times.acquire),
+            present: pick(|times| times.present),
+            total: pick(|times| times.total),
+        })
+    }
+
+    /// Get average of each phase over kept frames.
+    pub fn average(&self) -> Option<CpuFrameTimes> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let count = self.frames.len() as u32;
+        let sum = self.frames
+            .iter()
+            .fold(CpuFrameTimes::default(), |sum, times| CpuFrameTimes {
+                recording: sum.recording + times.recording,
+                fence_wait: sum.fence_wait + times.fence_wait,
+                acquire: sum.acquire + times.acquire,
+                present: sum.present + times.present,
+                total: sum.total + times.total,
+            });
+        Some(CpuFrameTimes {
+            recording: sum.recording / count,
+            fence_wait: sum.fence_wait / count,
+            acquire: sum.acquire / count,
+            present: sum.present / count,
+            total: sum.total / count,
+        })
+    }
+
+    /// Drop all kept frames.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub(crate) fn push(&mut self, times: CpuFrameTimes) {
+        if self.frames.len() == self.window {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(times);
+    }
+}
+
+impl Default for CpuFrameStats {
+    fn default() -> Self {
+        CpuFrameStats::new(DEFAULT_STATS_WINDOW)
+    }
+}
+
+/// Run `f` adding time it takes to `phase`.
+pub(crate) fn measure<F, T>(phase: &mut Duration, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let start = Instant::now();
+    let result = f();
+    *phase += start.elapsed();
+    result
+}
@@ -0,0 +1,44 @@
+//! Debug names of objects and debug regions in command buffers (like `VK_EXT_debug_utils`),
+//! making captures in tools like RenderDoc readable.
+//! Backends of the gfx-hal revision this crate depends on keep their raw handles private,
+//! so the crate can't call the extension itself. Applications owning a loader of the extension
+//! install their `DebugMarkers` with `Factory::set_debug_markers`. Naming is skipped until then.
+//! 
+
+use std::fmt::Debug;
+
+use hal::Backend;
+
+/// Object to attach debug name to.
+#[derive(Debug)]
+pub enum DebugObject<'a, B: Backend> {
+    /// Buffer.
+    Buffer(&'a B::Buffer),
+
+    /// Image.
+    Image(&'a B::Image),
+
+    /// Image view.
+    ImageView(&'a B::ImageView),
+
+    /// Sampler.
+    Sampler(&'a B::Sampler),
+
+    /// Command pool.
+    CommandPool(&'a B::CommandPool),
+
+    /// Command buffer.
+    CommandBuffer(&'a B::CommandBuffer),
+}
+
+/// Debug markers capability of the device.
+pub trait DebugMarkers<B: Backend>: Debug + Send + Sync {
+    /// Attach name to the object.
+    fn set_name(&self, device: &B::Device, object: DebugObject<B>, name: &str);
+
+    /// Begin named region of commands.
+    fn begin_region(&self, cbuf: &mut B::CommandBuffer, name: &str);
+
+    /// End region started last with `begin_region`.
+    fn end_region(&self, cbuf: &mut B::CommandBuffer);
+}
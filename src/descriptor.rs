@@ -0,0 +1,144 @@
+//! `DescriptorAllocator` allocates descriptor sets from automatically created pools.
+//! 
+
+use std::collections::VecDeque;
+use std::mem::replace;
+
+use hal::{Backend, Device, DescriptorPool};
+use hal::pso::DescriptorRangeDesc;
+
+use Error;
+
+/// Upper bound of sets in single pool.
+const MAX_SETS_PER_POOL: usize = 4096;
+
+/// Allocates descriptor sets for frames.
+/// Sets are allocated from pools which are created on demand, each one twice as large as previous.
+/// All sets allocated for a frame are freed at once by resetting pools when the frame is retired.
+/// 
+/// `ranges` passed at creation describe descriptors of single set. Every set allocated
+/// must require no more descriptors of each type than that.
+#[derive(Debug)]
+pub struct DescriptorAllocator<B: Backend> {
+    ranges: Vec<DescriptorRangeDesc>,
+    sets_per_pool: usize,
+    frame: u64,
+    active: Vec<B::DescriptorPool>,
+    used: VecDeque<(Vec<B::DescriptorPool>, u64)>,
+    free: Vec<B::DescriptorPool>,
+    pools: usize,
+}
+
+impl<B> DescriptorAllocator<B>
+where
+    B: Backend,
+{
+    /// Create new allocator.
+    /// 
+    /// # Parameters
+    /// 
+    /// `ranges`        - descriptors required by single set.
+    /// `sets_per_pool` - number of sets in first pool.
+    /// 
+    pub fn new(ranges: Vec<DescriptorRangeDesc>, sets_per_pool: usize) -> Self {
+        DescriptorAllocator {
+            ranges,
+            sets_per_pool: sets_per_pool.max(1),
+            frame: 0,
+            active: Vec::new(),
+            used: VecDeque::new(),
+            free: Vec::new(),
+            pools: 0,
+        }
+    }
+
+    /// Get number of pools created so far and not yet destroyed.
+    pub fn pools(&self) -> usize {
+        self.pools
+    }
+
+    /// Allocate descriptor set with `layout` for the `frame`.
+    /// Set stays valid until the `frame` is retired.
+    pub fn allocate(
+        &mut self,
+        device: &B::Device,
+        layout: &B::DescriptorSetLayout,
+        frame: u64,
+    ) -> Result<B::DescriptorSet, Error> {
+        assert!(frame >= self.frame, "Frame index must not decrease");
+        if frame != self.frame {
+            self.finish();
+            self.frame = frame;
+        }
+
+        if let Some(pool) = self.active.last_mut() {
+            if let Ok(set) = pool.allocate_set(layout) {
+                return Ok(set);
+            }
+        }
+
+        // Current pool is exhausted or there is no pool yet.
+        let mut pool = match self.free.pop() {
+            Some(pool) => pool,
+            None => self.create_pool(device),
+        };
+        let set = pool.allocate_set(layout)
+            .map_err(|err| Error::descriptor_set("descriptor set from fresh pool", err));
+        self.active.push(pool);
+        Ok(set?)
+    }
+
+    /// Reset pools of frames before `ongoing` making them available for allocation.
+    pub fn clear(&mut self, ongoing: u64) {
+        if self.frame < ongoing {
+            self.finish();
+        }
+        while let Some((pools, frame)) = self.used.pop_front() {
+            if frame >= ongoing {
+                self.used.push_front((pools, frame));
+                break;
+            }
+            for mut pool in pools {
+                pool.reset();
+                self.free.push(pool);
+            }
+        }
+    }
+
+    /// Destroy all pools.
+    /// All sets allocated must not be used by the device anymore.
+    pub fn dispose(mut self, device: &B::Device) {
+        self.finish();
+        for (pools, _) in self.used.drain(..) {
+            for pool in pools {
+                device.destroy_descriptor_pool(pool);
+            }
+        }
+        for pool in self.free.drain(..) {
+            device.destroy_descriptor_pool(pool);
+        }
+    }
+
+    fn finish(&mut self) {
+        if !self.active.is_empty() {
+            let pools = replace(&mut self.active, Vec::new());
+            self.used.push_back((pools, self.frame));
+        }
+    }
+
+    fn create_pool(&mut self, device: &B::Device) -> B::DescriptorPool {
+        let sets = self.sets_per_pool;
+        let ranges = self.ranges
+            .iter()
+            .map(|range| DescriptorRangeDesc {
+                ty: range.ty,
+                count: range.count * sets,
+            })
+            .collect::<Vec<_>>();
+        info!("Create descriptor pool for {} sets", sets);
+        let pool = device.create_descriptor_pool(sets, &ranges);
+        self.sets_per_pool = (sets * 2).min(MAX_SETS_PER_POOL);
+        self.pools += 1;
+        pool
+    }
+}
@@ -0,0 +1,205 @@
+//! Runtime selection of backend among ones enabled by features.
+//! `init_dynamic` tries backends in order of `BackendPreference` and passes the first one that initializes
+//! to `BackendSetup`, which returns renderer erased behind `DynRenderer`,
+//! so applications don't have to be generic over the backend above the setup.
+//!
+
+use std::time::Duration;
+
+use hal::Backend;
+
+use Error;
+use backend::BackendEx;
+use factory::Factory;
+use init::{init_with_config, Config};
+use renderer::{Render, Renderer};
+
+#[cfg(feature = "gfx-backend-vulkan")]
+use vulkan;
+
+#[cfg(feature = "gfx-backend-metal")]
+use metal;
+
+#[cfg(feature = "gfx-backend-dx12")]
+use dx12;
+
+#[cfg(not(any(feature = "gfx-backend-vulkan", feature = "gfx-backend-metal", feature = "gfx-backend-dx12")))]
+use empty;
+
+/// Backends the crate knows of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum BackendKind {
+    /// Vulkan. Enabled with `gfx-backend-vulkan` feature.
+    Vulkan,
+
+    /// DirectX 12. Enabled with `gfx-backend-dx12` feature.
+    Dx12,
+
+    /// Metal. Enabled with `gfx-backend-metal` feature.
+    Metal,
+
+    /// Backend that does nothing. Enabled when no other backend is.
+    Empty,
+}
+
+impl BackendKind {
+    /// Check if support of the backend is compiled in.
+    pub fn is_enabled(&self) -> bool {
+        match *self {
+            BackendKind::Vulkan => cfg!(feature = "gfx-backend-vulkan"),
+            BackendKind::Dx12 => cfg!(feature = "gfx-backend-dx12"),
+            BackendKind::Metal => cfg!(feature = "gfx-backend-metal"),
+            BackendKind::Empty => cfg!(not(any(
+                feature = "gfx-backend-vulkan",
+                feature = "gfx-backend-metal",
+                feature = "gfx-backend-dx12"
+            ))),
+        }
+    }
+}
+
+/// Order in which `init_dynamic` tries backends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackendPreference(pub Vec<BackendKind>);
+
+impl BackendPreference {
+    /// Get enabled backends in order of preference.
+    pub fn enabled(&self) -> Vec<BackendKind> {
+        self.0
+            .iter()
+            .cloned()
+            .filter(|kind| kind.is_enabled())
+            .collect()
+    }
+}
+
+impl Default for BackendPreference {
+    /// Native backend of the platform first, then Vulkan, then others.
+    fn default() -> Self {
+        let mut order = if cfg!(target_os = "macos") {
+            vec![BackendKind::Metal, BackendKind::Vulkan]
+        } else if cfg!(windows) {
+            vec![BackendKind::Vulkan, BackendKind::Dx12]
+        } else {
+            vec![BackendKind::Vulkan]
+        };
+        order.push(BackendKind::Empty);
+        BackendPreference(order)
+    }
+}
+
+/// Renderer with its `Factory` for backend picked at runtime.
+pub trait DynRenderer<T> {
+    /// Get kind of the backend.
+    fn backend(&self) -> BackendKind;
+
+    /// Submit uploads and draw a frame for each target. See `Renderer::run`.
+    fn run(&mut self, data: &mut T) -> Result<(), Error>;
+
+    /// Wait for frames to complete, but no longer than `timeout`. See `Renderer::wait_idle_timeout`.
+    fn wait_idle_timeout(&mut self, timeout: Duration) -> Result<(), Error>;
+
+    /// Dispose `Renderer` and then `Factory`. Surfaces and renders of targets are dropped.
+    fn dispose(self: Box<Self>);
+}
+
+/// `Factory` and `Renderer` of the backend `BackendSetup` erases behind `DynRenderer`.
+pub struct Backed<B: Backend, R> {
+    factory: Factory<B>,
+    renderer: Renderer<B, R>,
+}
+
+impl<B, R> Backed<B, R>
+where
+    B: BackendEx,
+    R: Send + Sync + 'static,
+{
+    /// Init the backend and create `Factory` and `Renderer` with `config`.
+    pub fn init(config: Config) -> Result<Self, Error> {
+        let (factory, renderer) = init_with_config(config)?;
+        Ok(Backed { factory, renderer })
+    }
+
+    /// Get `Factory` of the backend.
+    pub fn factory(&mut self) -> &mut Factory<B> {
+        &mut self.factory
+    }
+
+    /// Get `Renderer` and `Factory` of the backend to add targets and renders.
+    pub fn renderer(&mut self) -> (&mut Renderer<B, R>, &mut Factory<B>) {
+        (&mut self.renderer, &mut self.factory)
+    }
+}
+
+impl<B, R, T> DynRenderer<T> for Backed<B, R>
+where
+    B: BackendEx,
+    R: Render<B, T> + Send + Sync + 'static,
+{
+    fn backend(&self) -> BackendKind {
+        B::kind()
+    }
+
+    fn run(&mut self, data: &mut T) -> Result<(), Error> {
+        self.renderer.run(data, &mut self.factory)
+    }
+
+    fn wait_idle_timeout(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.renderer.wait_idle_timeout(&mut self.factory, timeout)
+    }
+
+    fn dispose(self: Box<Self>) {
+        let Backed { mut factory, renderer } = *self;
+        renderer.dispose(&mut factory);
+        factory.dispose();
+    }
+}
+
+/// Creates backend specific parts of the application once backend is picked.
+pub trait BackendSetup<T> {
+    /// Init the backend, usually with `Backed::init`, and add targets and renders.
+    fn setup<B>(&mut self, config: &Config) -> Result<Box<DynRenderer<T>>, Error>
+    where
+        B: BackendEx;
+}
+
+/// Try enabled backends in order of `preference` until `setup` succeeds with one of them.
+///
+/// # Parameters
+///
+/// `preference`    - order of backends to try. Backends not enabled by features are skipped.
+/// `config`        - configuration to init each backend with.
+/// `setup`         - creates renderer for the backend.
+///
+pub fn init_dynamic<T, S>(
+    preference: &BackendPreference,
+    config: Config,
+    setup: &mut S,
+) -> Result<Box<DynRenderer<T>>, Error>
+where
+    S: BackendSetup<T>,
+{
+    for kind in preference.enabled() {
+        let result = match kind {
+            #[cfg(feature = "gfx-backend-vulkan")]
+            BackendKind::Vulkan => setup.setup::<vulkan::Backend>(&config),
+            #[cfg(feature = "gfx-backend-dx12")]
+            BackendKind::Dx12 => setup.setup::<dx12::Backend>(&config),
+            #[cfg(feature = "gfx-backend-metal")]
+            BackendKind::Metal => setup.setup::<metal::Backend>(&config),
+            #[cfg(not(any(feature = "gfx-backend-vulkan", feature = "gfx-backend-metal",
+                          feature = "gfx-backend-dx12")))]
+            BackendKind::Empty => setup.setup::<empty::Backend>(&config),
+            _ => continue,
+        };
+        match result {
+            Ok(renderer) => {
+                info!("Backend {:?} picked", kind);
+                return Ok(renderer);
+            }
+            Err(err) => warn!("Failed to init backend {:?}: {}", kind, err),
+        }
+    }
+    Err(Error::NoBackend(preference.0.clone()))
+}
@@ -0,0 +1,347 @@
+//! Error type of the crate.
+//! Failures callers may handle are separate variants keeping their causes.
+//! Device and allocator running out of memory is always reported as `Error::OutOfMemory`
+//! so callers can free resources and retry.
+//! Invalid arguments that have no dedicated variant are reported as `Error::InvalidArgument`.
+//!
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::fmt::Debug;
+use std::io;
+
+use hal::error::HostExecutionError;
+use hal::format::Format;
+use hal::mapping::Error as MappingError;
+use hal::memory::Properties;
+use hal::pso::AllocationError;
+use hal::queue::QueueFamilyId;
+use mem::{FactoryError, MemoryError};
+
+#[cfg(feature = "gltf")]
+use gltf;
+
+use adapter::AdapterReport;
+use dynamic::BackendKind;
+use renderer::TargetId;
+use stuck::StuckWork;
+
+/// Error of the crate.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Device or host is out of memory.
+    OutOfMemory {
+        /// What was created.
+        what: &'static str,
+    },
+
+    /// Memory budget of the heap set with `Factory::set_memory_budget` is exceeded.
+    BudgetExceeded {
+        /// Index of the memory heap.
+        heap: usize,
+        /// Bytes allocated from the heap.
+        allocated: u64,
+        /// Bytes requested.
+        requested: u64,
+        /// Budget of the heap.
+        budget: u64,
+    },
+
+    /// Allocator failed to create resource or allocate memory for it for reason other than lack of memory.
+    Allocation {
+        /// What was created.
+        what: &'static str,
+        /// Error of the allocator.
+        cause: Box<StdError + Send>,
+    },
+
+    /// Device failed to create object.
+    Creation {
+        /// What was created.
+        what: String,
+        /// Description of the device error.
+        cause: String,
+    },
+
+    /// Memory can't be mapped.
+    MappingFailed(MappingError),
+
+    /// Range of `len` bytes at `offset` is out of bounds of resource of `size` bytes.
+    OutOfBounds {
+        /// Start of the range.
+        offset: u64,
+        /// Length of the range.
+        len: u64,
+        /// Size of the resource.
+        size: u64,
+    },
+
+    /// Device is lost. Objects created from it must be destroyed and recreated with new device.
+    /// See `recover`.
+    DeviceLost,
+
+    /// Submitted work didn't complete in time. See `Renderer::wait_idle_timeout`.
+    WaitTimeout(StuckWork),
+
+    /// Waiting for device or queue failed for reason other than device loss.
+    Execution(HostExecutionError),
+
+    /// Capability is not supported by the device or backend.
+    Unsupported(&'static str),
+
+    /// Format is not supported for the use.
+    UnsupportedFormat(Format),
+
+    /// None of candidate formats is supported for the use.
+    NoSupportedFormat(Vec<Format>),
+
+    /// No memory type compatible with the resource has the properties.
+    NoMemoryType(Properties),
+
+    /// Memory heap with the index doesn't exist.
+    UnknownHeap {
+        /// Index of the memory heap.
+        heap: usize,
+        /// Number of heaps of the device.
+        heaps: usize,
+    },
+
+    /// No suitable adapter is found. Report lists reasons of rejection.
+    NoSuitableAdapter(AdapterReport),
+
+    /// None of preferred backends could be initialized.
+    NoBackend(Vec<BackendKind>),
+
+    /// Target with the id doesn't exist or was removed.
+    UnknownTarget(TargetId),
+
+    /// Target doesn't render into the expected kind of output.
+    WrongTargetKind {
+        /// Id of the target.
+        target: TargetId,
+        /// Expected kind of output.
+        expected: &'static str,
+    },
+
+    /// Index is out of range.
+    IndexOutOfRange {
+        /// What is indexed.
+        what: &'static str,
+        /// The index.
+        index: usize,
+        /// Number of items.
+        len: usize,
+    },
+
+    /// Size or count of provided data doesn't match the expected one.
+    SizeMismatch {
+        /// What is measured.
+        what: &'static str,
+        /// Expected size.
+        expected: u64,
+        /// Actual size.
+        actual: u64,
+    },
+
+    /// Fixed capacity is used up.
+    Exhausted(&'static str),
+
+    /// Resource or commands belong to queue family other than the one they are used on.
+    QueueFamilyMismatch {
+        /// Queue family resource or commands belong to.
+        expected: QueueFamilyId,
+        /// Queue family they are used on.
+        found: QueueFamilyId,
+    },
+
+    /// Shader code is not valid SPIR-V or uses constructs reflection doesn't support.
+    InvalidSpirv(&'static str),
+
+    /// Descriptor binding is declared differently across stages.
+    BindingMismatch {
+        /// Index of descriptor set.
+        set: usize,
+        /// Binding within the set.
+        binding: usize,
+    },
+
+    /// Vertex input of shader isn't provided by vertex formats or has different type.
+    VertexInputMismatch {
+        /// Location of the input.
+        location: u32,
+    },
+
+    /// Render graph is invalid.
+    InvalidGraph {
+        /// Name of the pass or resource.
+        name: String,
+        /// What is wrong with it.
+        reason: &'static str,
+    },
+
+    /// Passes of render graph depend on each other.
+    GraphCycle(Vec<String>),
+
+    /// Argument is invalid.
+    InvalidArgument(&'static str),
+
+    /// Renderer config can't be parsed or doesn't match the device. See `RendererConfig`.
+    Config(String),
+
+    /// Reading file failed.
+    Io(io::Error),
+
+    /// Importing glTF failed.
+    #[cfg(feature = "gltf")]
+    Gltf(gltf::Error),
+}
+
+impl Error {
+    /// Wrap error of the allocator creating resource.
+    pub(crate) fn allocation(what: &'static str, cause: FactoryError) -> Self {
+        match cause {
+            FactoryError::MemoryError(cause) => Error::memory(what, cause),
+            cause => Error::Allocation {
+                what,
+                cause: Box::new(cause),
+            },
+        }
+    }
+
+    /// Wrap error of the allocator allocating memory.
+    pub(crate) fn memory(what: &'static str, cause: MemoryError) -> Self {
+        match cause {
+            MemoryError::OutOfMemory { .. } => Error::OutOfMemory { what },
+            cause => Error::Allocation {
+                what,
+                cause: Box::new(cause),
+            },
+        }
+    }
+
+    /// Wrap error of descriptor pool allocating set.
+    pub(crate) fn descriptor_set(what: &'static str, cause: AllocationError) -> Self {
+        match cause {
+            AllocationError::OutOfHostMemory | AllocationError::OutOfDeviceMemory => Error::OutOfMemory { what },
+            cause => Error::creation(what, cause),
+        }
+    }
+
+    /// Wrap error of the device creating object.
+    pub(crate) fn creation<W, E>(what: W, cause: E) -> Self
+    where
+        W: Into<String>,
+        E: Debug,
+    {
+        Error::Creation {
+            what: what.into(),
+            cause: format!("{:?}", cause),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::OutOfMemory { what } => write!(fmt, "Out of memory creating {}", what),
+            Error::BudgetExceeded {
+                heap,
+                allocated,
+                requested,
+                budget,
+            } => write!(
+                fmt,
+                "Memory budget of heap {} exceeded. Allocated: {}, requested: {}, budget: {}",
+                heap, allocated, requested, budget
+            ),
+            Error::Allocation { what, ref cause } => write!(fmt, "Failed to create {}: {}", what, cause),
+            Error::Creation { ref what, ref cause } => write!(fmt, "Failed to create {}: {}", what, cause),
+            Error::MappingFailed(ref err) => write!(fmt, "Failed to map memory: {}", err),
+            Error::OutOfBounds { offset, len, size } => write!(
+                fmt,
+                "Range of {} bytes at {} is out of bounds 0..{}",
+                len, offset, size
+            ),
+            Error::DeviceLost => write!(fmt, "Device lost"),
+            Error::WaitTimeout(ref work) => write!(fmt, "Timed out waiting for device to become idle: {}", work),
+            Error::Execution(ref err) => write!(fmt, "Failed to wait for device: {}", err),
+            Error::Unsupported(what) => write!(fmt, "{} is not supported", what),
+            Error::UnsupportedFormat(format) => write!(fmt, "Format {:?} is not supported", format),
+            Error::NoSupportedFormat(ref formats) => write!(fmt, "None of formats {:?} is supported", formats),
+            Error::NoMemoryType(properties) => write!(fmt, "No compatible memory type with {:?} properties", properties),
+            Error::UnknownHeap { heap, heaps } => write!(
+                fmt,
+                "Memory heap {} doesn't exist. Device has {} heaps",
+                heap, heaps
+            ),
+            Error::NoSuitableAdapter(ref report) => write!(fmt, "No suitable adapter found:\n{}", report),
+            Error::NoBackend(ref kinds) => write!(fmt, "None of backends {:?} could be initialized", kinds),
+            Error::UnknownTarget(id) => write!(fmt, "No render with id {:?}", id),
+            Error::WrongTargetKind { target, expected } => write!(fmt, "Target {:?} is not {}", target, expected),
+            Error::IndexOutOfRange { what, index, len } => write!(fmt, "No {} with index {} of {}", what, index, len),
+            Error::SizeMismatch {
+                what,
+                expected,
+                actual,
+            } => write!(fmt, "Expected {} of {}, got {}", what, expected, actual),
+            Error::Exhausted(what) => write!(fmt, "{} is exhausted", what),
+            Error::QueueFamilyMismatch { expected, found } => write!(
+                fmt,
+                "Expected queue family {:?}, used on queue family {:?}",
+                expected, found
+            ),
+            Error::InvalidSpirv(what) => write!(fmt, "Invalid SPIR-V: {}", what),
+            Error::BindingMismatch { set, binding } => write!(
+                fmt,
+                "Binding {} of set {} declared differently across stages",
+                binding, set
+            ),
+            Error::VertexInputMismatch { location } => write!(
+                fmt,
+                "Vertex input at location {} is not provided by vertex formats or has different type",
+                location
+            ),
+            Error::InvalidGraph { ref name, reason } => write!(fmt, "Invalid render graph. {}: {}", name, reason),
+            Error::GraphCycle(ref passes) => write!(fmt, "Render graph has cyclic dependencies between passes {:?}", passes),
+            Error::InvalidArgument(what) => write!(fmt, "Invalid argument: {}", what),
+            Error::Config(ref msg) => write!(fmt, "Invalid renderer config: {}", msg),
+            Error::Io(ref err) => write!(fmt, "IO error: {}", err),
+            #[cfg(feature = "gltf")]
+            Error::Gltf(ref err) => write!(fmt, "Failed to import glTF: {}", err),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(StdError + 'static)> {
+        match *self {
+            Error::Allocation { ref cause, .. } => Some(&**cause),
+            Error::MappingFailed(ref err) => Some(err),
+            Error::Execution(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            #[cfg(feature = "gltf")]
+            Error::Gltf(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<MappingError> for Error {
+    fn from(err: MappingError) -> Self {
+        Error::MappingFailed(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "gltf")]
+impl From<gltf::Error> for Error {
+    fn from(err: gltf::Error) -> Self {
+        Error::Gltf(err)
+    }
+}
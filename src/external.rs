@@ -0,0 +1,139 @@
+//! Semaphores and fences of producers and consumers of frames outside of the `Renderer`,
+//! like video decoders and streaming submitting to queues of the same device.
+//! They are waited for and signaled by frames of the `Renderer`,
+//! so such producers and consumers synchronize with rendering without waiting on the host.
+//!
+
+use std::collections::VecDeque;
+
+use hal::{Backend, Device};
+use hal::pso::PipelineStage;
+use hal::queue::{RawCommandQueue, RawSubmission};
+
+use factory::Factory;
+use frame::{FrameGuard, Frames};
+
+/// Semaphores and fences of external producers and consumers of frames of the `Renderer`.
+/// Ones passed to `Renderer` are used by the next frame and given back once the frame is complete.
+pub(crate) struct ExternalSubmits<B: Backend> {
+    waits: Vec<(B::Semaphore, PipelineStage)>,
+    signals: Vec<B::Semaphore>,
+    fences: Vec<B::Fence>,
+    used: VecDeque<(Vec<B::Semaphore>, Vec<B::Fence>, u64)>,
+}
+
+impl<B> ExternalSubmits<B>
+where
+    B: Backend,
+{
+    pub(crate) fn new() -> Self {
+        ExternalSubmits {
+            waits: Vec::new(),
+            signals: Vec::new(),
+            fences: Vec::new(),
+            used: VecDeque::new(),
+        }
+    }
+
+    /// Let the next frame wait for the semaphore before rendering at `stages`.
+    pub(crate) fn wait(&mut self, semaphore: B::Semaphore, stages: PipelineStage) {
+        self.waits.push((semaphore, stages));
+    }
+
+    /// Let the next frame signal the semaphore after rendering.
+    pub(crate) fn signal(&mut self, semaphore: B::Semaphore) {
+        self.signals.push(semaphore);
+    }
+
+    /// Let the next frame signal the fence after rendering.
+    pub(crate) fn signal_fence(&mut self, fence: B::Fence) {
+        self.fences.push(fence);
+    }
+
+    /// Get semaphores submissions of the frame wait for.
+    pub(crate) fn waits(&self) -> &[(B::Semaphore, PipelineStage)] {
+        &self.waits
+    }
+
+    /// Submit signaling of semaphores and fences after commands submitted to the queue earlier.
+    /// Last submission signals new fence of the `current` frame,
+    /// so semaphores and fences are kept until all their operations are complete.
+    pub(crate) fn flush(&mut self, queue: &mut B::CommandQueue, current: &mut FrameGuard<B>, factory: &mut Factory<B>) {
+        if self.signals.is_empty() && self.waits.is_empty() && self.fences.is_empty() {
+            return;
+        }
+        let fences = self.fences.drain(..).collect::<Vec<_>>();
+        if !self.signals.is_empty() || !fences.is_empty() {
+            unsafe {
+                for fence in &fences {
+                    queue.submit_raw(
+                        RawSubmission {
+                            cmd_buffers: None::<&B::CommandBuffer>,
+                            wait_semaphores: &[],
+                            signal_semaphores: &[],
+                        },
+                        Some(fence),
+                    );
+                }
+                let signals = self.signals.iter().collect::<Vec<_>>();
+                queue.submit_raw(
+                    RawSubmission {
+                        cmd_buffers: None::<&B::CommandBuffer>,
+                        wait_semaphores: &[],
+                        signal_semaphores: &signals,
+                    },
+                    Some(current.add_fence(factory.acquire_fence())),
+                );
+            }
+        }
+        let frame = current.index();
+        let semaphores = self.waits
+            .drain(..)
+            .map(|(semaphore, _)| semaphore)
+            .chain(self.signals.drain(..))
+            .collect();
+        self.used.push_back((semaphores, fences, frame));
+    }
+
+    /// Take semaphores and fences used by complete frames.
+    pub(crate) fn retire(&mut self, frames: &Frames<B>) -> (Vec<B::Semaphore>, Vec<B::Fence>) {
+        let mut semaphores = Vec::new();
+        let mut fences = Vec::new();
+        while self.used
+            .front()
+            .map_or(false, |&(_, _, frame)| frames.is_complete(frame))
+        {
+            let (s, f, _) = self.used.pop_front().unwrap();
+            semaphores.extend(s);
+            fences.extend(f);
+        }
+        (semaphores, fences)
+    }
+
+    /// Destroy all semaphores and fences. Queues must be idle.
+    pub(crate) fn dispose(self, device: &B::Device) {
+        let ExternalSubmits {
+            waits,
+            signals,
+            fences,
+            used,
+        } = self;
+        for (semaphore, _) in waits {
+            device.destroy_semaphore(semaphore);
+        }
+        for semaphore in signals {
+            device.destroy_semaphore(semaphore);
+        }
+        for fence in fences {
+            device.destroy_fence(fence);
+        }
+        for (semaphores, fences, _) in used {
+            for semaphore in semaphores {
+                device.destroy_semaphore(semaphore);
+            }
+            for fence in fences {
+                device.destroy_fence(fence);
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 //! This module provide convenient `Factory` type that can be used for several tasks:
 //! 1. Creating new `Buffer`s and `Image`s.
-//! 2. Destroying `Buffer`s and `Image`s with additional safety.
+//! 2. Destroying `Buffer`s, `Image`s, views, samplers, render passes, framebuffers and pipelines with additional safety.
 //! 3. Uploading data to `Buffer`s and `Image`s.
 //! 4. Creating `Surface`s and fetching their capabilities.
 //! 5. Fetching `Features` and `Limits` of the GPU.
@@ -9,40 +9,86 @@
 
 use std::any::Any;
 use std::borrow::{Borrow, BorrowMut};
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::mem::replace;
+use std::ptr::write_bytes;
 use std::ops::{Deref, DerefMut, Range};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
-use hal::{Backend, Instance, Surface};
-use hal::buffer::Usage as BufferUsage;
+use hal::{Backend, Device, Instance, Limits, MemoryProperties, MemoryTypeId, Surface};
+use hal::adapter::PhysicalDevice;
+use hal::buffer::{Access as BufferAccess, Usage as BufferUsage};
+use hal::command::{BufferImageCopy, RawCommandBuffer};
 use hal::device::Extent;
-use hal::format::Format;
-use hal::image::{Kind, Level, ImageLayout, Offset, Usage as ImageUsage, SubresourceLayers};
-use hal::memory::Properties;
+use hal::format::{Aspects, Format, Properties as FormatProperties};
+use hal::image::{Access as ImageAccess, Kind, Layer, Level, ImageLayout, Offset, SamplerInfo, State as ImageState,
+                 Usage as ImageUsage, SubresourceLayers, SubresourceRange};
+use hal::pass::Subpass;
+use hal::pso::{ComputePipelineDesc, EntryPoint, PipelineStage};
+use hal::memory::{Barrier, Properties, Requirements};
 use hal::queue;
 use hal::window::SurfaceCapabilities;
 
 use mem::{Block, Factory as FactoryTrait, SmartAllocator, SmartBlock, Type};
 
+use raw_window_handle::HasRawWindowHandle;
+#[cfg(feature = "winit")]
 use winit::Window;
 
 use Error;
+use adapter::AdapterReport;
 use backend::BackendEx;
+use budget::{HeapUsage, MemoryBudget, MemoryReport, MemoryTag, TagUsage};
+use breadcrumb::{BreadcrumbMarkers, BreadcrumbReport, BreadcrumbStream, Breadcrumbs};
+use builder::{required_features, BufferBuilder, ImageBuilder};
+use compute::{Compute, ComputeEncoder};
 use escape::{Escape, Terminal};
+use frame::{FrameGuard, Frames};
+use debug::{DebugMarkers, DebugObject};
+use handle::{Handle, Registry};
+use leak::{LeakTracker, LeakedResource, LiveResource};
+use instrument;
+use lost::host_execution_error;
+use pipeline::GraphicsPipelineBuilder;
 use reclamation::ReclamationQueue;
-use upload::Upload;
+use robustness::Robustness;
+use shared::{Shared, SharedBuffer, SharedComputePipeline, SharedFramebuffer, SharedGraphicsPipeline, SharedImage,
+             SharedImageView, SharedRenderPass, SharedSampler, SharedTerminals};
+use state::{BufferTracking, ImageTracking, Tracked};
+use stuck::StuckWork;
+use sync::SyncPool;
+use telemetry;
+use texture::kind_extent;
+use topology::QueueTopology;
+use upload::{create_staging_buffer, read_cpu_visible_block, tune_staging_threshold, update_cpu_visible_block,
+             write_cpu_visible_block,
+             ResourceKey, StagingInfo, StagingStats, Upload, UploadGroup, UploadId, UploadPriority, UploadRecorder,
+             STAGING_CHUNK_SIZE};
+#[cfg(feature = "trace")]
+use trace::{Trace, TraceOp, TraceRecorder};
 
 pub use mem::Item as RelevantItem;
 
 
 /// Wrapper around raw gpu resource like `B::Buffer` or `B::Image`
+/// along with state its last recorded use left it in.
 /// It will send raw resource back to the `Factory` if dropped.
 /// Destroying it manually with `Factory::destroy_*` is better performance-wise.
 #[derive(Debug)]
-pub struct Item<I, B> {
+pub struct Item<I, B, S> {
     inner: Escape<RelevantItem<I, B>>,
+    state: S,
+    padding: u64,
+    tracked: Arc<LiveResource>,
+    #[cfg(feature = "trace")]
+    trace_id: u64,
 }
 
-impl<I, B> Item<I, B> {
+impl<I, B, S> Item<I, B, S> {
     /// Get raw gpu resource.
     pub fn raw(&self) -> &I {
         self.inner.raw()
@@ -59,24 +105,51 @@ impl<I, B> Item<I, B> {
     pub fn into_inner(self) -> RelevantItem<I, B> {
         Escape::into_inner(self.inner)
     }
+
+    /// Get number of bytes robustness mode appended to the buffer. See `Robustness::padding`.
+    pub fn padding(&self) -> u64 {
+        self.padding
+    }
+
+    /// Get tracked state of the resource.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Get tracked state of the resource to update it after barriers recorded by hand.
+    pub fn state_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
+    /// Name the resource for leak reports of `Factory::dispose`.
+    /// Use `Factory::set_debug_name` to name it for debugging tools.
+    pub fn set_name(&self, name: &str) {
+        self.tracked.set_name(name);
+    }
+
+    /// Get name given with `Item::set_name`.
+    pub fn name(&self) -> Option<String> {
+        self.tracked.name()
+    }
 }
 
-impl<I, B> Borrow<I> for Item<I, B> {
+impl<I, B, S> Borrow<I> for Item<I, B, S> {
     fn borrow(&self) -> &I {
         (&*self.inner).borrow()
     }
 }
 
-impl<I, B> BorrowMut<I> for Item<I, B> {
+impl<I, B, S> BorrowMut<I> for Item<I, B, S> {
     fn borrow_mut(&mut self) -> &mut I {
         (&mut *self.inner).borrow_mut()
     }
 }
 
-impl<I, B> Block for Item<I, B>
+impl<I, B, S> Block for Item<I, B, S>
 where
     I: Debug + Send + Sync,
     B: Block,
+    S: Debug + Send + Sync,
 {
     type Memory = B::Memory;
     fn memory(&self) -> &Self::Memory {
@@ -88,28 +161,61 @@ where
 }
 
 /// Buffer type `Factory` creates
-pub type Buffer<B: Backend> = Item<B::Buffer, SmartBlock<B::Memory>>;
+pub type Buffer<B: Backend> = Item<B::Buffer, SmartBlock<B::Memory>, BufferTracking>;
 
 /// Image type `Factory` creates
-pub type Image<B: Backend> = Item<B::Image, SmartBlock<B::Memory>>;
+pub type Image<B: Backend> = Item<B::Image, SmartBlock<B::Memory>, ImageTracking>;
+
+/// Handle to `Buffer` stored in `Factory`.
+pub type BufferHandle<B: Backend> = Handle<Buffer<B>>;
+
+/// Handle to `Image` stored in `Factory`.
+pub type ImageHandle<B: Backend> = Handle<Image<B>>;
 
 /// `Factory` is a central type that wraps GPU device and responsible for:
 /// 1. Creating new `Buffer`s and `Image`s.
-/// 2. Destroying `Buffer`s and `Image`s with additional safety.
+/// 2. Destroying `Buffer`s, `Image`s, views, samplers, render passes, framebuffers and pipelines with additional safety.
 /// 3. Uploading data to `Buffer`s and `Image`s.
 /// 4. Creating `Surface`s and fetching their capabilities.
 /// 5. Fetching `Features` and `Limits` of the GPU.
+///
+/// Creating `Buffer`s, `Image`s and staging buffers requires only shared reference,
+/// allocator and memory budget are guarded by their own locks.
+/// Worker threads upload with `Factory::upload_buffer_with` and `Factory::upload_image_with`
+/// into recorders fetched with `Factory::upload_recorder`, one per thread, so recording isn't serialized.
+/// Resources dropped on any thread are destroyed by the `Factory` after commands referencing them complete.
 /// 
 pub struct Factory<B: Backend> {
     instance: Box<Instance<Backend = B>>,
     physical: B::PhysicalDevice,
     device: B::Device,
-    allocator: SmartAllocator<B>,
+    allocator: Mutex<SmartAllocator<B>>,
     reclamation: ReclamationQueue<AnyItem<B>>,
-    current: u64,
+    pinned: Vec<AnyItem<B>>,
+    frames: Frames<B>,
     upload: Upload<B>,
+    compute: Compute<B>,
+    acquires: Compute<B>,
+    recorders: UploadGroup<B>,
+    topology: QueueTopology,
+    lost: bool,
+    robustness: Option<Robustness>,
+    budget: Mutex<MemoryBudget>,
+    debug: Option<Arc<DebugMarkers<B>>>,
+    breadcrumb_markers: Option<Arc<BreadcrumbMarkers<B>>>,
+    breadcrumbs: Option<Breadcrumbs<B>>,
+    adapter_report: Option<AdapterReport>,
+    leaks: Mutex<LeakTracker>,
+    frame_allocations: AtomicUsize,
+    #[cfg(feature = "trace")]
+    trace: Mutex<TraceRecorder>,
+    samplers: HashMap<SamplerKey, SharedSampler<B>>,
+    sync: SyncPool<B>,
+    buffer_handles: Registry<Buffer<B>>,
+    image_handles: Registry<Image<B>>,
     buffers: Terminal<RelevantBuffer<B>>,
     images: Terminal<RelevantImage<B>>,
+    shared: SharedTerminals<B>,
 }
 
 impl<B> Factory<B>
@@ -124,21 +230,67 @@ where
     /// `usage`         - how buffer is supposed to be used. Caller must specify all usages and never use buffer otherwise.
     /// 
     pub fn create_buffer(
-        &mut self,
+        &self,
+        size: u64,
+        properties: Properties,
+        usage: BufferUsage,
+    ) -> Result<Buffer<B>, Error> {
+        self.create_buffer_of_type(Type::General, size, properties, usage)
+    }
+
+    /// Start building new `Buffer` with presets for common cases.
+    pub fn buffer(&mut self) -> BufferBuilder<B> {
+        BufferBuilder::new(self)
+    }
+
+    pub(crate) fn create_buffer_of_type(
+        &self,
+        ty: Type,
         size: u64,
         properties: Properties,
         usage: BufferUsage,
     ) -> Result<Buffer<B>, Error> {
-        let buffer: RelevantBuffer<B> = self.allocator
+        let padding = self.robustness.map_or(0, |robustness| robustness.padding);
+        let size = size + padding;
+        let mut allocator = self.allocator.lock().unwrap();
+        let buffer: RelevantBuffer<B> = allocator
             .create_buffer(
                 self.device.borrow(),
-                (Type::General, properties),
+                (ty, properties),
                 size,
                 usage,
             )
-            .map_err(|err| Error::with_chain(err, "Failed to create buffer"))?;
+            .map_err(|err| Error::allocation("buffer", err))?;
+        let allocated = {
+            let mut budget = self.budget.lock().unwrap();
+            let heaps = budget.heaps(!0, allocator.properties(buffer.block()));
+            budget.allocate_block(MemoryTag::Buffer, memory_address(buffer.block()), &heaps, buffer.block().range())
+        };
+        if let Err(err) = allocated {
+            allocator.destroy_buffer(self.device.borrow(), buffer);
+            return Err(err);
+        }
+        drop(allocator);
+        self.frame_allocations.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "trace")]
+        let trace_id = {
+            let mut trace = self.trace.lock().unwrap();
+            let trace_id = trace.next_id();
+            trace.record(|| TraceOp::CreateBuffer {
+                id: trace_id,
+                size,
+                properties,
+                usage,
+            });
+            trace_id
+        };
         Ok(Item {
             inner: self.buffers.escape(buffer),
+            state: BufferTracking::new(BufferAccess::empty()),
+            padding,
+            tracked: self.leaks.lock().unwrap().track(format!("Buffer of {} bytes with usage {:?}", size, usage)),
+            #[cfg(feature = "trace")]
+            trace_id,
         })
     }
 
@@ -153,14 +305,15 @@ where
     /// `usage`         - how buffer is supposed to be used. Caller must specify all usages and never use buffer otherwise.
     /// 
     pub fn create_image(
-        &mut self,
+        &self,
         kind: Kind,
         level: Level,
         format: Format,
         properties: Properties,
         usage: ImageUsage,
     ) -> Result<Image<B>, Error> {
-        let image = self.allocator
+        let mut allocator = self.allocator.lock().unwrap();
+        let image = allocator
             .create_image(
                 self.device.borrow(),
                 (Type::General, properties),
@@ -169,42 +322,495 @@ where
                 format,
                 usage,
             )
-            .map_err(|err| Error::with_chain(err, "Failed to create image"))?;
+            .map_err(|err| Error::allocation("image", err))?;
+        let allocated = {
+            let mut budget = self.budget.lock().unwrap();
+            let heaps = budget.heaps(!0, allocator.properties(image.block()));
+            budget.allocate_block(MemoryTag::Image, memory_address(image.block()), &heaps, image.block().range())
+        };
+        if let Err(err) = allocated {
+            allocator.destroy_image(self.device.borrow(), image);
+            return Err(err);
+        }
+        drop(allocator);
+        self.frame_allocations.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "trace")]
+        let trace_id = {
+            let mut trace = self.trace.lock().unwrap();
+            let trace_id = trace.next_id();
+            trace.record(|| TraceOp::CreateImage {
+                id: trace_id,
+                kind,
+                levels: level,
+                format,
+                properties,
+                usage,
+            });
+            trace_id
+        };
         Ok(Item {
             inner: self.images.escape(image),
+            state: ImageTracking::new(kind, level, format),
+            padding: 0,
+            tracked: self.leaks.lock().unwrap().track(format!("Image {:?} of {:?} with usage {:?}", kind, format, usage)),
+            #[cfg(feature = "trace")]
+            trace_id,
+        })
+    }
+
+    /// Create buffer bound to memory object of its own instead of memory sub-allocated by the allocator.
+    /// Such buffer can stay mapped for its lifetime without conflicting with mappings of other resources.
+    /// Memory is accounted in the budget of its heap with `MemoryTag::Dedicated`.
+    /// Destroy it with `Factory::destroy_dedicated_buffer`.
+    ///
+    /// # Parameters
+    /// `size`          - size of buffer.
+    /// `properties`    - memory properties required for buffer.
+    /// `usage`         - how buffer is supposed to be used.
+    ///
+    pub fn create_dedicated_buffer(
+        &self,
+        size: u64,
+        properties: Properties,
+        usage: BufferUsage,
+    ) -> Result<DedicatedBuffer<B>, Error> {
+        let unbound = self.device
+            .create_buffer(size, usage)
+            .map_err(|err| Error::creation("dedicated buffer", err))?;
+        let requirements = self.device.get_buffer_requirements(&unbound);
+        let (memory_type, heap) = self.memory_properties()
+            .memory_types
+            .iter()
+            .enumerate()
+            .find(|&(index, ty)| requirements.type_mask & (1 << index) != 0 && ty.properties.contains(properties))
+            .map(|(index, ty)| (MemoryTypeId(index), ty.heap_index))
+            .ok_or(Error::NoMemoryType(properties))?;
+        // Memory type is picked here, so its heap is known exactly.
+        let heap = self.budget
+            .lock()
+            .unwrap()
+            .allocate(MemoryTag::Dedicated, &[heap], requirements.size)?;
+        let memory = match self.device.allocate_memory(memory_type, requirements.size) {
+            Ok(memory) => memory,
+            Err(_) => {
+                self.budget
+                    .lock()
+                    .unwrap()
+                    .free(MemoryTag::Dedicated, heap, requirements.size);
+                return Err(Error::OutOfMemory { what: "dedicated buffer" });
+            }
+        };
+        let buffer = match self.device.bind_buffer_memory(&memory, 0, unbound) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                self.device.free_memory(memory);
+                self.budget
+                    .lock()
+                    .unwrap()
+                    .free(MemoryTag::Dedicated, heap, requirements.size);
+                return Err(Error::creation("dedicated buffer", err));
+            }
+        };
+        Ok(DedicatedBuffer {
+            buffer,
+            memory,
+            size: requirements.size,
+            heap,
+        })
+    }
+
+    /// Get queue family uploads are recorded for.
+    pub fn upload_family(&self) -> queue::QueueFamilyId {
+        self.recorders.family()
+    }
+
+    /// Get queue families chosen for the device.
+    /// Subsystems check it to find out whether uploads and compute go to separate queues.
+    pub fn topology(&self) -> &QueueTopology {
+        &self.topology
+    }
+
+    /// Start building new `Image` with format fallback.
+    pub fn image(&mut self) -> ImageBuilder<B> {
+        ImageBuilder::new(self)
+    }
+
+    /// Build graphics pipeline for `subpass` with `layout`.
+    pub fn graphics_pipeline<'a, 'b>(
+        &'a mut self,
+        layout: &'b B::PipelineLayout,
+        subpass: Subpass<'b, B>,
+    ) -> GraphicsPipelineBuilder<'a, 'b, B> {
+        GraphicsPipelineBuilder::new(self, layout, subpass)
+    }
+
+    /// Create compute pipeline with `shader` and `layout`.
+    /// Pipeline created should be destroyed with `Factory::destroy_compute_pipeline`.
+    pub fn create_compute_pipeline(
+        &mut self,
+        shader: EntryPoint<B>,
+        layout: &B::PipelineLayout,
+    ) -> Result<B::ComputePipeline, Error> {
+        let pipeline = self.device
+            .create_compute_pipelines(Some(ComputePipelineDesc::new(shader, layout)))
+            .pop()
+            .expect("One pipeline requested")
+            .map_err(|err| Error::creation("compute pipeline", err))?;
+        Ok(pipeline)
+    }
+
+    /// Record compute commands.
+    /// Commands are submitted with the current frame after uploads.
+    /// Buffers and images they use can be destroyed through the `Factory` right after recording.
+    pub fn compute(&mut self) -> ComputeEncoder<B> {
+        self.compute.encoder(&self.device)
+    }
+
+    /// Get memory properties of the physical device.
+    pub fn memory_properties(&self) -> MemoryProperties {
+        self.physical.memory_properties()
+    }
+
+    /// Check if device has memory type with `LAZILY_ALLOCATED` property.
+    pub fn supports_lazily_allocated(&self) -> bool {
+        self.memory_properties()
+            .memory_types
+            .iter()
+            .any(|ty| ty.properties.contains(Properties::LAZILY_ALLOCATED))
+    }
+
+    /// Get report of adapter selection made by `init_with_config`.
+    pub fn adapter_report(&self) -> Option<&AdapterReport> {
+        self.adapter_report.as_ref()
+    }
+
+    pub(crate) fn set_adapter_report(&mut self, report: AdapterReport) {
+        self.adapter_report = Some(report);
+    }
+
+    /// Start recording operations into `Trace`. Recording started earlier is discarded.
+    #[cfg(feature = "trace")]
+    pub fn start_trace(&mut self) {
+        self.trace.get_mut().unwrap().start();
+    }
+
+    /// Stop recording operations and get recorded `Trace`.
+    #[cfg(feature = "trace")]
+    pub fn stop_trace(&mut self) -> Option<Trace> {
+        self.trace.get_mut().unwrap().stop()
+    }
+
+    /// Check if operations are being recorded.
+    #[cfg(feature = "trace")]
+    pub fn is_tracing(&self) -> bool {
+        self.trace.lock().unwrap().is_recording()
+    }
+
+    /// Record boundary of the pass recorded by render graph.
+    #[cfg(feature = "trace")]
+    pub(crate) fn trace_pass(&mut self, name: &str, begin: bool) {
+        self.trace.get_mut().unwrap().record(|| if begin {
+            TraceOp::BeginPass { name: name.to_string() }
+        } else {
+            TraceOp::EndPass { name: name.to_string() }
+        });
+    }
+
+    /// Get `Buffer`s and `Image`s created by the `Factory` that are neither destroyed nor dropped yet.
+    /// Those still alive at `Factory::dispose` are reported as leaked.
+    pub fn live_resources(&mut self) -> Vec<LeakedResource> {
+        self.leaks.get_mut().unwrap().leaks()
+    }
+
+    /// Get limits of the physical device.
+    pub fn limits(&self) -> Limits {
+        self.physical.limits()
+    }
+
+    /// Get properties of the format.
+    pub fn format_properties(&self, format: Format) -> FormatProperties {
+        self.physical.format_properties(Some(format))
+    }
+
+    /// Pick first format from `candidates` that supports `usage` with optimal (or `linear`) tiling.
+    pub fn pick_format(&self, candidates: &[Format], usage: ImageUsage, linear: bool) -> Option<Format> {
+        let required = required_features(usage);
+        candidates.iter().cloned().find(|&format| {
+            let properties = self.format_properties(format);
+            let features = if linear {
+                properties.linear_tiling
+            } else {
+                properties.optimal_tiling
+            };
+            features.contains(required)
         })
     }
 
     /// Destroy `Buffer`.
     /// Factory will destroy this buffer after all commands referencing this buffer will complete.
+    /// Uploads to the buffer held back by budget or deferred keep it alive until they are submitted.
     pub fn destroy_buffer(&mut self, buffer: Buffer<B>) {
-        self.reclamation
-            .push(self.current, AnyItem::Buffer(buffer.into_inner()));
+        #[cfg(feature = "trace")]
+        self.trace.get_mut().unwrap().record(|| TraceOp::DestroyBuffer { id: buffer.trace_id });
+        self.push_reclamation(AnyItem::Buffer(buffer.into_inner()));
     }
 
     /// Destroy `Image`
     /// Factory will destroy this buffer after all commands referencing this image will complete.
+    /// Uploads to the image held back by budget or deferred keep it alive until they are submitted.
     pub fn destroy_image(&mut self, image: Image<B>) {
-        self.reclamation
-            .push(self.current, AnyItem::Image(image.into_inner()));
+        #[cfg(feature = "trace")]
+        self.trace.get_mut().unwrap().record(|| TraceOp::DestroyImage { id: image.trace_id });
+        self.push_reclamation(AnyItem::Image(image.into_inner()));
     }
 
     /// Destroy `RelevantBuffer`
     /// Factory will destroy this buffer after all commands referencing this buffer will complete.
     pub fn destroy_relevant_buffer(&mut self, buffer: RelevantBuffer<B>) {
-        self.reclamation.push(self.current, AnyItem::Buffer(buffer));
+        self.push_reclamation(AnyItem::Buffer(buffer));
     }
 
     /// Destroy `RelevantImage`
     /// Factory will destroy this buffer after all commands referencing this image will complete.
     pub fn destroy_relevant_image(&mut self, image: RelevantImage<B>) {
-        self.reclamation.push(self.current, AnyItem::Image(image));
+        self.push_reclamation(AnyItem::Image(image));
+    }
+
+    /// Destroy `DedicatedBuffer` along with its memory.
+    /// Factory will destroy this buffer after all commands referencing this buffer will complete.
+    pub fn destroy_dedicated_buffer(&mut self, buffer: DedicatedBuffer<B>) {
+        self.reclamation.push(&self.frames, AnyItem::Dedicated(buffer));
+    }
+
+    /// Destroy `ImageView`
+    /// Factory will destroy this view after all commands referencing this view will complete.
+    pub fn destroy_image_view(&mut self, view: B::ImageView) {
+        self.reclamation.push(&self.frames, AnyItem::ImageView(view));
+    }
+
+    /// Destroy buffer bound to memory not managed by the allocator.
+    /// Factory will destroy this buffer after all commands referencing this buffer will complete.
+    pub fn destroy_raw_buffer(&mut self, buffer: B::Buffer) {
+        self.reclamation.push(&self.frames, AnyItem::RawBuffer(buffer));
+    }
+
+    /// Destroy image bound to memory not managed by the allocator.
+    /// Factory will destroy this image after all commands referencing this image will complete.
+    pub fn destroy_raw_image(&mut self, image: B::Image) {
+        self.reclamation.push(&self.frames, AnyItem::RawImage(image));
+    }
+
+    /// Allocate block satisfying `requirements` from the allocator and account it in the memory budget.
+    /// Resources bound to the block are managed by the caller. Free it with `Factory::free_block`.
+    pub(crate) fn allocate_block(
+        &self,
+        tag: MemoryTag,
+        properties: Properties,
+        requirements: Requirements,
+    ) -> Result<SmartBlock<B::Memory>, Error> {
+        use mem::MemoryAllocator;
+
+        let type_mask = requirements.type_mask;
+        let mut allocator = self.allocator.lock().unwrap();
+        let block = allocator
+            .alloc(self.device.borrow(), (Type::General, properties), requirements)
+            .map_err(|err| Error::memory("memory block", err))?;
+        let allocated = {
+            let mut budget = self.budget.lock().unwrap();
+            // Type mask of requirements narrows types the allocator could pick.
+            let heaps = budget.heaps(type_mask, allocator.properties(&block));
+            budget.allocate_block(tag, memory_address(&block), &heaps, block.range())
+        };
+        if let Err(err) = allocated {
+            allocator.free(self.device.borrow(), block);
+            return Err(err);
+        }
+        Ok(block)
+    }
+
+    /// Free block allocated by `Factory::allocate_block` with the same `tag`.
+    /// Factory will free it after all commands referencing resources bound to it will complete.
+    /// Resources bound to the block must be destroyed before.
+    pub(crate) fn free_block(&mut self, tag: MemoryTag, block: SmartBlock<B::Memory>) {
+        self.reclamation.push(&self.frames, AnyItem::Block(tag, block));
+    }
+
+    /// Free memory not managed by the allocator.
+    /// Factory will free this memory after all commands referencing resources bound to it will complete.
+    /// Resources bound to the memory must be destroyed before.
+    pub fn free_raw_memory(&mut self, memory: B::Memory) {
+        self.reclamation.push(&self.frames, AnyItem::Memory(memory));
+    }
+
+    /// Share the buffer.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    /// Tracked state of the buffer is discarded.
+    /// Buffer is reported by `Factory::live_resources` until the last reference is dropped.
+    pub fn share_buffer(&self, buffer: Buffer<B>) -> SharedBuffer<B> {
+        let tracked = buffer.tracked.clone();
+        Shared::tracked(self.buffers.escape(buffer.into_inner()), tracked)
+    }
+
+    /// Share the image.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    /// Tracked state of the image is discarded.
+    /// Image is reported by `Factory::live_resources` until the last reference is dropped.
+    pub fn share_image(&self, image: Image<B>) -> SharedImage<B> {
+        let tracked = image.tracked.clone();
+        Shared::tracked(self.images.escape(image.into_inner()), tracked)
+    }
+
+    /// Share the image view.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    pub fn share_image_view(&self, view: B::ImageView) -> SharedImageView<B> {
+        Shared::new(self.shared.views.escape(view))
+    }
+
+    /// Share the sampler. It must not be one cached by the `Factory`.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    pub fn share_sampler(&self, sampler: B::Sampler) -> SharedSampler<B> {
+        Shared::new(self.shared.samplers.escape(sampler))
+    }
+
+    /// Share the render pass.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    pub fn share_render_pass(&self, render_pass: B::RenderPass) -> SharedRenderPass<B> {
+        Shared::new(self.shared.render_passes.escape(render_pass))
+    }
+
+    /// Share the framebuffer.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    pub fn share_framebuffer(&self, framebuffer: B::Framebuffer) -> SharedFramebuffer<B> {
+        Shared::new(self.shared.framebuffers.escape(framebuffer))
+    }
+
+    /// Share the graphics pipeline.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    pub fn share_graphics_pipeline(&self, pipeline: B::GraphicsPipeline) -> SharedGraphicsPipeline<B> {
+        Shared::new(self.shared.graphics_pipelines.escape(pipeline))
+    }
+
+    /// Share the compute pipeline.
+    /// Factory will destroy it after the last reference is dropped and all commands referencing it complete.
+    pub fn share_compute_pipeline(&self, pipeline: B::ComputePipeline) -> SharedComputePipeline<B> {
+        Shared::new(self.shared.compute_pipelines.escape(pipeline))
+    }
+
+    /// Store buffer in the `Factory` and get handle to it.
+    pub fn register_buffer(&mut self, buffer: Buffer<B>) -> BufferHandle<B> {
+        self.buffer_handles.insert(buffer)
+    }
+
+    /// Store image in the `Factory` and get handle to it.
+    pub fn register_image(&mut self, image: Image<B>) -> ImageHandle<B> {
+        self.image_handles.insert(image)
+    }
+
+    /// Resolve buffer handle. Returns `None` if buffer was destroyed.
+    pub fn get_buffer(&self, handle: BufferHandle<B>) -> Option<&Buffer<B>> {
+        self.buffer_handles.get(handle)
+    }
+
+    /// Resolve buffer handle mutably. Returns `None` if buffer was destroyed.
+    pub fn get_buffer_mut(&mut self, handle: BufferHandle<B>) -> Option<&mut Buffer<B>> {
+        self.buffer_handles.get_mut(handle)
+    }
+
+    /// Resolve image handle. Returns `None` if image was destroyed.
+    pub fn get_image(&self, handle: ImageHandle<B>) -> Option<&Image<B>> {
+        self.image_handles.get(handle)
+    }
+
+    /// Resolve image handle mutably. Returns `None` if image was destroyed.
+    pub fn get_image_mut(&mut self, handle: ImageHandle<B>) -> Option<&mut Image<B>> {
+        self.image_handles.get_mut(handle)
+    }
+
+    /// Destroy buffer referenced by the handle.
+    /// Factory will destroy this buffer after all commands referencing this buffer will complete.
+    /// Handle stops resolving immediately. Returns `false` if buffer was already destroyed.
+    pub fn destroy_buffer_handle(&mut self, handle: BufferHandle<B>) -> bool {
+        match self.buffer_handles.remove(handle) {
+            Some(buffer) => {
+                self.destroy_buffer(buffer);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Destroy image referenced by the handle.
+    /// Factory will destroy this image after all commands referencing this image will complete.
+    /// Handle stops resolving immediately. Returns `false` if image was already destroyed.
+    pub fn destroy_image_handle(&mut self, handle: ImageHandle<B>) -> bool {
+        match self.image_handles.remove(handle) {
+            Some(image) => {
+                self.destroy_image(image);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get sampler with parameters specified by `info`.
+    /// Sampler is created once and shared by all callers requesting the same parameters.
+    /// Factory keeps a reference to each cached sampler until `Factory::destroy_shared_samplers` or `Factory::dispose`
+    /// and destroys the sampler after the last reference is dropped.
+    pub fn get_sampler(&mut self, info: SamplerInfo) -> SharedSampler<B> {
+        let key = SamplerKey(info.clone());
+        if let Some(sampler) = self.samplers.get(&key) {
+            return sampler.clone();
+        }
+        let sampler = self.share_sampler(self.device.create_sampler(info));
+        self.samplers.insert(key, sampler.clone());
+        sampler
+    }
+
+    /// Drop references the `Factory` keeps to samplers cached by `Factory::get_sampler`.
+    /// Factory will destroy each of them after the last reference is dropped and all commands referencing it complete.
+    pub fn destroy_shared_samplers(&mut self) {
+        self.samplers.clear();
+    }
+
+    /// Destroy `Sampler`
+    /// Factory will destroy this sampler after all commands referencing this sampler will complete.
+    pub fn destroy_sampler(&mut self, sampler: B::Sampler) {
+        self.reclamation.push(&self.frames, AnyItem::Sampler(sampler));
+    }
+
+    /// Destroy `RenderPass`
+    /// Factory will destroy this render pass after all commands referencing this render pass will complete.
+    pub fn destroy_render_pass(&mut self, render_pass: B::RenderPass) {
+        self.reclamation
+            .push(&self.frames, AnyItem::RenderPass(render_pass));
+    }
+
+    /// Destroy `Framebuffer`
+    /// Factory will destroy this framebuffer after all commands referencing this framebuffer will complete.
+    pub fn destroy_framebuffer(&mut self, framebuffer: B::Framebuffer) {
+        self.reclamation
+            .push(&self.frames, AnyItem::Framebuffer(framebuffer));
+    }
+
+    /// Destroy graphics pipeline.
+    /// Factory will destroy this pipeline after all commands referencing this pipeline will complete.
+    pub fn destroy_graphics_pipeline(&mut self, pipeline: B::GraphicsPipeline) {
+        self.reclamation
+            .push(&self.frames, AnyItem::GraphicsPipeline(pipeline));
+    }
+
+    /// Destroy compute pipeline.
+    /// Factory will destroy this pipeline after all commands referencing this pipeline will complete.
+    pub fn destroy_compute_pipeline(&mut self, pipeline: B::ComputePipeline) {
+        self.reclamation
+            .push(&self.frames, AnyItem::ComputePipeline(pipeline));
     }
 
     /// Upload data to the buffer.
     /// Factory will try to use most appropriate way to write data to the buffer.
     /// For cpu-visible buffers it will write via memory mapping.
-    /// If size of the `data` is bigger than `staging_threshold` then it will perform staging.
+    /// If size of the `data` is bigger than `staging_threshold` or either `offset` or size of the `data`
+    /// is not multiple of 4 then it will perform staging.
     /// Otherwise it will write through command buffer directly.
     ///
     /// # Parameters
@@ -218,20 +824,31 @@ where
         offset: u64,
         data: &[u8],
     ) -> Result<(), Error> {
-        let ref device = self.device;
-        let ref mut allocator = self.allocator;
-        if let Some(staging) =
-            self.upload
-                .upload_buffer(device, allocator, &mut *buffer.inner, offset, data)?
+        #[cfg(feature = "trace")]
+        self.trace.get_mut().unwrap().record(|| TraceOp::UploadBuffer {
+            id: buffer.trace_id,
+            offset,
+            data: data.to_vec(),
+        });
+        telemetry::uploaded(data.len() as u64);
+        let _span = instrument::upload("buffer", &*buffer, data.len());
+        if !self.allocator
+            .get_mut()
+            .unwrap()
+            .properties(buffer.block())
+            .contains(Properties::CPU_VISIBLE)
         {
-            self.reclamation
-                .push(self.current, AnyItem::Buffer(staging));
+            self.prepare_upload(buffer, BufferAccess::TRANSFER_WRITE)?;
         }
-        Ok(())
+        let ref device = self.device;
+        let allocator = self.allocator.get_mut().unwrap();
+        self.upload
+            .upload_buffer(device, allocator, &mut *buffer.inner, offset, data)
     }
 
     /// Upload data to the image.
-    /// Factory will use staging buffer to write data to the image.
+    /// Factory will use staging buffer to write data to the image
+    /// as images are created with optimal tiling even in cpu-visible memory.
     /// 
     /// # Parameters
     /// 
@@ -250,115 +867,1229 @@ where
         extent: Extent,
         data: &[u8],
     ) -> Result<(), Error> {
+        #[cfg(feature = "trace")]
+        self.trace.get_mut().unwrap().record(|| TraceOp::UploadImage {
+            id: image.trace_id,
+            layout,
+            aspects: layers.aspects,
+            level: layers.level,
+            layers: (layers.layers.start, layers.layers.end),
+            offset: (offset.x, offset.y, offset.z),
+            extent: (extent.width, extent.height, extent.depth),
+            data: data.to_vec(),
+        });
+        telemetry::uploaded(data.len() as u64);
+        let _span = instrument::upload("image", &*image, data.len());
+        self.prepare_upload(image, (ImageAccess::TRANSFER_WRITE, layout))?;
         let ref device = self.device;
-        let ref mut allocator = self.allocator;
-        let staging =
-            self.upload
-                .upload_image(device, allocator, &mut *image.inner, data, layout, layers, offset, extent)?;
-        self.reclamation
-            .push(self.current, AnyItem::Buffer(staging));
-        Ok(())
+        let allocator = self.allocator.get_mut().unwrap();
+        self.upload
+            .upload_image(device, allocator, &mut *image.inner, data, layout, layers, offset, extent)
     }
 
-    /// Create new `Surface`.
+    /// Record layout transition of the image ordered with uploads made through the `Factory`.
+    /// Tracked state of the image is set to the end of `states` regardless of `range`.
+    /// Prefer `Factory::upload_transition_to` which computes the barrier from tracked state.
     /// 
     /// # Parameters
     /// 
-    /// `window`    - window handler which will be represented by new surface.
-    pub fn create_surface(&mut self, window: &Window) -> B::Surface
-    where
-        B: BackendEx,
-    {
-        B::create_surface(
-            Any::downcast_ref::<B::Instance>(&self.instance).unwrap(),
-            window,
-        )
+    /// `image`     - image to transition.
+    /// `range`     - subresources to transition.
+    /// `stages`    - pipeline stages to wait for and stages to block.
+    /// `states`    - access and layout before and after transition.
+    /// 
+    pub fn transition_image(
+        &mut self,
+        image: &mut Image<B>,
+        range: SubresourceRange,
+        stages: Range<PipelineStage>,
+        states: Range<ImageState>,
+    ) {
+        #[cfg(feature = "trace")]
+        self.trace.get_mut().unwrap().record(|| TraceOp::TransitionImage {
+            id: image.trace_id,
+            aspects: range.aspects,
+            levels: (range.levels.start, range.levels.end),
+            layers: (range.layers.start, range.layers.end),
+            stages: (stages.start, stages.end),
+            states: (states.start, states.end),
+        });
+        let family = self.upload_family();
+        image.state.assume(states.end, stages.end, family);
+        self.upload
+            .transition_image(&self.device, &*image.inner, range, stages, states);
     }
 
-    /// Get capabilities and formats for surface.
-    /// If formats are `None` then `Surface` has no preferences for formats.
-    /// Otherwise `Swapchain` can be created only with one of formats returned by this function.
+    /// Record barriers required to use the resource in `state` at `stages` on general queue ordered with uploads made through the `Factory`.
+    /// Barriers are computed from tracked state of the resource which is updated accordingly.
+    /// Uploads transition resources to transfer state by themselves.
+    /// Fails if uploads are recorded for dedicated transfer family which owns the resource.
     /// 
     /// # Parameters
     /// 
-    /// `surface`   - surface object which capabilities and supported formats are retrieved.
+    /// `resource`  - `Buffer` or `Image` created by the `Factory`.
+    /// `state`     - access (and layout) of the next use.
+    /// `stages`    - pipeline stages of the next use.
     /// 
-    pub fn capabilities_and_formats(
-        &self,
-        surface: &B::Surface,
-    ) -> (SurfaceCapabilities, Option<Vec<Format>>) {
-        surface.capabilities_and_formats(&self.physical)
-    }
-
-    /// Construct `Factory` from its parts.
-    pub fn new(
-        instance: B::Instance,
-        physical: B::PhysicalDevice,
-        device: B::Device,
-        allocator: SmartAllocator<B>,
-        staging_threshold: usize,
-        upload_family: queue::QueueFamilyId,
-    ) -> Self
+    pub fn upload_transition_to<R>(
+        &mut self,
+        resource: &mut R,
+        state: R::State,
+        stages: PipelineStage,
+    ) -> Result<(), Error>
     where
-        B: BackendEx,
+        R: Tracked<B> + Block,
     {
-        Factory {
-            instance: Box::new(instance),
-            physical: physical.into(),
-            device: device.into(),
-            allocator,
-            reclamation: ReclamationQueue::new(),
-            current: 0,
-            upload: Upload::new(staging_threshold, upload_family),
-            buffers: Terminal::new(),
-            images: Terminal::new(),
+        let family = self.topology.graphics();
+        if self.upload_family() == family {
+            let cbuf = self.upload.command_buffer(&self.device, Some(ResourceKey::of(&*resource)));
+            return resource.transition(cbuf, state, stages, family);
         }
+        // Acquires are submitted to general queue after uploads.
+        let cbuf = self.acquires.command_buffer(&self.device);
+        resource.transition(cbuf, state, stages, family)
     }
 
-    /// Borrow both `Device` and `SmartAllocator` from the `Factory`.
-    pub fn device_and_allocator(&mut self) -> (&B::Device, &mut SmartAllocator<B>) {
-        (self.device.borrow(), &mut self.allocator)
+    /// Record barriers required for transfer writes of upload recorded for upload family.
+    fn prepare_upload<R>(&mut self, resource: &mut R, state: R::State) -> Result<(), Error>
+    where
+        R: Tracked<B> + Block,
+    {
+        let family = self.upload_family();
+        let cbuf = self.upload.command_buffer(&self.device, Some(ResourceKey::of(&*resource)));
+        resource.transition(cbuf, state, PipelineStage::TRANSFER, family)
     }
 
-    /// Fetch command buffer with uploads recorded.
-    pub(crate) fn uploads(&mut self) -> Option<(&mut B::CommandBuffer, queue::QueueFamilyId)> {
-        self.upload.uploads(self.current)
+    fn prepare_recorder<R>(&self, recorder: &mut UploadRecorder<B>, resource: &mut R, state: R::State) -> Result<(), Error>
+    where
+        R: Tracked<B> + Block,
+    {
+        let family = self.upload_family();
+        let cbuf = recorder.command_buffer(&self.device, Some(ResourceKey::of(&*resource)));
+        resource.transition(cbuf, state, PipelineStage::TRANSFER, family)
     }
 
-    /// `RenderSystem` call this to know with which frame index recorded commands are associated.
-    pub(crate) fn current(&mut self) -> u64 {
-        self.current
+    /// Record barriers required to use the resource in `state` at `stages` on queue of `family`.
+    /// Barriers are computed from tracked state of the resource which is updated accordingly.
+    /// Consecutive reads in the same layout don't need barriers between them.
+    /// Tracking assumes command buffers are submitted in order they are recorded.
+    /// Resource stays owned by the family that used it first. Use on another family fails.
+    /// 
+    /// # Parameters
+    /// 
+    /// `cbuf`      - command buffer to record barriers into. It must be in recording state and belong to `family`.
+    /// `resource`  - `Buffer` or `Image` created by the `Factory`.
+    /// `state`     - access (and layout) of the next use.
+    /// `stages`    - pipeline stages of the next use.
+    /// `family`    - queue family of the next use.
+    /// 
+    pub fn transition_to<R>(
+        &self,
+        cbuf: &mut B::CommandBuffer,
+        resource: &mut R,
+        state: R::State,
+        stages: PipelineStage,
+        family: queue::QueueFamilyId,
+    ) -> Result<(), Error>
+    where
+        R: Tracked<B> + Block,
+    {
+        resource.transition(cbuf, state, stages, family)
     }
 
-    /// `RenderSystem` call this with least frame index with which ongoing job is associated.
-    /// Hence all resources released before this index can be destroyed.
-    pub(crate) unsafe fn advance(&mut self, ongoing: u64) {
-        debug_assert!(ongoing <= self.current);
-        for buffer in self.buffers.drain() {
-            self.reclamation.push(self.current, AnyItem::Buffer(buffer));
-        }
-        for image in self.images.drain() {
-            self.reclamation.push(self.current, AnyItem::Image(image));
-        }
+    /// Upload multiple regions of the image, e.g. all mip-levels and layers, from single staging buffer.
+    ///
+    /// # Parameters
+    ///
+    /// `image`     - where to upload. It must be created with `TRANSFER_DST` usage.
+    /// `layout`    - layout in which `Image` is during command execution (before next frame starts). It must be either `General` or `TransferDstOptimal`
+    /// `data`      - texels of all regions. It is copied into staging buffer as is.
+    /// `regions`   - regions of the image and offsets of their texels in `data`.
+    ///               Offsets must be multiples of 4 and of texel block size of the format.
+    pub fn upload_image_regions(
+        &mut self,
+        image: &mut Image<B>,
+        layout: ImageLayout,
+        data: &[u8],
+        regions: &[BufferImageCopy],
+    ) -> Result<(), Error> {
+        self.prepare_upload(image, (ImageAccess::TRANSFER_WRITE, layout))?;
         let ref device = self.device;
-        let ref mut allocator = self.allocator;
-        self.reclamation.clear(ongoing, |item| {
-            item.destroy(device, allocator);
-        });
-        self.upload.clear(ongoing);
-        self.current += 1;
-    }
-}
-
-impl<B> Deref for Factory<B>
-where
-    B: Backend,
-{
-    type Target = B::Device;
-    fn deref(&self) -> &B::Device {
-        &self.device
+        let allocator = self.allocator.get_mut().unwrap();
+        self.upload.upload_image_regions(
+            device,
+            allocator,
+            &mut *image.inner,
+            layout,
+            data,
+            regions.iter().cloned(),
+        )
     }
-}
+
+    /// Upload depth and/or stencil data to the image.
+    /// Each aspect is copied with separate region from single staging buffer.
+    /// 
+    /// # Parameters
+    /// 
+    /// `image`     - where to upload. It must be created with `TRANSFER_DST` usage.
+    /// `format`    - depth-stencil format the image was created with.
+    /// `layout`    - layout in which `Image` is during command execution (before next frame starts). It must be either `General` or `TransferDstOptimal`
+    /// `level`     - mip-level to upload.
+    /// `layers`    - array layers to upload.
+    /// `offset`    - offsets in texels of the sub-region of the destination image data.
+    /// `extent`    - size in texels of the sub-region of the destination image data.
+    /// `depth`     - depth data. 2 bytes per texel for `D16` formats, 4 bytes per texel for `D24` and `D32` formats.
+    /// `stencil`   - stencil data. 1 byte per texel.
+    pub fn upload_depth_stencil_image(
+        &mut self,
+        image: &mut Image<B>,
+        format: Format,
+        layout: ImageLayout,
+        level: Level,
+        layers: Range<Layer>,
+        offset: Offset,
+        extent: Extent,
+        depth: Option<&[u8]>,
+        stencil: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        self.prepare_upload(image, (ImageAccess::TRANSFER_WRITE, layout))?;
+        let ref device = self.device;
+        let allocator = self.allocator.get_mut().unwrap();
+        self.upload.upload_depth_stencil_image(
+            device,
+            allocator,
+            &mut *image.inner,
+            format,
+            layout,
+            level,
+            layers,
+            offset,
+            extent,
+            depth,
+            stencil,
+        )
+    }
+
+    /// Get counters of staging buffers created and retired by uploads.
+    pub fn staging_stats(&self) -> StagingStats {
+        self.upload
+            .staging_stats()
+            .merge(self.recorders.staging_stats())
+    }
+
+    /// List staging buffers that are not retired after `age` frames since they were recorded.
+    /// 
+    /// # Parameters
+    /// 
+    /// `age`       - number of frames after which staging buffer is considered orphaned.
+    /// 
+    pub fn orphaned_staging(&self, age: u64) -> Vec<StagingInfo> {
+        let mut orphaned = self.upload.orphaned_staging(self.frames.current(), age);
+        orphaned.extend(self.recorders.orphaned_staging(self.frames.current(), age));
+        orphaned
+    }
+
+    /// Wait for device to become idle and destroy staging buffers that are not retired after `age` frames since they were recorded.
+    /// 
+    /// # Parameters
+    /// 
+    /// `age`       - number of frames after which staging buffer is considered orphaned.
+    /// 
+    pub fn force_retire_staging(&mut self, age: u64) -> Result<(), Error> {
+        let result = self.device
+            .wait_idle()
+            .map_err(host_execution_error);
+        self.check_lost(result)?;
+        unsafe {
+            // Safe due to device is idle.
+            self.upload.force_retire(self.frames.current(), age);
+            self.recorders.force_retire(self.frames.current(), age);
+        }
+        self.destroy_retired_staging();
+        Ok(())
+    }
+
+    /// Enable robustness mode.
+    /// Buffers created afterwards will be padded and ranges checked with `Factory::check_buffer_range` validated.
+    /// Buffers created before keep their sizes and are checked without padding.
+    /// 
+    /// gfx-hal revision this crate depends on opens devices without optional features,
+    /// so robust buffer access is never enabled.
+    /// 
+    /// # Parameters
+    /// 
+    /// `robustness`    - robustness configuration.
+    /// 
+    pub fn set_robustness(&mut self, robustness: Robustness) {
+        warn!("Robust buffer access is not enabled. Only padding and range validation are done");
+        self.robustness = Some(robustness);
+    }
+
+    /// Get robustness configuration if enabled.
+    pub fn robustness(&self) -> Option<Robustness> {
+        self.robustness
+    }
+
+    /// Check that range of the buffer can be bound to descriptor.
+    /// Always succeeds unless robustness mode with range validation is enabled.
+    /// 
+    /// # Parameters
+    /// 
+    /// `buffer`    - buffer to bind.
+    /// `range`     - range of the buffer in bytes.
+    /// 
+    pub fn check_buffer_range(&self, buffer: &Buffer<B>, range: Range<u64>) -> Result<(), Error> {
+        match self.robustness {
+            Some(ref robustness) => robustness.check_range(buffer.size() - buffer.padding(), range),
+            None => Ok(()),
+        }
+    }
+
+    /// Get memory usage of each heap by resources created through the `Factory`.
+    /// Staging buffers are not accounted.
+    pub fn memory_usage(&self) -> Vec<HeapUsage> {
+        self.budget.lock().unwrap().usage().to_vec()
+    }
+
+    /// Get heap of memory type the allocator picked for the block.
+    /// `None` if memory of the block is untracked. See `MemoryReport::untracked`.
+    pub(crate) fn memory_heap(&self, block: &SmartBlock<B::Memory>) -> Option<usize> {
+        self.budget
+            .lock()
+            .unwrap()
+            .block_heap(memory_address(block), block.range())
+    }
+
+    /// Get report of memory used by resources created through the `Factory` per heap and kind of resources.
+    pub fn memory_report(&self) -> MemoryReport {
+        let staging = self.staging_stats();
+        let budget = self.budget.lock().unwrap();
+        MemoryReport {
+            heaps: budget.report(),
+            buffers: budget.tag_usage(MemoryTag::Buffer),
+            images: budget.tag_usage(MemoryTag::Image),
+            untracked: budget.untracked(),
+            staging: TagUsage {
+                count: staging.outstanding(),
+                bytes: staging.outstanding_bytes(),
+                largest: 0,
+            },
+        }
+    }
+
+    /// Set soft budget of memory heap.
+    /// Creating buffer or image that would exceed budget of the heap fails with `Error::BudgetExceeded`
+    /// instead of overcommitting device memory.
+    /// `None` removes the budget. Fails if device has no such heap.
+    pub fn set_memory_budget(&mut self, heap: usize, budget: Option<u64>) -> Result<(), Error> {
+        self.budget.get_mut().unwrap().set_budget(heap, budget)
+    }
+
+    /// Create new `Surface`.
+    /// 
+    /// # Parameters
+    /// 
+    /// `window`    - window handler which will be represented by new surface.
+    #[cfg(feature = "winit")]
+    pub fn create_surface(&mut self, window: &Window) -> B::Surface
+    where
+        B: BackendEx,
+    {
+        B::create_surface(
+            Any::downcast_ref::<B::Instance>(&self.instance).unwrap(),
+            window,
+        )
+    }
+
+    /// Create new `Surface` for window of any windowing library.
+    /// Fails if backend can't present to windows of the platform.
+    ///
+    /// # Parameters
+    ///
+    /// `window`    - window which raw handle is represented by new surface. It must outlive the surface.
+    pub fn create_surface_from_raw<W>(&mut self, window: &W) -> Result<B::Surface, Error>
+    where
+        B: BackendEx,
+        W: HasRawWindowHandle,
+    {
+        B::create_surface_from_raw(
+            Any::downcast_ref::<B::Instance>(&self.instance).unwrap(),
+            window.raw_window_handle(),
+        )
+    }
+
+    /// Get capabilities and formats for surface.
+    /// If formats are `None` then `Surface` has no preferences for formats.
+    /// Otherwise `Swapchain` can be created only with one of formats returned by this function.
+    /// 
+    /// # Parameters
+    /// 
+    /// `surface`   - surface object which capabilities and supported formats are retrieved.
+    /// 
+    pub fn capabilities_and_formats(
+        &self,
+        surface: &B::Surface,
+    ) -> (SurfaceCapabilities, Option<Vec<Format>>) {
+        surface.capabilities_and_formats(&self.physical)
+    }
+
+
+    /// Construct `Factory` from its parts.
+    pub fn new(
+        instance: B::Instance,
+        physical: B::PhysicalDevice,
+        device: B::Device,
+        allocator: SmartAllocator<B>,
+        staging_threshold: usize,
+        topology: QueueTopology,
+    ) -> Self
+    where
+        B: BackendEx,
+    {
+        let budget = MemoryBudget::new(&physical.memory_properties());
+        let upload_family = topology.upload_family();
+        Factory {
+            instance: Box::new(instance),
+            physical: physical.into(),
+            device: device.into(),
+            allocator: Mutex::new(allocator),
+            reclamation: ReclamationQueue::new(),
+            pinned: Vec::new(),
+            frames: Frames::new(),
+            upload: Upload::new(staging_threshold, upload_family),
+            compute: Compute::new(topology.compute_family()),
+            acquires: Compute::new(topology.graphics()),
+            recorders: UploadGroup::new(staging_threshold, upload_family),
+            topology,
+            lost: false,
+            robustness: None,
+            budget: Mutex::new(budget),
+            debug: None,
+            breadcrumb_markers: None,
+            breadcrumbs: None,
+            adapter_report: None,
+            leaks: Mutex::new(LeakTracker::new()),
+            frame_allocations: AtomicUsize::new(0),
+            #[cfg(feature = "trace")]
+            trace: Mutex::new(TraceRecorder::new()),
+            samplers: HashMap::new(),
+            sync: SyncPool::new(),
+            buffer_handles: Registry::new(),
+            image_handles: Registry::new(),
+            buffers: Terminal::new(),
+            images: Terminal::new(),
+            shared: SharedTerminals::new(),
+        }
+    }
+
+    /// Construct `Factory` from its parts picking staging threshold suitable for memory topology of the device.
+    /// See `tune_staging_threshold`.
+    pub fn with_tuned_staging_threshold(
+        instance: B::Instance,
+        physical: B::PhysicalDevice,
+        device: B::Device,
+        allocator: SmartAllocator<B>,
+        topology: QueueTopology,
+    ) -> Self
+    where
+        B: BackendEx,
+    {
+        let staging_threshold = tune_staging_threshold(&physical.memory_properties());
+        info!("Staging threshold picked: {}", staging_threshold);
+        Factory::new(
+            instance,
+            physical,
+            device,
+            allocator,
+            staging_threshold,
+            topology,
+        )
+    }
+
+    /// Borrow both `Device` and `SmartAllocator` from the `Factory`.
+    pub fn device_and_allocator(&mut self) -> (&B::Device, &mut SmartAllocator<B>) {
+        (self.device.borrow(), self.allocator.get_mut().unwrap())
+    }
+
+    /// Get upload recorder which can be sent to another thread.
+    /// Commands recorded will be submitted with the frame that starts after recorder is dropped.
+    /// Recorder must use allocator borrowed from this `Factory` as staging buffers it creates are destroyed by the `Factory`.
+    pub fn upload_recorder(&self) -> UploadRecorder<B> {
+        self.recorders.recorder()
+    }
+
+    /// Upload data to the buffer recording commands into the `recorder`.
+    /// Same as `Factory::upload_buffer` but requires only shared reference to the `Factory`,
+    /// so worker threads each holding own recorder can upload simultaneously.
+    /// Data is written into staging arena of the recorder, so only creation of arena chunks is serialized with other threads.
+    /// Resource must not be uploaded through the `Factory` and the recorder in the same frame.
+    ///
+    /// # Parameters
+    /// `recorder`  - recorder fetched with `Factory::upload_recorder`.
+    /// `buffer`    - where to upload data. It must be created with at least one of `TRANSFER_DST` usage or `CPU_VISIBLE` property.
+    /// `offset`    - write data to the buffer starting from this byte.
+    /// `data`      - data to upload.
+    ///
+    pub fn upload_buffer_with(
+        &self,
+        recorder: &mut UploadRecorder<B>,
+        buffer: &mut Buffer<B>,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        #[cfg(feature = "trace")]
+        self.trace.lock().unwrap().record(|| TraceOp::UploadBuffer {
+            id: buffer.trace_id,
+            offset,
+            data: data.to_vec(),
+        });
+        telemetry::uploaded(data.len() as u64);
+        let _span = instrument::upload("buffer", &*buffer, data.len());
+        if buffer.size() < offset + data.len() as u64 {
+            return Err(Error::OutOfBounds {
+                offset,
+                len: data.len() as u64,
+                size: buffer.size(),
+            });
+        }
+        // Allocator is locked only to query properties and allocate staging chunks, never while data is copied.
+        let props = self.allocator.lock().unwrap().properties(buffer.block());
+        if props.contains(Properties::CPU_VISIBLE) {
+            unsafe {
+                // Safe due to block is checked to have `CPU_VISIBLE` property.
+                update_cpu_visible_block::<B>(
+                    &self.device,
+                    props.contains(Properties::COHERENT),
+                    buffer.block(),
+                    offset,
+                    data,
+                );
+            }
+            return Ok(());
+        }
+        self.prepare_recorder(recorder, buffer, BufferAccess::TRANSFER_WRITE)?;
+        if recorder.update_buffer(&self.device, &mut *buffer.inner, offset, data) {
+            return Ok(());
+        }
+        if data.len() as u64 > STAGING_CHUNK_SIZE {
+            let staging = self.create_staging_buffer(data.len() as u64)?;
+            self.write_staging(&staging, |slice| slice.copy_from_slice(data));
+            return recorder.upload_buffer_from_staging(&self.device, &mut *buffer.inner, offset, staging, 0..data.len() as u64);
+        }
+        recorder.upload_buffer_from_arena(&self.device, &mut *buffer.inner, offset, data, || {
+            self.create_staging_chunk()
+        })
+    }
+
+    /// Upload data to the image recording commands into the `recorder`.
+    /// Same as `Factory::upload_image` but requires only shared reference to the `Factory`.
+    /// See `Factory::upload_buffer_with`.
+    ///
+    /// # Parameters
+    ///
+    /// `recorder`  - recorder fetched with `Factory::upload_recorder`.
+    /// `image`     - where to upload. It must be created with `TRANSFER_DST` usage.
+    /// `layout`    - layout in which `Image` is during command execution (before next frame starts). It must be either `General` or `TransferDstOptimal`
+    /// `layers`    - specific image subresources of the image used for the destination image data.
+    /// `offset`    - offsets in texels of the sub-region of the destination image data.
+    /// `extent`    - size in texels of the sub-region of the destination image data.
+    /// `data`      - data containing texels in image's format.
+    pub fn upload_image_with(
+        &self,
+        recorder: &mut UploadRecorder<B>,
+        image: &mut Image<B>,
+        layout: ImageLayout,
+        layers: SubresourceLayers,
+        offset: Offset,
+        extent: Extent,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        #[cfg(feature = "trace")]
+        self.trace.lock().unwrap().record(|| TraceOp::UploadImage {
+            id: image.trace_id,
+            layout,
+            aspects: layers.aspects,
+            level: layers.level,
+            layers: (layers.layers.start, layers.layers.end),
+            offset: (offset.x, offset.y, offset.z),
+            extent: (extent.width, extent.height, extent.depth),
+            data: data.to_vec(),
+        });
+        telemetry::uploaded(data.len() as u64);
+        let _span = instrument::upload("image", &*image, data.len());
+        if layers.aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+            return Err(Error::InvalidArgument(
+                "Depth and stencil aspects can't be uploaded with single copy. Use `upload_depth_stencil_image`",
+            ));
+        }
+        self.prepare_recorder(recorder, image, (ImageAccess::TRANSFER_WRITE, layout))?;
+        if data.len() as u64 > STAGING_CHUNK_SIZE {
+            let staging = self.create_staging_buffer(data.len() as u64)?;
+            self.write_staging(&staging, |slice| slice.copy_from_slice(data));
+            let format = image.state().format();
+            return recorder.upload_image_from_staging(
+                &self.device,
+                &mut *image.inner,
+                format,
+                layout,
+                layers,
+                offset,
+                extent,
+                staging,
+                0,
+            );
+        }
+        recorder.upload_image_from_arena(&self.device, &mut *image.inner, layout, layers, offset, extent, data, || {
+            self.create_staging_chunk()
+        })
+    }
+
+    /// Set priority of subsequent uploads made through the `Factory`.
+    /// Returns previous priority.
+    pub fn set_upload_priority(&mut self, priority: UploadPriority) -> UploadPriority {
+        self.upload.set_priority(priority)
+    }
+
+    /// Get semaphore from the pool of the `Factory`. New one is created if none is free.
+    /// Return it with `Factory::release_semaphore` after submitting commands that use it
+    /// instead of destroying it.
+    pub fn acquire_semaphore(&mut self) -> B::Semaphore {
+        self.sync.acquire_semaphore(&self.device)
+    }
+
+    /// Return semaphore to the pool of the `Factory`.
+    /// It will be reused after commands associated with current frame complete.
+    pub fn release_semaphore(&mut self, semaphore: B::Semaphore) {
+        self.sync.release_semaphore(semaphore, self.frames.current());
+    }
+
+    /// Get unsignaled fence from the pool of the `Factory`. New one is created if none is free.
+    /// Return it with `Factory::release_fence` after submitting commands that signal it
+    /// instead of destroying it.
+    pub fn acquire_fence(&mut self) -> B::Fence {
+        self.sync.acquire_fence(&self.device)
+    }
+
+    /// Return fence to the pool of the `Factory`.
+    /// It will be reset and reused after commands associated with current frame complete.
+    pub fn release_fence(&mut self, fence: B::Fence) {
+        self.sync.release_fence(fence, self.frames.current());
+    }
+
+    /// Submit all recorded uploads and wait for them to complete.
+    /// Staging buffers are destroyed right away.
+    ///
+    /// # Parameters
+    ///
+    /// `queue`     - queue of the family uploads are recorded for.
+    ///
+    pub fn wait_uploads_idle(&mut self, queue: &mut B::CommandQueue) -> Result<(), Error> {
+        let result = self.upload.wait_idle(queue);
+        self.check_lost(result)?;
+        let result = self.recorders.wait_idle(queue);
+        self.check_lost(result)?;
+        self.unpin();
+        self.destroy_retired_staging();
+        Ok(())
+    }
+
+    /// Create cpu-visible staging buffer for `Factory::upload_buffer_from_staging` and `Factory::upload_image_from_staging`.
+    /// Caller can decode data right into it with `Factory::write_staging`.
+    /// Can be called from any thread.
+    pub fn create_staging_buffer(&self, size: u64) -> Result<RelevantBuffer<B>, Error> {
+        create_staging_buffer(&self.device, &mut self.allocator.lock().unwrap(), size)
+    }
+
+    /// Create chunk of staging arena of upload recorder and check if its memory is coherent.
+    fn create_staging_chunk(&self) -> Result<(RelevantBuffer<B>, bool), Error> {
+        let mut allocator = self.allocator.lock().unwrap();
+        let chunk = create_staging_buffer(&self.device, &mut allocator, STAGING_CHUNK_SIZE)?;
+        let coherent = allocator.properties(chunk.block()).contains(Properties::COHERENT);
+        Ok((chunk, coherent))
+    }
+
+    /// Map staging buffer and let `f` write into it.
+    ///
+    /// # Parameters
+    ///
+    /// `staging`   - buffer created by `Factory::create_staging_buffer`.
+    /// `f`         - function to write data into mapped memory.
+    ///
+    pub fn write_staging<F>(&self, staging: &RelevantBuffer<B>, f: F)
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        let props = self.allocator.lock().unwrap().properties(staging.block());
+        assert!(props.contains(Properties::CPU_VISIBLE));
+        unsafe {
+            // Safe due to block is checked to have `CPU_VISIBLE` property.
+            write_cpu_visible_block::<B, _>(
+                &self.device,
+                props.contains(Properties::COHERENT),
+                staging.block(),
+                0..staging.size(),
+                f,
+            );
+        }
+    }
+
+    /// Read range of cpu-visible buffer.
+    /// Non-coherent memory is invalidated before reading.
+    /// Commands writing the range must be complete.
+    ///
+    /// # Parameters
+    ///
+    /// `buffer`    - buffer with `CPU_VISIBLE` memory, e.g. created with `BufferBuilder::readback`.
+    /// `range`     - range of the buffer to read.
+    /// `f`         - function to read the data.
+    ///
+    pub fn read_buffer<F, T>(&self, buffer: &Buffer<B>, range: Range<u64>, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&[u8]) -> T,
+    {
+        if range.start > range.end || buffer.size() < range.end {
+            return Err(Error::OutOfBounds {
+                offset: range.start,
+                len: range.end.saturating_sub(range.start),
+                size: buffer.size(),
+            });
+        }
+        let props = self.allocator.lock().unwrap().properties(buffer.block());
+        if !props.contains(Properties::CPU_VISIBLE) {
+            return Err(Error::InvalidArgument("Buffer to read is not cpu-visible"));
+        }
+        unsafe {
+            // Safe due to block is checked to have `CPU_VISIBLE` property.
+            read_cpu_visible_block::<B, _, _>(
+                &self.device,
+                props.contains(Properties::COHERENT),
+                buffer.block(),
+                range,
+                f,
+            )
+        }
+    }
+
+    /// Upload data to the buffer from staging buffer without intermediate copying.
+    ///
+    /// # Parameters
+    ///
+    /// `buffer`    - where to upload data. It must be created with `TRANSFER_DST` usage.
+    /// `offset`    - write data to the buffer starting from this byte.
+    /// `staging`   - buffer created by `Factory::create_staging_buffer`. It will be destroyed after upload completes.
+    /// `range`     - range of the staging buffer to copy.
+    ///
+    pub fn upload_buffer_from_staging(
+        &mut self,
+        buffer: &mut Buffer<B>,
+        offset: u64,
+        staging: RelevantBuffer<B>,
+        range: Range<u64>,
+    ) -> Result<(), Error> {
+        self.prepare_upload(buffer, BufferAccess::TRANSFER_WRITE)?;
+        self.upload
+            .upload_buffer_from_staging(&self.device, &mut *buffer.inner, offset, staging, range)
+    }
+
+    /// Upload data to the image from staging buffer without intermediate copying.
+    /// Fails with `Error::OutOfBounds` if texels of the region don't fit into the staging buffer after `staging_offset`.
+    ///
+    /// # Parameters
+    ///
+    /// `image`     - where to upload. It must be created with `TRANSFER_DST` usage.
+    /// `layout`    - layout in which `Image` is during command execution (before next frame starts). It must be either `General` or `TransferDstOptimal`
+    /// `layers`    - specific image subresources of the image used for the destination image data.
+    /// `offset`    - offsets in texels of the sub-region of the destination image data.
+    /// `extent`    - size in texels of the sub-region of the destination image data.
+    /// `staging`   - buffer created by `Factory::create_staging_buffer`. It will be destroyed after upload completes.
+    /// `staging_offset` - offset of the first texel in the staging buffer.
+    pub fn upload_image_from_staging(
+        &mut self,
+        image: &mut Image<B>,
+        layout: ImageLayout,
+        layers: SubresourceLayers,
+        offset: Offset,
+        extent: Extent,
+        staging: RelevantBuffer<B>,
+        staging_offset: u64,
+    ) -> Result<(), Error> {
+        self.prepare_upload(image, (ImageAccess::TRANSFER_WRITE, layout))?;
+        let format = image.state().format();
+        self.upload.upload_image_from_staging(
+            &self.device,
+            &mut *image.inner,
+            format,
+            layout,
+            layers,
+            offset,
+            extent,
+            staging,
+            staging_offset,
+        )
+    }
+
+    /// Set debug markers capability used to name objects and mark regions.
+    /// See `DebugMarkers`.
+    pub fn set_debug_markers(&mut self, debug: Option<Arc<DebugMarkers<B>>>) {
+        self.upload.set_debug_markers(debug.clone());
+        self.debug = debug;
+    }
+
+    /// Get number of nanoseconds per timestamp tick.
+    /// `None` if device can't write timestamps.
+    pub fn timestamp_period(&self) -> Option<f32> {
+        let period = self.physical.limits().timestamp_period;
+        if period > 0.0 {
+            Some(period)
+        } else {
+            None
+        }
+    }
+
+    /// Check if GPU timestamps can be queried.
+    pub fn supports_timestamp_queries(&self) -> bool {
+        self.timestamp_period().is_some()
+    }
+
+    /// Set breadcrumb marker capability.
+    /// See `BackendEx::breadcrumb_markers`.
+    /// Breadcrumbs enabled before keep writing markers into the buffer.
+    pub fn set_breadcrumb_markers(&mut self, breadcrumb_markers: Option<Arc<BreadcrumbMarkers<B>>>) {
+        self.breadcrumb_markers = breadcrumb_markers;
+    }
+
+    /// Check if breadcrumb markers can be written with checkpoint extensions.
+    pub fn supports_breadcrumb_markers(&self) -> bool {
+        self.breadcrumb_markers.is_some()
+    }
+
+    /// Start writing breadcrumb markers between passes of render graphs and after upload batches.
+    /// On device loss last reached markers are reported with `error!`.
+    /// Markers are written with breadcrumb marker capability if set or into cpu-visible buffer otherwise.
+    pub fn enable_breadcrumbs(&mut self) -> Result<(), Error> {
+        if self.breadcrumbs.is_some() {
+            return Ok(());
+        }
+        let buffer = match self.breadcrumb_markers {
+            Some(_) => None,
+            None => {
+                let buffer = self.create_buffer(
+                    8,
+                    Properties::CPU_VISIBLE | Properties::COHERENT,
+                    BufferUsage::TRANSFER_DST,
+                )?;
+                buffer.set_name("breadcrumbs");
+                unsafe {
+                    let range = buffer.range();
+                    let ptr = self.device
+                        .map_memory(buffer.memory(), range)
+                        .map_err(Error::MappingFailed)?;
+                    write_bytes(ptr, 0, 8);
+                    self.device.unmap_memory(buffer.memory());
+                }
+                Some(buffer)
+            }
+        };
+        let family = self.upload_family();
+        self.breadcrumbs = Some(Breadcrumbs::new(self.breadcrumb_markers.clone(), buffer, family));
+        Ok(())
+    }
+
+    /// Check if breadcrumb markers are written.
+    pub fn breadcrumbs_enabled(&self) -> bool {
+        self.breadcrumbs.is_some()
+    }
+
+    /// Read last breadcrumb markers reached by the device.
+    /// Returns `None` if breadcrumbs aren't enabled.
+    /// Reached markers are meaningful only after device loss or when device is idle.
+    pub fn breadcrumb_report(&self) -> Result<Option<BreadcrumbReport>, Error> {
+        match self.breadcrumbs {
+            Some(ref breadcrumbs) => breadcrumbs.report(&self.device).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Record breadcrumb marker reached after commands of the pass complete.
+    pub(crate) fn breadcrumb(&mut self, cbuf: &mut B::CommandBuffer, pass: &str) {
+        let frame = self.frames.current();
+        if let Some(ref mut breadcrumbs) = self.breadcrumbs {
+            unsafe {
+                // Called by render graph outside of render passes.
+                breadcrumbs.write(cbuf, BreadcrumbStream::Passes, format!("pass {} of frame {}", pass, frame));
+            }
+        }
+    }
+
+    /// Attach debug name to the object. Does nothing if debug markers are not supported.
+    pub fn set_debug_name(&self, object: DebugObject<B>, name: &str) {
+        if let Some(ref debug) = self.debug {
+            debug.set_name(&self.device, object, name);
+        }
+    }
+
+    /// Begin named region of commands. Does nothing if debug markers are not supported.
+    pub fn begin_debug_region(&self, cbuf: &mut B::CommandBuffer, name: &str) {
+        if let Some(ref debug) = self.debug {
+            debug.begin_region(cbuf, name);
+        }
+    }
+
+    /// End region started last with `begin_debug_region`. Does nothing if debug markers are not supported.
+    pub fn end_debug_region(&self, cbuf: &mut B::CommandBuffer) {
+        if let Some(ref debug) = self.debug {
+            debug.end_region(cbuf);
+        }
+    }
+
+    /// Set tag of subsequent uploads made through the `Factory`.
+    /// Returns previous tag.
+    pub fn set_upload_tag(&mut self, tag: Option<UploadId>) -> Option<UploadId> {
+        self.upload.set_tag(tag)
+    }
+
+    /// Get tag of subsequent uploads made through the `Factory`.
+    pub fn upload_tag(&self) -> Option<UploadId> {
+        self.upload.tag()
+    }
+
+    /// Cancel uploads made through the `Factory` with specified tag that are not yet submitted.
+    /// Their staging buffers are destroyed immediately.
+    /// Tracked state of resources is not restored, use `Texture::roll_back` for textures uploaded with the tag.
+    /// Returns `true` if any uploads were cancelled.
+    pub fn cancel_upload(&mut self, id: UploadId) -> bool {
+        let cancelled = self.upload.cancel(id);
+        self.destroy_retired_staging();
+        cancelled
+    }
+
+    /// Set maximum number of bytes of uploads made through the `Factory` submitted per frame.
+    /// Uploads beyond the budget are submitted with subsequent frames.
+    pub fn set_upload_budget(&mut self, budget: Option<u64>) {
+        self.upload.set_budget(budget);
+    }
+
+    /// Keep `Background` uploads made through the `Factory` from submission until called with `false`.
+    pub fn defer_background_uploads(&mut self, defer: bool) {
+        self.upload.defer_background(defer);
+    }
+
+    /// Get number of bytes of uploads made through the `Factory` held back by the budget.
+    pub fn pending_upload_bytes(&self) -> u64 {
+        self.upload.pending_bytes()
+    }
+
+    /// Fetch command buffers with uploads recorded in order of priority,
+    /// acquisitions of uploaded resources by general family
+    /// and compute commands to submit after them.
+    pub(crate) fn submissions(
+        &mut self,
+    ) -> (
+        Vec<&mut B::CommandBuffer>,
+        Option<&mut B::CommandBuffer>,
+        Option<&mut B::CommandBuffer>,
+    ) {
+        let current = self.frames.current();
+        let mut cbufs = self.upload.uploads(&self.frames);
+        cbufs.extend(self.recorders.uploads(&self.frames));
+        // Stable sort keeps order of command buffers of same priority.
+        cbufs.sort_by_key(|&(priority, _)| priority);
+        let mut cbufs: Vec<_> = cbufs.into_iter().map(|(_, cbuf)| cbuf).collect();
+        if let Some(ref mut breadcrumbs) = self.breadcrumbs {
+            let markers = breadcrumbs.upload_markers(&self.device, current, cbufs.len());
+            cbufs = cbufs
+                .into_iter()
+                .zip(markers)
+                .flat_map(|(upload, marker)| vec![upload, marker])
+                .collect();
+        }
+        let acquires = self.acquires.submit(current);
+        let compute = self.compute.submit(current);
+        (cbufs, acquires, compute)
+    }
+
+    /// Get index of the frame recorded commands are associated with.
+    pub(crate) fn current(&mut self) -> u64 {
+        self.frames.current()
+    }
+
+    /// Get least frame index with which ongoing job is associated.
+    /// Commands recorded for frames before this index are complete.
+    pub(crate) fn ongoing(&self) -> u64 {
+        self.frames.ongoing()
+    }
+
+    /// Get frame counter uploads, deferred destruction and `Renderer` key off.
+    pub fn frames(&self) -> &Frames<B> {
+        &self.frames
+    }
+
+    /// Start recording current frame.
+    /// `Renderer` calls this at the beginning of `Renderer::run`.
+    pub fn begin_frame(&mut self) -> FrameGuard<B> {
+        self.frames.begin()
+    }
+
+    /// Finish current frame and advance to the next one.
+    /// Resources released during frames which fences are signaled are destroyed.
+    ///
+    /// # Safety
+    ///
+    /// Fences added to the frame must be signaled only after all commands associated with the frame complete.
+    /// Uploads fetched for the frame must be submitted before them to the same queue.
+    pub unsafe fn finish_frame(&mut self, frame: FrameGuard<B>) {
+        #[cfg(feature = "trace")]
+        self.trace.get_mut().unwrap().record(|| TraceOp::Frame { index: frame.index() });
+        // Uploads fetched for the frame are submitted by now.
+        self.unpin();
+        self.frames.finish(frame);
+        let signaled = self.frames.poll(&self.device);
+        self.reclaim(signaled);
+        let allocations = self.frame_allocations.swap(0, Ordering::Relaxed) as u64;
+        telemetry::frame_finished(allocations, self.staging_stats().outstanding_bytes());
+    }
+
+    /// Wait for commands of the frame and frames before it to complete.
+    /// Resources released during those frames are destroyed.
+    pub fn wait_frame(&mut self, index: u64) -> Result<(), Error> {
+        let result = self.frames.wait(&self.device, index);
+        let signaled = self.check_lost(result)?;
+        self.reclaim(signaled);
+        Ok(())
+    }
+
+    /// Wait for commands of the frame and frames before it to complete, but no longer than `timeout`.
+    /// Resources released during completed frames are destroyed.
+    /// Returns `false` if the frame isn't complete by then.
+    pub fn wait_frame_timeout(&mut self, index: u64, timeout: Duration) -> Result<bool, Error> {
+        let result = self.frames.wait_timeout(&self.device, index, timeout);
+        let (signaled, complete) = self.check_lost(result)?;
+        self.reclaim(signaled);
+        Ok(complete)
+    }
+
+    /// Report frames which fences aren't signaled
+    /// and frames of uploads and compute commands recorded by the `Factory` that aren't retired.
+    pub fn stuck_work(&self) -> StuckWork {
+        StuckWork {
+            frames: self.frames.stuck(&self.device),
+            uploads: self.upload.outstanding_frames(),
+            recorders: self.recorders.outstanding_frames(),
+            acquires: self.acquires.outstanding_frames(),
+            computes: self.compute.outstanding_frames(),
+            ..StuckWork::default()
+        }
+    }
+
+    /// Check if device loss was detected.
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+
+    /// Record that device is lost.
+    /// `Factory` and `Renderer` detect loss when they wait for frames and queues.
+    /// Call this when loss is detected otherwise, e.g. by waiting for fences of own submissions.
+    pub fn mark_lost(&mut self) {
+        if !self.lost {
+            error!("Device lost");
+            match self.breadcrumb_report() {
+                Ok(Some(report)) => error!("{}", report),
+                Ok(None) => {}
+                Err(err) => warn!("Failed to read breadcrumbs: {}", err),
+            }
+        }
+        self.lost = true;
+    }
+
+    /// Mark device lost if `result` is `Error::DeviceLost` error.
+    pub(crate) fn check_lost<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        if let Err(Error::DeviceLost) = result {
+            self.mark_lost();
+        }
+        result
+    }
+
+    /// Destroy the `Factory` and everything it keeps in order:
+    ///
+    /// 1. Resources and objects destroyed through the `Factory`, shared or referenced by handles.
+    /// 2. Command pools and staging buffers of uploads and compute commands.
+    /// 3. Semaphores and fences.
+    /// 4. Memory of the allocator.
+    /// 5. Device and instance.
+    ///
+    /// Waits for device to become idle unless it is lost.
+    /// `Buffer`s and `Image`s that are neither destroyed through the `Factory` nor dropped are leaked.
+    /// They are reported with their names and, with `leak-backtraces` feature, creation backtraces.
+    pub fn dispose(mut self) {
+        use mem::MemoryAllocator;
+
+        if !self.lost {
+            if let Err(err) = self.device.wait_idle() {
+                error!("Failed to wait for device to become idle: {:?}", err);
+            }
+        }
+        // Commands of idle or lost device are complete.
+        let fences = unsafe { self.frames.abandon() };
+        for buffer in self.buffer_handles.drain() {
+            self.destroy_buffer(buffer);
+        }
+        for image in self.image_handles.drain() {
+            self.destroy_image(image);
+        }
+        let breadcrumbs = self.breadcrumbs.as_mut().and_then(Breadcrumbs::take_buffer);
+        if let Some(buffer) = breadcrumbs {
+            self.destroy_buffer(buffer);
+        }
+        // Cached samplers are destroyed with other shared objects once their last references are dropped.
+        self.samplers.clear();
+        self.drain_dropped();
+        // Pending uploads are never submitted.
+        for item in replace(&mut self.pinned, Vec::new()) {
+            self.reclamation.push(&self.frames, item);
+        }
+        let leaks = self.leaks.get_mut().unwrap().leaks();
+        if !leaks.is_empty() {
+            error!("{} resources were not destroyed before `Factory::dispose`", leaks.len());
+            for leak in leaks {
+                error!("Leaked {}", leak);
+            }
+        }
+
+        let Factory {
+            instance,
+            device,
+            allocator,
+            mut reclamation,
+            upload,
+            compute,
+            acquires,
+            recorders,
+            budget,
+            sync,
+            breadcrumbs,
+            ..
+        } = self;
+        let mut allocator = allocator.into_inner().unwrap();
+        let mut budget = budget.into_inner().unwrap();
+        reclamation.drain_all(|item| item.destroy(&device, &mut allocator, &mut budget));
+        unsafe {
+            let staging = upload.dispose(&device).into_iter().chain(recorders.dispose(&device));
+            for buffer in staging {
+                allocator.destroy_buffer(&device, buffer);
+            }
+            compute.dispose(&device);
+            acquires.dispose(&device);
+            if let Some(breadcrumbs) = breadcrumbs {
+                breadcrumbs.dispose(&device);
+            }
+        }
+        for fence in fences {
+            device.destroy_fence(fence);
+        }
+        sync.dispose(&device);
+        if allocator.dispose(&device).is_err() {
+            error!("Memory allocator is not empty. Some resources were not destroyed through the `Factory`");
+        }
+        drop(device);
+        drop(instance);
+    }
+
+    /// Destroy resources and reuse command buffers, semaphores and `signaled` fences of complete frames.
+    fn reclaim(&mut self, signaled: Vec<B::Fence>) {
+        self.drain_dropped();
+        let ongoing = self.frames.ongoing();
+        {
+            let ref device = self.device;
+            let allocator = self.allocator.get_mut().unwrap();
+            let budget = self.budget.get_mut().unwrap();
+            self.reclamation.clear(&self.frames, |item| {
+                item.destroy(device, allocator, budget);
+            });
+            for fence in signaled {
+                self.sync.recycle_fence(device, fence);
+            }
+            self.sync.clear(device, ongoing);
+        }
+        self.upload.clear(&self.frames);
+        self.recorders.clear(&self.frames);
+        self.compute.clear(ongoing);
+        self.acquires.clear(ongoing);
+        if let Some(ref mut breadcrumbs) = self.breadcrumbs {
+            breadcrumbs.clear(ongoing);
+        }
+        self.destroy_retired_staging();
+    }
+
+    /// Queue destruction of dropped `Item`s and `Shared` resources.
+    fn drain_dropped(&mut self) {
+        for buffer in self.buffers.drain() {
+            self.push_reclamation(AnyItem::Buffer(buffer));
+        }
+        for image in self.images.drain() {
+            self.push_reclamation(AnyItem::Image(image));
+        }
+        let ref mut shared = self.shared;
+        for view in shared.views.drain() {
+            self.reclamation.push(&self.frames, AnyItem::ImageView(view));
+        }
+        for sampler in shared.samplers.drain() {
+            self.reclamation.push(&self.frames, AnyItem::Sampler(sampler));
+        }
+        for render_pass in shared.render_passes.drain() {
+            self.reclamation.push(&self.frames, AnyItem::RenderPass(render_pass));
+        }
+        for framebuffer in shared.framebuffers.drain() {
+            self.reclamation.push(&self.frames, AnyItem::Framebuffer(framebuffer));
+        }
+        for pipeline in shared.graphics_pipelines.drain() {
+            self.reclamation.push(&self.frames, AnyItem::GraphicsPipeline(pipeline));
+        }
+        for pipeline in shared.compute_pipelines.drain() {
+            self.reclamation.push(&self.frames, AnyItem::ComputePipeline(pipeline));
+        }
+    }
+
+    /// Queue destruction of the item after commands of the current frame complete.
+    /// Buffers and images referenced by uploads held back by budget or deferred
+    /// are pinned until those uploads are submitted.
+    fn push_reclamation(&mut self, item: AnyItem<B>) {
+        if let Some(key) = item.key() {
+            if self.upload.references(key) || self.recorders.references(key) {
+                self.pinned.push(item);
+                return;
+            }
+        }
+        self.reclamation.push(&self.frames, item);
+    }
+
+    /// Queue destruction of pinned items which uploads are submitted.
+    fn unpin(&mut self) {
+        for item in replace(&mut self.pinned, Vec::new()) {
+            self.push_reclamation(item);
+        }
+    }
+
+    fn destroy_retired_staging(&mut self) {
+        let ref device = self.device;
+        let allocator = self.allocator.get_mut().unwrap();
+        for staging in self.upload
+            .drain_retired()
+            .chain(self.recorders.drain_retired())
+        {
+            allocator.destroy_buffer(device, staging);
+        }
+    }
+}
+
+impl<B> Deref for Factory<B>
+where
+    B: Backend,
+{
+    type Target = B::Device;
+    fn deref(&self) -> &B::Device {
+        &self.device
+    }
+}
 
 impl<B> DerefMut for Factory<B>
 where
@@ -369,27 +2100,166 @@ where
     }
 }
 
-pub(crate) type RelevantBuffer<B: Backend> = RelevantItem<B::Buffer, SmartBlock<B::Memory>>;
-pub(crate) type RelevantImage<B: Backend> = RelevantItem<B::Image, SmartBlock<B::Memory>>;
+/// Buffer without `Escape` wrapper. It must be disposed manually.
+pub type RelevantBuffer<B: Backend> = RelevantItem<B::Buffer, SmartBlock<B::Memory>>;
+
+/// Image without `Escape` wrapper. It must be disposed manually.
+pub type RelevantImage<B: Backend> = RelevantItem<B::Image, SmartBlock<B::Memory>>;
+
+/// Buffer bound to memory object of its own. Created with `Factory::create_dedicated_buffer`.
+/// It must be destroyed with `Factory::destroy_dedicated_buffer`.
+#[derive(Debug)]
+pub struct DedicatedBuffer<B: Backend> {
+    buffer: B::Buffer,
+    memory: B::Memory,
+    size: u64,
+    heap: Option<usize>,
+}
+
+impl<B> DedicatedBuffer<B>
+where
+    B: Backend,
+{
+    /// Get raw buffer.
+    pub fn raw(&self) -> &B::Buffer {
+        &self.buffer
+    }
+
+    /// Get memory the buffer is bound to at offset 0.
+    pub fn memory(&self) -> &B::Memory {
+        &self.memory
+    }
+
+    /// Get size of the memory.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Address of the memory object the block is sub-allocated from.
+/// Identifies the block in `MemoryBudget` along with block's range.
+fn memory_address<T>(block: &T) -> usize
+where
+    T: Block,
+{
+    block.memory() as *const _ as usize
+}
+
+/// Key of cached samplers.
+/// `SamplerInfo` has floating point fields and isn't `Hash`, so they are compared and hashed by their bits.
+#[derive(Clone, Debug)]
+struct SamplerKey(SamplerInfo);
+
+impl PartialEq for SamplerKey {
+    fn eq(&self, other: &Self) -> bool {
+        let (ref a, ref b) = (&self.0, &other.0);
+        a.min_filter == b.min_filter && a.mag_filter == b.mag_filter && a.mip_filter == b.mip_filter
+            && a.wrap_mode == b.wrap_mode && a.lod_bias.to_bits() == b.lod_bias.to_bits()
+            && a.lod_range.start.to_bits() == b.lod_range.start.to_bits()
+            && a.lod_range.end.to_bits() == b.lod_range.end.to_bits() && a.comparison == b.comparison
+            && a.border == b.border && a.anisotropic == b.anisotropic
+    }
+}
+
+impl Eq for SamplerKey {}
+
+impl Hash for SamplerKey {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        let ref info = self.0;
+        info.min_filter.hash(state);
+        info.mag_filter.hash(state);
+        info.mip_filter.hash(state);
+        info.wrap_mode.hash(state);
+        info.lod_bias.to_bits().hash(state);
+        info.lod_range.start.to_bits().hash(state);
+        info.lod_range.end.to_bits().hash(state);
+        info.comparison.hash(state);
+        info.border.hash(state);
+        info.anisotropic.hash(state);
+    }
+}
 
 #[derive(Debug)]
 enum AnyItem<B: Backend> {
     Buffer(RelevantBuffer<B>),
     Image(RelevantImage<B>),
+    Dedicated(DedicatedBuffer<B>),
+    ImageView(B::ImageView),
+    Sampler(B::Sampler),
+    RenderPass(B::RenderPass),
+    Framebuffer(B::Framebuffer),
+    GraphicsPipeline(B::GraphicsPipeline),
+    ComputePipeline(B::ComputePipeline),
+    RawBuffer(B::Buffer),
+    RawImage(B::Image),
+    Memory(B::Memory),
+    Block(MemoryTag, SmartBlock<B::Memory>),
 }
 
 impl<B> AnyItem<B>
 where
     B: Backend,
 {
-    pub fn destroy(self, device: &B::Device, allocator: &mut SmartAllocator<B>) {
+    /// Get key of the buffer or image uploads can reference.
+    fn key(&self) -> Option<ResourceKey> {
+        match *self {
+            AnyItem::Buffer(ref buffer) => Some(ResourceKey::of(buffer.block())),
+            AnyItem::Image(ref image) => Some(ResourceKey::of(image.block())),
+            _ => None,
+        }
+    }
+
+    pub fn destroy(self, device: &B::Device, allocator: &mut SmartAllocator<B>, budget: &mut MemoryBudget) {
         match self {
             AnyItem::Buffer(buffer) => {
+                budget.free_block(MemoryTag::Buffer, memory_address(buffer.block()), buffer.block().range());
                 allocator.destroy_buffer(device, buffer);
             }
             AnyItem::Image(image) => {
+                budget.free_block(MemoryTag::Image, memory_address(image.block()), image.block().range());
                 allocator.destroy_image(device, image);
             }
+            AnyItem::Dedicated(buffer) => {
+                budget.free(MemoryTag::Dedicated, buffer.heap, buffer.size);
+                device.destroy_buffer(buffer.buffer);
+                device.free_memory(buffer.memory);
+            }
+            AnyItem::ImageView(view) => {
+                device.destroy_image_view(view);
+            }
+            AnyItem::Sampler(sampler) => {
+                device.destroy_sampler(sampler);
+            }
+            AnyItem::RenderPass(render_pass) => {
+                device.destroy_renderpass(render_pass);
+            }
+            AnyItem::Framebuffer(framebuffer) => {
+                device.destroy_framebuffer(framebuffer);
+            }
+            AnyItem::GraphicsPipeline(pipeline) => {
+                device.destroy_graphics_pipeline(pipeline);
+            }
+            AnyItem::ComputePipeline(pipeline) => {
+                device.destroy_compute_pipeline(pipeline);
+            }
+            AnyItem::RawBuffer(buffer) => {
+                device.destroy_buffer(buffer);
+            }
+            AnyItem::RawImage(image) => {
+                device.destroy_image(image);
+            }
+            AnyItem::Memory(memory) => {
+                device.free_memory(memory);
+            }
+            AnyItem::Block(tag, block) => {
+                use mem::MemoryAllocator;
+
+                budget.free_block(tag, memory_address(&block), block.range());
+                allocator.free(device, block);
+            }
         }
     }
 }
@@ -402,3 +2272,31 @@ fn factory_send_sync() {
         is_send_sync::<Factory<B>>();
     }
 }
+
+#[test]
+fn sampler_keys_compare_float_fields_by_bits() {
+    use std::collections::hash_map::DefaultHasher;
+    use hal::image::{Filter, WrapMode};
+
+    let hash = |key: &SamplerKey| {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    };
+    let info = SamplerInfo::new(Filter::Linear, WrapMode::Tile);
+    let same = SamplerKey(info.clone());
+    assert_eq!(SamplerKey(info.clone()), same);
+    assert_eq!(hash(&SamplerKey(info.clone())), hash(&same));
+
+    let mut biased = info.clone();
+    biased.lod_bias = 1.0;
+    assert_ne!(SamplerKey(biased), same);
+
+    let mut clamped = info.clone();
+    clamped.lod_range.end = 4.0;
+    assert_ne!(SamplerKey(clamped), same);
+
+    let mut nearest = info;
+    nearest.mag_filter = Filter::Nearest;
+    assert_ne!(SamplerKey(nearest), same);
+}
@@ -0,0 +1,225 @@
+//! Frame synchronization. `Frames` counts frames and keeps fences of submitted ones
+//! to find out which frames are complete.
+//! Everything that defers work until commands complete keys off it:
+//! uploads, deferred destruction of resources and `Renderer`.
+//!
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use hal::{Backend, Device};
+
+use Error;
+use stuck::StuckFrame;
+
+/// Frame being recorded. Fences signaled by submissions of the frame are collected into it.
+/// It must be finished with `Factory::finish_frame` (or `Frames::finish`).
+#[derive(Debug)]
+pub struct FrameGuard<B: Backend> {
+    index: u64,
+    fences: Vec<B::Fence>,
+}
+
+impl<B> FrameGuard<B>
+where
+    B: Backend,
+{
+    /// Get index of the frame.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Add fence signaled by submission of the frame.
+    /// Frame is complete when all its fences are signaled.
+    /// Returns reference to the fence to submit with.
+    pub fn add_fence(&mut self, fence: B::Fence) -> &B::Fence {
+        self.fences.push(fence);
+        self.fences.last().unwrap()
+    }
+
+    /// Check if any fence was added to the frame.
+    pub fn has_fences(&self) -> bool {
+        !self.fences.is_empty()
+    }
+}
+
+/// Frame counter with fences of submitted frames.
+#[derive(Debug)]
+pub struct Frames<B: Backend> {
+    current: u64,
+    ongoing: u64,
+    recording: bool,
+    pending: VecDeque<(u64, Vec<B::Fence>)>,
+}
+
+impl<B> Frames<B>
+where
+    B: Backend,
+{
+    /// Create counter starting from frame 0.
+    pub fn new() -> Self {
+        Frames {
+            current: 0,
+            ongoing: 0,
+            recording: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Get index of the frame commands are recorded for.
+    pub fn current(&self) -> u64 {
+        self.current
+    }
+
+    /// Get least index of the frame which commands may be incomplete.
+    /// Commands of all frames before it are complete.
+    pub fn ongoing(&self) -> u64 {
+        self.ongoing
+    }
+
+    /// Check if all commands of the frame are complete.
+    pub fn is_complete(&self, index: u64) -> bool {
+        index < self.ongoing
+    }
+
+    /// Start recording current frame.
+    /// Panics if previous frame isn't finished.
+    pub fn begin(&mut self) -> FrameGuard<B> {
+        assert!(!self.recording, "Frame {} isn't finished", self.current);
+        self.recording = true;
+        FrameGuard {
+            index: self.current,
+            fences: Vec::new(),
+        }
+    }
+
+    /// Finish recording current frame and advance to the next one.
+    /// Frame without fences is complete as soon as all frames before it are.
+    ///
+    /// # Safety
+    ///
+    /// Fences of the frame must be signaled only after all commands associated with the frame complete.
+    pub unsafe fn finish(&mut self, frame: FrameGuard<B>) {
+        assert_eq!(frame.index, self.current, "Frame guard doesn't belong to current frame");
+        self.recording = false;
+        self.pending.push_back((frame.index, frame.fences));
+        self.current += 1;
+    }
+
+    /// Find frames which fences are signaled.
+    /// Returns their fences. They are signaled and can be reset for reuse.
+    pub fn poll(&mut self, device: &B::Device) -> Vec<B::Fence> {
+        self.poll_with(|_, fence| device.wait_for_fence(fence, 0))
+    }
+
+    /// Find frames which fences are reported signaled by `is_signaled` called with index of the frame and its fence.
+    /// Returns their fences. Lets frames advance without device, e.g. in tests.
+    pub fn poll_with<F>(&mut self, mut is_signaled: F) -> Vec<B::Fence>
+    where
+        F: FnMut(u64, &B::Fence) -> bool,
+    {
+        let mut signaled = Vec::new();
+        while let Some((index, fences)) = self.pending.pop_front() {
+            if !fences.iter().all(|fence| is_signaled(index, fence)) {
+                self.pending.push_front((index, fences));
+                break;
+            }
+            self.ongoing = index + 1;
+            signaled.extend(fences);
+        }
+        signaled
+    }
+
+    /// Wait for all commands of the frame and frames before it to complete.
+    /// Returns fences of completed frames. They are signaled and can be reset for reuse.
+    /// Fails with `Error::DeviceLost` if fences can't be waited for.
+    pub fn wait(&mut self, device: &B::Device, index: u64) -> Result<Vec<B::Fence>, Error> {
+        if index >= self.current {
+            return Err(Error::InvalidArgument("Frame to wait for isn't finished"));
+        }
+        let mut signaled = Vec::new();
+        while self.pending.front().map_or(false, |&(pending, _)| pending <= index) {
+            let (pending, fences) = self.pending.pop_front().unwrap();
+            if !fences.iter().all(|fence| device.wait_for_fence(fence, !0)) {
+                self.pending.push_front((pending, fences));
+                error!("Failed to wait for frame {}", pending);
+                return Err(Error::DeviceLost);
+            }
+            self.ongoing = pending + 1;
+            signaled.extend(fences);
+        }
+        Ok(signaled)
+    }
+
+    /// Wait for all commands of the frame and frames before it to complete, but no longer than `timeout`.
+    /// Returns fences of completed frames and whether the frame is complete.
+    /// Fences are signaled and can be reset for reuse.
+    /// Device loss can't be told apart from timeout here.
+    pub fn wait_timeout(
+        &mut self,
+        device: &B::Device,
+        index: u64,
+        timeout: Duration,
+    ) -> Result<(Vec<B::Fence>, bool), Error> {
+        if index >= self.current {
+            return Err(Error::InvalidArgument("Frame to wait for isn't finished"));
+        }
+        let deadline = Instant::now() + timeout;
+        let mut signaled = Vec::new();
+        while self.pending.front().map_or(false, |&(pending, _)| pending <= index) {
+            let (pending, fences) = self.pending.pop_front().unwrap();
+            if !fences.iter().all(|fence| device.wait_for_fence(fence, remaining_ms(deadline))) {
+                self.pending.push_front((pending, fences));
+                return Ok((signaled, false));
+            }
+            self.ongoing = pending + 1;
+            signaled.extend(fences);
+        }
+        Ok((signaled, true))
+    }
+
+    /// List finished frames which fences aren't all signaled.
+    pub fn stuck(&self, device: &B::Device) -> Vec<StuckFrame> {
+        self.pending
+            .iter()
+            .map(|&(index, ref fences)| StuckFrame {
+                index,
+                fences: fences.len(),
+                unsignaled: fences
+                    .iter()
+                    .filter(|fence| !device.wait_for_fence(fence, 0))
+                    .count(),
+            })
+            .filter(|frame| frame.unsignaled > 0)
+            .collect()
+    }
+
+    /// Consider all finished frames complete without waiting for their fences.
+    /// Returns their fences. They can be destroyed but not reused.
+    ///
+    /// # Safety
+    ///
+    /// Device must be idle or lost.
+    pub unsafe fn abandon(&mut self) -> Vec<B::Fence> {
+        self.ongoing = self.current;
+        self.pending
+            .drain(..)
+            .flat_map(|(_, fences)| fences)
+            .collect()
+    }
+}
+
+/// Get milliseconds left until `deadline`.
+fn remaining_ms(deadline: Instant) -> u32 {
+    let now = Instant::now();
+    if deadline <= now {
+        return 0;
+    }
+    let remaining = deadline - now;
+    let ms = remaining.as_secs() * 1000 + (remaining.subsec_nanos() as u64 + 999_999) / 1_000_000;
+    if ms > u32::max_value() as u64 {
+        u32::max_value()
+    } else {
+        ms as u32
+    }
+}
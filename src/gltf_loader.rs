@@ -0,0 +1,474 @@
+//! Loader that ingests glTF file and uploads its meshes, textures and materials via `Factory`.
+//! Enabled with `gltf` feature.
+//! Textures sampled as base color or emission get sRGB formats,
+//! others, like normal and metallic-roughness maps, hold linear data and get `Unorm` formats.
+//! 
+
+use std::mem::size_of;
+use std::path::Path;
+use std::slice::from_raw_parts;
+
+use hal::Backend;
+use hal::buffer::Usage as BufferUsage;
+use hal::format::Format;
+use hal::image::{AaMode, FilterMethod, Kind, SamplerInfo, WrapMode};
+use hal::memory::Properties;
+use hal::pso::Element;
+
+use gltf::{import, Document, Material, Primitive, Texture as GltfTextureRef};
+use gltf::image::Format as GltfFormat;
+use gltf::texture::{MagFilter, WrappingMode};
+
+use Error;
+use factory::{Buffer, Factory};
+use mesh::{Indices, Mesh, VertexFormat};
+use texture::Texture;
+
+/// Primitive of the glTF mesh uploaded to the device.
+/// Each vertex attribute is stored in separate buffer.
+#[derive(Debug)]
+pub struct GltfPrimitive<B: Backend> {
+    /// Vertex positions. `[f32; 3]` per vertex.
+    pub positions: Buffer<B>,
+
+    /// Vertex normals. `[f32; 3]` per vertex.
+    pub normals: Option<Buffer<B>>,
+
+    /// First set of texture coordinates. `[f32; 2]` per vertex.
+    pub tex_coords: Option<Buffer<B>>,
+
+    /// Indices. `u32` per index.
+    pub indices: Option<Buffer<B>>,
+
+    /// Number of vertices.
+    pub vertex_count: u32,
+
+    /// Number of indices.
+    pub index_count: u32,
+
+    /// Index of the material in `GltfScene::materials`.
+    pub material: Option<usize>,
+}
+
+/// Texture of the glTF document uploaded to the device.
+#[derive(Debug)]
+pub struct GltfTexture<B: Backend> {
+    /// Texture in `ShaderReadOnlyOptimal` layout with sampler created.
+    pub texture: Texture<B>,
+
+    /// Format of the image.
+    pub format: Format,
+
+    /// Sampler parameters.
+    pub sampler: SamplerInfo,
+}
+
+/// Material parameters block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GltfMaterial {
+    /// Base color factor.
+    pub base_color_factor: [f32; 4],
+
+    /// Metalness factor.
+    pub metallic_factor: f32,
+
+    /// Roughness factor.
+    pub roughness_factor: f32,
+
+    /// Index of base color texture in `GltfScene::textures`.
+    pub base_color_texture: Option<usize>,
+
+    /// Index of metallic-roughness texture in `GltfScene::textures`.
+    pub metallic_roughness_texture: Option<usize>,
+
+    /// Index of normal texture in `GltfScene::textures`.
+    pub normal_texture: Option<usize>,
+}
+
+/// All meshes, textures and materials of the glTF document.
+#[derive(Debug)]
+pub struct GltfScene<B: Backend> {
+    /// Primitives of each mesh.
+    pub meshes: Vec<Vec<GltfPrimitive<B>>>,
+
+    /// Textures referenced by materials.
+    pub textures: Vec<GltfTexture<B>>,
+
+    /// Materials referenced by primitives.
+    pub materials: Vec<GltfMaterial>,
+}
+
+/// Primitive of the glTF mesh uploaded into `Mesh` along with its material.
+#[derive(Debug)]
+pub struct GltfMesh<B: Backend> {
+    /// Vertex buffers in order of positions (`Rgb32Float`), normals (`Rgb32Float`)
+    /// and texture coordinates (`Rg32Float`). Missing attributes are skipped. Indices are `u32`.
+    pub mesh: Mesh<B>,
+
+    /// Whether the mesh has normals buffer.
+    pub has_normals: bool,
+
+    /// Whether the mesh has texture coordinates buffer.
+    pub has_tex_coords: bool,
+
+    /// Material of the primitive. Texture indices refer to `GltfModel::textures`.
+    /// Primitives without material should use glTF default one.
+    pub material: Option<GltfMaterial>,
+}
+
+/// Meshes and textures of the glTF document uploaded by `upload_gltf`.
+/// Destroy it with `destroy_gltf_model`.
+#[derive(Debug)]
+pub struct GltfModel<B: Backend> {
+    /// Primitives of each mesh.
+    pub meshes: Vec<Vec<GltfMesh<B>>>,
+
+    /// Textures referenced by materials.
+    pub textures: Vec<Texture<B>>,
+}
+
+/// Load glTF file and upload its content.
+/// Textures are ready for sampling in `ShaderReadOnlyOptimal` layout with samplers created.
+/// 
+/// # Parameters
+/// 
+/// `factory`   - factory to create and upload resources.
+/// `path`      - path to the `.gltf` or `.glb` file.
+/// 
+pub fn load_gltf<B, P>(factory: &mut Factory<B>, path: P) -> Result<GltfScene<B>, Error>
+where
+    B: Backend,
+    P: AsRef<Path>,
+{
+    let (document, buffers, images) =
+        import(path).map_err(Error::Gltf)?;
+
+    let srgb = srgb_textures(&document);
+    let textures = document
+        .textures()
+        .map(|texture| {
+            let data = &images[texture.source().index()];
+            let (format, pixels) = texture_texels(data.format, &data.pixels, srgb[texture.index()]);
+            let sampler = texture_sampler(&texture);
+            let texture = factory.create_texture(
+                Kind::D2(data.width as _, data.height as _, AaMode::Single),
+                format,
+                &pixels,
+                Some(sampler.clone()),
+            )?;
+            Ok(GltfTexture {
+                texture,
+                format,
+                sampler,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let materials = document.materials().map(|material| convert_material(&material)).collect();
+
+    let meshes = document
+        .meshes()
+        .map(|mesh| {
+            mesh.primitives()
+                .map(|primitive| load_primitive(factory, &buffers, primitive))
+                .collect()
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(GltfScene {
+        meshes,
+        textures,
+        materials,
+    })
+}
+
+/// Upload glTF document parsed by `gltf::import` into `Mesh`es and `Texture`s.
+/// Unlike `load_gltf` primitives are grouped with their materials,
+/// and textures are ready for sampling in `ShaderReadOnlyOptimal` layout with samplers created.
+/// 
+/// # Parameters
+/// 
+/// `factory`   - factory to create and upload resources.
+/// `document`  - parsed glTF document.
+/// `buffers`   - buffer data imported along with the document.
+/// `images`    - image data imported along with the document.
+/// 
+pub fn upload_gltf<B>(
+    factory: &mut Factory<B>,
+    document: &Document,
+    buffers: &[::gltf::buffer::Data],
+    images: &[::gltf::image::Data],
+) -> Result<GltfModel<B>, Error>
+where
+    B: Backend,
+{
+    let srgb = srgb_textures(document);
+    let textures = document
+        .textures()
+        .map(|texture| {
+            let data = &images[texture.source().index()];
+            let (format, pixels) = texture_texels(data.format, &data.pixels, srgb[texture.index()]);
+            factory.create_texture(
+                Kind::D2(data.width as _, data.height as _, AaMode::Single),
+                format,
+                &pixels,
+                Some(texture_sampler(&texture)),
+            )
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let vertex_format = |format, size| VertexFormat {
+        stride: size,
+        attributes: vec![Element { format, offset: 0 }],
+    };
+    let meshes = document
+        .meshes()
+        .map(|mesh| {
+            mesh.primitives()
+                .map(|primitive| {
+                    let data = read_primitive(buffers, &primitive)?;
+                    let mut vertices = vec![(as_bytes(&data.positions), vertex_format(Format::Rgb32Float, 12))];
+                    if let Some(ref normals) = data.normals {
+                        vertices.push((as_bytes(normals), vertex_format(Format::Rgb32Float, 12)));
+                    }
+                    if let Some(ref tex_coords) = data.tex_coords {
+                        vertices.push((as_bytes(tex_coords), vertex_format(Format::Rg32Float, 8)));
+                    }
+                    let indices = data.indices.as_ref().map(|indices| Indices::U32(indices));
+                    let mesh = factory.create_mesh(&vertices, indices)?;
+                    let material = primitive.material();
+                    Ok(GltfMesh {
+                        mesh,
+                        has_normals: data.normals.is_some(),
+                        has_tex_coords: data.tex_coords.is_some(),
+                        material: material.index().map(|_| convert_material(&material)),
+                    })
+                })
+                .collect()
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(GltfModel { meshes, textures })
+}
+
+/// Load glTF file and upload it with `upload_gltf`.
+/// 
+/// # Parameters
+/// 
+/// `factory`   - factory to create and upload resources.
+/// `path`      - path to the `.gltf` or `.glb` file.
+/// 
+pub fn load_gltf_model<B, P>(factory: &mut Factory<B>, path: P) -> Result<GltfModel<B>, Error>
+where
+    B: Backend,
+    P: AsRef<Path>,
+{
+    let (document, buffers, images) =
+        import(path).map_err(Error::Gltf)?;
+    upload_gltf(factory, &document, &buffers, &images)
+}
+
+/// Destroy meshes and textures of the model.
+/// Factory will destroy resources after all commands referencing them will complete.
+pub fn destroy_gltf_model<B>(factory: &mut Factory<B>, model: GltfModel<B>)
+where
+    B: Backend,
+{
+    for mesh in model.meshes.into_iter().flat_map(|primitives| primitives) {
+        factory.destroy_mesh(mesh.mesh);
+    }
+    for texture in model.textures {
+        factory.destroy_texture(texture);
+    }
+}
+
+/// Vertex attributes and indices of the primitive.
+struct PrimitiveData {
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    tex_coords: Option<Vec<[f32; 2]>>,
+    indices: Option<Vec<u32>>,
+}
+
+fn read_primitive(buffers: &[::gltf::buffer::Data], primitive: &Primitive) -> Result<PrimitiveData, Error> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions = reader
+        .read_positions()
+        .ok_or(Error::Unsupported("glTF primitives without positions"))?
+        .collect::<Vec<[f32; 3]>>();
+    let normals = reader
+        .read_normals()
+        .map(|normals| normals.collect::<Vec<[f32; 3]>>());
+    let tex_coords = reader
+        .read_tex_coords(0)
+        .map(|tex_coords| tex_coords.into_f32().collect::<Vec<[f32; 2]>>());
+    let indices = reader
+        .read_indices()
+        .map(|indices| indices.into_u32().collect::<Vec<u32>>());
+
+    Ok(PrimitiveData {
+        positions,
+        normals,
+        tex_coords,
+        indices,
+    })
+}
+
+fn load_primitive<B>(
+    factory: &mut Factory<B>,
+    buffers: &[::gltf::buffer::Data],
+    primitive: Primitive,
+) -> Result<GltfPrimitive<B>, Error>
+where
+    B: Backend,
+{
+    let PrimitiveData {
+        positions,
+        normals,
+        tex_coords,
+        indices,
+    } = read_primitive(buffers, &primitive)?;
+
+    let vertex = BufferUsage::VERTEX;
+    Ok(GltfPrimitive {
+        vertex_count: positions.len() as u32,
+        index_count: indices.as_ref().map_or(0, |indices| indices.len() as u32),
+        positions: upload_slice(factory, &positions, vertex)?,
+        normals: match normals {
+            Some(normals) => Some(upload_slice(factory, &normals, vertex)?),
+            None => None,
+        },
+        tex_coords: match tex_coords {
+            Some(tex_coords) => Some(upload_slice(factory, &tex_coords, vertex)?),
+            None => None,
+        },
+        indices: match indices {
+            Some(indices) => Some(upload_slice(factory, &indices, BufferUsage::INDEX)?),
+            None => None,
+        },
+        material: primitive.material().index(),
+    })
+}
+
+/// Find textures materials sample as sRGB color. Those are base color and emissive textures.
+/// If texture is also sampled as linear data it stays sRGB and a warning is logged.
+fn srgb_textures(document: &Document) -> Vec<bool> {
+    let mut srgb = vec![false; document.textures().count()];
+    let mut linear = srgb.clone();
+    for material in document.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        if let Some(info) = pbr.base_color_texture() {
+            srgb[info.texture().index()] = true;
+        }
+        if let Some(info) = material.emissive_texture() {
+            srgb[info.texture().index()] = true;
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            linear[info.texture().index()] = true;
+        }
+        if let Some(info) = material.normal_texture() {
+            linear[info.texture().index()] = true;
+        }
+        if let Some(info) = material.occlusion_texture() {
+            linear[info.texture().index()] = true;
+        }
+    }
+    for (index, _) in srgb.iter().zip(&linear).enumerate().filter(|&(_, (&srgb, &linear))| srgb && linear) {
+        warn!("glTF texture {} is sampled both as color and as linear data", index);
+    }
+    srgb
+}
+
+/// Get format of the image and its texels. RGB texels are extended with opaque alpha.
+/// Color textures get `Srgb` formats, textures of linear data get `Unorm` ones.
+fn texture_texels(format: GltfFormat, pixels: &[u8], srgb: bool) -> (Format, Vec<u8>) {
+    let (r, rg, rgba) = if srgb {
+        (Format::R8Srgb, Format::Rg8Srgb, Format::Rgba8Srgb)
+    } else {
+        (Format::R8Unorm, Format::Rg8Unorm, Format::Rgba8Unorm)
+    };
+    match format {
+        GltfFormat::R8 => (r, pixels.to_vec()),
+        GltfFormat::R8G8 => (rg, pixels.to_vec()),
+        GltfFormat::R8G8B8 => (
+            rgba,
+            pixels
+                .chunks(3)
+                .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], !0])
+                .collect(),
+        ),
+        GltfFormat::R8G8B8A8 => (rgba, pixels.to_vec()),
+    }
+}
+
+/// Get sampler parameters of the texture.
+fn texture_sampler(texture: &GltfTextureRef) -> SamplerInfo {
+    let sampler = texture.sampler();
+    let filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => FilterMethod::Scale,
+        _ => FilterMethod::Bilinear,
+    };
+    let wrap = |mode| match mode {
+        WrappingMode::ClampToEdge => WrapMode::Clamp,
+        WrappingMode::MirroredRepeat => WrapMode::Mirror,
+        WrappingMode::Repeat => WrapMode::Tile,
+    };
+    let mut info = SamplerInfo::new(filter, wrap(sampler.wrap_s()));
+    info.wrap_mode.1 = wrap(sampler.wrap_t());
+    info
+}
+
+fn convert_material(material: &Material) -> GltfMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    GltfMaterial {
+        base_color_factor: pbr.base_color_factor(),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        base_color_texture: pbr.base_color_texture()
+            .map(|info| info.texture().index()),
+        metallic_roughness_texture: pbr.metallic_roughness_texture()
+            .map(|info| info.texture().index()),
+        normal_texture: material
+            .normal_texture()
+            .map(|info| info.texture().index()),
+    }
+}
+
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe {
+        // Safe due to `T` is plain data (`f32` or `u32` arrays).
+        from_raw_parts(data.as_ptr() as *const u8, data.len() * size_of::<T>())
+    }
+}
+
+fn upload_slice<B, T>(factory: &mut Factory<B>, data: &[T], usage: BufferUsage) -> Result<Buffer<B>, Error>
+where
+    B: Backend,
+    T: Copy,
+{
+    let bytes = as_bytes(data);
+    let mut buffer = factory.create_buffer(
+        bytes.len() as u64,
+        Properties::DEVICE_LOCAL,
+        usage | BufferUsage::TRANSFER_DST,
+    )?;
+    factory.upload_buffer(&mut buffer, 0, bytes)?;
+    Ok(buffer)
+}
+
+#[test]
+fn linear_textures_get_unorm_formats() {
+    let rgb = [1u8, 2, 3, 4, 5, 6];
+    assert_eq!(
+        texture_texels(GltfFormat::R8G8B8, &rgb, true),
+        (Format::Rgba8Srgb, vec![1, 2, 3, 255, 4, 5, 6, 255])
+    );
+    assert_eq!(
+        texture_texels(GltfFormat::R8G8B8, &rgb, false),
+        (Format::Rgba8Unorm, vec![1, 2, 3, 255, 4, 5, 6, 255])
+    );
+    assert_eq!(texture_texels(GltfFormat::R8G8B8A8, &rgb[..4], false).0, Format::Rgba8Unorm);
+    assert_eq!(texture_texels(GltfFormat::R8G8, &rgb[..2], false).0, Format::Rg8Unorm);
+    assert_eq!(texture_texels(GltfFormat::R8, &rgb[..1], true).0, Format::R8Srgb);
+}
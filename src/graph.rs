@@ -0,0 +1,2644 @@
+//! Render graph. Passes declare images and buffers they use, graph orders passes by their dependencies,
+//! creates transient resources and records barriers between passes before recording each pass.
+//! 
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::ops::Range;
+use std::time::Duration;
+
+use hal::{Backend, Device};
+use hal::buffer::{Access as BufferAccess, State as BufferState, Usage as BufferUsage};
+use hal::command::{ClearColor, ClearValue, ClearValueRaw, CommandBufferFlags, RawCommandBuffer, RawLevel,
+                   SubpassContents};
+use hal::device::Extent;
+use hal::format::{Aspects, Format, Swizzle};
+use hal::image::{Access as ImageAccess, ImageLayout, Kind, Level, State as ImageState, SubresourceRange,
+                 Usage as ImageUsage};
+use hal::memory::{Barrier, Properties};
+use hal::pass::{Attachment, AttachmentLoadOp, AttachmentOps, AttachmentRef, AttachmentStoreOp, Subpass,
+                SubpassDependency, SubpassDesc, SubpassRef};
+use hal::pool::{CommandPoolCreateFlags, RawCommandPool};
+use hal::pso::{PipelineStage, Rect};
+use hal::queue::{QueueFamilyId, RawCommandQueue, RawSubmission};
+
+use Error;
+use alias::{AliasedResources, AliasingBuilder, TransientId};
+use factory::{Buffer, Factory, Image};
+use instrument;
+use state::format_aspects;
+use texture::kind_layers;
+use timestamp::{elapsed_nanos, nanos_to_duration, TimestampPool};
+
+/// Identifier of image declared in `GraphBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ImageId(usize);
+
+/// Identifier of buffer declared in `GraphBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct BufferId(usize);
+
+/// Identifier of pass added to `GraphBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PassId(usize);
+
+/// How pass uses an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum ImageUse {
+    /// Color attachment of render pass.
+    ColorAttachment,
+
+    /// Depth-stencil attachment of render pass with depth writes.
+    DepthStencilAttachment,
+
+    /// Depth-stencil attachment of render pass without depth writes.
+    DepthStencilRead,
+
+    /// Input attachment read by fragment shader of a subpass.
+    InputAttachment,
+
+    /// Sampled in shaders of specified stages.
+    Sampled(PipelineStage),
+
+    /// Storage image read in shaders of specified stages.
+    StorageRead(PipelineStage),
+
+    /// Storage image written in shaders of specified stages.
+    StorageWrite(PipelineStage),
+
+    /// Source of transfer commands.
+    TransferSrc,
+
+    /// Destination of transfer commands.
+    TransferDst,
+}
+
+impl ImageUse {
+    /// Get state and stages of the image for this use.
+    pub fn state(&self) -> (ImageState, PipelineStage) {
+        let fragment_tests = PipelineStage::EARLY_FRAGMENT_TESTS | PipelineStage::LATE_FRAGMENT_TESTS;
+        match *self {
+            ImageUse::ColorAttachment => (
+                (ImageAccess::COLOR_ATTACHMENT_READ | ImageAccess::COLOR_ATTACHMENT_WRITE, ImageLayout::ColorAttachmentOptimal),
+                PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            ),
+            ImageUse::DepthStencilAttachment => (
+                (
+                    ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    ImageLayout::DepthStencilAttachmentOptimal,
+                ),
+                fragment_tests,
+            ),
+            ImageUse::DepthStencilRead => (
+                (ImageAccess::DEPTH_STENCIL_ATTACHMENT_READ, ImageLayout::DepthStencilReadOnlyOptimal),
+                fragment_tests,
+            ),
+            ImageUse::InputAttachment => (
+                (ImageAccess::INPUT_ATTACHMENT_READ, ImageLayout::ShaderReadOnlyOptimal),
+                PipelineStage::FRAGMENT_SHADER,
+            ),
+            ImageUse::Sampled(stages) => ((ImageAccess::SHADER_READ, ImageLayout::ShaderReadOnlyOptimal), stages),
+            ImageUse::StorageRead(stages) => ((ImageAccess::SHADER_READ, ImageLayout::General), stages),
+            ImageUse::StorageWrite(stages) => (
+                (ImageAccess::SHADER_READ | ImageAccess::SHADER_WRITE, ImageLayout::General),
+                stages,
+            ),
+            ImageUse::TransferSrc => ((ImageAccess::TRANSFER_READ, ImageLayout::TransferSrcOptimal), PipelineStage::TRANSFER),
+            ImageUse::TransferDst => ((ImageAccess::TRANSFER_WRITE, ImageLayout::TransferDstOptimal), PipelineStage::TRANSFER),
+        }
+    }
+
+    /// Check if this use writes to the image.
+    pub fn is_write(&self) -> bool {
+        match *self {
+            ImageUse::ColorAttachment
+            | ImageUse::DepthStencilAttachment
+            | ImageUse::StorageWrite(_)
+            | ImageUse::TransferDst => true,
+            _ => false,
+        }
+    }
+
+    fn usage(&self) -> ImageUsage {
+        match *self {
+            ImageUse::ColorAttachment => ImageUsage::COLOR_ATTACHMENT,
+            ImageUse::DepthStencilAttachment | ImageUse::DepthStencilRead => ImageUsage::DEPTH_STENCIL_ATTACHMENT,
+            ImageUse::InputAttachment => ImageUsage::INPUT_ATTACHMENT,
+            ImageUse::Sampled(_) => ImageUsage::SAMPLED,
+            ImageUse::StorageRead(_) | ImageUse::StorageWrite(_) => ImageUsage::STORAGE,
+            ImageUse::TransferSrc => ImageUsage::TRANSFER_SRC,
+            ImageUse::TransferDst => ImageUsage::TRANSFER_DST,
+        }
+    }
+
+    fn is_attachment(&self) -> bool {
+        match *self {
+            ImageUse::ColorAttachment
+            | ImageUse::DepthStencilAttachment
+            | ImageUse::DepthStencilRead
+            | ImageUse::InputAttachment => true,
+            _ => false,
+        }
+    }
+
+    fn is_sampled(&self) -> bool {
+        match *self {
+            ImageUse::Sampled(_) | ImageUse::InputAttachment => true,
+            _ => false,
+        }
+    }
+}
+
+/// How pass uses a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum BufferUse {
+    /// Vertex buffer.
+    Vertex,
+
+    /// Index buffer.
+    Index,
+
+    /// Source of indirect draw or dispatch arguments.
+    Indirect,
+
+    /// Uniform buffer read in shaders of specified stages.
+    Uniform(PipelineStage),
+
+    /// Storage buffer read in shaders of specified stages.
+    StorageRead(PipelineStage),
+
+    /// Storage buffer written in shaders of specified stages.
+    StorageWrite(PipelineStage),
+
+    /// Source of transfer commands.
+    TransferSrc,
+
+    /// Destination of transfer commands.
+    TransferDst,
+}
+
+impl BufferUse {
+    /// Get state and stages of the buffer for this use.
+    pub fn state(&self) -> (BufferState, PipelineStage) {
+        match *self {
+            BufferUse::Vertex => (BufferAccess::VERTEX_BUFFER_READ, PipelineStage::VERTEX_INPUT),
+            BufferUse::Index => (BufferAccess::INDEX_BUFFER_READ, PipelineStage::VERTEX_INPUT),
+            BufferUse::Indirect => (BufferAccess::INDIRECT_COMMAND_READ, PipelineStage::DRAW_INDIRECT),
+            BufferUse::Uniform(stages) => (BufferAccess::CONSTANT_BUFFER_READ, stages),
+            BufferUse::StorageRead(stages) => (BufferAccess::SHADER_READ, stages),
+            BufferUse::StorageWrite(stages) => (BufferAccess::SHADER_READ | BufferAccess::SHADER_WRITE, stages),
+            BufferUse::TransferSrc => (BufferAccess::TRANSFER_READ, PipelineStage::TRANSFER),
+            BufferUse::TransferDst => (BufferAccess::TRANSFER_WRITE, PipelineStage::TRANSFER),
+        }
+    }
+
+    /// Check if this use writes to the buffer.
+    pub fn is_write(&self) -> bool {
+        match *self {
+            BufferUse::StorageWrite(_) | BufferUse::TransferDst => true,
+            _ => false,
+        }
+    }
+
+    fn usage(&self) -> BufferUsage {
+        match *self {
+            BufferUse::Vertex => BufferUsage::VERTEX,
+            BufferUse::Index => BufferUsage::INDEX,
+            BufferUse::Indirect => BufferUsage::INDIRECT,
+            BufferUse::Uniform(_) => BufferUsage::UNIFORM,
+            BufferUse::StorageRead(_) | BufferUse::StorageWrite(_) => BufferUsage::STORAGE,
+            BufferUse::TransferSrc => BufferUsage::TRANSFER_SRC,
+            BufferUse::TransferDst => BufferUsage::TRANSFER_DST,
+        }
+    }
+}
+
+/// Resources available to the pass while it's recorded.
+/// Imported resources are resolved to the ones passed to `Graph::record`.
+#[derive(Debug)]
+pub struct PassContext<'a, B: Backend> {
+    images: Vec<Option<&'a B::Image>>,
+    sampled_views: Vec<Option<&'a B::ImageView>>,
+    buffers: Vec<Option<&'a B::Buffer>>,
+    subpass: Option<(&'a B::RenderPass, usize)>,
+    framebuffer: Option<&'a B::Framebuffer>,
+}
+
+impl<'a, B> PassContext<'a, B>
+where
+    B: Backend,
+{
+    /// Get image declared in the graph.
+    pub fn image(&self, id: ImageId) -> &'a B::Image {
+        self.images[id.0].expect("Imported image is not provided")
+    }
+
+    /// Get depth-only view of transient depth-stencil image the pass samples or reads as input attachment.
+    /// Views of other images are created by passes themselves.
+    pub fn sampled_view(&self, id: ImageId) -> Option<&'a B::ImageView> {
+        self.sampled_views[id.0]
+    }
+
+    /// Get buffer declared in the graph.
+    pub fn buffer(&self, id: BufferId) -> &'a B::Buffer {
+        self.buffers[id.0].expect("Imported buffer is not provided")
+    }
+
+    /// Get subpass the pass is recorded in if it was declared with `PassDesc::subpass`.
+    pub fn subpass(&self) -> Option<Subpass<'a, B>> {
+        self.subpass.map(|(main_pass, index)| Subpass { index, main_pass })
+    }
+
+    /// Get framebuffer of the render pass the pass is recorded in if it was declared with `PassDesc::subpass`.
+    /// Secondary command buffers continuing the subpass are begun with it.
+    pub fn framebuffer(&self) -> Option<&'a B::Framebuffer> {
+        self.framebuffer
+    }
+}
+
+/// Function that records commands of the pass.
+pub type RecordFn<B, T> =
+    Box<FnMut(&mut <B as Backend>::CommandBuffer, &PassContext<B>, &mut Factory<B>, &mut T) + Send + Sync>;
+
+/// Pass of the render graph with resources it uses.
+pub struct PassDesc<B: Backend, T> {
+    name: String,
+    images: Vec<(ImageId, ImageUse)>,
+    buffers: Vec<(BufferId, BufferUse)>,
+    async_compute: bool,
+    subpass: bool,
+    secondary: bool,
+    clears: Vec<(ImageId, ClearValue)>,
+    enabled: Option<Box<Fn(&T) -> bool + Send + Sync>>,
+    record: RecordFn<B, T>,
+}
+
+impl<B, T> PassDesc<B, T>
+where
+    B: Backend,
+{
+    /// Create pass with function that records its commands.
+    pub fn new<F>(name: &str, record: F) -> Self
+    where
+        F: FnMut(&mut B::CommandBuffer, &PassContext<B>, &mut Factory<B>, &mut T) + Send + Sync + 'static,
+    {
+        PassDesc {
+            name: name.to_string(),
+            images: Vec::new(),
+            buffers: Vec::new(),
+            async_compute: false,
+            subpass: false,
+            secondary: false,
+            clears: Vec::new(),
+            enabled: None,
+            record: Box::new(record),
+        }
+    }
+
+    /// Declare use of the image.
+    pub fn image(mut self, id: ImageId, usage: ImageUse) -> Self {
+        self.images.push((id, usage));
+        self
+    }
+
+    /// Declare use of the buffer.
+    pub fn buffer(mut self, id: BufferId, usage: BufferUse) -> Self {
+        self.buffers.push((id, usage));
+        self
+    }
+
+    /// Allow the pass to run on compute queue concurrently with graphics passes.
+    /// Pass must record only compute and transfer commands and must not use attachments.
+    /// It still runs on graphics queue if graph has no compute queue or pass uses imported resources.
+    pub fn async_compute(mut self) -> Self {
+        self.async_compute = true;
+        self
+    }
+
+    /// Record the pass inside render pass created by the graph with attachments the pass uses.
+    /// Consecutive subpass passes rendering to attachments of the same size are merged into subpasses
+    /// of single render pass if all dependencies between them are on attachments,
+    /// e.g. G-buffer written as color attachments and read as input attachments.
+    /// Pipelines must be created for subpass returned by `Graph::subpass` or `PassContext::subpass`.
+    pub fn subpass(mut self) -> Self {
+        self.subpass = true;
+        self
+    }
+
+    /// Record contents of the subpass only with secondary command buffers, e.g. from `ParallelRecorder`
+    /// begun with `render_pass_continue`.
+    /// Only for passes declared with `PassDesc::subpass`.
+    pub fn secondary(mut self) -> Self {
+        self.secondary = true;
+        self
+    }
+
+    /// Clear attachment when render pass begins. Only for passes declared with `PassDesc::subpass`.
+    /// Pass clearing an attachment used by previous pass is never merged with it.
+    pub fn clear(mut self, id: ImageId, value: ClearValue) -> Self {
+        self.clears.push((id, value));
+        self
+    }
+
+    /// Record the pass only in frames where `predicate` returns `true` for data
+    /// passed to `Graph::record` or `Graph::submit`.
+    /// Passes whose results are used only by disabled passes are skipped in those frames as well.
+    /// Barriers of skipped passes are still recorded, so following passes find resources in expected states.
+    pub fn enabled<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.enabled = Some(Box::new(predicate));
+        self
+    }
+
+    /// Get name of the pass.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn writes_image(&self, id: ImageId) -> bool {
+        self.images.iter().any(|&(i, usage)| i == id && usage.is_write())
+    }
+
+    fn writes_buffer(&self, id: BufferId) -> bool {
+        self.buffers.iter().any(|&(i, usage)| i == id && usage.is_write())
+    }
+
+    /// Check if the pass has results outside of the graph,
+    /// i.e. it writes imported resources or doesn't declare writes at all.
+    fn is_output(&self, images: &[ImageNode], buffers: &[BufferNode]) -> bool {
+        let mut writes = false;
+        for &(id, usage) in &self.images {
+            if usage.is_write() {
+                writes = true;
+                if !images[id.0].source.is_transient() {
+                    return true;
+                }
+            }
+        }
+        for &(id, usage) in &self.buffers {
+            if usage.is_write() {
+                writes = true;
+                if !buffers[id.0].source.is_transient() {
+                    return true;
+                }
+            }
+        }
+        !writes
+    }
+
+    /// Check if the pass uses resource of the barrier.
+    fn uses(&self, barrier: &GraphBarrier) -> bool {
+        match *barrier {
+            GraphBarrier::Image { id, .. } => self.images.iter().any(|&(other, _)| other == id),
+            GraphBarrier::Buffer { id, .. } => self.buffers.iter().any(|&(other, _)| other == id),
+        }
+    }
+
+    /// Check if the pass uses resources written by `other` pass.
+    fn consumes(&self, other: &PassDesc<B, T>) -> bool {
+        self.images.iter().any(|&(id, _)| other.writes_image(id))
+            || self.buffers.iter().any(|&(id, _)| other.writes_buffer(id))
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Source<S> {
+    Transient,
+    Imported {
+        initial: (S, PipelineStage),
+        final_state: S,
+    },
+}
+
+impl<S> Source<S> {
+    fn is_transient(&self) -> bool {
+        match *self {
+            Source::Transient => true,
+            Source::Imported { .. } => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ImageNode {
+    name: String,
+    kind: Kind,
+    levels: Level,
+    format: Format,
+    source: Source<ImageState>,
+}
+
+#[derive(Clone, Debug)]
+struct BufferNode {
+    name: String,
+    size: u64,
+    source: Source<BufferState>,
+}
+
+/// Collects resources and passes of the render graph.
+pub struct GraphBuilder<B: Backend, T> {
+    images: Vec<ImageNode>,
+    buffers: Vec<BufferNode>,
+    passes: Vec<PassDesc<B, T>>,
+    aliasing: bool,
+    timing: bool,
+    families: Option<(QueueFamilyId, Option<QueueFamilyId>)>,
+}
+
+impl<B, T> GraphBuilder<B, T>
+where
+    B: Backend,
+{
+    /// Create empty graph builder.
+    pub fn new() -> Self {
+        GraphBuilder {
+            images: Vec::new(),
+            buffers: Vec::new(),
+            passes: Vec::new(),
+            aliasing: true,
+            timing: false,
+            families: None,
+        }
+    }
+
+    /// Set queue families graph is submitted to with `Graph::submit`.
+    /// If `compute` family is specified passes marked with `PassDesc::async_compute` run on compute queue.
+    /// Resources can't be transferred between families, so compute queue is used only if it is
+    /// of the same family as graphics queue, e.g. the one reserved by `Renderer::with_general_compute`.
+    pub fn set_queue_families(&mut self, graphics: QueueFamilyId, compute: Option<QueueFamilyId>) {
+        self.families = Some((graphics, compute));
+    }
+
+    /// Enable or disable aliasing of transient resources. Enabled by default.
+    /// Transient resources which lifetimes within a frame don't overlap share memory when enabled.
+    /// Otherwise each transient resource gets its own allocation.
+    pub fn set_aliasing(&mut self, aliasing: bool) {
+        self.aliasing = aliasing;
+    }
+
+    /// Enable or disable timing of passes. Disabled by default.
+    /// When enabled each pass is surrounded by timestamp queries and `Graph::pass_timings`
+    /// reports average GPU duration of the passes. Requires `Factory::supports_timestamp_queries`.
+    pub fn set_timing(&mut self, timing: bool) {
+        self.timing = timing;
+    }
+
+    /// Declare transient image created by the graph.
+    /// Its usage is derived from passes using it. Content doesn't survive between frames.
+    pub fn create_image(&mut self, name: &str, kind: Kind, levels: Level, format: Format) -> ImageId {
+        self.push_image(name, kind, levels, format, Source::Transient)
+    }
+
+    /// Declare image provided to `Graph::record` each frame, like the backbuffer.
+    ///
+    /// # Parameters
+    ///
+    /// `initial`       - state of the image and stages that accessed it before the graph.
+    /// `final_state`   - state the image is transitioned to after the last pass using it.
+    ///
+    pub fn import_image(
+        &mut self,
+        name: &str,
+        kind: Kind,
+        levels: Level,
+        format: Format,
+        initial: (ImageState, PipelineStage),
+        final_state: ImageState,
+    ) -> ImageId {
+        self.push_image(name, kind, levels, format, Source::Imported { initial, final_state })
+    }
+
+    /// Declare transient buffer created by the graph.
+    /// Its usage is derived from passes using it. Content doesn't survive between frames.
+    pub fn create_buffer(&mut self, name: &str, size: u64) -> BufferId {
+        self.push_buffer(name, size, Source::Transient)
+    }
+
+    /// Declare buffer provided to `Graph::record` each frame.
+    /// See `GraphBuilder::import_image` for parameters.
+    pub fn import_buffer(
+        &mut self,
+        name: &str,
+        size: u64,
+        initial: (BufferState, PipelineStage),
+        final_state: BufferState,
+    ) -> BufferId {
+        self.push_buffer(name, size, Source::Imported { initial, final_state })
+    }
+
+    /// Add pass to the graph.
+    /// Passes access each resource in order they were added in. Pass that reads a resource
+    /// runs after the last pass added before it that writes the resource,
+    /// pass that writes a resource runs after passes added before it that access the resource.
+    pub fn add_pass(&mut self, pass: PassDesc<B, T>) -> PassId {
+        self.passes.push(pass);
+        PassId(self.passes.len() - 1)
+    }
+
+    fn push_image(&mut self, name: &str, kind: Kind, levels: Level, format: Format, source: Source<ImageState>) -> ImageId {
+        self.images.push(ImageNode {
+            name: name.to_string(),
+            kind,
+            levels,
+            format,
+            source,
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    fn push_buffer(&mut self, name: &str, size: u64, source: Source<BufferState>) -> BufferId {
+        self.buffers.push(BufferNode {
+            name: name.to_string(),
+            size,
+            source,
+        });
+        BufferId(self.buffers.len() - 1)
+    }
+
+    /// Order passes, create transient resources and compute barriers.
+    /// Passes that don't contribute to results of the graph are culled along with resources only they use.
+    pub fn build(self, factory: &mut Factory<B>) -> Result<Graph<B, T>, Error> {
+        let _span = instrument::graph_build(self.passes.len());
+        let live = live_passes(
+            &self.passes.iter().collect::<Vec<_>>(),
+            &vec![true; self.passes.len()],
+            &self.images,
+            &self.buffers,
+        );
+        for (pass, _) in self.passes.iter().zip(&live).filter(|&(_, &live)| !live) {
+            debug!("Pass {} doesn't contribute to results of the graph and is culled", pass.name);
+        }
+        let order = self.order(&live)?;
+        let queues = self.queues(&order)?;
+
+        let mut image_usage = vec![ImageUsage::empty(); self.images.len()];
+        let mut sampled = vec![false; self.images.len()];
+        let mut buffer_usage = vec![BufferUsage::empty(); self.buffers.len()];
+        for &index in &order {
+            let ref pass = self.passes[index];
+            for &(id, usage) in &pass.images {
+                image_usage[id.0] |= usage.usage();
+                sampled[id.0] |= usage.is_sampled();
+            }
+            for &(id, usage) in &pass.buffers {
+                buffer_usage[id.0] |= usage.usage();
+            }
+        }
+
+        let ranges = self.images
+            .iter()
+            .map(|node| SubresourceRange {
+                aspects: format_aspects(node.format),
+                levels: 0..node.levels,
+                layers: 0..kind_layers(node.kind),
+            })
+            .collect::<Vec<_>>();
+
+        let transients = if self.aliasing {
+            self.alias_transients(&order, &queues, &image_usage, &buffer_usage, &ranges, factory)?
+        } else {
+            self.allocate_transients(&image_usage, &buffer_usage, factory)?
+        };
+
+        let aliased = (
+            (0..self.images.len())
+                .map(|index| transients.aliased_image_stages(index))
+                .collect::<Vec<_>>(),
+            (0..self.buffers.len())
+                .map(|index| transients.aliased_buffer_stages(index))
+                .collect::<Vec<_>>(),
+        );
+        let (barriers, final_barriers, transfers) = self.barriers(&order, &queues, &aliased.0, &aliased.1);
+        let batches = schedule(&queues, &transfers);
+        let timing = match (self.timing, factory.timestamp_period()) {
+            (false, _) => None,
+            (true, None) => {
+                info!("Timestamp queries are not supported. Graph passes are not timed");
+                None
+            }
+            (true, Some(period)) => Some(GraphTiming::new(period, order.len())),
+        };
+        let GraphBuilder {
+            images: image_nodes,
+            buffers: buffer_nodes,
+            passes,
+            families,
+            ..
+        } = self;
+        let mut positions = vec![None; passes.len()];
+        for (position, &index) in order.iter().enumerate() {
+            positions[index] = Some(position);
+        }
+        let mut passes = passes.into_iter().map(Some).collect::<Vec<_>>();
+        let mut passes = order
+            .iter()
+            .zip(barriers)
+            .zip(queues)
+            .map(|((&index, barriers), queue)| CompiledPass {
+                desc: passes[index].take().expect("Each pass is ordered once"),
+                barriers,
+                queue,
+                subpass: None,
+            })
+            .collect::<Vec<_>>();
+
+        let groups = render_groups(&mut passes, &image_nodes, &ranges, &batches, factory)?;
+        let mut views = Vec::with_capacity(image_nodes.len());
+        let mut sampled_views = Vec::with_capacity(image_nodes.len());
+        {
+            let context = transients.context(&[], &[]);
+            for (index, node) in image_nodes.iter().enumerate() {
+                // Shaders may read only one aspect of depth-stencil image through a view.
+                let ref range = ranges[index];
+                sampled_views.push(match context.images[index] {
+                    Some(image) if sampled[index] && range.aspects.contains(Aspects::DEPTH) => Some(
+                        factory
+                            .create_image_view(
+                                image,
+                                node.format,
+                                Swizzle::NO,
+                                SubresourceRange {
+                                    aspects: Aspects::DEPTH,
+                                    ..range.clone()
+                                },
+                            )
+                            .map_err(|err| Error::creation(format!("depth view of {}", node.name), err))?,
+                    ),
+                    _ => None,
+                });
+                let attachment = groups
+                    .iter()
+                    .any(|group| group.attachments.iter().any(|id| id.0 == index));
+                views.push(match (context.images[index], attachment) {
+                    (Some(image), true) => Some(
+                        factory
+                            .create_image_view(image, node.format, Swizzle::NO, ranges[index].clone())
+                            .map_err(|err| Error::creation(format!("view of {}", node.name), err))?,
+                    ),
+                    _ => None,
+                });
+            }
+        }
+
+        Ok(Graph {
+            image_nodes,
+            buffer_nodes,
+            ranges,
+            transients,
+            passes,
+            final_barriers,
+            families,
+            batches,
+            frames: VecDeque::new(),
+            free: Vec::new(),
+            positions,
+            groups,
+            views,
+            sampled_views,
+            imported_views: HashMap::new(),
+            framebuffers: HashMap::new(),
+            timing,
+        })
+    }
+
+    /// Create each transient resource in its own allocation.
+    fn allocate_transients(
+        &self,
+        image_usage: &[ImageUsage],
+        buffer_usage: &[BufferUsage],
+        factory: &mut Factory<B>,
+    ) -> Result<Transients<B>, Error> {
+        let mut images = Vec::with_capacity(self.images.len());
+        for (node, &usage) in self.images.iter().zip(image_usage) {
+            images.push(match node.source {
+                Source::Transient if !usage.is_empty() => Some(factory.create_image(
+                    node.kind,
+                    node.levels,
+                    node.format,
+                    Properties::DEVICE_LOCAL,
+                    usage,
+                )?),
+                _ => None,
+            });
+        }
+        let mut buffers = Vec::with_capacity(self.buffers.len());
+        for (node, &usage) in self.buffers.iter().zip(buffer_usage) {
+            buffers.push(match node.source {
+                Source::Transient if !usage.is_empty() => {
+                    Some(factory.create_buffer(node.size, Properties::DEVICE_LOCAL, usage)?)
+                }
+                _ => None,
+            });
+        }
+        Ok(Transients::Allocated { images, buffers })
+    }
+
+    /// Create transient resources sharing memory where their lifetimes within the frame don't overlap.
+    /// Lifetime of the resource spans from the first to the last pass using it in `order`.
+    /// Resources used on compute queue live through the whole frame since passes on other queue may overlap them.
+    fn alias_transients(
+        &self,
+        order: &[usize],
+        queues: &[QueueKind],
+        image_usage: &[ImageUsage],
+        buffer_usage: &[BufferUsage],
+        ranges: &[SubresourceRange],
+        factory: &mut Factory<B>,
+    ) -> Result<Transients<B>, Error> {
+        let mut image_lifetimes: Vec<Option<(Range<usize>, ImageState, PipelineStage)>> = vec![None; self.images.len()];
+        let mut buffer_lifetimes: Vec<Option<(Range<usize>, BufferState, PipelineStage)>> = vec![None; self.buffers.len()];
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+            for &(id, usage) in &pass.images {
+                let (state, stages) = usage.state();
+                let lifetime = image_lifetimes[id.0].get_or_insert((position..position, state, stages));
+                lifetime.0.end = lifetime.0.end.max(position + 1);
+                lifetime.2 |= stages;
+                if queues[position] == QueueKind::Compute {
+                    lifetime.0 = 0..order.len();
+                }
+            }
+            for &(id, usage) in &pass.buffers {
+                let (state, stages) = usage.state();
+                let lifetime = buffer_lifetimes[id.0].get_or_insert((position..position, state, stages));
+                lifetime.0.end = lifetime.0.end.max(position + 1);
+                lifetime.2 |= stages;
+                if queues[position] == QueueKind::Compute {
+                    lifetime.0 = 0..order.len();
+                }
+            }
+        }
+
+        let mut builder = AliasingBuilder::new();
+        let mut images = Vec::with_capacity(self.images.len());
+        for (index, node) in self.images.iter().enumerate() {
+            images.push(match (&node.source, image_lifetimes[index].clone()) {
+                (&Source::Transient, Some((passes, state, stages))) => Some(builder.image(
+                    node.kind,
+                    node.levels,
+                    node.format,
+                    image_usage[index],
+                    ranges[index].clone(),
+                    state,
+                    stages,
+                    passes,
+                )),
+                _ => None,
+            });
+        }
+        let mut buffers = Vec::with_capacity(self.buffers.len());
+        for (index, node) in self.buffers.iter().enumerate() {
+            buffers.push(match (&node.source, buffer_lifetimes[index].clone()) {
+                (&Source::Transient, Some((passes, state, stages))) => {
+                    Some(builder.buffer(node.size, buffer_usage[index], state, stages, passes))
+                }
+                _ => None,
+            });
+        }
+
+        if images.iter().chain(&buffers).all(Option::is_none) {
+            return Ok(Transients::Allocated {
+                images: images.iter().map(|_| None).collect(),
+                buffers: buffers.iter().map(|_| None).collect(),
+            });
+        }
+        let resources = builder.build(factory)?;
+        Ok(Transients::Aliased {
+            resources,
+            images,
+            buffers,
+        })
+    }
+
+    /// Get passes each pass depends on.
+    /// Reads depend on the last write added before them,
+    /// writes depend on the last write and all reads added since it.
+    fn dependencies(&self) -> Vec<HashSet<usize>> {
+        let mut dependencies = vec![HashSet::new(); self.passes.len()];
+        let mut images = vec![(None, Vec::new()); self.images.len()];
+        let mut buffers = vec![(None, Vec::new()); self.buffers.len()];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &(id, usage) in &pass.images {
+                depend(&mut dependencies[index], &mut images[id.0], index, usage.is_write());
+            }
+            for &(id, usage) in &pass.buffers {
+                depend(&mut dependencies[index], &mut buffers[id.0], index, usage.is_write());
+            }
+        }
+        dependencies
+    }
+
+    /// Sort `live` passes topologically. Ties are broken by order passes were added in.
+    fn order(&self, live: &[bool]) -> Result<Vec<usize>, Error> {
+        let count = self.passes.len();
+        let dependencies = self.dependencies();
+
+        let mut order = Vec::with_capacity(count);
+        let mut done = live.iter().map(|&live| !live).collect::<Vec<_>>();
+        while done.iter().any(|&done| !done) {
+            let next = (0..count)
+                .find(|&index| !done[index] && dependencies[index].iter().all(|&dep| done[dep]))
+                .ok_or_else(|| {
+                    let stuck = (0..count)
+                        .filter(|&index| !done[index])
+                        .map(|index| self.passes[index].name.clone())
+                        .collect::<Vec<_>>();
+                    Error::GraphCycle(stuck)
+                })?;
+            done[next] = true;
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    /// Compute barriers recorded before each pass in `order` and after the last one.
+    /// `aliased_images` and `aliased_buffers` are stages of resources sharing memory with transient resources.
+    /// Resources used on different queues by consecutive passes are transferred between queues instead.
+    fn barriers(
+        &self,
+        order: &[usize],
+        queues: &[QueueKind],
+        aliased_images: &[PipelineStage],
+        aliased_buffers: &[PipelineStage],
+    ) -> (Vec<Vec<GraphBarrier>>, Vec<GraphBarrier>, Vec<Transfer>) {
+        // Transient resources start each frame in undefined state, waiting for their last use in previous frame
+        // and for resources sharing their memory.
+        let mut image_states = self.images
+            .iter()
+            .zip(aliased_images)
+            .map(|(node, &aliased)| match node.source {
+                Source::Transient => ((ImageAccess::empty(), ImageLayout::Undefined), aliased, false),
+                Source::Imported { initial, .. } => (initial.0, initial.1, true),
+            })
+            .collect::<Vec<_>>();
+        let mut buffer_states = self.buffers
+            .iter()
+            .zip(aliased_buffers)
+            .map(|(node, &aliased)| match node.source {
+                Source::Transient => (BufferAccess::empty(), aliased, !aliased.is_empty()),
+                Source::Imported { initial, .. } => (initial.0, initial.1, true),
+            })
+            .collect::<Vec<_>>();
+        for &index in order {
+            let pass = &self.passes[index];
+            for &(id, usage) in &pass.images {
+                if let Source::Transient = self.images[id.0].source {
+                    image_states[id.0].1 |= usage.state().1;
+                }
+            }
+            for &(id, usage) in &pass.buffers {
+                if let Source::Transient = self.buffers[id.0].source {
+                    buffer_states[id.0].1 |= usage.state().1;
+                }
+            }
+        }
+
+        let mut image_users = vec![None; self.images.len()];
+        let mut buffer_users = vec![None; self.buffers.len()];
+        let mut barriers = Vec::with_capacity(order.len());
+        let mut transfers = Vec::new();
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+            let queue = queues[position];
+            let mut pass_barriers = Vec::new();
+            for &(id, usage) in &pass.images {
+                let (state, stages) = usage.state();
+                let (last_state, last_stages, last_write) = image_states[id.0];
+                let reads = !last_write && !usage.is_write();
+                let barrier = GraphBarrier::Image {
+                    id,
+                    states: last_state..state,
+                    stages: last_stages..stages,
+                };
+                match image_users[id.0] {
+                    Some(from) if queues[from] != queue => {
+                        transfers.push(Transfer {
+                            barrier,
+                            from,
+                            to: position,
+                        });
+                        image_states[id.0] = (state, stages, usage.is_write());
+                    }
+                    _ => {
+                        // Consecutive reads are synchronized once per access, layout and stage.
+                        // Following write waits for all of them.
+                        let covered = last_state.1 == state.1 && last_state.0.contains(state.0) && last_stages.contains(stages);
+                        if !reads || !covered {
+                            pass_barriers.push(barrier);
+                        }
+                        image_states[id.0] = if reads {
+                            ((last_state.0 | state.0, state.1), last_stages | stages, false)
+                        } else {
+                            (state, stages, usage.is_write())
+                        };
+                    }
+                }
+                image_users[id.0] = Some(position);
+            }
+            for &(id, usage) in &pass.buffers {
+                let (state, stages) = usage.state();
+                let (last_state, last_stages, last_write) = buffer_states[id.0];
+                let reads = !last_write && !usage.is_write();
+                let barrier = GraphBarrier::Buffer {
+                    id,
+                    states: last_state..state,
+                    stages: last_stages..stages,
+                };
+                match buffer_users[id.0] {
+                    Some(from) if queues[from] != queue => {
+                        transfers.push(Transfer {
+                            barrier,
+                            from,
+                            to: position,
+                        });
+                        buffer_states[id.0] = (state, stages, usage.is_write());
+                    }
+                    _ => {
+                        // Nothing to wait for before transient buffer is first used.
+                        let covered = last_stages.is_empty() || (last_state.contains(state) && last_stages.contains(stages));
+                        if !reads || !covered {
+                            pass_barriers.push(barrier);
+                        }
+                        buffer_states[id.0] = if reads {
+                            (last_state | state, last_stages | stages, false)
+                        } else {
+                            (state, stages, usage.is_write())
+                        };
+                    }
+                }
+                buffer_users[id.0] = Some(position);
+            }
+            barriers.push(pass_barriers);
+        }
+
+        let mut final_barriers = Vec::new();
+        for (index, node) in self.images.iter().enumerate() {
+            if let Source::Imported { final_state, .. } = node.source {
+                let (state, stages, _) = image_states[index];
+                final_barriers.push(GraphBarrier::Image {
+                    id: ImageId(index),
+                    states: state..final_state,
+                    stages: stages..PipelineStage::BOTTOM_OF_PIPE,
+                });
+            }
+        }
+        for (index, node) in self.buffers.iter().enumerate() {
+            if let Source::Imported { final_state, .. } = node.source {
+                let (state, stages, _) = buffer_states[index];
+                final_barriers.push(GraphBarrier::Buffer {
+                    id: BufferId(index),
+                    states: state..final_state,
+                    stages: stages..PipelineStage::BOTTOM_OF_PIPE,
+                });
+            }
+        }
+        (barriers, final_barriers, transfers)
+    }
+
+    /// Pick queue for each pass in `order`.
+    /// Passes marked with `PassDesc::async_compute` go to compute queue if there is one,
+    /// unless they use imported resources which are owned by graphics queue.
+    fn queues(&self, order: &[usize]) -> Result<Vec<QueueKind>, Error> {
+        let compute = match self.families {
+            Some((graphics, Some(compute))) => graphics == compute,
+            _ => false,
+        };
+        if !compute && self.passes.iter().any(|pass| pass.async_compute) {
+            info!("Async compute passes are scheduled on graphics queue");
+        }
+
+        let mut queues = Vec::with_capacity(order.len());
+        for &index in order {
+            let pass = &self.passes[index];
+            if !pass.async_compute {
+                queues.push(QueueKind::Graphics);
+                continue;
+            }
+            if pass.images.iter().any(|&(_, usage)| match usage {
+                ImageUse::ColorAttachment | ImageUse::DepthStencilAttachment | ImageUse::DepthStencilRead => true,
+                _ => false,
+            }) {
+                return Err(Error::InvalidGraph {
+                    name: pass.name.clone(),
+                    reason: "async compute pass uses attachments",
+                });
+            }
+            let imported = pass.images
+                .iter()
+                .any(|&(id, _)| !self.images[id.0].source.is_transient())
+                || pass.buffers
+                    .iter()
+                    .any(|&(id, _)| !self.buffers[id.0].source.is_transient());
+            if imported {
+                debug!("Async compute pass {} uses imported resources and runs on graphics queue", pass.name);
+            }
+            queues.push(if compute && !imported {
+                QueueKind::Compute
+            } else {
+                QueueKind::Graphics
+            });
+        }
+        Ok(queues)
+    }
+}
+
+/// Add dependencies of pass `index` accessing resource with `history` of last writer and readers since it.
+fn depend(dependencies: &mut HashSet<usize>, history: &mut (Option<usize>, Vec<usize>), index: usize, write: bool) {
+    dependencies.extend(history.0.iter().cloned().filter(|&other| other != index));
+    if write {
+        dependencies.extend(history.1.drain(..).filter(|&other| other != index));
+        history.0 = Some(index);
+    } else if !history.1.contains(&index) {
+        history.1.push(index);
+    }
+}
+
+/// Find passes contributing to results of the graph.
+/// Pass is live if it is enabled and either has results outside of the graph
+/// or writes resources used by live passes added after it.
+fn live_passes<B, T>(
+    passes: &[&PassDesc<B, T>],
+    enabled: &[bool],
+    images: &[ImageNode],
+    buffers: &[BufferNode],
+) -> Vec<bool>
+where
+    B: Backend,
+{
+    let mut live = passes
+        .iter()
+        .zip(enabled)
+        .map(|(pass, &enabled)| enabled && pass.is_output(images, buffers))
+        .collect::<Vec<_>>();
+    loop {
+        let mut changed = false;
+        for index in 0..passes.len() {
+            if live[index] || !enabled[index] {
+                continue;
+            }
+            if (0..passes.len()).any(|other| other > index && live[other] && passes[other].consumes(passes[index])) {
+                live[index] = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            return live;
+        }
+    }
+}
+
+/// Split passes into batches submitted to their queues.
+/// Batch ends when a pass on another queue depends on it and waits for its semaphore.
+/// Last batch is always on graphics queue and waits for all compute batches nothing waits for,
+/// so completion of the last batch means the whole graph is complete.
+fn schedule(queues: &[QueueKind], transfers: &[Transfer]) -> Vec<Batch> {
+    let mut batches: Vec<Batch> = Vec::new();
+    let mut open = [None, None];
+    let mut batch_of = vec![0; queues.len()];
+    for (position, &queue) in queues.iter().enumerate() {
+        let incoming = transfers
+            .iter()
+            .filter(|transfer| transfer.to == position)
+            .collect::<Vec<_>>();
+        if !incoming.is_empty() {
+            // Source batches signal once they end. This pass starts new batch to wait at its beginning.
+            open = [None, None];
+        }
+        let batch = match open[queue as usize] {
+            Some(batch) => batch,
+            None => {
+                batches.push(Batch::new(queue));
+                open[queue as usize] = Some(batches.len() - 1);
+                batches.len() - 1
+            }
+        };
+        for transfer in incoming {
+            let source = batch_of[transfer.from];
+            batches[source].signal = true;
+            batches[batch].acquires.push(transfer.barrier.acquire());
+            batches[batch].wait(source, transfer.barrier.stages().end);
+        }
+        batches[batch].passes.push(position);
+        batch_of[position] = batch;
+    }
+
+    let dangling = batches
+        .iter()
+        .enumerate()
+        .filter(|&(_, batch)| batch.queue == QueueKind::Compute && !batch.signal)
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if !dangling.is_empty() || batches.last().map_or(true, |batch| batch.queue != QueueKind::Graphics) {
+        let mut last = Batch::new(QueueKind::Graphics);
+        for index in dangling {
+            batches[index].signal = true;
+            last.wait(index, graphics_stages());
+        }
+        batches.push(last);
+    }
+    batches
+}
+
+/// Queue pass is submitted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum QueueKind {
+    /// Queue of the family passed as graphics to `GraphBuilder::set_queue_families`.
+    Graphics = 0,
+
+    /// Queue of the family passed as compute to `GraphBuilder::set_queue_families`.
+    Compute = 1,
+}
+
+/// Resource used by passes on different queues of the same family.
+/// Destination batch waits for the source and records barrier after that.
+#[derive(Clone, Debug)]
+struct Transfer {
+    barrier: GraphBarrier,
+    from: usize,
+    to: usize,
+}
+
+/// Passes recorded into single command buffer and submitted together.
+#[derive(Clone, Debug)]
+struct Batch {
+    queue: QueueKind,
+    passes: Vec<usize>,
+    waits: Vec<(usize, PipelineStage)>,
+    acquires: Vec<GraphBarrier>,
+    signal: bool,
+}
+
+impl Batch {
+    fn new(queue: QueueKind) -> Self {
+        Batch {
+            queue,
+            passes: Vec::new(),
+            waits: Vec::new(),
+            acquires: Vec::new(),
+            signal: false,
+        }
+    }
+
+    fn wait(&mut self, batch: usize, stages: PipelineStage) {
+        match self.waits.iter().position(|&(index, _)| index == batch) {
+            Some(position) => self.waits[position].1 |= stages,
+            None => self.waits.push((batch, stages)),
+        }
+    }
+}
+
+/// Stages graphics queue waits on to finish the graph.
+fn graphics_stages() -> PipelineStage {
+    PipelineStage::all() - PipelineStage::HOST
+}
+
+/// Stages compute queue waits on before the frame starts.
+fn compute_stages() -> PipelineStage {
+    PipelineStage::COMPUTE_SHADER | PipelineStage::TRANSFER
+}
+
+/// Barrier graph records between passes.
+#[derive(Clone, Debug)]
+enum GraphBarrier {
+    Image {
+        id: ImageId,
+        states: Range<ImageState>,
+        stages: Range<PipelineStage>,
+    },
+    Buffer {
+        id: BufferId,
+        states: Range<BufferState>,
+        stages: Range<PipelineStage>,
+    },
+}
+
+impl GraphBarrier {
+    fn stages(&self) -> Range<PipelineStage> {
+        match *self {
+            GraphBarrier::Image { ref stages, .. } | GraphBarrier::Buffer { ref stages, .. } => stages.clone(),
+        }
+    }
+
+    /// Barrier recorded on destination queue after waiting for semaphore at destination stages.
+    /// Semaphore makes writes of the source queue available, so only layout transition is left.
+    fn acquire(&self) -> GraphBarrier {
+        match *self {
+            GraphBarrier::Image {
+                id,
+                ref states,
+                ref stages,
+            } => GraphBarrier::Image {
+                id,
+                states: (ImageAccess::empty(), states.start.1)..states.end,
+                stages: stages.end..stages.end,
+            },
+            GraphBarrier::Buffer {
+                id,
+                ref states,
+                ref stages,
+            } => GraphBarrier::Buffer {
+                id,
+                states: BufferAccess::empty()..states.end,
+                stages: stages.end..stages.end,
+            },
+        }
+    }
+}
+
+struct CompiledPass<B: Backend, T> {
+    desc: PassDesc<B, T>,
+    barriers: Vec<GraphBarrier>,
+    queue: QueueKind,
+    subpass: Option<(usize, usize)>,
+}
+
+/// Render pass created by the graph for consecutive subpass passes.
+#[derive(Debug)]
+struct RenderGroup<B: Backend> {
+    passes: Range<usize>,
+    attachments: Vec<ImageId>,
+    clears: Vec<ClearValueRaw>,
+    barriers: Vec<GraphBarrier>,
+    area: Rect,
+    render_pass: B::RenderPass,
+}
+
+/// Framebuffer is created for render group and addresses of imported attachments.
+type FramebufferKey = (usize, Vec<usize>);
+
+/// Command buffers and semaphores of the graph submitted for one frame.
+#[derive(Debug)]
+struct GraphFrame<B: Backend> {
+    graphics_pool: B::CommandPool,
+    graphics: Vec<B::CommandBuffer>,
+    compute: Option<(B::CommandPool, Vec<B::CommandBuffer>)>,
+    semaphores: Vec<B::Semaphore>,
+}
+
+/// Timestamp queries written around each pass and average durations read from them.
+/// Each frame in flight writes its own query pool.
+#[derive(Debug)]
+struct GraphTiming<B: Backend> {
+    period: f32,
+    pending: VecDeque<(TimestampPool<B>, u64, Vec<bool>)>,
+    free: Vec<TimestampPool<B>>,
+    averages: Vec<Option<u64>>,
+}
+
+impl<B> GraphTiming<B>
+where
+    B: Backend,
+{
+    fn new(period: f32, passes: usize) -> Self {
+        GraphTiming {
+            period,
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            averages: vec![None; passes],
+        }
+    }
+
+    /// Read timestamps of frames that are complete and take query pool for the current frame.
+    /// Only passes `active` in the frame contribute to averages.
+    /// Returns `false` if pool can't be created, in which case the frame isn't timed.
+    fn begin(&mut self, active: &[bool], factory: &mut Factory<B>) -> bool {
+        let current = factory.current();
+        let ongoing = factory.ongoing();
+        let device: &B::Device = &**factory;
+        while self.pending.front().map_or(false, |&(_, index, _)| index < ongoing) {
+            let (pool, _, recorded) = self.pending.pop_front().unwrap();
+            let mut results = vec![0; pool.count() as usize];
+            if pool.read(device, 0..pool.count(), &mut results) {
+                let timed = self.averages.iter_mut().zip(results.chunks(2)).zip(recorded);
+                for ((average, timestamps), _) in timed.filter(|&(_, recorded)| recorded) {
+                    let nanos = elapsed_nanos(timestamps[0], timestamps[1], self.period);
+                    *average = Some(match *average {
+                        Some(average) => average - average / 16 + nanos / 16,
+                        None => nanos,
+                    });
+                }
+            }
+            self.free.push(pool);
+        }
+        let pool = match self.free.pop() {
+            Some(pool) => pool,
+            None => match TimestampPool::new(device, self.averages.len() as u32 * 2) {
+                Ok(pool) => pool,
+                Err(err) => {
+                    warn!("{}. Frame {} is not timed", err, current);
+                    return false;
+                }
+            },
+        };
+        self.pending.push_back((pool, current, active.to_vec()));
+        true
+    }
+
+    /// Get query pool of the current frame.
+    fn pool(&self) -> &TimestampPool<B> {
+        &self.pending.back().expect("Frame is begun").0
+    }
+
+    /// Record reset of queries of passes at `positions`.
+    fn reset(&self, cbuf: &mut B::CommandBuffer, positions: Range<usize>) {
+        self.pool()
+            .reset(cbuf, positions.start as u32 * 2..positions.end as u32 * 2);
+    }
+
+    /// Record timestamp before or after pass at `position`.
+    fn write(&self, cbuf: &mut B::CommandBuffer, position: usize, end: bool) {
+        let (stage, query) = if end {
+            (PipelineStage::BOTTOM_OF_PIPE, position * 2 + 1)
+        } else {
+            (PipelineStage::TOP_OF_PIPE, position * 2)
+        };
+        self.pool().write(cbuf, stage, query as u32);
+    }
+
+    /// Destroy query pools. Frames using them must be complete.
+    fn dispose(self, device: &B::Device) {
+        for pool in self.pending.into_iter().map(|(pool, _, _)| pool).chain(self.free) {
+            pool.dispose(device);
+        }
+    }
+}
+
+/// Compiled render graph.
+pub struct Graph<B: Backend, T> {
+    image_nodes: Vec<ImageNode>,
+    buffer_nodes: Vec<BufferNode>,
+    ranges: Vec<SubresourceRange>,
+    transients: Transients<B>,
+    passes: Vec<CompiledPass<B, T>>,
+    final_barriers: Vec<GraphBarrier>,
+    families: Option<(QueueFamilyId, Option<QueueFamilyId>)>,
+    batches: Vec<Batch>,
+    frames: VecDeque<(GraphFrame<B>, u64)>,
+    free: Vec<GraphFrame<B>>,
+    positions: Vec<Option<usize>>,
+    groups: Vec<RenderGroup<B>>,
+    views: Vec<Option<B::ImageView>>,
+    sampled_views: Vec<Option<B::ImageView>>,
+    imported_views: HashMap<(usize, usize), B::ImageView>,
+    framebuffers: HashMap<FramebufferKey, B::Framebuffer>,
+    timing: Option<GraphTiming<B>>,
+}
+
+impl<B, T> Graph<B, T>
+where
+    B: Backend,
+{
+    /// Get names of passes in order they are recorded.
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.desc.name()).collect()
+    }
+
+    /// Get queue each pass is submitted to, in order passes are recorded.
+    pub fn pass_queues(&self) -> Vec<QueueKind> {
+        self.passes.iter().map(|pass| pass.queue).collect()
+    }
+
+    /// Get average GPU duration of each pass, in order passes are recorded.
+    /// Duration is `None` if graph is built without `GraphBuilder::set_timing`
+    /// or until first timed frame completes. Frames where the pass is skipped don't contribute to its average.
+    pub fn pass_timings(&self) -> Vec<(&str, Option<Duration>)> {
+        self.passes
+            .iter()
+            .enumerate()
+            .map(|(position, pass)| {
+                let average = self.timing
+                    .as_ref()
+                    .and_then(|timing| timing.averages[position])
+                    .map(nanos_to_duration);
+                (pass.desc.name(), average)
+            })
+            .collect()
+    }
+
+    /// Get name of the image.
+    pub fn image_name(&self, id: ImageId) -> &str {
+        &self.image_nodes[id.0].name
+    }
+
+    /// Get name of the buffer.
+    pub fn buffer_name(&self, id: BufferId) -> &str {
+        &self.buffer_nodes[id.0].name
+    }
+
+    /// Record all passes with barriers between them.
+    /// Graph with passes scheduled on compute queue must be submitted with `Graph::submit` instead.
+    ///
+    /// # Parameters
+    ///
+    /// `cbuf`      - command buffer to record into. It must be in recording state.
+    /// `images`    - imported images for this frame.
+    /// `buffers`   - imported buffers for this frame.
+    ///
+    pub fn record(
+        &mut self,
+        cbuf: &mut B::CommandBuffer,
+        images: &[(ImageId, &B::Image)],
+        buffers: &[(BufferId, &B::Buffer)],
+        factory: &mut Factory<B>,
+        data: &mut T,
+    ) -> Result<(), Error> {
+        assert!(
+            self.passes.iter().all(|pass| pass.queue == QueueKind::Graphics),
+            "Graph with async compute passes can't be recorded into single command buffer"
+        );
+        let active = self.active(data);
+        let keys = self.prepare_framebuffers(images, factory)?;
+        let ref framebuffers = self.framebuffers;
+        let framebuffers = keys.iter().map(|key| &framebuffers[key]).collect::<Vec<_>>();
+        let mut context = self.transients.context(images, buffers);
+        context.sampled_views = self.sampled_views.iter().map(Option::as_ref).collect();
+        let timed = self.timing.as_mut().map_or(false, |timing| timing.begin(&active, factory));
+        let timing = if timed { self.timing.as_ref() } else { None };
+        let positions = (0..self.passes.len()).collect::<Vec<_>>();
+        record_passes(
+            cbuf,
+            &positions,
+            &mut self.passes,
+            &self.groups,
+            &framebuffers,
+            &mut context,
+            &self.ranges,
+            &active,
+            timing,
+            factory,
+            data,
+        );
+        record_barriers(cbuf, &self.final_barriers, &context, &self.ranges);
+        Ok(())
+    }
+
+    /// Record and submit all passes to their queues.
+    /// Passes on different queues are synchronized with semaphores.
+    /// Graph must be built with `GraphBuilder::set_queue_families`.
+    ///
+    /// # Parameters
+    ///
+    /// `graphics`  - queue of graphics family.
+    /// `compute`   - queue of compute family. Required if any pass is scheduled on compute queue.
+    /// `images`    - imported images for this frame.
+    /// `buffers`   - imported buffers for this frame.
+    /// `wait`      - semaphores first graphics submission waits for.
+    /// `signal`    - semaphores last graphics submission signals.
+    /// `fence`     - fence last graphics submission signals. All submissions of the graph are complete then.
+    ///
+    pub fn submit(
+        &mut self,
+        graphics: &mut B::CommandQueue,
+        mut compute: Option<&mut B::CommandQueue>,
+        images: &[(ImageId, &B::Image)],
+        buffers: &[(BufferId, &B::Buffer)],
+        wait: &[(&B::Semaphore, PipelineStage)],
+        signal: &[&B::Semaphore],
+        fence: Option<&B::Fence>,
+        factory: &mut Factory<B>,
+        data: &mut T,
+    ) -> Result<(), Error> {
+        let active = self.active(data);
+        let keys = self.prepare_framebuffers(images, factory)?;
+        let mut frame = self.frame(factory);
+        let ref framebuffers = self.framebuffers;
+        let framebuffers = keys.iter().map(|key| &framebuffers[key]).collect::<Vec<_>>();
+        let mut context = self.transients.context(images, buffers);
+        context.sampled_views = self.sampled_views.iter().map(Option::as_ref).collect();
+        let timed = self.timing.as_mut().map_or(false, |timing| timing.begin(&active, factory));
+        let timing = if timed { self.timing.as_ref() } else { None };
+
+        let last = self.batches.len() - 1;
+        let mut cbufs = Vec::with_capacity(self.batches.len());
+        let (mut graphics_index, mut compute_index) = (0, 0);
+        for (index, batch) in self.batches.iter().enumerate() {
+            let cbuf = match batch.queue {
+                QueueKind::Graphics => {
+                    graphics_index += 1;
+                    cbufs.push((QueueKind::Graphics, graphics_index - 1));
+                    &mut frame.graphics[graphics_index - 1]
+                }
+                QueueKind::Compute => {
+                    compute_index += 1;
+                    cbufs.push((QueueKind::Compute, compute_index - 1));
+                    let compute = frame.compute.as_mut().expect("Compute command pool is created for compute batches");
+                    &mut compute.1[compute_index - 1]
+                }
+            };
+            cbuf.begin(CommandBufferFlags::ONE_TIME_SUBMIT);
+            record_barriers(cbuf, &batch.acquires, &context, &self.ranges);
+            record_passes(
+                cbuf,
+                &batch.passes,
+                &mut self.passes,
+                &self.groups,
+                &framebuffers,
+                &mut context,
+                &self.ranges,
+                &active,
+                timing,
+                factory,
+                data,
+            );
+            if index == last {
+                record_barriers(cbuf, &self.final_barriers, &context, &self.ranges);
+            }
+            cbuf.finish();
+        }
+
+        {
+            let start = &frame.semaphores[self.batches.len()];
+            let has_compute = compute_index > 0;
+            let first_graphics = self.batches
+                .iter()
+                .position(|batch| batch.queue == QueueKind::Graphics);
+            unsafe {
+                if has_compute {
+                    // Compute work of this frame starts after graphics work of previous frames.
+                    graphics.submit_raw(
+                        RawSubmission {
+                            cmd_buffers: None::<&B::CommandBuffer>,
+                            wait_semaphores: &[],
+                            signal_semaphores: &[start],
+                        },
+                        None,
+                    );
+                }
+                for (index, (batch, &(queue, cbuf))) in self.batches.iter().zip(&cbufs).enumerate() {
+                    let mut waits = batch
+                        .waits
+                        .iter()
+                        .map(|&(source, stages)| (&frame.semaphores[source], stages))
+                        .collect::<Vec<_>>();
+                    if Some(index) == first_graphics {
+                        waits.extend(wait.iter().cloned());
+                    }
+                    if queue == QueueKind::Compute && batch.waits.is_empty() {
+                        waits.push((start, compute_stages()));
+                    }
+                    let mut signals = Vec::new();
+                    if batch.signal {
+                        signals.push(&frame.semaphores[index]);
+                    }
+                    if index == last {
+                        signals.extend(signal.iter().cloned());
+                    }
+                    let (queue, cbuf) = match queue {
+                        QueueKind::Graphics => (&mut *graphics, &frame.graphics[cbuf]),
+                        QueueKind::Compute => {
+                            let queue = compute.as_mut().expect("Compute queue is required to submit async compute passes");
+                            let cbufs = &frame.compute.as_ref().expect("Compute command pool is created for compute batches").1;
+                            (&mut **queue, &cbufs[cbuf])
+                        }
+                    };
+                    queue.submit_raw(
+                        RawSubmission {
+                            cmd_buffers: Some(cbuf),
+                            wait_semaphores: &waits,
+                            signal_semaphores: &signals,
+                        },
+                        if index == last { fence } else { None },
+                    );
+                }
+            }
+        }
+
+        let current = factory.current();
+        self.frames.push_back((frame, current));
+        Ok(())
+    }
+
+    /// Get subpass the pass is recorded in if it was declared with `PassDesc::subpass`.
+    /// Graphics pipelines used by the pass must be created for this subpass.
+    /// Returns `None` for culled passes.
+    pub fn subpass(&self, pass: PassId) -> Option<Subpass<B>> {
+        self.positions[pass.0]
+            .and_then(|position| self.passes[position].subpass)
+            .map(|(group, index)| Subpass {
+                index,
+                main_pass: &self.groups[group].render_pass,
+            })
+    }
+
+    /// Destroy views and framebuffers created for imported attachments.
+    /// Must be called before imported attachments are destroyed, e.g. when swapchain is recreated.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn invalidate_imported(&mut self, factory: &mut Factory<B>) {
+        for (_, view) in self.imported_views.drain() {
+            factory.destroy_image_view(view);
+        }
+        for (_, framebuffer) in self.framebuffers.drain() {
+            factory.destroy_framebuffer(framebuffer);
+        }
+    }
+
+    /// Transition imported resources created by `Factory` from their tracked states to initial states
+    /// declared with `GraphBuilder::import_image` and `GraphBuilder::import_buffer`,
+    /// and set their tracked states to final states the graph leaves them in.
+    /// Barriers must be submitted before commands recorded by `Graph::record` or `Graph::submit`.
+    ///
+    /// # Parameters
+    ///
+    /// `cbuf`      - command buffer to record barriers into. It must be in recording state.
+    /// `images`    - imported images for this frame.
+    /// `buffers`   - imported buffers for this frame.
+    /// `family`    - queue family of `cbuf`. Imported resources are used on graphics queue of the graph.
+    ///
+    pub fn transition_imported(
+        &self,
+        cbuf: &mut B::CommandBuffer,
+        images: &mut [(ImageId, &mut Image<B>)],
+        buffers: &mut [(BufferId, &mut Buffer<B>)],
+        family: QueueFamilyId,
+        factory: &mut Factory<B>,
+    ) -> Result<(), Error> {
+        for &mut (id, ref mut image) in images.iter_mut() {
+            let ref node = self.image_nodes[id.0];
+            match node.source {
+                Source::Imported { initial, final_state } => {
+                    factory.transition_to(cbuf, &mut **image, initial.0, initial.1, family)?;
+                    image
+                        .state_mut()
+                        .assume(final_state, PipelineStage::BOTTOM_OF_PIPE, family);
+                }
+                Source::Transient => {
+                    return Err(Error::InvalidGraph {
+                        name: node.name.clone(),
+                        reason: "image is not imported",
+                    });
+                }
+            }
+        }
+        for &mut (id, ref mut buffer) in buffers.iter_mut() {
+            let ref node = self.buffer_nodes[id.0];
+            match node.source {
+                Source::Imported { initial, final_state } => {
+                    factory.transition_to(cbuf, &mut **buffer, initial.0, initial.1, family)?;
+                    buffer
+                        .state_mut()
+                        .assume(final_state, PipelineStage::BOTTOM_OF_PIPE, family);
+                }
+                Source::Transient => {
+                    return Err(Error::InvalidGraph {
+                        name: node.name.clone(),
+                        reason: "buffer is not imported",
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate `PassDesc::enabled` predicates and find passes recorded in this frame.
+    fn active(&self, data: &T) -> Vec<bool> {
+        let enabled = self.passes
+            .iter()
+            .map(|pass| pass.desc.enabled.as_ref().map_or(true, |enabled| enabled(data)))
+            .collect::<Vec<_>>();
+        if enabled.iter().all(|&enabled| enabled) {
+            // Passes that aren't live were culled when graph was built.
+            return enabled;
+        }
+        let passes = self.passes.iter().map(|pass| &pass.desc).collect::<Vec<_>>();
+        live_passes(&passes, &enabled, &self.image_nodes, &self.buffer_nodes)
+    }
+
+    /// Create views of imported attachments and framebuffers for all render passes of the graph.
+    /// Imported images are identified by their address.
+    fn prepare_framebuffers(
+        &mut self,
+        images: &[(ImageId, &B::Image)],
+        factory: &mut Factory<B>,
+    ) -> Result<Vec<FramebufferKey>, Error> {
+        let mut keys = Vec::with_capacity(self.groups.len());
+        for (index, group) in self.groups.iter().enumerate() {
+            let mut addresses = Vec::with_capacity(group.attachments.len());
+            for &id in &group.attachments {
+                if self.views[id.0].is_some() {
+                    addresses.push(None);
+                    continue;
+                }
+                let ref node = self.image_nodes[id.0];
+                let image = images
+                    .iter()
+                    .find(|&&(other, _)| other == id)
+                    .map(|&(_, image)| image)
+                    .ok_or_else(|| Error::InvalidGraph {
+                        name: node.name.clone(),
+                        reason: "imported attachment is not provided",
+                    })?;
+                let address = image as *const B::Image as usize;
+                if !self.imported_views.contains_key(&(id.0, address)) {
+                    let view = factory
+                        .create_image_view(image, node.format, Swizzle::NO, self.ranges[id.0].clone())
+                        .map_err(|err| Error::creation(format!("view of {}", node.name), err))?;
+                    self.imported_views.insert((id.0, address), view);
+                }
+                addresses.push(Some(address));
+            }
+
+            let key = (index, addresses.iter().filter_map(|&address| address).collect());
+            if !self.framebuffers.contains_key(&key) {
+                let views = group
+                    .attachments
+                    .iter()
+                    .zip(&addresses)
+                    .map(|(id, address)| match *address {
+                        Some(address) => &self.imported_views[&(id.0, address)],
+                        None => self.views[id.0].as_ref().unwrap(),
+                    })
+                    .collect::<Vec<_>>();
+                let extent = Extent {
+                    width: group.area.w as u32,
+                    height: group.area.h as u32,
+                    depth: 1,
+                };
+                let framebuffer = factory
+                    .create_framebuffer(&group.render_pass, &views, extent)
+                    .map_err(|err| Error::creation("framebuffer", err))?;
+                self.framebuffers.insert(key.clone(), framebuffer);
+            }
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    /// Get command buffers and semaphores not used by ongoing frames.
+    fn frame(&mut self, factory: &mut Factory<B>) -> GraphFrame<B> {
+        let ongoing = factory.ongoing();
+        while self.frames.front().map_or(false, |&(_, index)| index < ongoing) {
+            let (mut frame, _) = self.frames.pop_front().unwrap();
+            frame.graphics_pool.reset();
+            if let Some((ref mut pool, _)) = frame.compute {
+                pool.reset();
+            }
+            self.free.push(frame);
+        }
+        if let Some(frame) = self.free.pop() {
+            return frame;
+        }
+
+        let (graphics_family, compute_family) = self.families
+            .expect("Graph must be built with queue families to be submitted");
+        let count = |queue| self.batches.iter().filter(|batch| batch.queue == queue).count();
+        let device: &B::Device = &**factory;
+        let mut graphics_pool = device.create_command_pool(graphics_family, CommandPoolCreateFlags::TRANSIENT);
+        let graphics = graphics_pool.allocate(count(QueueKind::Graphics), RawLevel::Primary);
+        let compute = match count(QueueKind::Compute) {
+            0 => None,
+            compute => {
+                let family = compute_family.expect("Compute batches require compute family");
+                let mut pool = device.create_command_pool(family, CommandPoolCreateFlags::TRANSIENT);
+                let cbufs = pool.allocate(compute, RawLevel::Primary);
+                Some((pool, cbufs))
+            }
+        };
+        GraphFrame {
+            graphics_pool,
+            graphics,
+            compute,
+            semaphores: (0..self.batches.len() + 1)
+                .map(|_| device.create_semaphore())
+                .collect(),
+        }
+    }
+
+    /// Destroy transient resources of the graph.
+    /// Factory will destroy them after all commands referencing them will complete.
+    /// Command pools and semaphores used by `Graph::submit` are destroyed immediately,
+    /// so all submissions of the graph must be complete.
+    pub fn dispose(mut self, factory: &mut Factory<B>) {
+        self.invalidate_imported(factory);
+        for view in self.views.into_iter().chain(self.sampled_views).filter_map(|view| view) {
+            factory.destroy_image_view(view);
+        }
+        for group in self.groups {
+            factory.destroy_render_pass(group.render_pass);
+        }
+        for (frame, _) in self.frames.into_iter().chain(self.free.into_iter().map(|frame| (frame, 0))) {
+            let GraphFrame {
+                mut graphics_pool,
+                graphics,
+                compute,
+                semaphores,
+            } = frame;
+            graphics_pool.free(graphics);
+            factory.destroy_command_pool(graphics_pool);
+            if let Some((mut pool, cbufs)) = compute {
+                pool.free(cbufs);
+                factory.destroy_command_pool(pool);
+            }
+            for semaphore in semaphores {
+                factory.destroy_semaphore(semaphore);
+            }
+        }
+        match self.transients {
+            Transients::Allocated { images, buffers } => {
+                for image in images.into_iter().filter_map(|image| image) {
+                    factory.destroy_image(image);
+                }
+                for buffer in buffers.into_iter().filter_map(|buffer| buffer) {
+                    factory.destroy_buffer(buffer);
+                }
+            }
+            Transients::Aliased { resources, .. } => resources.dispose(factory),
+        }
+        if let Some(timing) = self.timing {
+            timing.dispose(&**factory);
+        }
+    }
+
+    /// Dump compiled graph in Graphviz DOT format.
+    /// Passes are grouped into submissions to their queues with semaphore waits between them.
+    /// Pass labels list barriers recorded before the pass.
+    /// Resource labels show passes using them and memory bound to aliased transient resources.
+    pub fn dump_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph render_graph {{").unwrap();
+        writeln!(dot, "    node [shape=box];").unwrap();
+
+        for (index, batch) in self.batches.iter().enumerate() {
+            writeln!(dot, "    subgraph cluster_batch{} {{", index).unwrap();
+            writeln!(dot, "        label=\"submission {} ({:?})\";", index, batch.queue).unwrap();
+            writeln!(dot, "        batch{} [shape=point];", index).unwrap();
+            for &position in &batch.passes {
+                let pass = &self.passes[position];
+                let mut label = format!("#{} {}", position, escape(&pass.desc.name));
+                for barrier in &batch.acquires {
+                    if self.barrier_pass(barrier, position) {
+                        label.push_str(&format!("\\lacquire {}", self.describe(barrier)));
+                    }
+                }
+                if let Some((group, subpass)) = pass.subpass {
+                    label.push_str(&format!("\\lsubpass {} of render pass {}", subpass, group));
+                    if subpass == 0 {
+                        for barrier in &self.groups[group].barriers {
+                            label.push_str(&format!("\\lbarrier {}", self.describe(barrier)));
+                        }
+                    }
+                }
+                for barrier in &pass.barriers {
+                    label.push_str(&format!("\\lbarrier {}", self.describe(barrier)));
+                }
+                writeln!(dot, "        pass{} [label=\"{}\\l\"];", position, label).unwrap();
+            }
+            writeln!(dot, "    }}").unwrap();
+            for &(source, stages) in &batch.waits {
+                writeln!(
+                    dot,
+                    "    batch{} -> batch{} [style=dashed, label=\"wait {:?}\"];",
+                    source,
+                    index,
+                    stages
+                ).unwrap();
+            }
+        }
+
+        for (index, node) in self.image_nodes.iter().enumerate() {
+            let users = self.passes
+                .iter()
+                .enumerate()
+                .filter(|&(_, pass)| pass.desc.images.iter().any(|&(id, _)| id.0 == index))
+                .map(|(position, _)| position)
+                .collect::<Vec<_>>();
+            let memory = self.transients.image_memory(index);
+            writeln!(
+                dot,
+                "    image{} [shape=ellipse, label=\"{}\\n{:?} {:?}\\n{}\\npasses {:?}{}\"];",
+                index,
+                escape(&node.name),
+                node.kind,
+                node.format,
+                if node.source.is_transient() { "transient" } else { "imported" },
+                users,
+                memory.map_or(String::new(), |range| format!("\\nmemory {:?}", range))
+            ).unwrap();
+        }
+        for (index, node) in self.buffer_nodes.iter().enumerate() {
+            let users = self.passes
+                .iter()
+                .enumerate()
+                .filter(|&(_, pass)| pass.desc.buffers.iter().any(|&(id, _)| id.0 == index))
+                .map(|(position, _)| position)
+                .collect::<Vec<_>>();
+            let memory = self.transients.buffer_memory(index);
+            writeln!(
+                dot,
+                "    buffer{} [shape=ellipse, label=\"{}\\n{} bytes\\n{}\\npasses {:?}{}\"];",
+                index,
+                escape(&node.name),
+                node.size,
+                if node.source.is_transient() { "transient" } else { "imported" },
+                users,
+                memory.map_or(String::new(), |range| format!("\\nmemory {:?}", range))
+            ).unwrap();
+        }
+
+        for (position, pass) in self.passes.iter().enumerate() {
+            for &(id, usage) in &pass.desc.images {
+                if usage.is_write() {
+                    writeln!(dot, "    pass{} -> image{} [label=\"{:?}\"];", position, id.0, usage).unwrap();
+                } else {
+                    writeln!(dot, "    image{} -> pass{} [label=\"{:?}\"];", id.0, position, usage).unwrap();
+                }
+            }
+            for &(id, usage) in &pass.desc.buffers {
+                if usage.is_write() {
+                    writeln!(dot, "    pass{} -> buffer{} [label=\"{:?}\"];", position, id.0, usage).unwrap();
+                } else {
+                    writeln!(dot, "    buffer{} -> pass{} [label=\"{:?}\"];", id.0, position, usage).unwrap();
+                }
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    /// Check if acquire barrier belongs to the pass at `position`.
+    fn barrier_pass(&self, barrier: &GraphBarrier, position: usize) -> bool {
+        self.passes[position].desc.uses(barrier)
+    }
+
+    fn describe(&self, barrier: &GraphBarrier) -> String {
+        match *barrier {
+            GraphBarrier::Image {
+                id,
+                ref states,
+                ref stages,
+            } => format!(
+                "{}: {:?} -> {:?}, {:?} -> {:?}",
+                escape(&self.image_nodes[id.0].name),
+                states.start,
+                states.end,
+                stages.start,
+                stages.end
+            ),
+            GraphBarrier::Buffer {
+                id,
+                ref states,
+                ref stages,
+            } => format!(
+                "{}: {:?} -> {:?}, {:?} -> {:?}",
+                escape(&self.buffer_nodes[id.0].name),
+                states.start,
+                states.end,
+                stages.start,
+                stages.end
+            ),
+        }
+    }
+
+    /// Get size of memory shared by transient resources if they are aliased.
+    pub fn aliased_memory_size(&self) -> Option<u64> {
+        match self.transients {
+            Transients::Allocated { .. } => None,
+            Transients::Aliased { ref resources, .. } => Some(resources.memory_size()),
+        }
+    }
+}
+
+/// Transient resources of the graph indexed by `ImageId` and `BufferId`.
+/// Imported resources have no entries.
+#[derive(Debug)]
+enum Transients<B: Backend> {
+    Allocated {
+        images: Vec<Option<Image<B>>>,
+        buffers: Vec<Option<Buffer<B>>>,
+    },
+    Aliased {
+        resources: AliasedResources<B>,
+        images: Vec<Option<TransientId>>,
+        buffers: Vec<Option<TransientId>>,
+    },
+}
+
+impl<B> Transients<B>
+where
+    B: Backend,
+{
+    fn context<'a>(
+        &'a self,
+        imported_images: &[(ImageId, &'a B::Image)],
+        imported_buffers: &[(BufferId, &'a B::Buffer)],
+    ) -> PassContext<'a, B> {
+        let mut context = match *self {
+            Transients::Allocated {
+                ref images,
+                ref buffers,
+            } => PassContext {
+                images: images.iter().map(|image| image.as_ref().map(|image| image.raw())).collect(),
+                sampled_views: images.iter().map(|_| None).collect(),
+                buffers: buffers
+                    .iter()
+                    .map(|buffer| buffer.as_ref().map(|buffer| buffer.raw()))
+                    .collect(),
+                subpass: None,
+                framebuffer: None,
+            },
+            Transients::Aliased {
+                ref resources,
+                ref images,
+                ref buffers,
+            } => PassContext {
+                images: images.iter().map(|id| id.and_then(|id| resources.image(id))).collect(),
+                sampled_views: images.iter().map(|_| None).collect(),
+                buffers: buffers.iter().map(|id| id.and_then(|id| resources.buffer(id))).collect(),
+                subpass: None,
+                framebuffer: None,
+            },
+        };
+        for &(id, image) in imported_images {
+            context.images[id.0] = Some(image);
+        }
+        for &(id, buffer) in imported_buffers {
+            context.buffers[id.0] = Some(buffer);
+        }
+        context
+    }
+
+    fn image_memory(&self, index: usize) -> Option<Range<u64>> {
+        match *self {
+            Transients::Aliased {
+                ref resources,
+                ref images,
+                ..
+            } => images[index].map(|id| resources.memory_range(id)),
+            Transients::Allocated { .. } => None,
+        }
+    }
+
+    fn buffer_memory(&self, index: usize) -> Option<Range<u64>> {
+        match *self {
+            Transients::Aliased {
+                ref resources,
+                ref buffers,
+                ..
+            } => buffers[index].map(|id| resources.memory_range(id)),
+            Transients::Allocated { .. } => None,
+        }
+    }
+
+    fn aliased_image_stages(&self, index: usize) -> PipelineStage {
+        match *self {
+            Transients::Aliased {
+                ref resources,
+                ref images,
+                ..
+            } => images[index].map_or(PipelineStage::empty(), |id| resources.aliased_stages(id)),
+            Transients::Allocated { .. } => PipelineStage::empty(),
+        }
+    }
+
+    fn aliased_buffer_stages(&self, index: usize) -> PipelineStage {
+        match *self {
+            Transients::Aliased {
+                ref resources,
+                ref buffers,
+                ..
+            } => buffers[index].map_or(PipelineStage::empty(), |id| resources.aliased_stages(id)),
+            Transients::Allocated { .. } => PipelineStage::empty(),
+        }
+    }
+}
+
+fn record_barriers<B>(
+    cbuf: &mut B::CommandBuffer,
+    barriers: &[GraphBarrier],
+    context: &PassContext<B>,
+    ranges: &[SubresourceRange],
+) where
+    B: Backend,
+{
+    if barriers.is_empty() {
+        return;
+    }
+    let (stages, barriers) = raw_barriers(barriers, context, ranges);
+    cbuf.pipeline_barrier(stages, barriers);
+}
+
+/// Get barriers on resources of `context` and stages they synchronize.
+fn raw_barriers<'a, B>(
+    barriers: &[GraphBarrier],
+    context: &PassContext<'a, B>,
+    ranges: &[SubresourceRange],
+) -> (Range<PipelineStage>, Vec<Barrier<'a, B>>)
+where
+    B: Backend,
+{
+    let mut stages = PipelineStage::empty()..PipelineStage::empty();
+    let barriers = barriers
+        .iter()
+        .map(|barrier| match *barrier {
+            GraphBarrier::Image {
+                id,
+                ref states,
+                stages: ref barrier_stages,
+            } => {
+                stages.start |= barrier_stages.start;
+                stages.end |= barrier_stages.end;
+                Barrier::Image {
+                    states: states.clone(),
+                    target: context.image(id),
+                    range: ranges[id.0].clone(),
+                }
+            }
+            GraphBarrier::Buffer {
+                id,
+                ref states,
+                stages: ref barrier_stages,
+            } => {
+                stages.start |= barrier_stages.start;
+                stages.end |= barrier_stages.end;
+                Barrier::Buffer {
+                    states: states.clone(),
+                    target: context.buffer(id),
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    if stages.start.is_empty() {
+        // Transient resources used first don't wait for anything.
+        stages.start = PipelineStage::TOP_OF_PIPE;
+    }
+    (stages, barriers)
+}
+
+/// Record passes at `positions` beginning, advancing and ending render passes of subpass passes.
+/// Only barriers are recorded for passes not `active` in this frame.
+fn record_passes<'a, B, T>(
+    cbuf: &mut B::CommandBuffer,
+    positions: &[usize],
+    passes: &mut [CompiledPass<B, T>],
+    groups: &'a [RenderGroup<B>],
+    framebuffers: &[&'a B::Framebuffer],
+    context: &mut PassContext<'a, B>,
+    ranges: &[SubresourceRange],
+    active: &[bool],
+    timing: Option<&GraphTiming<B>>,
+    factory: &mut Factory<B>,
+    data: &mut T,
+) where
+    B: Backend,
+{
+    for &position in positions {
+        let pass = &mut passes[position];
+        match pass.subpass {
+            Some((index, subpass)) => {
+                let ref group = groups[index];
+                let contents = if pass.desc.secondary {
+                    SubpassContents::SecondaryBuffers
+                } else {
+                    SubpassContents::Inline
+                };
+                if subpass == 0 {
+                    if let Some(timing) = timing {
+                        timing.reset(cbuf, group.passes.clone());
+                    }
+                    record_barriers(cbuf, &group.barriers, context, ranges);
+                    cbuf.begin_renderpass(
+                        &group.render_pass,
+                        framebuffers[index],
+                        group.area,
+                        &group.clears,
+                        contents,
+                    );
+                } else {
+                    cbuf.next_subpass(contents);
+                }
+                context.subpass = Some((&group.render_pass, subpass));
+                context.framebuffer = Some(framebuffers[index]);
+            }
+            None => {
+                if let Some(timing) = timing {
+                    timing.reset(cbuf, position..position + 1);
+                }
+                record_barriers(cbuf, &pass.barriers, context, ranges);
+                context.subpass = None;
+                context.framebuffer = None;
+            }
+        }
+        if let Some(timing) = timing {
+            timing.write(cbuf, position, false);
+        }
+        if active[position] {
+            let _span = instrument::pass(&pass.desc.name);
+            #[cfg(feature = "trace")]
+            factory.trace_pass(&pass.desc.name, true);
+            (pass.desc.record)(cbuf, context, factory, data);
+            #[cfg(feature = "trace")]
+            factory.trace_pass(&pass.desc.name, false);
+        }
+        if let Some(timing) = timing {
+            timing.write(cbuf, position, true);
+        }
+        match pass.subpass {
+            Some((index, subpass)) => if subpass + 1 == groups[index].passes.len() {
+                cbuf.end_renderpass();
+                factory.breadcrumb(cbuf, &pass.desc.name);
+            },
+            None => factory.breadcrumb(cbuf, &pass.desc.name),
+        }
+    }
+}
+
+/// Get size of attachments of the subpass pass.
+fn subpass_area<B, T>(pass: &PassDesc<B, T>, images: &[ImageNode]) -> Result<(u16, u16), Error>
+where
+    B: Backend,
+{
+    let mut area = None;
+    for &(id, usage) in &pass.images {
+        if !usage.is_attachment() {
+            continue;
+        }
+        let ref node = images[id.0];
+        let size = match node.kind {
+            Kind::D2(width, height, _) => (width, height),
+            _ => {
+                return Err(Error::InvalidGraph {
+                    name: node.name.clone(),
+                    reason: "attachment of subpass must be 2D image with single layer",
+                })
+            }
+        };
+        if area.map_or(false, |area| area != size) {
+            return Err(Error::InvalidGraph {
+                name: pass.name.clone(),
+                reason: "attachments of subpass have different sizes",
+            });
+        }
+        area = Some(size);
+    }
+    area.ok_or_else(|| Error::InvalidGraph {
+        name: pass.name.clone(),
+        reason: "subpass has no attachments",
+    })
+}
+
+/// Check if pass can be recorded as next subpass of render pass with `group` passes.
+/// Resources used in the group may be synchronized with the pass only by subpass dependencies on attachments.
+fn can_merge<B, T>(group: &[CompiledPass<B, T>], pass: &CompiledPass<B, T>) -> bool
+where
+    B: Backend,
+{
+    let image_uses = |id: ImageId| {
+        group
+            .iter()
+            .flat_map(|other| other.desc.images.iter())
+            .filter(|&&(other, _)| other == id)
+            .map(|&(_, usage)| usage)
+            .collect::<Vec<_>>()
+    };
+    if pass.desc.clears.iter().any(|&(id, _)| !image_uses(id).is_empty()) {
+        return false;
+    }
+    pass.barriers.iter().all(|barrier| match *barrier {
+        GraphBarrier::Image { id, .. } => {
+            let uses = image_uses(id);
+            let pass_uses = pass.desc
+                .images
+                .iter()
+                .filter(|&&(other, _)| other == id)
+                .map(|&(_, usage)| usage);
+            uses.is_empty() || uses.iter().cloned().chain(pass_uses).all(|usage| usage.is_attachment())
+        }
+        GraphBarrier::Buffer { id, .. } => group
+            .iter()
+            .all(|other| other.desc.buffers.iter().all(|&(other, _)| other != id)),
+    })
+}
+
+/// Merge consecutive subpass passes into render passes and create them.
+/// Barriers of merged passes on attachments used by earlier subpasses become subpass dependencies,
+/// other barriers are recorded before render pass begins.
+/// Attachments are transitioned to layout of their first subpass by those barriers,
+/// so render pass only transitions them between subpasses.
+fn render_groups<B, T>(
+    passes: &mut [CompiledPass<B, T>],
+    images: &[ImageNode],
+    ranges: &[SubresourceRange],
+    batches: &[Batch],
+    factory: &Factory<B>,
+) -> Result<Vec<RenderGroup<B>>, Error>
+where
+    B: Backend,
+{
+    let mut batch_of = vec![0; passes.len()];
+    for (index, batch) in batches.iter().enumerate() {
+        for &position in &batch.passes {
+            batch_of[position] = index;
+        }
+    }
+
+    let mut runs: Vec<(Range<usize>, (u16, u16))> = Vec::new();
+    for position in 0..passes.len() {
+        if !passes[position].desc.subpass {
+            continue;
+        }
+        let area = subpass_area(&passes[position].desc, images)?;
+        let merge = match runs.last() {
+            Some(&(ref run, run_area)) => {
+                run.end == position && run_area == area && batch_of[run.start] == batch_of[position]
+                    && can_merge(&passes[run.clone()], &passes[position])
+            }
+            None => false,
+        };
+        if merge {
+            runs.last_mut().unwrap().0.end += 1;
+        } else {
+            runs.push((position..position + 1, area));
+        }
+    }
+
+    let mut groups = Vec::with_capacity(runs.len());
+    for (group_index, (run, (width, height))) in runs.into_iter().enumerate() {
+        let mut attachments: Vec<ImageId> = Vec::new();
+        let mut layouts: Vec<Range<ImageLayout>> = Vec::new();
+        let mut used: HashMap<ImageId, usize> = HashMap::new();
+        let mut users: Vec<Vec<usize>> = Vec::new();
+        let mut barriers = Vec::new();
+        let mut dependencies = Vec::new();
+        let mut refs = Vec::new();
+        for (subpass, position) in run.clone().enumerate() {
+            let pass = &mut passes[position];
+            for barrier in pass.barriers.drain(..) {
+                match barrier {
+                    GraphBarrier::Image {
+                        id,
+                        ref states,
+                        ref stages,
+                    } if used.contains_key(&id) =>
+                    {
+                        dependencies.push(SubpassDependency {
+                            passes: SubpassRef::Pass(used[&id])..SubpassRef::Pass(subpass),
+                            stages: stages.clone(),
+                            accesses: states.start.0..states.end.0,
+                        });
+                        continue;
+                    }
+                    _ => {}
+                }
+                barriers.push(barrier);
+            }
+
+            let mut colors: Vec<AttachmentRef> = Vec::new();
+            let mut depth_stencil: Option<AttachmentRef> = None;
+            let mut inputs: Vec<AttachmentRef> = Vec::new();
+            for &(id, usage) in &pass.desc.images {
+                if !usage.is_attachment() {
+                    continue;
+                }
+                let layout = (usage.state().0).1;
+                let index = match attachments.iter().position(|&other| other == id) {
+                    Some(index) => {
+                        layouts[index].end = layout;
+                        index
+                    }
+                    None => {
+                        attachments.push(id);
+                        layouts.push(layout..layout);
+                        users.push(Vec::new());
+                        attachments.len() - 1
+                    }
+                };
+                users[index].push(subpass);
+                used.insert(id, subpass);
+                match usage {
+                    ImageUse::ColorAttachment => colors.push((index, layout)),
+                    ImageUse::InputAttachment => inputs.push((index, layout)),
+                    _ => depth_stencil = Some((index, layout)),
+                }
+            }
+            refs.push((colors, depth_stencil, inputs));
+            pass.subpass = Some((group_index, subpass));
+        }
+
+        let count = run.len();
+        let preserves = (0..count)
+            .map(|subpass| {
+                users
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, users)| {
+                        !users.contains(&subpass) && users.iter().any(|&user| user < subpass)
+                            && users.iter().any(|&user| user > subpass)
+                    })
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let subpasses = refs
+            .iter()
+            .zip(&preserves)
+            .map(|(&(ref colors, ref depth_stencil, ref inputs), preserves)| SubpassDesc {
+                colors,
+                depth_stencil: depth_stencil.as_ref(),
+                inputs,
+                preserves,
+            })
+            .collect::<Vec<_>>();
+
+        let mut clears = Vec::with_capacity(attachments.len());
+        let mut descs = Vec::with_capacity(attachments.len());
+        for (index, &id) in attachments.iter().enumerate() {
+            let first = run.start + users[index][0];
+            let clear = passes[first]
+                .desc
+                .clears
+                .iter()
+                .find(|&&(other, _)| other == id)
+                .map(|&(_, value)| value);
+            let undefined = barriers.iter().any(|barrier| match *barrier {
+                GraphBarrier::Image {
+                    id: other,
+                    ref states,
+                    ..
+                } => other == id && (states.start.1 == ImageLayout::Undefined),
+                GraphBarrier::Buffer { .. } => false,
+            });
+            let load = match clear {
+                Some(_) => AttachmentLoadOp::Clear,
+                None if undefined => AttachmentLoadOp::DontCare,
+                None => AttachmentLoadOp::Load,
+            };
+            let used_later = !images[id.0].source.is_transient()
+                || passes[run.end..]
+                    .iter()
+                    .any(|pass| pass.desc.images.iter().any(|&(other, _)| other == id));
+            let store = if used_later {
+                AttachmentStoreOp::Store
+            } else {
+                AttachmentStoreOp::DontCare
+            };
+            let ops = AttachmentOps::new(load, store);
+            descs.push(Attachment {
+                format: Some(images[id.0].format),
+                ops,
+                stencil_ops: if ranges[id.0].aspects.contains(Aspects::STENCIL) {
+                    ops
+                } else {
+                    AttachmentOps::DONT_CARE
+                },
+                layouts: layouts[index].clone(),
+            });
+            clears.push(ClearValueRaw::from(
+                clear.unwrap_or(ClearValue::Color(ClearColor::Float([0.0; 4]))),
+            ));
+        }
+
+        let render_pass = factory.create_render_pass(&descs, &subpasses, &dependencies);
+        if count > 1 {
+            info!(
+                "Passes {:?} merged into render pass with {} subpasses",
+                passes[run.clone()].iter().map(|pass| &pass.desc.name).collect::<Vec<_>>(),
+                count
+            );
+        }
+        groups.push(RenderGroup {
+            passes: run,
+            attachments,
+            clears,
+            barriers,
+            area: Rect {
+                x: 0,
+                y: 0,
+                w: width,
+                h: height,
+            },
+            render_pass,
+        });
+    }
+    Ok(groups)
+}
+
+/// Escape string to put into DOT label.
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[test]
+fn culls_and_orders_passes() {
+    use hal::image::AaMode;
+    use testing::TestBackend;
+
+    let mut builder = GraphBuilder::<TestBackend, ()>::new();
+    let kind = Kind::D2(4, 4, AaMode::Single);
+    let backbuffer = builder.import_image(
+        "backbuffer",
+        kind,
+        1,
+        Format::Rgba8Unorm,
+        ((ImageAccess::empty(), ImageLayout::Undefined), PipelineStage::TOP_OF_PIPE),
+        (ImageAccess::empty(), ImageLayout::Present),
+    );
+    let gbuffer = builder.create_image("gbuffer", kind, 1, Format::Rgba8Unorm);
+    let unused = builder.create_image("unused", kind, 1, Format::Rgba8Unorm);
+    builder.add_pass(PassDesc::new("geometry", |_, _, _, _| {}).image(gbuffer, ImageUse::ColorAttachment));
+    builder.add_pass(PassDesc::new("debug", |_, _, _, _| {}).image(unused, ImageUse::ColorAttachment));
+    builder.add_pass(
+        PassDesc::new("lighting", |_, _, _, _| {})
+            .image(gbuffer, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER))
+            .image(backbuffer, ImageUse::ColorAttachment),
+    );
+    // Written after the last read, so nothing consumes it.
+    builder.add_pass(PassDesc::new("overwrite", |_, _, _, _| {}).image(gbuffer, ImageUse::TransferDst));
+
+    let live = live_passes(
+        &builder.passes.iter().collect::<Vec<_>>(),
+        &[true; 4],
+        &builder.images,
+        &builder.buffers,
+    );
+    assert_eq!(live, vec![true, false, true, false]);
+    assert_eq!(builder.order(&live).unwrap(), vec![0, 2]);
+}
+
+#[test]
+fn passes_depend_on_last_write_and_reads_since() {
+    use testing::TestBackend;
+
+    let mut builder = GraphBuilder::<TestBackend, ()>::new();
+    let buffer = builder.create_buffer("buffer", 256);
+    let other = builder.create_buffer("other", 256);
+    let stages = PipelineStage::COMPUTE_SHADER;
+    builder.add_pass(PassDesc::new("write", |_, _, _, _| {}).buffer(buffer, BufferUse::StorageWrite(stages)));
+    builder.add_pass(PassDesc::new("read", |_, _, _, _| {}).buffer(buffer, BufferUse::StorageRead(stages)));
+    builder.add_pass(PassDesc::new("read other", |_, _, _, _| {}).buffer(other, BufferUse::Uniform(stages)));
+    builder.add_pass(PassDesc::new("overwrite", |_, _, _, _| {}).buffer(buffer, BufferUse::TransferDst));
+    builder.add_pass(
+        PassDesc::new("read and write", |_, _, _, _| {})
+            .buffer(buffer, BufferUse::StorageRead(stages))
+            .buffer(buffer, BufferUse::StorageWrite(stages)),
+    );
+    builder.add_pass(PassDesc::new("read again", |_, _, _, _| {}).buffer(buffer, BufferUse::Vertex));
+
+    let dependencies = builder
+        .dependencies()
+        .into_iter()
+        .map(|dependencies| {
+            let mut dependencies = dependencies.into_iter().collect::<Vec<_>>();
+            dependencies.sort();
+            dependencies
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dependencies,
+        vec![vec![], vec![0], vec![], vec![0, 1], vec![3], vec![4]]
+    );
+    assert_eq!(builder.order(&[true; 6]).unwrap(), vec![0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn barriers_wait_for_all_reads_before_write() {
+    use hal::image::AaMode;
+    use testing::TestBackend;
+
+    let mut builder = GraphBuilder::<TestBackend, ()>::new();
+    let kind = Kind::D2(4, 4, AaMode::Single);
+    let image = builder.create_image("image", kind, 1, Format::Rgba8Unorm);
+    builder.add_pass(PassDesc::new("write", |_, _, _, _| {}).image(image, ImageUse::ColorAttachment));
+    builder.add_pass(
+        PassDesc::new("fragment", |_, _, _, _| {}).image(image, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER)),
+    );
+    builder.add_pass(
+        PassDesc::new("fragment again", |_, _, _, _| {})
+            .image(image, ImageUse::Sampled(PipelineStage::FRAGMENT_SHADER)),
+    );
+    builder.add_pass(
+        PassDesc::new("vertex", |_, _, _, _| {}).image(image, ImageUse::Sampled(PipelineStage::VERTEX_SHADER)),
+    );
+    builder.add_pass(PassDesc::new("overwrite", |_, _, _, _| {}).image(image, ImageUse::TransferDst));
+
+    let order = (0..5).collect::<Vec<_>>();
+    let (barriers, final_barriers, transfers) = builder.barriers(
+        &order,
+        &[QueueKind::Graphics; 5],
+        &[PipelineStage::empty()],
+        &[],
+    );
+    assert!(final_barriers.is_empty() && transfers.is_empty());
+    let stages = barriers
+        .iter()
+        .map(|barriers| barriers.iter().map(GraphBarrier::stages).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let read = PipelineStage::FRAGMENT_SHADER | PipelineStage::VERTEX_SHADER;
+    // Transient image waits for all its uses in the previous frame.
+    let all = read | PipelineStage::COLOR_ATTACHMENT_OUTPUT | PipelineStage::TRANSFER;
+    assert_eq!(
+        stages,
+        vec![
+            vec![all..PipelineStage::COLOR_ATTACHMENT_OUTPUT],
+            vec![PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::FRAGMENT_SHADER],
+            vec![],
+            vec![PipelineStage::FRAGMENT_SHADER..PipelineStage::VERTEX_SHADER],
+            vec![read..PipelineStage::TRANSFER],
+        ]
+    );
+}
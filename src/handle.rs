@@ -0,0 +1,141 @@
+//! Generational handles to resources stored by `Factory`.
+//! Handles are plain `Copy` values that can be shared freely between systems.
+//! Handle to destroyed resource never resolves even if its slot is reused.
+//! 
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// Handle to value stored in `Registry`.
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    /// Get index of the slot.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Get generation of the slot the handle was created for.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Slot map storing values addressed by generational handles.
+#[derive(Debug)]
+pub(crate) struct Registry<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+}
+
+impl<T> Registry<T> {
+    pub(crate) fn new() -> Self {
+        Registry {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, value: T) -> Handle<T> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: None,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        let ref mut slot = self.slots[index as usize];
+        debug_assert!(slot.value.is_none());
+        slot.value = Some(value);
+        Handle {
+            index,
+            generation: slot.generation,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn get(&self, handle: Handle<T>) -> Option<&T> {
+        match self.slots.get(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation => slot.value.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.value.as_mut()
+    }
+
+    pub(crate) fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        let value = {
+            let slot = self.slots.get_mut(handle.index as usize)?;
+            if slot.generation != handle.generation {
+                return None;
+            }
+            let value = slot.value.take()?;
+            slot.generation = slot.generation.wrapping_add(1);
+            value
+        };
+        self.free.push(handle.index);
+        Some(value)
+    }
+
+    /// Take all values out. Handles to them become invalid.
+    pub(crate) fn drain(&mut self) -> Vec<T> {
+        let mut values = Vec::new();
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(value) = slot.value.take() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+                values.push(value);
+            }
+        }
+        values
+    }
+}
@@ -0,0 +1,172 @@
+//! Uploading images decoded by `image` crate, like PNG and JPEG files.
+//! Enabled with `image` feature.
+//!
+
+use hal::Backend;
+use hal::device::Extent;
+use hal::format::{Aspects, Format};
+use hal::image::{AaMode, ImageLayout, Kind, Offset, SamplerInfo, SubresourceLayers};
+
+use image::{DynamicImage, FilterType, GenericImage};
+
+use Error;
+use factory::{Factory, Image, RelevantImage};
+use mem::SmartAllocator;
+use texture::Texture;
+use upload::Upload;
+
+/// Get format suitable for sampling and texels of the image in it.
+/// Luma images map to `R8Unorm` and `Rg8Unorm`. Color images map to 4-component formats
+/// since 3-component formats are rarely supported, so RGB texels are extended with opaque alpha.
+///
+/// # Parameters
+///
+/// `image`     - decoded image.
+/// `srgb`      - whether color components are sRGB encoded, like colors of photos and albedo textures are.
+///               Normal maps and other data aren't.
+///
+pub fn dynamic_image_texels(image: &DynamicImage, srgb: bool) -> (Format, Vec<u8>) {
+    let (rgba, bgra) = if srgb {
+        (Format::Rgba8Srgb, Format::Bgra8Srgb)
+    } else {
+        (Format::Rgba8Unorm, Format::Bgra8Unorm)
+    };
+    match *image {
+        DynamicImage::ImageLuma8(ref luma) => (Format::R8Unorm, luma.clone().into_raw()),
+        DynamicImage::ImageLumaA8(ref luma) => (Format::Rg8Unorm, luma.clone().into_raw()),
+        DynamicImage::ImageRgba8(ref texels) => (rgba, texels.clone().into_raw()),
+        DynamicImage::ImageBgra8(ref texels) => (bgra, texels.clone().into_raw()),
+        DynamicImage::ImageBgr8(ref texels) => (bgra, extend_alpha(texels)),
+        DynamicImage::ImageRgb8(ref texels) => (rgba, extend_alpha(texels)),
+    }
+}
+
+/// Append opaque alpha to each 3-component texel.
+fn extend_alpha(texels: &[u8]) -> Vec<u8> {
+    let mut extended = Vec::with_capacity(texels.len() / 3 * 4);
+    for texel in texels.chunks(3) {
+        extended.extend_from_slice(texel);
+        extended.push(!0);
+    }
+    extended
+}
+
+/// Get 2D kind and extent of the image.
+fn dynamic_image_kind(image: &DynamicImage) -> (Kind, Extent) {
+    let (width, height) = image.dimensions();
+    let kind = Kind::D2(width as _, height as _, AaMode::Single);
+    let extent = Extent {
+        width,
+        height,
+        depth: 1,
+    };
+    (kind, extent)
+}
+
+impl<B> Upload<B>
+where
+    B: Backend,
+{
+    /// Upload the image decoded by `image` crate to the first mip-level of `target`.
+    /// `target` must be 2D image of the format returned by `dynamic_image_texels` and the same size.
+    ///
+    /// # Parameters
+    ///
+    /// `target`    - where to upload. It must be created with `TRANSFER_DST` usage.
+    /// `image`     - decoded image.
+    /// `srgb`      - whether color components are sRGB encoded.
+    /// `layout`    - layout in which `target` is during command execution. It must be either `General` or `TransferDstOptimal`
+    ///
+    pub fn upload_dynamic_image(
+        &mut self,
+        device: &B::Device,
+        allocator: &mut SmartAllocator<B>,
+        target: &mut RelevantImage<B>,
+        image: &DynamicImage,
+        srgb: bool,
+        layout: ImageLayout,
+    ) -> Result<(), Error> {
+        let (_, texels) = dynamic_image_texels(image, srgb);
+        let (_, extent) = dynamic_image_kind(image);
+        self.upload_image(
+            device,
+            allocator,
+            target,
+            &texels,
+            layout,
+            SubresourceLayers {
+                aspects: Aspects::COLOR,
+                level: 0,
+                layers: 0..1,
+            },
+            Offset { x: 0, y: 0, z: 0 },
+            extent,
+        )
+    }
+}
+
+impl<B> Factory<B>
+where
+    B: Backend,
+{
+    /// Upload the image decoded by `image` crate to the first mip-level of `target`.
+    /// See `Upload::upload_dynamic_image`.
+    pub fn upload_dynamic_image(
+        &mut self,
+        target: &mut Image<B>,
+        image: &DynamicImage,
+        srgb: bool,
+        layout: ImageLayout,
+    ) -> Result<(), Error> {
+        let (_, texels) = dynamic_image_texels(image, srgb);
+        let (_, extent) = dynamic_image_kind(image);
+        self.upload_image(
+            target,
+            layout,
+            SubresourceLayers {
+                aspects: Aspects::COLOR,
+                level: 0,
+                layers: 0..1,
+            },
+            Offset { x: 0, y: 0, z: 0 },
+            extent,
+            &texels,
+        )
+    }
+
+    /// Create `Texture` from the image decoded by `image` crate.
+    /// Format is picked by `dynamic_image_texels`.
+    /// Texture will be in `ShaderReadOnlyOptimal` layout after upload completes.
+    ///
+    /// # Parameters
+    ///
+    /// `image`     - decoded image.
+    /// `srgb`      - whether color components are sRGB encoded.
+    /// `mips`      - generate full mip chain. Levels are downscaled on the host with triangle filter
+    ///               without regard to sRGB encoding.
+    /// `sampler`   - parameters of the sampler to create with the texture.
+    ///
+    pub fn create_texture_from_image(
+        &mut self,
+        image: &DynamicImage,
+        srgb: bool,
+        mips: bool,
+        sampler: Option<SamplerInfo>,
+    ) -> Result<Texture<B>, Error> {
+        let (kind, extent) = dynamic_image_kind(image);
+        let (format, texels) = dynamic_image_texels(image, srgb);
+        if !mips {
+            return self.create_texture(kind, format, &texels, sampler);
+        }
+        let count = 32 - extent.width.max(extent.height).max(1).leading_zeros();
+        let mut levels = vec![texels];
+        for level in 1..count {
+            let width = (extent.width >> level).max(1);
+            let height = (extent.height >> level).max(1);
+            let scaled = image.resize_exact(width, height, FilterType::Triangle);
+            levels.push(dynamic_image_texels(&scaled, srgb).1);
+        }
+        let levels = levels.iter().map(|texels| &texels[..]).collect::<Vec<_>>();
+        self.create_texture_with_mips(kind, format, &levels, sampler)
+    }
+}
@@ -1,73 +1,325 @@
 
-use hal::Instance;
+use hal::{Features, Instance, Limits};
 use hal::adapter::PhysicalDevice;
-use hal::queue::{General, QueueFamily, QueueType};
+use hal::queue::{General, QueueFamily};
 use mem::SmartAllocator;
 
-use std::string::ToString;
+use std::fmt;
+use std::sync::Arc;
 
 use {Error};
+use adapter::{default_adapter_score, AdapterCandidate, AdapterPolicy};
 use backend::BackendEx;
 use factory::Factory;
 use renderer::Renderer;
+use robustness::Robustness;
+use topology::{select_families, QueuePolicy, QueueTopology};
 
-const STAGING_TRESHOLD: usize = 32 * 1024; // 32kb
+/// Default size above which resources get dedicated memory.
+pub const DEFAULT_DEDICATED_THRESHOLD: u64 = 1024 * 1024 * 64;
 
-/// Init chosen backend and create `Factory` and `Renderer` instances.
-/// 
-/// # TODO
-/// 
-/// Add config.
-/// 
+/// Configuration for `init_with_config`.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Uploads of this size or smaller are written through command buffer directly.
+    /// If `None` then threshold is picked according to memory topology of the device.
+    pub staging_threshold: Option<usize>,
+
+    /// Enable robustness mode.
+    pub robustness: Option<Robustness>,
+
+    /// Resources larger than this get their own memory object instead of being sub-allocated from chunks.
+    /// Rounded down to power of two as it is used as the largest chunk size of the allocator.
+    pub dedicated_threshold: u64,
+
+    /// Policy of picking queue families.
+    pub queues: QueuePolicy,
+
+    /// Policy of picking adapter.
+    pub adapters: AdapterPolicy,
+
+    /// Write breadcrumb markers between passes and upload batches to report progress of the device on loss.
+    pub breadcrumbs: bool,
+
+    /// Maximum number of frames `Renderer::run` lets the device lag behind.
+    /// If `None` then it is limited only by number of swapchain images.
+    pub frames_in_flight: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            staging_threshold: None,
+            robustness: None,
+            dedicated_threshold: DEFAULT_DEDICATED_THRESHOLD,
+            queues: QueuePolicy::default(),
+            adapters: AdapterPolicy::default(),
+            breadcrumbs: false,
+            frames_in_flight: None,
+        }
+    }
+}
+
+/// Init chosen backend and create `Factory` and `Renderer` instances with default configuration.
 pub fn init<B, R>() -> Result<(Factory<B>, Renderer<B, R>), Error>
 where
     B: BackendEx,
     R: Send + Sync + 'static,
 {
-    let instance = B::init();
-    let mut adapter = instance.enumerate_adapters().remove(0);
+    init_with_config(Config::default())
+}
+
+/// Init chosen backend and create `Factory` and `Renderer` instances.
+pub fn init_with_config<B, R>(config: Config) -> Result<(Factory<B>, Renderer<B, R>), Error>
+where
+    B: BackendEx,
+    R: Send + Sync + 'static,
+{
+    let instance = B::init()?;
+    let mut adapters = instance.enumerate_adapters();
+    info!("Adapter policy: {:#?}", config.adapters);
+    let report = config.adapters.select(&adapters);
+    info!("Adapters:\n{}", report);
+    let mut adapter = match report.chosen() {
+        Some(index) => adapters.swap_remove(index),
+        None => return Err(Error::NoSuitableAdapter(report)),
+    };
     info!("Adapter {:#?}", adapter.info);
 
     info!("Device features: {:#?}", adapter.physical_device.features());
     info!("Device limits: {:#?}", adapter.physical_device.limits());
 
-    let (device, queue_group) = {
+    let (device, queue_group, general_compute) = {
         info!("Queue families: {:#?}", adapter.queue_families);
-        let qf = adapter
-            .queue_families
-            .drain(..)
-            .filter(|family| family.queue_type() == QueueType::General)
-            .next()
-            .ok_or(format!("Can't find General queue family"))?;
+        info!("Queue policy: {:#?}", config.queues);
+        let ref families = adapter.queue_families;
+        let (general, compute) = select_families(families, &config.queues)?;
+        let ref priorities = config.queues.priorities;
+        priorities.check()?;
+        let mut queues = vec![priorities.graphics; 1];
+        if compute {
+            queues.push(priorities.compute);
+        }
         let mut gpu = adapter
             .physical_device
-            .open(vec![(&qf, vec![1.0; 1])])
-            .map_err(|err| err.to_string())?;
+            .open(vec![(&families[general], queues)])
+            .map_err(|err| Error::creation("device", err))?;
         let queue_group = gpu.queues
-            .take::<General>(qf.id())
+            .take::<General>(families[general].id())
             .expect("This group was requested");
-        (gpu.device, queue_group)
+        (gpu.device, queue_group, compute)
     };
     info!("Logical device created");
+    info!("Async compute queue of general family: {}", general_compute);
+
+    let topology = QueueTopology::single(queue_group.family());
+    info!("Queue topology: {:#?}", topology);
+
+    let breadcrumb_markers = B::breadcrumb_markers(&adapter.physical_device, &device);
+    info!("Breadcrumb markers supported: {}", breadcrumb_markers.is_some());
 
+    // Allocations bigger than largest chunk go to dedicated memory.
+    let max_chunk_size = max_chunk_size(config.dedicated_threshold);
+    info!("Dedicated allocation threshold: {}", max_chunk_size);
     let allocator = SmartAllocator::<B>::new(
         adapter.physical_device.memory_properties(),
         32,
         32,
         32,
-        1024 * 1024 * 64,
+        max_chunk_size,
     );
     info!("Allocator created: {:#?}", allocator);
 
-    let factory = Factory::new(
-        instance,
-        adapter.physical_device,
-        device,
-        allocator,
-        STAGING_TRESHOLD,
-        queue_group.family(),
-    );
-    let renderer = Renderer::<B, R>::new(queue_group);
+    let mut factory = match config.staging_threshold {
+        Some(staging_threshold) => Factory::new(
+            instance,
+            adapter.physical_device,
+            device,
+            allocator,
+            staging_threshold,
+            topology,
+        ),
+        None => Factory::with_tuned_staging_threshold(
+            instance,
+            adapter.physical_device,
+            device,
+            allocator,
+            topology,
+        ),
+    };
+    factory.set_breadcrumb_markers(breadcrumb_markers.map(Arc::from));
+    factory.set_adapter_report(report);
+    info!("Timestamp period: {:?}", factory.timestamp_period());
+    if let Some(robustness) = config.robustness {
+        factory.set_robustness(robustness);
+    }
+    if config.breadcrumbs {
+        factory.enable_breadcrumbs()?;
+    }
+    let mut renderer = if general_compute {
+        Renderer::<B, R>::with_general_compute(queue_group)?
+    } else {
+        Renderer::<B, R>::new(queue_group)
+    };
+    renderer.set_frames_in_flight(config.frames_in_flight);
 
     Ok((factory, renderer))
 }
+
+/// Check of device limits. Error rejects the adapter with the reason.
+pub type LimitsCheck = Arc<Fn(&Limits) -> Result<(), String> + Send + Sync>;
+
+/// Builder of `Factory` and `Renderer` wired together.
+/// `build` initializes them in order:
+///
+/// 1. Instance is created.
+/// 2. Adapter is picked among those supporting requested features and limits.
+/// 3. Queue families are picked and logical device is opened.
+/// 4. Capabilities of the backend are queried and set to the `Factory`.
+/// 5. `Factory` is created along with its `Upload` and allocator. Robustness and breadcrumbs are enabled.
+/// 6. `Renderer` is created with the queues and frames in flight.
+///
+/// # Example
+///
+/// ```ignore
+/// let (factory, renderer) = InitBuilder::new()
+///     .features(Features::SAMPLER_ANISOTROPY)
+///     .frames_in_flight(2)
+///     .build::<Backend, Box<Render>>()?;
+/// ```
+#[derive(Clone)]
+pub struct InitBuilder {
+    config: Config,
+    limits: Vec<LimitsCheck>,
+}
+
+impl InitBuilder {
+    /// Start with default configuration.
+    pub fn new() -> Self {
+        InitBuilder::from_config(Config::default())
+    }
+
+    /// Start with the configuration.
+    pub fn from_config(config: Config) -> Self {
+        InitBuilder {
+            config,
+            limits: Vec::new(),
+        }
+    }
+
+    /// Require features from the adapter.
+    pub fn features(mut self, features: Features) -> Self {
+        self.config.adapters.required_features |= features;
+        self
+    }
+
+    /// Require limits from the adapter. Adapters for which `check` fails are rejected with its reason.
+    pub fn limits<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&Limits) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.limits.push(Arc::new(check));
+        self
+    }
+
+    /// Require minimal total size of device-local memory in bytes.
+    pub fn min_device_local_memory(mut self, bytes: u64) -> Self {
+        self.config.adapters.min_device_local_memory = bytes;
+        self
+    }
+
+    /// Score adapters that satisfy requirements with the callback.
+    pub fn adapter_scorer<F>(mut self, scorer: F) -> Self
+    where
+        F: Fn(&AdapterCandidate) -> Result<u64, String> + Send + Sync + 'static,
+    {
+        self.config.adapters = self.config.adapters.with_scorer(scorer);
+        self
+    }
+
+    /// Set policy of picking queue families.
+    pub fn queues(mut self, queues: QueuePolicy) -> Self {
+        self.config.queues = queues;
+        self
+    }
+
+    /// Limit number of frames the device may lag behind.
+    pub fn frames_in_flight(mut self, frames: u64) -> Self {
+        assert_ne!(frames, 0, "At least one frame must be in flight");
+        self.config.frames_in_flight = Some(frames);
+        self
+    }
+
+    /// Set size of uploads written through command buffer directly instead of staging.
+    pub fn staging_threshold(mut self, threshold: usize) -> Self {
+        self.config.staging_threshold = Some(threshold);
+        self
+    }
+
+    /// Set size above which resources get dedicated memory.
+    pub fn dedicated_threshold(mut self, threshold: u64) -> Self {
+        self.config.dedicated_threshold = threshold;
+        self
+    }
+
+    /// Enable robustness mode.
+    pub fn robustness(mut self, robustness: Robustness) -> Self {
+        self.config.robustness = Some(robustness);
+        self
+    }
+
+    /// Enable or disable breadcrumb markers.
+    pub fn breadcrumbs(mut self, enable: bool) -> Self {
+        self.config.breadcrumbs = enable;
+        self
+    }
+
+    /// Get configuration built so far. Limit checks are applied to adapter scorer only by `build`.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Initialize backend and create `Factory` and `Renderer`.
+    pub fn build<B, R>(self) -> Result<(Factory<B>, Renderer<B, R>), Error>
+    where
+        B: BackendEx,
+        R: Send + Sync + 'static,
+    {
+        let InitBuilder { mut config, limits } = self;
+        if !limits.is_empty() {
+            let scorer = config.adapters.scorer.take();
+            config.adapters.scorer = Some(Arc::new(move |candidate: &AdapterCandidate| {
+                for check in &limits {
+                    check(&candidate.limits)?;
+                }
+                match scorer {
+                    Some(ref scorer) => scorer(candidate),
+                    None => default_adapter_score(candidate),
+                }
+            }));
+        }
+        init_with_config(config)
+    }
+}
+
+impl Default for InitBuilder {
+    fn default() -> Self {
+        InitBuilder::new()
+    }
+}
+
+impl fmt::Debug for InitBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("InitBuilder")
+            .field("config", &self.config)
+            .field("limits", &self.limits.len())
+            .finish()
+    }
+}
+
+/// Get largest power of two not exceeding `threshold`.
+/// Allocator requires chunk sizes to be powers of two.
+fn max_chunk_size(threshold: u64) -> u64 {
+    assert!(threshold >= 32, "Dedicated threshold must be no less than minimal block size");
+    1 << (63 - threshold.leading_zeros())
+}
@@ -0,0 +1,87 @@
+//! Spans emitted through `tracing` around phases of the frame.
+//! Frame, upload recording, graph build, pass recording, submission and presentation get own spans,
+//! so hitches can be correlated with renderer work in unified timeline.
+//! Enabled with `tracing` feature. Without it all functions here return no-op guards.
+//!
+
+#[cfg(feature = "tracing")]
+use tracing::Level;
+#[cfg(feature = "tracing")]
+use tracing::span::EnteredSpan;
+
+use factory::Item;
+
+/// Guard that exits the span when dropped.
+#[cfg(feature = "tracing")]
+pub(crate) type Guard = EnteredSpan;
+
+/// Guard that exits the span when dropped.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Guard;
+
+/// Enter span of `Renderer::run` of the frame.
+#[cfg(feature = "tracing")]
+pub(crate) fn frame(index: u64) -> Guard {
+    span!(Level::INFO, "frame", index).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn frame(_index: u64) -> Guard {
+    Guard
+}
+
+/// Enter span of upload recording into the resource.
+#[cfg(feature = "tracing")]
+pub(crate) fn upload<I, B, S>(resource: &'static str, item: &Item<I, B, S>, bytes: usize) -> Guard {
+    let name = item.name().unwrap_or_else(String::new);
+    span!(Level::DEBUG, "upload", resource, name = name.as_str(), bytes = bytes as u64).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn upload<I, B, S>(_resource: &'static str, _item: &Item<I, B, S>, _bytes: usize) -> Guard {
+    Guard
+}
+
+/// Enter span of `GraphBuilder::build`.
+#[cfg(feature = "tracing")]
+pub(crate) fn graph_build(passes: usize) -> Guard {
+    span!(Level::INFO, "graph_build", passes = passes as u64).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn graph_build(_passes: usize) -> Guard {
+    Guard
+}
+
+/// Enter span of recording commands of the pass.
+#[cfg(feature = "tracing")]
+pub(crate) fn pass(name: &str) -> Guard {
+    span!(Level::DEBUG, "pass", name).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn pass(_name: &str) -> Guard {
+    Guard
+}
+
+/// Enter span of submission. `what` names submitted work.
+#[cfg(feature = "tracing")]
+pub(crate) fn submit(what: &'static str) -> Guard {
+    span!(Level::INFO, "submit", what).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn submit(_what: &'static str) -> Guard {
+    Guard
+}
+
+/// Enter span of presentation.
+#[cfg(feature = "tracing")]
+pub(crate) fn present() -> Guard {
+    span!(Level::INFO, "present").entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn present() -> Guard {
+    Guard
+}
@@ -0,0 +1,125 @@
+//! `LayoutCache` deduplicates descriptor set layouts and pipeline layouts.
+//! 
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use hal::{Backend, Device};
+use hal::pso::{DescriptorSetLayoutBinding, DescriptorType, ShaderStageFlags};
+
+use push::PushConstants;
+
+/// Identifier of descriptor set layout in `LayoutCache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SetLayoutId(usize);
+
+/// Identifier of pipeline layout in `LayoutCache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PipelineLayoutId(usize);
+
+type BindingKey = (usize, DescriptorType, usize, ShaderStageFlags);
+type PipelineLayoutKey = (Vec<SetLayoutId>, Vec<(ShaderStageFlags, Range<u32>)>);
+
+/// Cache of descriptor set layouts keyed by their bindings
+/// and pipeline layouts keyed by set layouts and push constant ranges.
+/// Requesting layout with the same description twice returns the same object.
+#[derive(Debug)]
+pub struct LayoutCache<B: Backend> {
+    set_layouts: Vec<B::DescriptorSetLayout>,
+    set_layout_keys: HashMap<Vec<BindingKey>, SetLayoutId>,
+    pipeline_layouts: Vec<B::PipelineLayout>,
+    push_constants: Vec<PushConstants>,
+    pipeline_layout_keys: HashMap<PipelineLayoutKey, PipelineLayoutId>,
+}
+
+impl<B> LayoutCache<B>
+where
+    B: Backend,
+{
+    /// Create empty cache.
+    pub fn new() -> Self {
+        LayoutCache {
+            set_layouts: Vec::new(),
+            set_layout_keys: HashMap::new(),
+            pipeline_layouts: Vec::new(),
+            push_constants: Vec::new(),
+            pipeline_layout_keys: HashMap::new(),
+        }
+    }
+
+    /// Get or create descriptor set layout with `bindings`.
+    /// Order of bindings doesn't matter.
+    pub fn set_layout(&mut self, device: &B::Device, bindings: &[DescriptorSetLayoutBinding]) -> SetLayoutId {
+        let mut key = bindings
+            .iter()
+            .map(|binding| (binding.binding, binding.ty, binding.count, binding.stage_flags))
+            .collect::<Vec<_>>();
+        key.sort_by_key(|&(binding, _, _, _)| binding);
+
+        if let Some(&id) = self.set_layout_keys.get(&key) {
+            return id;
+        }
+
+        let id = SetLayoutId(self.set_layouts.len());
+        self.set_layouts
+            .push(device.create_descriptor_set_layout(bindings));
+        self.set_layout_keys.insert(key, id);
+        id
+    }
+
+    /// Get or create pipeline layout with `sets` and `push_constants`.
+    pub fn pipeline_layout(
+        &mut self,
+        device: &B::Device,
+        sets: &[SetLayoutId],
+        push_constants: &[(ShaderStageFlags, Range<u32>)],
+    ) -> PipelineLayoutId {
+        let key = (sets.to_vec(), push_constants.to_vec());
+        if let Some(&id) = self.pipeline_layout_keys.get(&key) {
+            return id;
+        }
+
+        let id = PipelineLayoutId(self.pipeline_layouts.len());
+        let layout = {
+            let set_layouts = sets.iter()
+                .map(|&id| &self.set_layouts[id.0])
+                .collect::<Vec<_>>();
+            device.create_pipeline_layout(&set_layouts, push_constants)
+        };
+        self.pipeline_layouts.push(layout);
+        self.push_constants.push(PushConstants::new(push_constants));
+        self.pipeline_layout_keys.insert(key, id);
+        id
+    }
+
+    /// Get descriptor set layout by id.
+    pub fn get_set_layout(&self, id: SetLayoutId) -> &B::DescriptorSetLayout {
+        &self.set_layouts[id.0]
+    }
+
+    /// Get pipeline layout by id.
+    pub fn get_pipeline_layout(&self, id: PipelineLayoutId) -> &B::PipelineLayout {
+        &self.pipeline_layouts[id.0]
+    }
+
+    /// Get push constant ranges of pipeline layout by id.
+    pub fn push_constants(&self, id: PipelineLayoutId) -> &PushConstants {
+        &self.push_constants[id.0]
+    }
+
+    /// Get number of unique descriptor set layouts and pipeline layouts created.
+    pub fn len(&self) -> (usize, usize) {
+        (self.set_layouts.len(), self.pipeline_layouts.len())
+    }
+
+    /// Destroy all layouts.
+    /// Pipelines and descriptor sets created with them must not be used anymore.
+    pub fn dispose(self, device: &B::Device) {
+        for layout in self.pipeline_layouts {
+            device.destroy_pipeline_layout(layout);
+        }
+        for layout in self.set_layouts {
+            device.destroy_descriptor_set_layout(layout);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Detection of buffers and images still alive when `Factory` is disposed.
+//! Each `Item` holds token registered in the `Factory`. Token is released when the item is destroyed or dropped,
+//! so tokens alive at disposal belong to leaked resources.
+//! With `leak-backtraces` feature creation backtraces are captured unresolved
+//! and symbols are resolved only for resources reported as leaked.
+//!
+
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+
+#[cfg(feature = "leak-backtraces")]
+use backtrace::Backtrace;
+
+/// Resource created by the `Factory` and not destroyed.
+#[derive(Clone, Debug)]
+pub struct LeakedResource {
+    /// Kind and parameters of the resource.
+    pub description: String,
+
+    /// Name given with `Item::set_name`.
+    pub name: Option<String>,
+
+    /// Backtrace of creation. Captured only with `leak-backtraces` feature.
+    pub backtrace: Option<String>,
+}
+
+impl fmt::Display for LeakedResource {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.name {
+            Some(ref name) => write!(fmt, "{} \"{}\"", self.description, name)?,
+            None => write!(fmt, "{}", self.description)?,
+        }
+        if let Some(ref backtrace) = self.backtrace {
+            write!(fmt, " created at:\n{}", backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+/// Token of live resource.
+#[derive(Debug)]
+pub(crate) struct LiveResource {
+    description: String,
+    name: Mutex<Option<String>>,
+    #[cfg(feature = "leak-backtraces")]
+    backtrace: Backtrace,
+}
+
+impl LiveResource {
+    pub(crate) fn set_name(&self, name: &str) {
+        *self.name.lock().unwrap() = Some(name.to_string());
+    }
+
+    pub(crate) fn name(&self) -> Option<String> {
+        self.name.lock().unwrap().clone()
+    }
+
+    #[cfg(feature = "leak-backtraces")]
+    fn backtrace(&self) -> Option<String> {
+        let mut backtrace = self.backtrace.clone();
+        backtrace.resolve();
+        Some(format!("{:?}", backtrace))
+    }
+
+    #[cfg(not(feature = "leak-backtraces"))]
+    fn backtrace(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Weak references to tokens of all resources created by the `Factory`.
+#[derive(Debug)]
+pub(crate) struct LeakTracker {
+    live: Vec<Weak<LiveResource>>,
+    prune_at: usize,
+}
+
+impl LeakTracker {
+    pub(crate) fn new() -> Self {
+        LeakTracker {
+            live: Vec::new(),
+            prune_at: 64,
+        }
+    }
+
+    /// Register new resource. Returned token must be kept along with the resource.
+    pub(crate) fn track(&mut self, description: String) -> Arc<LiveResource> {
+        if self.live.len() >= self.prune_at {
+            self.live.retain(|resource| resource.upgrade().is_some());
+            self.prune_at = (self.live.len() * 2).max(64);
+        }
+        let resource = Arc::new(LiveResource {
+            description,
+            name: Mutex::new(None),
+            #[cfg(feature = "leak-backtraces")]
+            backtrace: Backtrace::new_unresolved(),
+        });
+        self.live.push(Arc::downgrade(&resource));
+        resource
+    }
+
+    /// Get resources which tokens are alive.
+    pub(crate) fn leaks(&mut self) -> Vec<LeakedResource> {
+        self.live.retain(|resource| resource.upgrade().is_some());
+        self.live
+            .iter()
+            .filter_map(|resource| resource.upgrade())
+            .map(|resource| LeakedResource {
+                description: resource.description.clone(),
+                name: resource.name(),
+                backtrace: resource.backtrace(),
+            })
+            .collect()
+    }
+}
@@ -1,12 +1,45 @@
 
+#[cfg(feature = "leak-backtraces")]
+extern crate backtrace;
 extern crate crossbeam_channel;
 extern crate gfx_hal as hal;
 extern crate gfx_memory as mem;
-#[macro_use] extern crate error_chain;
 #[macro_use] extern crate log;
+extern crate raw_window_handle;
+#[cfg(feature = "winit")]
 extern crate winit;
 
-#[cfg(not(any(feature = "gfx-backend-vulkan", feature = "gfx-backend-dx12", feature = "gfx-backend-metal")))]
+#[cfg(feature = "gltf")]
+extern crate gltf;
+
+#[cfg(feature = "image")]
+extern crate image;
+
+#[cfg(feature = "metrics")]
+#[macro_use]
+extern crate metrics;
+
+#[cfg(feature = "renderdoc")]
+extern crate renderdoc;
+
+#[cfg(feature = "config-ron")]
+extern crate ron;
+
+#[cfg(feature = "config-toml")]
+extern crate toml;
+
+#[cfg(feature = "tracing")]
+#[macro_use(span)]
+extern crate tracing;
+
+#[cfg(feature = "serialize")]
+extern crate serde;
+#[cfg(feature = "serialize")]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(any(test, feature = "testing",
+          not(any(feature = "gfx-backend-vulkan", feature = "gfx-backend-dx12", feature = "gfx-backend-metal"))))]
 pub extern crate gfx_backend_empty as empty;
 
 #[cfg(feature = "gfx-backend-vulkan")]
@@ -18,16 +51,136 @@ pub extern crate gfx_backend_dx12 as dx12;
 #[cfg(feature = "gfx-backend-metal")]
 pub extern crate gfx_backend_metal as metal;
 
+mod adapter;
+mod alias;
+mod atlas;
+mod attachment;
 mod backend;
+mod bindless;
+mod budget;
+mod breadcrumb;
+mod builder;
+#[cfg(feature = "renderdoc")]
+mod capture;
+mod compute;
+mod container;
+mod cpu_stats;
+mod debug;
+mod descriptor;
+mod dynamic;
+mod error;
 mod escape;
+mod external;
 mod factory;
+mod frame;
+#[cfg(feature = "gltf")]
+mod gltf_loader;
+mod graph;
+mod handle;
+#[cfg(feature = "image")]
+mod image_loader;
+mod instrument;
+mod layout;
+mod leak;
+mod lost;
+mod mesh;
+mod pacing;
+mod parallel;
+mod pass;
+mod pipeline;
+mod post;
+mod profiler;
+mod push;
 mod reclamation;
+mod reflect;
 mod renderer;
+mod residency;
+mod robustness;
+mod settings;
+mod shader;
+mod shared;
+mod state;
+mod stuck;
+mod submit;
+mod surface;
+mod sync;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+mod telemetry;
+mod texture;
+mod timestamp;
+mod topology;
+#[cfg(feature = "trace")]
+mod trace;
+mod typed;
+mod uniform;
 mod upload;
+mod virtual_texture;
+#[cfg(feature = "winit")]
+mod window;
 mod init;
 
-pub use init::init;
-pub use factory::{Factory, Item, Buffer, Image};
+pub use init::{init, init_with_config, Config, InitBuilder, LimitsCheck, DEFAULT_DEDICATED_THRESHOLD};
+pub use adapter::{default_adapter_score, AdapterCandidate, AdapterKind, AdapterPolicy, AdapterReport, AdapterScorer};
+pub use alias::{AliasedResources, AliasingBuilder, TransientId};
+pub use atlas::{AtlasRegion, TextureAtlas};
+pub use bindless::BindlessTable;
+pub use attachment::{Attachments, ManagedAttachment};
+pub use budget::{HeapReport, HeapUsage, MemoryReport, MemoryTag, TagUsage};
+pub use breadcrumb::{BreadcrumbMarkers, BreadcrumbReport};
+pub use builder::{BufferBuilder, ImageBuilder};
+pub use compute::ComputeEncoder;
+pub use container::TextureContainer;
+pub use cpu_stats::{CpuFrameStats, CpuFrameTimes, DEFAULT_STATS_WINDOW};
+pub use debug::{DebugMarkers, DebugObject};
+pub use descriptor::DescriptorAllocator;
+pub use dynamic::{init_dynamic, Backed, BackendKind, BackendPreference, BackendSetup, DynRenderer};
+pub use error::Error;
+pub use factory::{Factory, Item, Buffer, BufferHandle, DedicatedBuffer, Image, ImageHandle, RelevantBuffer, RelevantImage};
+pub use frame::{FrameGuard, Frames};
+pub use graph::{BufferId, BufferUse, Graph, GraphBuilder, ImageId, ImageUse, PassContext, PassDesc, PassId, QueueKind,
+                RecordFn};
+pub use handle::Handle;
+#[cfg(feature = "image")]
+pub use image_loader::dynamic_image_texels;
+#[cfg(feature = "gltf")]
+pub use gltf_loader::{destroy_gltf_model, load_gltf, load_gltf_model, upload_gltf, GltfMaterial, GltfMesh, GltfModel,
+                      GltfPrimitive, GltfScene, GltfTexture};
+pub use layout::{LayoutCache, PipelineLayoutId, SetLayoutId};
+pub use leak::LeakedResource;
+pub use lost::{recover, DeviceLostHooks, Retained};
+pub use mesh::{Indices, Mesh, VertexFormat};
+pub use pacing::{FramePacer, PacingStats};
+pub use parallel::{ParallelRecorder, SecondaryCommandBuffer};
+pub use pass::{transient_attachment, AttachmentId, PassCache, RenderPassDesc, RenderPassId};
+pub use pipeline::{GraphicsPipelineBuilder, PipelineState};
+pub use post::{FullscreenPipelines, PingPong};
+pub use profiler::{FrameTimings, Profiler, ScopeTiming};
+pub use push::PushConstants;
+pub use reflect::{reflect_pipeline, reflect_shader, InputType, PipelineReflection, ShaderReflection,
+                  VertexInput};
 pub use renderer::{Renderer, TargetId};
-
-error_chain!{}
+pub use residency::{Evicted, ResidencyManager};
+pub use robustness::Robustness;
+pub use settings::{HeapBudget, RendererConfig};
+pub use shader::{ShaderId, ShaderManager};
+pub use shared::{Shared, SharedBuffer, SharedComputePipeline, SharedFramebuffer, SharedGraphicsPipeline, SharedImage,
+                 SharedImageView, SharedRenderPass, SharedSampler};
+pub use state::{BufferTracking, ImageTracking, Tracked, TrackedState, Tracking};
+pub use stuck::{StuckFrame, StuckTarget, StuckWork};
+pub use submit::{QueueRole, Schedule, Submitter};
+pub use surface::{ColorSpace, SurfaceFormat};
+pub use sync::SyncPool;
+#[cfg(any(test, feature = "testing"))]
+pub use testing::{assert_released, FrameDriver, TestBackend};
+pub use texture::Texture;
+#[cfg(feature = "trace")]
+pub use trace::{replay_trace, Trace, TraceOp};
+pub use topology::{QueuePolicy, QueuePriorities, QueueTopology};
+pub use typed::{formats, Pod, TexelFormat, TypedBuffer, TypedImage};
+pub use uniform::UniformRing;
+pub use upload::{tune_staging_threshold, StagingInfo, StagingStats, Upload, UploadGroup, UploadId, UploadPriority,
+                 UploadRecorder, STAGING_CHUNK_SIZE};
+pub use virtual_texture::{PageSource, VirtualTexture, PAGE_NOT_RESIDENT};
+#[cfg(feature = "winit")]
+pub use window::{init_window, WindowTarget};
@@ -0,0 +1,346 @@
+//! Recovery from device loss.
+//! Loss is detected when `Factory` and `Renderer` wait for frames and queues and is reported as `Error::DeviceLost`.
+//! `recover` tears down lost `Renderer` and `Factory` in defined order, creates new ones
+//! and lets `DeviceLostHooks` recreate resources. Data kept in `Retained` can be uploaded again.
+//! Copies overwritten by later uploads are pruned once both uploads complete.
+//!
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use hal::Backend;
+use hal::device::Extent;
+use hal::error::HostExecutionError;
+use hal::image::{ImageLayout, Offset, SubresourceLayers};
+
+use Error;
+use backend::BackendEx;
+use factory::{Buffer, Factory, Image};
+use init::{init_with_config, Config};
+use renderer::Renderer;
+
+/// Callbacks invoked by `recover`.
+pub trait DeviceLostHooks<B: Backend, R> {
+    /// Destroy objects created with lost device through its `Factory`.
+    /// Called after all targets are removed from lost `Renderer`. `renders` are renders of removed targets.
+    fn lost(&mut self, factory: &mut Factory<B>, renders: Vec<Vec<R>>);
+
+    /// Recreate resources, surfaces and targets with new `Factory` and `Renderer`.
+    fn recreate(&mut self, factory: &mut Factory<B>, renderer: &mut Renderer<B, R>) -> Result<(), Error>;
+}
+
+/// Recover from device loss.
+/// Teardown goes in order:
+///
+/// 1. Targets of the `Renderer` are removed. Their surfaces are destroyed.
+/// 2. `DeviceLostHooks::lost` destroys objects created by user.
+/// 3. `Renderer` and then `Factory` are disposed.
+///
+/// Then new `Factory` and `Renderer` are created with `config` and passed to `DeviceLostHooks::recreate`.
+///
+/// # Parameters
+///
+/// `factory`   - `Factory` of lost device.
+/// `renderer`  - `Renderer` of lost device.
+/// `config`    - configuration to create new `Factory` and `Renderer` with.
+/// `hooks`     - callbacks to destroy and recreate resources.
+///
+pub fn recover<B, R, H>(
+    mut factory: Factory<B>,
+    renderer: Renderer<B, R>,
+    config: Config,
+    hooks: &mut H,
+) -> Result<(Factory<B>, Renderer<B, R>), Error>
+where
+    B: BackendEx,
+    R: Send + Sync + 'static,
+    H: DeviceLostHooks<B, R>,
+{
+    warn!("Recovering from device loss");
+    factory.mark_lost();
+    let renders = renderer
+        .dispose(&mut factory)
+        .into_iter()
+        .map(|(_surface, renders)| renders)
+        .collect();
+    hooks.lost(&mut factory, renders);
+    factory.dispose();
+
+    let (mut factory, mut renderer) = init_with_config(config)?;
+    hooks.recreate(&mut factory, &mut renderer)?;
+    info!("Recovered from device loss");
+    Ok((factory, renderer))
+}
+
+/// Map error of waiting for queue or device to `Error::DeviceLost` if it is caused by device loss.
+pub(crate) fn host_execution_error(err: HostExecutionError) -> Error {
+    match err {
+        HostExecutionError::DeviceLost => Error::DeviceLost,
+        err => Error::Execution(err),
+    }
+}
+
+/// Upload kept on the host.
+#[derive(Clone, Debug, PartialEq)]
+enum RetainedUpload {
+    Buffer {
+        offset: u64,
+        data: Vec<u8>,
+    },
+    Image {
+        layout: ImageLayout,
+        layers: SubresourceLayers,
+        offset: Offset,
+        extent: Extent,
+        data: Vec<u8>,
+    },
+}
+
+impl RetainedUpload {
+    /// Check if this upload overwrites everything `other` wrote.
+    fn covers(&self, other: &RetainedUpload) -> bool {
+        match (self, other) {
+            (
+                &RetainedUpload::Buffer { offset, ref data },
+                &RetainedUpload::Buffer {
+                    offset: other_offset,
+                    data: ref other_data,
+                },
+            ) => offset <= other_offset && other_offset + other_data.len() as u64 <= offset + data.len() as u64,
+            (
+                &RetainedUpload::Image {
+                    ref layers,
+                    offset,
+                    extent,
+                    ..
+                },
+                &RetainedUpload::Image {
+                    layers: ref other_layers,
+                    offset: other_offset,
+                    extent: other_extent,
+                    ..
+                },
+            ) => {
+                let covers_axis = |start: i32, len: u32, other_start: i32, other_len: u32| {
+                    start as i64 <= other_start as i64
+                        && other_start as i64 + other_len as i64 <= start as i64 + len as i64
+                };
+                layers.aspects.contains(other_layers.aspects) && layers.level == other_layers.level
+                    && layers.layers.start <= other_layers.layers.start
+                    && other_layers.layers.end <= layers.layers.end
+                    && covers_axis(offset.x, extent.width, other_offset.x, other_extent.width)
+                    && covers_axis(offset.y, extent.height, other_offset.y, other_extent.height)
+                    && covers_axis(offset.z, extent.depth, other_offset.z, other_extent.depth)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Remove uploads complete before `ongoing` frame that are overwritten by later complete uploads.
+fn prune_uploads(uploads: &mut Vec<(u64, RetainedUpload)>, ongoing: u64) {
+    let mut index = 0;
+    while index < uploads.len() {
+        let covered = uploads[index].0 < ongoing && uploads[index + 1..]
+            .iter()
+            .any(|&(frame, ref later)| frame < ongoing && later.covers(&uploads[index].1));
+        if covered {
+            uploads.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// Copies of data uploaded through `Factory` kept on the host by key
+/// to upload them again into resources recreated after device loss.
+/// Each copy is stamped with the frame it was uploaded in. Call `Retained::prune` once per frame
+/// to drop copies that later complete uploads overwrote.
+#[derive(Clone, Debug)]
+pub struct Retained<K: Hash + Eq> {
+    uploads: HashMap<K, Vec<(u64, RetainedUpload)>>,
+}
+
+impl<K> Retained<K>
+where
+    K: Hash + Eq,
+{
+    /// Create empty storage.
+    pub fn new() -> Self {
+        Retained {
+            uploads: HashMap::new(),
+        }
+    }
+
+    /// Upload data to the buffer with `Factory::upload_buffer` and keep copy of it.
+    /// Copies are replayed in order they were kept.
+    pub fn upload_buffer<B>(
+        &mut self,
+        key: K,
+        factory: &mut Factory<B>,
+        buffer: &mut Buffer<B>,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), Error>
+    where
+        B: Backend,
+    {
+        factory.upload_buffer(buffer, offset, data)?;
+        let frame = factory.current();
+        self.uploads.entry(key).or_insert_with(Vec::new).push((
+            frame,
+            RetainedUpload::Buffer {
+                offset,
+                data: data.to_vec(),
+            },
+        ));
+        Ok(())
+    }
+
+    /// Upload data to the image with `Factory::upload_image` and keep copy of it.
+    /// Copies are replayed in order they were kept.
+    pub fn upload_image<B>(
+        &mut self,
+        key: K,
+        factory: &mut Factory<B>,
+        image: &mut Image<B>,
+        layout: ImageLayout,
+        layers: SubresourceLayers,
+        offset: Offset,
+        extent: Extent,
+        data: &[u8],
+    ) -> Result<(), Error>
+    where
+        B: Backend,
+    {
+        factory.upload_image(image, layout, layers.clone(), offset, extent, data)?;
+        let frame = factory.current();
+        self.uploads.entry(key).or_insert_with(Vec::new).push((
+            frame,
+            RetainedUpload::Image {
+                layout,
+                layers,
+                offset,
+                extent,
+                data: data.to_vec(),
+            },
+        ));
+        Ok(())
+    }
+
+    /// Upload all buffer data kept by `key` into recreated `buffer`.
+    pub fn replay_buffer<B>(&self, key: &K, factory: &mut Factory<B>, buffer: &mut Buffer<B>) -> Result<(), Error>
+    where
+        B: Backend,
+    {
+        let uploads = self.uploads.get(key).ok_or(Error::InvalidArgument("No data is retained by the key"))?;
+        for &(_, ref upload) in uploads {
+            if let RetainedUpload::Buffer { offset, ref data } = *upload {
+                factory.upload_buffer(buffer, offset, data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Upload all image data kept by `key` into recreated `image`.
+    pub fn replay_image<B>(&self, key: &K, factory: &mut Factory<B>, image: &mut Image<B>) -> Result<(), Error>
+    where
+        B: Backend,
+    {
+        let uploads = self.uploads.get(key).ok_or(Error::InvalidArgument("No data is retained by the key"))?;
+        for &(_, ref upload) in uploads {
+            if let RetainedUpload::Image {
+                layout,
+                ref layers,
+                offset,
+                extent,
+                ref data,
+            } = *upload
+            {
+                factory.upload_image(image, layout, layers.clone(), offset, extent, data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop data kept by `key`.
+    pub fn forget(&mut self, key: &K) {
+        self.uploads.remove(key);
+    }
+
+    /// Drop copies overwritten by later uploads of the same key once uploads of both are complete.
+    /// Replaying remaining copies produces the same contents.
+    pub fn prune<B>(&mut self, factory: &Factory<B>)
+    where
+        B: Backend,
+    {
+        let ongoing = factory.ongoing();
+        for uploads in self.uploads.values_mut() {
+            prune_uploads(uploads, ongoing);
+        }
+    }
+
+    /// Get total size of data kept in bytes.
+    pub fn bytes(&self) -> usize {
+        self.uploads
+            .values()
+            .flat_map(|uploads| uploads.iter())
+            .map(|&(_, ref upload)| match *upload {
+                RetainedUpload::Buffer { ref data, .. } | RetainedUpload::Image { ref data, .. } => data.len(),
+            })
+            .sum()
+    }
+}
+
+#[test]
+fn overwritten_uploads_are_pruned_after_completion() {
+    let buffer = |offset, len| RetainedUpload::Buffer {
+        offset,
+        data: vec![0; len],
+    };
+    let mut uploads = vec![
+        (0, buffer(16, 16)),
+        (0, buffer(0, 8)),
+        (1, buffer(0, 64)),
+        (2, buffer(32, 64)),
+    ];
+
+    // Upload of frame 1 is complete only after frame 2 starts.
+    prune_uploads(&mut uploads, 1);
+    assert_eq!(uploads.len(), 4);
+
+    // Both uploads of frame 0 are overwritten by upload of frame 1 which partially overlaps last one.
+    prune_uploads(&mut uploads, 2);
+    assert_eq!(uploads, vec![(1, buffer(0, 64)), (2, buffer(32, 64))]);
+
+    prune_uploads(&mut uploads, 3);
+    assert_eq!(uploads, vec![(1, buffer(0, 64)), (2, buffer(32, 64))]);
+}
+
+#[test]
+fn image_uploads_cover_only_same_level_and_region() {
+    use hal::format::Aspects;
+
+    let image = |level, x, width| RetainedUpload::Image {
+        layout: ImageLayout::TransferDstOptimal,
+        layers: SubresourceLayers {
+            aspects: Aspects::COLOR,
+            level,
+            layers: 0..1,
+        },
+        offset: Offset { x, y: 0, z: 0 },
+        extent: Extent {
+            width,
+            height: 4,
+            depth: 1,
+        },
+        data: Vec::new(),
+    };
+    assert!(image(0, 0, 8).covers(&image(0, 2, 4)));
+    assert!(!image(0, 0, 8).covers(&image(0, 6, 4)));
+    assert!(!image(1, 0, 8).covers(&image(0, 2, 4)));
+    assert!(!image(0, 0, 8).covers(&RetainedUpload::Buffer {
+        offset: 0,
+        data: Vec::new(),
+    }));
+}
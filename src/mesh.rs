@@ -0,0 +1,192 @@
+//! `Mesh` bundles vertex buffers with their formats and optional index buffer.
+//! 
+
+use std::mem::size_of;
+use std::ops::Range;
+use std::slice::from_raw_parts;
+
+use hal::{Backend, IndexType};
+use hal::buffer::{IndexBufferView, Usage as BufferUsage};
+use hal::command::RawCommandBuffer;
+use hal::format::Format;
+use hal::memory::Properties;
+use hal::pso::{Element, ElemStride, VertexBufferSet};
+
+use Error;
+use factory::{Buffer, Factory};
+
+/// Layout of vertices in single vertex buffer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct VertexFormat {
+    /// Size of one vertex in bytes.
+    pub stride: ElemStride,
+
+    /// Attributes of the vertex.
+    pub attributes: Vec<Element<Format>>,
+}
+
+/// Index data to upload into mesh.
+#[derive(Clone, Copy, Debug)]
+pub enum Indices<'a> {
+    /// 16-bit indices.
+    U16(&'a [u16]),
+
+    /// 32-bit indices.
+    U32(&'a [u32]),
+}
+
+#[derive(Debug)]
+struct VertexBuffer<B: Backend> {
+    buffer: Buffer<B>,
+    format: VertexFormat,
+}
+
+#[derive(Debug)]
+struct IndexBuffer<B: Backend> {
+    buffer: Buffer<B>,
+    index_type: IndexType,
+    len: u32,
+}
+
+/// Set of vertex buffers with optional index buffer.
+#[derive(Debug)]
+pub struct Mesh<B: Backend> {
+    vertices: Vec<VertexBuffer<B>>,
+    indices: Option<IndexBuffer<B>>,
+    len: u32,
+}
+
+impl<B> Mesh<B>
+where
+    B: Backend,
+{
+    /// Get formats of vertex buffers in binding order.
+    pub fn formats(&self) -> Vec<&VertexFormat> {
+        self.vertices.iter().map(|vertex| &vertex.format).collect()
+    }
+
+    /// Get number of vertices.
+    pub fn vertex_count(&self) -> u32 {
+        self.len
+    }
+
+    /// Get number of indices if mesh is indexed.
+    pub fn index_count(&self) -> Option<u32> {
+        self.indices.as_ref().map(|indices| indices.len)
+    }
+
+    /// Bind vertex buffers and index buffer.
+    /// Vertex buffers are bound to bindings in order they were supplied at creation.
+    pub fn bind(&self, cbuf: &mut B::CommandBuffer) {
+        cbuf.bind_vertex_buffers(VertexBufferSet(
+            self.vertices
+                .iter()
+                .map(|vertex| (vertex.buffer.raw(), 0))
+                .collect(),
+        ));
+        if let Some(ref indices) = self.indices {
+            cbuf.bind_index_buffer(IndexBufferView {
+                buffer: indices.buffer.raw(),
+                offset: 0,
+                index_type: indices.index_type,
+            });
+        }
+    }
+
+    /// Bind mesh and draw it.
+    /// Uses indexed draw if mesh has indices.
+    pub fn draw(&self, cbuf: &mut B::CommandBuffer, instances: Range<u32>) {
+        self.bind(cbuf);
+        match self.indices {
+            Some(ref indices) => cbuf.draw_indexed(0..indices.len, 0, instances),
+            None => cbuf.draw(0..self.len, instances),
+        }
+    }
+}
+
+impl<B> Factory<B>
+where
+    B: Backend,
+{
+    /// Create `Mesh` and upload vertex and index data to it.
+    /// 
+    /// # Parameters
+    /// 
+    /// `vertices`  - data and format of each vertex buffer. All buffers must contain the same number of vertices.
+    /// `indices`   - optional index data.
+    /// 
+    pub fn create_mesh(
+        &mut self,
+        vertices: &[(&[u8], VertexFormat)],
+        indices: Option<Indices>,
+    ) -> Result<Mesh<B>, Error> {
+        let mut len = None;
+        let mut buffers = Vec::with_capacity(vertices.len());
+        for &(data, ref format) in vertices {
+            if format.stride == 0 || data.len() % format.stride as usize != 0 {
+                return Err(Error::InvalidArgument("Vertex data size is not multiple of stride"));
+            }
+            let count = (data.len() / format.stride as usize) as u32;
+            if *len.get_or_insert(count) != count {
+                return Err(Error::SizeMismatch {
+                    what: "vertex count",
+                    expected: len.unwrap() as u64,
+                    actual: count as u64,
+                });
+            }
+            buffers.push(VertexBuffer {
+                buffer: self.create_uploaded_buffer(data, BufferUsage::VERTEX)?,
+                format: format.clone(),
+            });
+        }
+
+        let indices = match indices {
+            Some(Indices::U16(data)) => Some(IndexBuffer {
+                buffer: self.create_uploaded_buffer(as_bytes(data), BufferUsage::INDEX)?,
+                index_type: IndexType::U16,
+                len: data.len() as u32,
+            }),
+            Some(Indices::U32(data)) => Some(IndexBuffer {
+                buffer: self.create_uploaded_buffer(as_bytes(data), BufferUsage::INDEX)?,
+                index_type: IndexType::U32,
+                len: data.len() as u32,
+            }),
+            None => None,
+        };
+
+        Ok(Mesh {
+            vertices: buffers,
+            indices,
+            len: len.unwrap_or(0),
+        })
+    }
+
+    /// Destroy `Mesh`.
+    /// Factory will destroy buffers after all commands referencing them will complete.
+    pub fn destroy_mesh(&mut self, mesh: Mesh<B>) {
+        for vertex in mesh.vertices {
+            self.destroy_buffer(vertex.buffer);
+        }
+        if let Some(indices) = mesh.indices {
+            self.destroy_buffer(indices.buffer);
+        }
+    }
+
+    fn create_uploaded_buffer(&mut self, data: &[u8], usage: BufferUsage) -> Result<Buffer<B>, Error> {
+        let mut buffer = self.create_buffer(
+            data.len() as u64,
+            Properties::DEVICE_LOCAL,
+            usage | BufferUsage::TRANSFER_DST,
+        )?;
+        self.upload_buffer(&mut buffer, 0, data)?;
+        Ok(buffer)
+    }
+}
+
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe {
+        // Safe due to `T` is plain integer.
+        from_raw_parts(data.as_ptr() as *const u8, data.len() * size_of::<T>())
+    }
+}
@@ -0,0 +1,115 @@
+//! `FramePacer` limits frame rate by sleeping before frame starts.
+//! 
+
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Counters collected by `FramePacer`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PacingStats {
+    /// Number of paced frames.
+    pub frames: u64,
+
+    /// Number of frames that started after their deadline.
+    pub missed: u64,
+
+    /// Total time spent sleeping.
+    pub slept: Duration,
+}
+
+/// Frame rate limiter.
+/// Frames are spaced by fixed interval. Frame that misses its deadline starts new schedule
+/// instead of running subsequent frames back-to-back to catch up.
+/// Also estimates average interval between frames, which approximates display refresh interval
+/// when presentation is synchronized with vertical blank and rate is not limited.
+#[derive(Clone, Debug)]
+pub struct FramePacer {
+    interval: Option<Duration>,
+    deadline: Option<Instant>,
+    last: Option<Instant>,
+    estimate: Option<u64>,
+    stats: PacingStats,
+}
+
+impl FramePacer {
+    /// Create pacer that doesn't limit frame rate.
+    pub fn new() -> Self {
+        FramePacer {
+            interval: None,
+            deadline: None,
+            last: None,
+            estimate: None,
+            stats: PacingStats::default(),
+        }
+    }
+
+    /// Limit frame rate to `fps` frames per second. `None` removes the limit.
+    pub fn set_frame_rate(&mut self, fps: Option<u32>) {
+        self.set_interval(fps.map(|fps| Duration::from_secs(1) / fps.max(1)));
+    }
+
+    /// Set minimal interval between frames. `None` removes the limit.
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval;
+        self.deadline = None;
+    }
+
+    /// Get minimal interval between frames.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Get estimated average interval between frames.
+    pub fn estimated_interval(&self) -> Option<Duration> {
+        self.estimate
+            .map(|nanos| Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32))
+    }
+
+    /// Limit frame rate to the estimated refresh rate divided by `divisor`.
+    /// Does nothing until estimate is available.
+    pub fn align_to_refresh(&mut self, divisor: u32) {
+        if let Some(estimate) = self.estimated_interval() {
+            self.set_interval(Some(estimate * divisor.max(1)));
+        }
+    }
+
+    /// Get collected counters.
+    pub fn stats(&self) -> PacingStats {
+        self.stats
+    }
+
+    /// Sleep until the deadline of the next frame.
+    pub fn wait(&mut self) {
+        if let Some(interval) = self.interval {
+            let now = Instant::now();
+            match self.deadline {
+                Some(deadline) if deadline > now => {
+                    let slept = deadline - now;
+                    sleep(slept);
+                    self.stats.slept += slept;
+                    self.deadline = Some(deadline + interval);
+                }
+                Some(_) => {
+                    self.stats.missed += 1;
+                    self.deadline = Some(now + interval);
+                }
+                None => {
+                    self.deadline = Some(now + interval);
+                }
+            }
+        }
+
+        let now = Instant::now();
+        if let Some(last) = self.last {
+            let elapsed = now - last;
+            let nanos = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+            // Exponential moving average with weight of 1/16.
+            self.estimate = Some(match self.estimate {
+                Some(estimate) => estimate - estimate / 16 + nanos / 16,
+                None => nanos,
+            });
+        }
+        self.last = Some(now);
+        self.stats.frames += 1;
+    }
+}
@@ -0,0 +1,156 @@
+//! `ParallelRecorder` hands out secondary command buffers that are recorded on multiple threads
+//! and executed in order on primary command buffer.
+//! 
+
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+
+use hal::{Backend, Device};
+use hal::command::{CommandBufferFlags, RawCommandBuffer, RawLevel};
+use hal::pool::{CommandPoolCreateFlags, RawCommandPool};
+use hal::queue::QueueFamilyId;
+
+use factory::Factory;
+
+/// Secondary command buffer handed out by `ParallelRecorder::begin`.
+/// It owns its command pool, so it can be sent to another thread and recorded
+/// without synchronization. It must be returned to `ParallelRecorder::execute`.
+#[derive(Debug)]
+pub struct SecondaryCommandBuffer<B: Backend> {
+    index: usize,
+    pool: B::CommandPool,
+    cbuf: B::CommandBuffer,
+}
+
+impl<B> SecondaryCommandBuffer<B>
+where
+    B: Backend,
+{
+    /// Get index of the command buffer. Command buffers are executed in order of their indices.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<B> Deref for SecondaryCommandBuffer<B>
+where
+    B: Backend,
+{
+    type Target = B::CommandBuffer;
+
+    fn deref(&self) -> &B::CommandBuffer {
+        &self.cbuf
+    }
+}
+
+impl<B> DerefMut for SecondaryCommandBuffer<B>
+where
+    B: Backend,
+{
+    fn deref_mut(&mut self) -> &mut B::CommandBuffer {
+        &mut self.cbuf
+    }
+}
+
+/// Recorder of secondary command buffers for passes with many draws.
+/// Command pools are reused once frames they were executed in are complete.
+#[derive(Debug)]
+pub struct ParallelRecorder<B: Backend> {
+    family: QueueFamilyId,
+    frames: VecDeque<(Vec<(B::CommandPool, B::CommandBuffer)>, u64)>,
+    free: Vec<(B::CommandPool, B::CommandBuffer)>,
+}
+
+impl<B> ParallelRecorder<B>
+where
+    B: Backend,
+{
+    /// Create recorder for primary command buffers of the `family`.
+    pub fn new(family: QueueFamilyId) -> Self {
+        ParallelRecorder {
+            family,
+            frames: VecDeque::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Get `count` secondary command buffers in recording state.
+    /// If `render_pass_continue` is set they continue the subpass primary command buffer is in,
+    /// e.g. in `PassContext` of a pass declared with `PassDesc::secondary`.
+    /// Command buffers can be recorded on different threads, typically each gets an equal share of draws.
+    pub fn begin(
+        &mut self,
+        count: usize,
+        render_pass_continue: bool,
+        factory: &mut Factory<B>,
+    ) -> Vec<SecondaryCommandBuffer<B>> {
+        let flags = if render_pass_continue {
+            CommandBufferFlags::ONE_TIME_SUBMIT | CommandBufferFlags::RENDER_PASS_CONTINUE
+        } else {
+            CommandBufferFlags::ONE_TIME_SUBMIT
+        };
+        let ongoing = factory.ongoing();
+        while self.frames.front().map_or(false, |&(_, index)| index < ongoing) {
+            let (buffers, _) = self.frames.pop_front().unwrap();
+            for (mut pool, cbuf) in buffers {
+                pool.reset();
+                self.free.push((pool, cbuf));
+            }
+        }
+
+        let device: &B::Device = &**factory;
+        (0..count)
+            .map(|index| {
+                let (pool, mut cbuf) = self.free.pop().unwrap_or_else(|| {
+                    let mut pool = device.create_command_pool(self.family, CommandPoolCreateFlags::TRANSIENT);
+                    let cbuf = pool.allocate(1, RawLevel::Secondary).remove(0);
+                    (pool, cbuf)
+                });
+                cbuf.begin(flags);
+                SecondaryCommandBuffer { index, pool, cbuf }
+            })
+            .collect()
+    }
+
+    /// Finish recording of secondary command buffers and record their execution into `primary`
+    /// in order of their indices.
+    /// If they were begun with `render_pass_continue` then `primary` must be inside the same subpass
+    /// begun with `SubpassContents::SecondaryBuffers`.
+    /// All command buffers handed out by `ParallelRecorder::begin` must be returned at once.
+    pub fn execute(
+        &mut self,
+        primary: &mut B::CommandBuffer,
+        mut secondaries: Vec<SecondaryCommandBuffer<B>>,
+        factory: &mut Factory<B>,
+    ) {
+        secondaries.sort_by_key(|secondary| secondary.index);
+        for secondary in &mut secondaries {
+            secondary.cbuf.finish();
+        }
+        primary.execute_commands(secondaries.iter().map(|secondary| &secondary.cbuf));
+
+        let current = factory.current();
+        let buffers = secondaries.into_iter().map(|secondary| (secondary.pool, secondary.cbuf));
+        match self.frames.back_mut() {
+            Some(&mut (ref mut executed, index)) if index == current => {
+                executed.extend(buffers);
+                return;
+            }
+            _ => {}
+        }
+        self.frames.push_back((buffers.collect(), current));
+    }
+
+    /// Destroy command pools of the recorder.
+    /// Pools are destroyed immediately, so all command buffers executed by the recorder must be complete.
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        let pools = self.frames
+            .into_iter()
+            .flat_map(|(buffers, _)| buffers)
+            .chain(self.free);
+        for (mut pool, cbuf) in pools {
+            pool.free(vec![cbuf]);
+            factory.destroy_command_pool(pool);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! `PassCache` deduplicates render passes and framebuffers.
+//! 
+
+use std::collections::HashMap;
+
+use hal::{Backend, Device};
+use hal::device::Extent;
+use hal::format::Format;
+use hal::image::ImageLayout;
+use hal::pass::{Attachment, AttachmentLoadOp, AttachmentOps, AttachmentRef, AttachmentStoreOp, SubpassDesc};
+
+use Error;
+use factory::Factory;
+use handle::{Handle, Registry};
+
+/// Identifier of attachment view owned by `PassCache`.
+/// Id of destroyed view never matches views added later, even if its slot is reused.
+pub type AttachmentId<B: Backend> = Handle<B::ImageView>;
+
+/// Identifier of render pass in `PassCache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RenderPassId(usize);
+
+/// Description of single-subpass render pass.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct RenderPassDesc {
+    /// Color attachments.
+    pub colors: Vec<Attachment>,
+
+    /// Optional depth-stencil attachment.
+    pub depth_stencil: Option<Attachment>,
+}
+
+/// Attachment which contents are neither loaded nor stored.
+/// Suitable for images created with `ImageBuilder::transient`.
+/// Attachment is cleared at the start of the pass.
+pub fn transient_attachment(format: Format, depth_stencil: bool) -> Attachment {
+    let layout = if depth_stencil {
+        ImageLayout::DepthStencilAttachmentOptimal
+    } else {
+        ImageLayout::ColorAttachmentOptimal
+    };
+    let ops = AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::DontCare);
+    Attachment {
+        format: Some(format),
+        ops,
+        stencil_ops: if depth_stencil { ops } else { AttachmentOps::DONT_CARE },
+        layouts: ImageLayout::Undefined..layout,
+    }
+}
+
+/// Cache of render passes keyed by attachment formats and operations
+/// and framebuffers keyed by render pass, attachment views and extent.
+/// 
+/// Attachment views are owned by the cache and identified by generational `AttachmentId`s.
+/// Destroying attachment with `PassCache::destroy_attachment` destroys framebuffers created with it,
+/// e.g. when swapchain is recreated.
+#[derive(Debug)]
+pub struct PassCache<B: Backend> {
+    render_passes: Vec<B::RenderPass>,
+    render_pass_keys: HashMap<RenderPassDesc, RenderPassId>,
+    attachments: Registry<B::ImageView>,
+    framebuffers: HashMap<(RenderPassId, Vec<AttachmentId<B>>, (u32, u32, u32)), B::Framebuffer>,
+}
+
+impl<B> PassCache<B>
+where
+    B: Backend,
+{
+    /// Create empty cache.
+    pub fn new() -> Self {
+        PassCache {
+            render_passes: Vec::new(),
+            render_pass_keys: HashMap::new(),
+            attachments: Registry::new(),
+            framebuffers: HashMap::new(),
+        }
+    }
+
+    /// Get or create render pass with single subpass using all attachments.
+    /// Color attachments come first, depth-stencil attachment is last.
+    pub fn render_pass(&mut self, factory: &Factory<B>, desc: &RenderPassDesc) -> RenderPassId {
+        if let Some(&id) = self.render_pass_keys.get(desc) {
+            return id;
+        }
+
+        let colors = (0..desc.colors.len())
+            .map(|index| (index, ImageLayout::ColorAttachmentOptimal))
+            .collect::<Vec<AttachmentRef>>();
+        let depth_stencil = desc.depth_stencil
+            .as_ref()
+            .map(|_| (desc.colors.len(), ImageLayout::DepthStencilAttachmentOptimal));
+        let subpass = SubpassDesc {
+            colors: &colors,
+            depth_stencil: depth_stencil.as_ref(),
+            inputs: &[],
+            preserves: &[],
+        };
+        let attachments = desc.colors
+            .iter()
+            .chain(desc.depth_stencil.as_ref())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let render_pass = factory.create_render_pass(&attachments, &[subpass], &[]);
+        let id = RenderPassId(self.render_passes.len());
+        self.render_passes.push(render_pass);
+        self.render_pass_keys.insert(desc.clone(), id);
+        id
+    }
+
+    /// Get render pass by id.
+    pub fn get_render_pass(&self, id: RenderPassId) -> &B::RenderPass {
+        &self.render_passes[id.0]
+    }
+
+    /// Take ownership of attachment view to create framebuffers with.
+    pub fn add_attachment(&mut self, view: B::ImageView) -> AttachmentId<B> {
+        self.attachments.insert(view)
+    }
+
+    /// Get attachment view by id. Returns `None` if it was destroyed.
+    pub fn attachment(&self, id: AttachmentId<B>) -> Option<&B::ImageView> {
+        self.attachments.get(id)
+    }
+
+    /// Destroy attachment view and all framebuffers created with it.
+    /// Factory will destroy them after all commands referencing them will complete.
+    /// Does nothing if attachment is already destroyed.
+    pub fn destroy_attachment(&mut self, factory: &mut Factory<B>, id: AttachmentId<B>) {
+        let view = match self.attachments.remove(id) {
+            Some(view) => view,
+            None => return,
+        };
+        let keys = self.framebuffers
+            .keys()
+            .filter(|&&(_, ref attachments, _)| attachments.contains(&id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in keys {
+            let framebuffer = self.framebuffers.remove(&key).expect("Key is taken from the map");
+            factory.destroy_framebuffer(framebuffer);
+        }
+        factory.destroy_image_view(view);
+    }
+
+    /// Get or create framebuffer for render pass with views of `attachments`.
+    /// Fails if any attachment is destroyed.
+    pub fn framebuffer(
+        &mut self,
+        factory: &Factory<B>,
+        render_pass: RenderPassId,
+        attachments: &[AttachmentId<B>],
+        extent: Extent,
+    ) -> Result<&B::Framebuffer, Error> {
+        let key = (
+            render_pass,
+            attachments.to_vec(),
+            (extent.width, extent.height, extent.depth),
+        );
+        if !self.framebuffers.contains_key(&key) {
+            let views = attachments
+                .iter()
+                .map(|&id| {
+                    self.attachments
+                        .get(id)
+                        .ok_or(Error::InvalidArgument("Attachment is destroyed"))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let framebuffer = factory
+                .create_framebuffer(&self.render_passes[render_pass.0], &views, extent)
+                .map_err(|err| Error::creation("framebuffer", err))?;
+            self.framebuffers.insert(key.clone(), framebuffer);
+        }
+        Ok(&self.framebuffers[&key])
+    }
+
+    /// Destroy all cached framebuffers.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn invalidate_framebuffers(&mut self, factory: &mut Factory<B>) {
+        for (_, framebuffer) in self.framebuffers.drain() {
+            factory.destroy_framebuffer(framebuffer);
+        }
+    }
+
+    /// Destroy all cached render passes, framebuffers and attachment views.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn dispose(mut self, factory: &mut Factory<B>) {
+        self.invalidate_framebuffers(factory);
+        for view in self.attachments.drain() {
+            factory.destroy_image_view(view);
+        }
+        for render_pass in self.render_passes {
+            factory.destroy_render_pass(render_pass);
+        }
+    }
+}
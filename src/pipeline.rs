@@ -0,0 +1,236 @@
+//! Builders for pipelines created by `Factory`.
+//! Presets fill rasterizer, blend and depth state for common cases.
+//! 
+
+use hal::{Backend, Device, Primitive};
+use hal::pass::Subpass;
+use hal::pso::{AttributeDesc, BlendDesc, BlendState, ColorBlendDesc, ColorMask, Comparison,
+               DepthStencilDesc, DepthTest, EntryPoint, GraphicsPipelineDesc, GraphicsShaderSet,
+               InstanceRate, PolygonMode, Rasterizer, StencilTest, VertexBufferDesc};
+
+use Error;
+use factory::Factory;
+use mesh::VertexFormat;
+
+/// Graphics pipeline state apart from shaders, layout and subpass.
+/// With `serialize` feature it can be kept in data files and applied with `GraphicsPipelineBuilder::state`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct PipelineState {
+    /// Vertex buffers in binding order.
+    pub vertex_buffers: Vec<VertexBufferDesc>,
+
+    /// Vertex attributes.
+    pub attributes: Vec<AttributeDesc>,
+
+    /// Primitive topology.
+    pub primitive: Primitive,
+
+    /// Rasterizer state.
+    pub rasterizer: Rasterizer,
+
+    /// Blending of each color target.
+    pub targets: Vec<ColorBlendDesc>,
+
+    /// Depth-stencil state.
+    pub depth_stencil: Option<DepthStencilDesc>,
+}
+
+impl Default for PipelineState {
+    fn default() -> Self {
+        PipelineState {
+            vertex_buffers: Vec::new(),
+            attributes: Vec::new(),
+            primitive: Primitive::TriangleList,
+            rasterizer: Rasterizer::FILL,
+            targets: Vec::new(),
+            depth_stencil: None,
+        }
+    }
+}
+
+/// Builder for graphics pipeline. Created by `Factory::graphics_pipeline`.
+/// Pipeline created should be destroyed with `Factory::destroy_graphics_pipeline`.
+/// 
+/// # Example
+/// 
+/// ```ignore
+/// let pipeline = factory.graphics_pipeline(&layout, subpass)
+///     .vertex_shader(vs)
+///     .fragment_shader(fs)
+///     .vertex_format(&format)
+///     .opaque()
+///     .depth_test(true)
+///     .build()?;
+/// ```
+pub struct GraphicsPipelineBuilder<'a, 'b, B: Backend> {
+    factory: &'a mut Factory<B>,
+    layout: &'b B::PipelineLayout,
+    subpass: Subpass<'b, B>,
+    vertex: Option<EntryPoint<'b, B>>,
+    geometry: Option<EntryPoint<'b, B>>,
+    fragment: Option<EntryPoint<'b, B>>,
+    state: PipelineState,
+}
+
+impl<'a, 'b, B> GraphicsPipelineBuilder<'a, 'b, B>
+where
+    B: Backend,
+{
+    pub(crate) fn new(
+        factory: &'a mut Factory<B>,
+        layout: &'b B::PipelineLayout,
+        subpass: Subpass<'b, B>,
+    ) -> Self {
+        GraphicsPipelineBuilder {
+            factory,
+            layout,
+            subpass,
+            vertex: None,
+            geometry: None,
+            fragment: None,
+            state: PipelineState::default(),
+        }
+    }
+
+    /// Set vertex shader. Required.
+    pub fn vertex_shader(mut self, entry: EntryPoint<'b, B>) -> Self {
+        self.vertex = Some(entry);
+        self
+    }
+
+    /// Set geometry shader.
+    pub fn geometry_shader(mut self, entry: EntryPoint<'b, B>) -> Self {
+        self.geometry = Some(entry);
+        self
+    }
+
+    /// Set fragment shader.
+    pub fn fragment_shader(mut self, entry: EntryPoint<'b, B>) -> Self {
+        self.fragment = Some(entry);
+        self
+    }
+
+    /// Add per-vertex buffer with `format`.
+    /// Buffers are bound in order they are added. Attribute locations are assigned sequentially across buffers.
+    pub fn vertex_format(self, format: &VertexFormat) -> Self {
+        self.vertex_buffer(format, 0)
+    }
+
+    /// Add per-instance buffer with `format`.
+    pub fn instance_format(self, format: &VertexFormat) -> Self {
+        self.vertex_buffer(format, 1)
+    }
+
+    /// Set primitive topology. `TriangleList` by default.
+    pub fn primitive(mut self, primitive: Primitive) -> Self {
+        self.state.primitive = primitive;
+        self
+    }
+
+    /// Set rasterizer state. `Rasterizer::FILL` by default.
+    pub fn rasterizer(mut self, rasterizer: Rasterizer) -> Self {
+        self.state.rasterizer = rasterizer;
+        self
+    }
+
+    /// Draw wireframe.
+    pub fn wireframe(mut self) -> Self {
+        self.state.rasterizer.polygon_mode = PolygonMode::Line(1.0);
+        self
+    }
+
+    /// Add color target with custom blending.
+    pub fn blend(mut self, desc: ColorBlendDesc) -> Self {
+        self.state.targets.push(desc);
+        self
+    }
+
+    /// Add color target without blending.
+    pub fn opaque(self) -> Self {
+        self.blend(ColorBlendDesc(ColorMask::ALL, BlendState::Off))
+    }
+
+    /// Add color target with alpha blending.
+    pub fn alpha_blend(self) -> Self {
+        self.blend(ColorBlendDesc(ColorMask::ALL, BlendState::ALPHA))
+    }
+
+    /// Set custom depth-stencil state.
+    pub fn depth_stencil(mut self, desc: DepthStencilDesc) -> Self {
+        self.state.depth_stencil = Some(desc);
+        self
+    }
+
+    /// Enable `LessEqual` depth test, optionally writing depth.
+    pub fn depth_test(self, write: bool) -> Self {
+        self.depth_stencil(DepthStencilDesc {
+            depth: DepthTest::On {
+                fun: Comparison::LessEqual,
+                write,
+            },
+            depth_bounds: false,
+            stencil: StencilTest::Off,
+        })
+    }
+
+    /// Replace vertex input, rasterizer, blend and depth-stencil state, e.g. loaded from data file.
+    pub fn state(mut self, state: PipelineState) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Get vertex input, rasterizer, blend and depth-stencil state set so far.
+    pub fn pipeline_state(&self) -> &PipelineState {
+        &self.state
+    }
+
+    /// Create the pipeline.
+    pub fn build(self) -> Result<B::GraphicsPipeline, Error> {
+        let shaders = GraphicsShaderSet {
+            vertex: self.vertex.ok_or(Error::InvalidArgument("Vertex shader is not set"))?,
+            hull: None,
+            domain: None,
+            geometry: self.geometry,
+            fragment: self.fragment,
+        };
+        let mut desc = GraphicsPipelineDesc::new(
+            shaders,
+            self.state.primitive,
+            self.state.rasterizer,
+            self.layout,
+            self.subpass,
+        );
+        desc.vertex_buffers = self.state.vertex_buffers;
+        desc.attributes = self.state.attributes;
+        desc.blender = BlendDesc {
+            logic_op: None,
+            targets: self.state.targets,
+        };
+        desc.depth_stencil = self.state.depth_stencil;
+
+        let pipeline = self.factory
+            .create_graphics_pipelines(Some(desc))
+            .pop()
+            .expect("One pipeline requested")
+            .map_err(|err| Error::creation("graphics pipeline", err))?;
+        Ok(pipeline)
+    }
+
+    fn vertex_buffer(mut self, format: &VertexFormat, rate: InstanceRate) -> Self {
+        let binding = self.state.vertex_buffers.len() as u32;
+        let location = self.state.attributes.len() as u32;
+        self.state.vertex_buffers.push(VertexBufferDesc {
+            stride: format.stride,
+            rate,
+        });
+        self.state.attributes.extend(format.attributes.iter().enumerate().map(
+            |(index, &element)| AttributeDesc {
+                location: location + index as u32,
+                binding,
+                element,
+            },
+        ));
+        self
+    }
+}
@@ -0,0 +1,277 @@
+//! Helpers for fullscreen post-processing passes: cached pipelines drawing single triangle
+//! without vertex buffers, pair of color images passes alternate between and blit into the target.
+//! 
+
+use std::collections::HashMap;
+
+use hal::{Backend, Device};
+use hal::command::{ImageBlit, RawCommandBuffer};
+use hal::format::{Aspects, Format, Swizzle};
+use hal::image::{Access, AaMode, Filter, ImageLayout, Kind, Offset, SubresourceLayers, SubresourceRange,
+                 Usage as ImageUsage};
+use hal::memory::Barrier;
+use hal::pass::Subpass;
+use hal::pso::{EntryPoint, PipelineStage};
+
+use Error;
+use factory::{Factory, Image};
+use texture::kind_extent;
+
+/// Vertex shader of fullscreen triangle compiled from `shaders/fullscreen.vert`.
+const FULLSCREEN_VERTEX: &[u8] = include_bytes!("shaders/fullscreen.vert.spv");
+
+/// Pipelines are created for layout, render pass, subpass index and fragment shader entry point.
+/// Objects are identified by their address.
+type FullscreenKey = (usize, usize, usize, usize, String);
+
+/// Cache of pipelines drawing triangle that covers the whole viewport.
+/// Vertex shader is provided by the cache and passes `vec2 uv` at location 0 to fragment shader,
+/// (0, 0) at the top-left corner and (1, 1) at the bottom-right one.
+/// Record draw with `FullscreenPipelines::draw` after binding the pipeline.
+///
+/// Layouts, render passes and shader modules must not be moved or destroyed while pipelines
+/// created for them are cached. Call `invalidate` before destroying them.
+#[derive(Debug)]
+pub struct FullscreenPipelines<B: Backend> {
+    vertex: B::ShaderModule,
+    pipelines: HashMap<FullscreenKey, B::GraphicsPipeline>,
+}
+
+impl<B> FullscreenPipelines<B>
+where
+    B: Backend,
+{
+    /// Create empty cache.
+    pub fn new(factory: &Factory<B>) -> Result<Self, Error> {
+        let vertex = factory
+            .create_shader_module(FULLSCREEN_VERTEX)
+            .map_err(|err| Error::creation("fullscreen vertex shader", err))?;
+        Ok(FullscreenPipelines {
+            vertex,
+            pipelines: HashMap::new(),
+        })
+    }
+
+    /// Get or create pipeline drawing fullscreen triangle with `fragment` shader
+    /// into single color target of `subpass` without blending.
+    pub fn pipeline(
+        &mut self,
+        factory: &mut Factory<B>,
+        layout: &B::PipelineLayout,
+        subpass: Subpass<B>,
+        fragment: EntryPoint<B>,
+    ) -> Result<&B::GraphicsPipeline, Error> {
+        let key = (
+            layout as *const B::PipelineLayout as usize,
+            subpass.main_pass as *const B::RenderPass as usize,
+            subpass.index,
+            fragment.module as *const B::ShaderModule as usize,
+            fragment.entry.to_string(),
+        );
+        if !self.pipelines.contains_key(&key) {
+            let vertex = EntryPoint {
+                entry: "main",
+                module: &self.vertex,
+                specialization: &[],
+            };
+            let pipeline = factory
+                .graphics_pipeline(layout, subpass)
+                .vertex_shader(vertex)
+                .fragment_shader(fragment)
+                .opaque()
+                .build()?;
+            self.pipelines.insert(key.clone(), pipeline);
+        }
+        Ok(&self.pipelines[&key])
+    }
+
+    /// Record draw of fullscreen triangle. Pipeline from the cache must be bound.
+    pub fn draw(&self, cbuf: &mut B::CommandBuffer) {
+        cbuf.draw(0..3, 0..1);
+    }
+
+    /// Destroy all cached pipelines.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn invalidate(&mut self, factory: &mut Factory<B>) {
+        for (_, pipeline) in self.pipelines.drain() {
+            factory.destroy_graphics_pipeline(pipeline);
+        }
+    }
+
+    /// Destroy cached pipelines and vertex shader.
+    pub fn dispose(mut self, factory: &mut Factory<B>) {
+        self.invalidate(factory);
+        factory.destroy_shader_module(self.vertex);
+    }
+}
+
+/// Pair of color images post-processing passes alternate between.
+/// Each pass reads `source` written by previous pass, writes `target` and then images are swapped.
+/// Images can be rendered into, sampled and blitted from. Result of the last pass
+/// is placed into the backbuffer with `PingPong::blit_to_target`.
+#[derive(Debug)]
+pub struct PingPong<B: Backend> {
+    kind: Kind,
+    format: Format,
+    images: Vec<(Image<B>, B::ImageView)>,
+    current: usize,
+}
+
+impl<B> PingPong<B>
+where
+    B: Backend,
+{
+    /// Create pair of images of `format` sized to match the target of `kind`.
+    pub fn new(kind: Kind, format: Format, factory: &mut Factory<B>) -> Result<Self, Error> {
+        let mut ping_pong = PingPong {
+            kind,
+            format,
+            images: Vec::new(),
+            current: 0,
+        };
+        ping_pong.resize(kind, factory)?;
+        Ok(ping_pong)
+    }
+
+    /// Get format of the images.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Get image written last and its view.
+    pub fn source(&self) -> (&Image<B>, &B::ImageView) {
+        let (ref image, ref view) = self.images[self.current];
+        (image, view)
+    }
+
+    /// Get image to write next and its view.
+    pub fn target(&self) -> (&Image<B>, &B::ImageView) {
+        let (ref image, ref view) = self.images[1 - self.current];
+        (image, view)
+    }
+
+    /// Make `target` the `source` for the next pass.
+    pub fn swap(&mut self) {
+        self.current = 1 - self.current;
+    }
+
+    /// Recreate images to match the target of new size, e.g. in `Render::resize`.
+    /// Factory will destroy old images after all commands referencing them will complete.
+    pub fn resize(&mut self, kind: Kind, factory: &mut Factory<B>) -> Result<(), Error> {
+        for (image, view) in self.images.drain(..) {
+            factory.destroy_image_view(view);
+            factory.destroy_image(image);
+        }
+        let extent = kind_extent(kind);
+        self.kind = Kind::D2(extent.width as u16, extent.height as u16, AaMode::Single);
+        for _ in 0..2 {
+            let (image, format) = factory
+                .image()
+                .kind(self.kind)
+                .format(self.format)
+                .color_attachment()
+                .usage(ImageUsage::SAMPLED | ImageUsage::TRANSFER_SRC)
+                .name("ping-pong")
+                .build()?;
+            let view = factory
+                .create_image_view(image.raw(), format, Swizzle::NO, color_range())
+                .map_err(|err| Error::creation("ping-pong view", err))?;
+            self.format = format;
+            self.images.push((image, view));
+        }
+        self.current = 0;
+        Ok(())
+    }
+
+    /// Record blit of `source` image into `target` image of `kind` scaling it with linear filtering.
+    /// Formats of images may differ, e.g. HDR image blitted into sRGB backbuffer, texels are converted by the blit.
+    /// Integer formats can be blitted only into integer formats of the same signedness.
+    /// `source` must be in `ColorAttachmentOptimal` layout. It's left in `TransferSrcOptimal` layout,
+    /// so render passes should use `Undefined` initial layout for it.
+    /// `target` is transitioned from `Undefined` to `layout`.
+    pub fn blit_to_target(&self, cbuf: &mut B::CommandBuffer, target: &B::Image, kind: Kind, layout: ImageLayout) {
+        let source = self.source().0.raw();
+        blit::<B>(cbuf, source, self.kind, target, kind, layout);
+    }
+
+    /// Destroy both images.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn dispose(mut self, factory: &mut Factory<B>) {
+        for (image, view) in self.images.drain(..) {
+            factory.destroy_image_view(view);
+            factory.destroy_image(image);
+        }
+    }
+}
+
+/// Record blit of color image in `ColorAttachmentOptimal` layout into `target`.
+fn blit<B>(
+    cbuf: &mut B::CommandBuffer,
+    source: &B::Image,
+    source_kind: Kind,
+    target: &B::Image,
+    target_kind: Kind,
+    layout: ImageLayout,
+) where
+    B: Backend,
+{
+    cbuf.pipeline_barrier(
+        PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::TRANSFER,
+        vec![
+            Barrier::Image {
+                states: (Access::COLOR_ATTACHMENT_WRITE, ImageLayout::ColorAttachmentOptimal)
+                    ..(Access::TRANSFER_READ, ImageLayout::TransferSrcOptimal),
+                target: source,
+                range: color_range(),
+            },
+            Barrier::Image {
+                states: (Access::empty(), ImageLayout::Undefined)..(Access::TRANSFER_WRITE, ImageLayout::TransferDstOptimal),
+                target,
+                range: color_range(),
+            },
+        ],
+    );
+    let layers = SubresourceLayers {
+        aspects: Aspects::COLOR,
+        level: 0,
+        layers: 0..1,
+    };
+    let bounds = |kind| {
+        let extent = kind_extent(kind);
+        Offset { x: 0, y: 0, z: 0 }..Offset {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: 1,
+        }
+    };
+    cbuf.blit_image(
+        source,
+        ImageLayout::TransferSrcOptimal,
+        target,
+        ImageLayout::TransferDstOptimal,
+        Filter::Linear,
+        Some(ImageBlit {
+            src_subresource: layers.clone(),
+            src_bounds: bounds(source_kind),
+            dst_subresource: layers,
+            dst_bounds: bounds(target_kind),
+        }),
+    );
+    cbuf.pipeline_barrier(
+        PipelineStage::TRANSFER..PipelineStage::BOTTOM_OF_PIPE,
+        Some(Barrier::Image {
+            states: (Access::TRANSFER_WRITE, ImageLayout::TransferDstOptimal)..(Access::empty(), layout),
+            target,
+            range: color_range(),
+        }),
+    );
+}
+
+/// Get range of the single color subresource.
+fn color_range() -> SubresourceRange {
+    SubresourceRange {
+        aspects: Aspects::COLOR,
+        levels: 0..1,
+        layers: 0..1,
+    }
+}
@@ -0,0 +1,361 @@
+//! GPU profiler measuring nested scopes of commands with timestamp queries.
+//! Each frame gets its own query pool which is read back once the frame is complete,
+//! so reading results never waits for the device.
+//! Scopes are recorded with `gpu_scope!` or `Profiler::begin_scope` and `Profiler::end_scope`.
+//!
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use hal::Backend;
+use hal::pso::PipelineStage;
+
+use Error;
+use factory::Factory;
+use timestamp::{elapsed_nanos, nanos_to_duration, TimestampPool};
+
+/// Record commands of the block within profiler scope.
+///
+/// ```ignore
+/// gpu_scope!(profiler, cbuf, "shadows", {
+///     record_shadows(cbuf);
+/// });
+/// ```
+#[macro_export]
+macro_rules! gpu_scope {
+    ($profiler:expr, $cbuf:expr, $name:expr, $body:block) => {{
+        $profiler.begin_scope($cbuf, $name);
+        let result = $body;
+        $profiler.end_scope($cbuf);
+        result
+    }};
+}
+
+/// GPU timing of the scope and scopes nested in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeTiming {
+    /// Name of the scope.
+    pub name: String,
+
+    /// Time between commands before the scope and commands after it are complete.
+    /// `None` if timestamps weren't available.
+    pub duration: Option<Duration>,
+
+    /// Nested scopes in order they were recorded.
+    pub children: Vec<ScopeTiming>,
+}
+
+/// GPU timings of scopes of the frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameTimings {
+    /// Index of the frame.
+    pub frame: u64,
+
+    /// Top-level scopes in order they were recorded.
+    pub scopes: Vec<ScopeTiming>,
+}
+
+#[derive(Debug)]
+struct Scope {
+    name: String,
+    parent: Option<usize>,
+    closed: bool,
+}
+
+/// Scopes recorded in a frame. Scope at index `i` writes queries `2 * i` and `2 * i + 1`.
+#[derive(Debug)]
+struct ScopeTree {
+    capacity: u32,
+    scopes: Vec<Scope>,
+    // Indices of open scopes. `None` for scopes exceeding capacity.
+    stack: Vec<Option<usize>>,
+    dropped: usize,
+}
+
+impl ScopeTree {
+    fn new(capacity: u32) -> Self {
+        ScopeTree {
+            capacity,
+            scopes: Vec::new(),
+            stack: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Open scope nested in currently open one.
+    /// Returns query to write beginning timestamp into or `None` if capacity is exceeded.
+    fn open(&mut self, name: &str) -> Option<u32> {
+        if self.scopes.len() as u32 >= self.capacity {
+            self.dropped += 1;
+            // Keep stack balanced with `close`.
+            self.stack.push(None);
+            return None;
+        }
+        let index = self.scopes.len();
+        let parent = self.stack.last().cloned().and_then(|parent| parent);
+        self.scopes.push(Scope {
+            name: name.to_string(),
+            parent,
+            closed: false,
+        });
+        self.stack.push(Some(index));
+        Some(index as u32 * 2)
+    }
+
+    /// Close the scope opened last.
+    /// Returns query to write ending timestamp into or `None` if the scope isn't timed.
+    fn close(&mut self) -> Option<u32> {
+        match self.stack.pop() {
+            Some(Some(index)) => {
+                self.scopes[index].closed = true;
+                Some(index as u32 * 2 + 1)
+            }
+            Some(None) => None,
+            None => {
+                warn!("Profiler scope is closed without being opened");
+                None
+            }
+        }
+    }
+
+    /// Get number of queries written by the scopes.
+    fn queries(&self) -> u32 {
+        self.scopes.len() as u32 * 2
+    }
+
+    /// Build hierarchy of the scopes.
+    /// `results` are timestamps of the queries or `None` if they are not available.
+    fn timings(&self, results: Option<&[u64]>, period: f32) -> Vec<ScopeTiming> {
+        let mut timings = self.scopes
+            .iter()
+            .enumerate()
+            .map(|(index, scope)| {
+                let duration = match results {
+                    Some(results) if scope.closed => {
+                        let nanos = elapsed_nanos(results[index * 2], results[index * 2 + 1], period);
+                        Some(nanos_to_duration(nanos))
+                    }
+                    _ => None,
+                };
+                Some(ScopeTiming {
+                    name: scope.name.clone(),
+                    duration,
+                    children: Vec::new(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Children are always recorded after their parents, so attach them in reverse order.
+        let mut scopes = Vec::new();
+        for (index, scope) in self.scopes.iter().enumerate().rev() {
+            let timing = timings[index].take().expect("Each scope is attached once");
+            match scope.parent {
+                Some(parent) => timings[parent]
+                    .as_mut()
+                    .expect("Parent is attached after children")
+                    .children
+                    .insert(0, timing),
+                None => scopes.insert(0, timing),
+            }
+        }
+        scopes
+    }
+}
+
+#[derive(Debug)]
+struct ProfiledFrame<B: Backend> {
+    pool: TimestampPool<B>,
+    frame: u64,
+    scopes: ScopeTree,
+}
+
+/// Profiler of GPU scopes.
+/// Call `Profiler::begin_frame` before recording scopes of each frame.
+#[derive(Debug)]
+pub struct Profiler<B: Backend> {
+    period: f32,
+    capacity: u32,
+    current: Option<ProfiledFrame<B>>,
+    pending: VecDeque<ProfiledFrame<B>>,
+    free: Vec<TimestampPool<B>>,
+    latest: Option<FrameTimings>,
+}
+
+impl<B> Profiler<B>
+where
+    B: Backend,
+{
+    /// Create profiler.
+    /// Fails if timestamp queries are not supported.
+    ///
+    /// # Parameters
+    ///
+    /// `capacity`  - maximum number of scopes per frame. Scopes beyond it are not timed.
+    ///
+    pub fn new(factory: &Factory<B>, capacity: u32) -> Result<Self, Error> {
+        let period = factory
+            .timestamp_period()
+            .ok_or(Error::Unsupported("Timestamp queries"))?;
+        Ok(Profiler {
+            period,
+            capacity,
+            current: None,
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            latest: None,
+        })
+    }
+
+    /// Resolve timings of complete frames and start profiling the current frame.
+    /// Records reset of the frame's queries, so `cbuf` must be submitted before other command buffers of the frame
+    /// that record scopes.
+    ///
+    /// # Parameters
+    ///
+    /// `cbuf`      - command buffer in recording state outside of render pass.
+    ///
+    pub fn begin_frame(&mut self, factory: &mut Factory<B>, cbuf: &mut B::CommandBuffer) {
+        if let Some(frame) = self.current.take() {
+            if !frame.scopes.stack.is_empty() {
+                warn!("{} profiler scopes are left open in frame {}", frame.scopes.stack.len(), frame.frame);
+            }
+            if frame.scopes.dropped > 0 {
+                warn!("{} profiler scopes exceeded capacity of {}", frame.scopes.dropped, self.capacity);
+            }
+            self.pending.push_back(frame);
+        }
+
+        let current = factory.current();
+        let ongoing = factory.ongoing();
+        while self.pending.front().map_or(false, |frame| frame.frame < ongoing) {
+            let frame = self.pending.pop_front().unwrap();
+            self.latest = Some(self.resolve(&**factory, &frame));
+            self.free.push(frame.pool);
+        }
+
+        let pool = match self.free.pop() {
+            Some(pool) => Ok(pool),
+            None => TimestampPool::new(&**factory, self.capacity * 2),
+        };
+        match pool {
+            Ok(pool) => {
+                pool.reset(cbuf, 0..pool.count());
+                self.current = Some(ProfiledFrame {
+                    pool,
+                    frame: current,
+                    scopes: ScopeTree::new(self.capacity),
+                });
+            }
+            Err(err) => warn!("{}. Frame {} is not profiled", err, current),
+        }
+    }
+
+    /// Open scope nested in currently open one.
+    /// Scopes may span command buffers, but they must be submitted in order of recording.
+    pub fn begin_scope(&mut self, cbuf: &mut B::CommandBuffer, name: &str) {
+        if let Some(ref mut frame) = self.current {
+            if let Some(query) = frame.scopes.open(name) {
+                frame.pool.write(cbuf, PipelineStage::TOP_OF_PIPE, query);
+            }
+        }
+    }
+
+    /// Close the scope opened last.
+    pub fn end_scope(&mut self, cbuf: &mut B::CommandBuffer) {
+        if let Some(ref mut frame) = self.current {
+            if let Some(query) = frame.scopes.close() {
+                frame.pool.write(cbuf, PipelineStage::BOTTOM_OF_PIPE, query);
+            }
+        }
+    }
+
+    /// Get timings of the latest complete frame.
+    pub fn latest(&self) -> Option<&FrameTimings> {
+        self.latest.as_ref()
+    }
+
+    /// Destroy query pools. Commands recorded with the profiler must be complete.
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        let device: &B::Device = &**factory;
+        let frames = self.current.into_iter().chain(self.pending);
+        for pool in frames.map(|frame| frame.pool).chain(self.free) {
+            pool.dispose(device);
+        }
+    }
+
+    /// Read timestamps of the frame and build hierarchy of its scopes.
+    fn resolve(&self, device: &B::Device, frame: &ProfiledFrame<B>) -> FrameTimings {
+        let count = frame.scopes.queries();
+        let mut results = vec![0; count as usize];
+        let available = count > 0 && frame.pool.read(device, 0..count, &mut results);
+        let results = if available { Some(&results[..]) } else { None };
+        FrameTimings {
+            frame: frame.frame,
+            scopes: frame.scopes.timings(results, self.period),
+        }
+    }
+}
+
+#[test]
+fn nested_scopes_form_hierarchy() {
+    let mut tree = ScopeTree::new(8);
+    assert_eq!(tree.open("frame"), Some(0));
+    assert_eq!(tree.open("shadows"), Some(2));
+    assert_eq!(tree.close(), Some(3));
+    assert_eq!(tree.open("opaque"), Some(4));
+    assert_eq!(tree.open("terrain"), Some(6));
+    assert_eq!(tree.close(), Some(7));
+    assert_eq!(tree.close(), Some(5));
+    assert_eq!(tree.close(), Some(1));
+    assert_eq!(tree.open("ui"), Some(8));
+    assert_eq!(tree.close(), Some(9));
+
+    let results = [0, 100, 10, 20, 20, 90, 30, 50, 100, 110];
+    let timings = tree.timings(Some(&results), 1.0);
+    let names = |scopes: &[ScopeTiming]| scopes.iter().map(|scope| scope.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&timings), vec!["frame", "ui"]);
+    assert_eq!(names(&timings[0].children), vec!["shadows", "opaque"]);
+    assert_eq!(names(&timings[0].children[1].children), vec!["terrain"]);
+    assert!(timings[1].children.is_empty());
+    assert_eq!(timings[0].duration, Some(Duration::new(0, 100)));
+    assert_eq!(timings[0].children[1].duration, Some(Duration::new(0, 70)));
+    assert_eq!(timings[0].children[1].children[0].duration, Some(Duration::new(0, 20)));
+}
+
+#[test]
+fn scopes_beyond_capacity_are_not_timed() {
+    let mut tree = ScopeTree::new(2);
+    assert_eq!(tree.open("outer"), Some(0));
+    assert_eq!(tree.open("inner"), Some(2));
+    assert_eq!(tree.open("dropped"), None);
+    assert_eq!(tree.close(), None);
+    assert_eq!(tree.close(), Some(3));
+    assert_eq!(tree.open("dropped"), None);
+    assert_eq!(tree.close(), None);
+    assert_eq!(tree.close(), Some(1));
+    assert_eq!(tree.dropped, 2);
+    assert_eq!(tree.queries(), 4);
+    assert!(tree.stack.is_empty());
+
+    let timings = tree.timings(Some(&[0, 10, 2, 4]), 1.0);
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].children.len(), 1);
+}
+
+#[test]
+fn open_scopes_and_missing_results_have_no_duration() {
+    let mut tree = ScopeTree::new(4);
+    tree.open("closed");
+    tree.close();
+    tree.open("left open");
+    let timings = tree.timings(None, 1.0);
+    assert!(timings.iter().all(|scope| scope.duration.is_none()));
+    let timings = tree.timings(Some(&[0, 10, 20, 0]), 1.0);
+    assert_eq!(timings[0].duration, Some(Duration::new(0, 10)));
+    assert_eq!(timings[1].duration, None);
+
+    // Closing without opening is ignored.
+    let mut tree = ScopeTree::new(4);
+    assert_eq!(tree.close(), None);
+    assert_eq!(tree.queries(), 0);
+}
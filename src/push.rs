@@ -0,0 +1,151 @@
+//! `PushConstants` records `Pod` values as push constants.
+//! Offset and size of each write are checked against ranges the pipeline layout was created with
+//! and stage flags are derived from ranges the write overlaps.
+//! Offsets and ranges are in 32-bit words as `hal` expects.
+//!
+
+use std::mem::size_of;
+use std::ops::Range;
+use std::ptr::copy_nonoverlapping;
+
+use hal::Backend;
+use hal::command::RawCommandBuffer;
+use hal::pso::ShaderStageFlags;
+
+use Error;
+use typed::Pod;
+
+/// Push constant ranges of pipeline layout. Get it with `LayoutCache::push_constants`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PushConstants {
+    ranges: Vec<(ShaderStageFlags, Range<u32>)>,
+}
+
+impl PushConstants {
+    /// Create from ranges pipeline layout was created with.
+    pub fn new(ranges: &[(ShaderStageFlags, Range<u32>)]) -> Self {
+        PushConstants {
+            ranges: ranges.to_vec(),
+        }
+    }
+
+    /// Get ranges.
+    pub fn ranges(&self) -> &[(ShaderStageFlags, Range<u32>)] {
+        &self.ranges
+    }
+
+    /// Get stages that must be specified to write `words` push constants.
+    /// Fails if any word isn't declared for all of them.
+    pub fn stages(&self, words: Range<u32>) -> Result<ShaderStageFlags, Error> {
+        if words.start >= words.end {
+            return Err(Error::InvalidArgument("Push constants write is empty"));
+        }
+        let stages = self.ranges
+            .iter()
+            .filter(|&&(_, ref range)| range.start < words.end && words.start < range.end)
+            .fold(ShaderStageFlags::empty(), |stages, &(range_stages, _)| stages | range_stages);
+        for word in words.clone() {
+            let covered = self.ranges
+                .iter()
+                .filter(|&&(_, ref range)| range.start <= word && word < range.end)
+                .fold(ShaderStageFlags::empty(), |stages, &(range_stages, _)| stages | range_stages);
+            if !covered.contains(stages) || covered.is_empty() {
+                return Err(Error::InvalidArgument("Push constants write isn't covered by ranges of the layout"));
+            }
+        }
+        Ok(stages)
+    }
+
+    /// Record write of `data` into push constants of graphics pipeline starting from word `offset`.
+    ///
+    /// # Parameters
+    ///
+    /// `cbuf`      - command buffer in recording state.
+    /// `layout`    - pipeline layout created with ranges of these `PushConstants`.
+    /// `offset`    - first word to write.
+    /// `data`      - plain data which size is multiple of 4.
+    ///
+    pub fn push_graphics<B, T>(
+        &self,
+        cbuf: &mut B::CommandBuffer,
+        layout: &B::PipelineLayout,
+        offset: u32,
+        data: &T,
+    ) -> Result<(), Error>
+    where
+        B: Backend,
+        T: Pod,
+    {
+        let words = to_words(data)?;
+        let stages = self.stages(offset..offset + words.len() as u32)?;
+        cbuf.push_graphics_constants(layout, stages, offset, &words);
+        Ok(())
+    }
+
+    /// Record write of `data` into push constants of compute pipeline starting from word `offset`.
+    /// See `PushConstants::push_graphics`.
+    pub fn push_compute<B, T>(
+        &self,
+        cbuf: &mut B::CommandBuffer,
+        layout: &B::PipelineLayout,
+        offset: u32,
+        data: &T,
+    ) -> Result<(), Error>
+    where
+        B: Backend,
+        T: Pod,
+    {
+        let words = to_words(data)?;
+        let stages = self.stages(offset..offset + words.len() as u32)?;
+        if stages != ShaderStageFlags::COMPUTE {
+            return Err(Error::InvalidArgument("Push constants write is declared for stages other than compute"));
+        }
+        cbuf.push_compute_constants(layout, offset, &words);
+        Ok(())
+    }
+}
+
+/// Copy bytes of `data` into words.
+fn to_words<T: Pod>(data: &T) -> Result<Vec<u32>, Error> {
+    let size = size_of::<T>();
+    if size % 4 != 0 {
+        return Err(Error::InvalidArgument("Size of push constants is not multiple of 4"));
+    }
+    let mut words = vec![0u32; size / 4];
+    unsafe {
+        // Safe due to `words` are `size` bytes large and `Pod` types have no padding bytes.
+        copy_nonoverlapping(data as *const T as *const u8, words.as_mut_ptr() as *mut u8, size);
+    }
+    Ok(words)
+}
+
+#[test]
+fn overlapping_ranges_combine_stages() {
+    let push = PushConstants::new(&[
+        (ShaderStageFlags::VERTEX, 0..4),
+        (ShaderStageFlags::FRAGMENT, 2..6),
+    ]);
+    assert_eq!(push.stages(0..2).unwrap(), ShaderStageFlags::VERTEX);
+    assert_eq!(push.stages(2..4).unwrap(), ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT);
+    assert_eq!(push.stages(4..6).unwrap(), ShaderStageFlags::FRAGMENT);
+    // Word 0 isn't declared for fragment stage and word 5 isn't declared for vertex stage.
+    assert!(push.stages(0..3).is_err());
+    assert!(push.stages(3..6).is_err());
+}
+
+#[test]
+fn writes_outside_of_ranges_are_rejected() {
+    let push = PushConstants::new(&[(ShaderStageFlags::COMPUTE, 0..4)]);
+    assert!(push.stages(4..5).is_err());
+    assert!(push.stages(2..6).is_err());
+    assert!(push.stages(2..2).is_err());
+    assert_eq!(push.stages(0..4).unwrap(), ShaderStageFlags::COMPUTE);
+}
+
+#[test]
+fn data_must_be_whole_words() {
+    assert_eq!(to_words(&[1u16, 2, 3, 4]).unwrap().len(), 2);
+    assert_eq!(to_words(&1.0f32).unwrap(), vec![1.0f32.to_bits()]);
+    assert!(to_words(&[0u8; 3]).is_err());
+    assert!(to_words(&[0u16; 3]).is_err());
+}
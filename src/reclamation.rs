@@ -1,3 +1,9 @@
+use std::cmp::min;
+
+use hal::Backend;
+
+use frame::Frames;
+
 #[derive(Debug)]
 struct ReclamationNode<I> {
     items: Vec<I>,
@@ -33,18 +39,24 @@ impl<I> ReclamationQueue<I> {
         }
     }
 
-    pub fn push(&mut self, current: u64, item: I) {
+    pub fn push<B: Backend>(&mut self, frames: &Frames<B>, item: I) {
+        let current = frames.current();
         self.grow(current);
         self.queue[(current - self.offset) as usize]
             .items
             .push(item);
     }
 
-    pub fn clear<F>(&mut self, ongoing: u64, mut f: F)
+    pub fn clear<B: Backend, F>(&mut self, frames: &Frames<B>, mut f: F)
     where
         F: FnMut(I),
     {
-        for mut node in self.queue.drain(..(ongoing - self.offset) as usize) {
+        let ongoing = frames.ongoing();
+        if ongoing <= self.offset {
+            return;
+        }
+        let count = min((ongoing - self.offset) as usize, self.queue.len());
+        for mut node in self.queue.drain(..count) {
             for item in node.items.drain(..) {
                 f(item);
             }
@@ -52,4 +64,47 @@ impl<I> ReclamationQueue<I> {
         }
         self.offset = ongoing;
     }
+
+    /// Pass all items to `f` regardless of frames they were pushed in.
+    pub fn drain_all<F>(&mut self, mut f: F)
+    where
+        F: FnMut(I),
+    {
+        for mut node in self.queue.drain(..) {
+            for item in node.items.drain(..) {
+                f(item);
+            }
+            self.cache.push(node);
+        }
+    }
+}
+
+#[test]
+fn reclaims_items_of_complete_frames() {
+    use testing::{assert_released, FrameDriver, TestBackend};
+
+    let mut driver = FrameDriver::<TestBackend>::new();
+    let mut queue = ReclamationQueue::new();
+    let mut released = Vec::new();
+
+    queue.push(driver.frames(), 0);
+    driver.submit();
+    queue.push(driver.frames(), 1);
+    queue.push(driver.frames(), 2);
+    driver.submit();
+    queue.push(driver.frames(), 3);
+
+    queue.clear(driver.frames(), |item| released.push(item));
+    assert_released(&released, &[]);
+
+    driver.complete(0);
+    queue.clear(driver.frames(), |item| released.push(item));
+    assert_released(&released, &[0]);
+
+    driver.complete(1);
+    queue.clear(driver.frames(), |item| released.push(item));
+    assert_released(&released, &[0, 1, 2]);
+
+    queue.drain_all(|item| released.push(item));
+    assert_released(&released, &[0, 1, 2, 3]);
 }
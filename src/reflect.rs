@@ -0,0 +1,616 @@
+//! Minimal SPIR-V reflection deriving descriptor set layouts, push constant ranges
+//! and vertex inputs from shader modules.
+//! 
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use hal::Backend;
+use hal::format::Format;
+use hal::pso::{DescriptorSetLayoutBinding, DescriptorType, ShaderStageFlags};
+
+use Error;
+use layout::{LayoutCache, PipelineLayoutId};
+use mesh::VertexFormat;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// Type of vertex input components.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InputType {
+    /// Floating point components.
+    Float,
+
+    /// Signed integer components.
+    Int,
+
+    /// Unsigned integer components.
+    Uint,
+}
+
+/// Vertex shader input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VertexInput {
+    /// Location of the input.
+    pub location: u32,
+
+    /// Type of components.
+    pub ty: InputType,
+
+    /// Number of components.
+    pub components: u32,
+}
+
+/// Resources used by single shader module.
+#[derive(Clone, Debug)]
+pub struct ShaderReflection {
+    /// Stages of entry points in the module.
+    pub stages: ShaderStageFlags,
+
+    /// Descriptor bindings with their set indices.
+    pub bindings: Vec<(usize, DescriptorSetLayoutBinding)>,
+
+    /// Size of push constant block if any.
+    pub push_constants: Option<u32>,
+
+    /// Vertex inputs. Empty unless module has vertex entry point.
+    pub inputs: Vec<VertexInput>,
+}
+
+/// Resources used by all shaders of a pipeline.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineReflection {
+    /// Bindings of each descriptor set.
+    pub sets: Vec<Vec<DescriptorSetLayoutBinding>>,
+
+    /// Push constant ranges.
+    pub push_constants: Vec<(ShaderStageFlags, Range<u32>)>,
+
+    /// Vertex inputs.
+    pub inputs: Vec<VertexInput>,
+}
+
+#[derive(Clone, Debug)]
+enum Type {
+    Scalar(InputType, u32),
+    Vector(u32, u32),
+    Matrix(u32, u32),
+    Image(u32, u32),
+    Sampler,
+    SampledImage,
+    Array(u32, u32),
+    RuntimeArray(u32),
+    Struct(Vec<u32>),
+    Pointer(u32, u32),
+}
+
+#[derive(Default)]
+struct Decorations {
+    set: Option<u32>,
+    binding: Option<u32>,
+    location: Option<u32>,
+    block: bool,
+    buffer_block: bool,
+    built_in: bool,
+}
+
+/// Reflect SPIR-V module.
+pub fn reflect_shader(code: &[u8]) -> Result<ShaderReflection, Error> {
+    if code.len() < 20 || code.len() % 4 != 0 {
+        return Err(Error::InvalidSpirv("size is not multiple of 4 or too small for header"));
+    }
+    let words = code.chunks(4)
+        .map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+        .collect::<Vec<_>>();
+    if words[0] != SPIRV_MAGIC {
+        return Err(Error::InvalidSpirv("magic number doesn't match"));
+    }
+
+    let mut stages = ShaderStageFlags::empty();
+    let mut types = HashMap::new();
+    let mut constants = HashMap::new();
+    let mut decorations: HashMap<u32, Decorations> = HashMap::new();
+    let mut member_offsets: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut matrix_strides: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut array_strides: HashMap<u32, u32> = HashMap::new();
+    let mut variables = Vec::new();
+
+    let mut index = 5;
+    while index < words.len() {
+        let count = (words[index] >> 16) as usize;
+        let opcode = words[index] & 0xffff;
+        if count == 0 || index + count > words.len() {
+            return Err(Error::InvalidSpirv("malformed instruction"));
+        }
+        let ops = &words[index + 1..index + count];
+        match opcode {
+            OP_ENTRY_POINT => {
+                stages |= match ops[0] {
+                    0 => ShaderStageFlags::VERTEX,
+                    1 => ShaderStageFlags::HULL,
+                    2 => ShaderStageFlags::DOMAIN,
+                    3 => ShaderStageFlags::GEOMETRY,
+                    4 => ShaderStageFlags::FRAGMENT,
+                    5 => ShaderStageFlags::COMPUTE,
+                    _ => ShaderStageFlags::empty(),
+                };
+            }
+            OP_TYPE_INT => {
+                let ty = if ops[2] == 0 { InputType::Uint } else { InputType::Int };
+                types.insert(ops[0], Type::Scalar(ty, ops[1] / 8));
+            }
+            OP_TYPE_FLOAT => {
+                types.insert(ops[0], Type::Scalar(InputType::Float, ops[1] / 8));
+            }
+            OP_TYPE_VECTOR => {
+                types.insert(ops[0], Type::Vector(ops[1], ops[2]));
+            }
+            OP_TYPE_MATRIX => {
+                types.insert(ops[0], Type::Matrix(ops[1], ops[2]));
+            }
+            OP_TYPE_IMAGE => {
+                types.insert(ops[0], Type::Image(ops[2], ops[6]));
+            }
+            OP_TYPE_SAMPLER => {
+                types.insert(ops[0], Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                types.insert(ops[0], Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                types.insert(ops[0], Type::Array(ops[1], ops[2]));
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                types.insert(ops[0], Type::RuntimeArray(ops[1]));
+            }
+            OP_TYPE_STRUCT => {
+                types.insert(ops[0], Type::Struct(ops[1..].to_vec()));
+            }
+            OP_TYPE_POINTER => {
+                types.insert(ops[0], Type::Pointer(ops[1], ops[2]));
+            }
+            OP_CONSTANT => {
+                constants.insert(ops[1], ops[2]);
+            }
+            OP_VARIABLE => {
+                variables.push((ops[0], ops[1], ops[2]));
+            }
+            OP_DECORATE => {
+                let entry = decorations.entry(ops[0]).or_insert_with(Decorations::default);
+                match ops[1] {
+                    DECORATION_BLOCK => entry.block = true,
+                    DECORATION_BUFFER_BLOCK => entry.buffer_block = true,
+                    DECORATION_BUILT_IN => entry.built_in = true,
+                    DECORATION_LOCATION => entry.location = Some(ops[2]),
+                    DECORATION_BINDING => entry.binding = Some(ops[2]),
+                    DECORATION_DESCRIPTOR_SET => entry.set = Some(ops[2]),
+                    DECORATION_ARRAY_STRIDE => {
+                        array_strides.insert(ops[0], ops[2]);
+                    }
+                    _ => {}
+                }
+            }
+            OP_MEMBER_DECORATE => match ops[2] {
+                DECORATION_OFFSET => {
+                    member_offsets.insert((ops[0], ops[1]), ops[3]);
+                }
+                DECORATION_MATRIX_STRIDE => {
+                    matrix_strides.insert((ops[0], ops[1]), ops[3]);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        index += count;
+    }
+
+    let reflector = Reflector {
+        types: &types,
+        constants: &constants,
+        member_offsets: &member_offsets,
+        matrix_strides: &matrix_strides,
+        array_strides: &array_strides,
+    };
+    let no_decorations = Decorations::default();
+
+    let mut bindings = Vec::new();
+    let mut push_constants = None;
+    let mut inputs = Vec::new();
+    for &(pointer, id, storage) in &variables {
+        let decoration = decorations.get(&id).unwrap_or(&no_decorations);
+        let pointee = match types.get(&pointer) {
+            Some(&Type::Pointer(_, pointee)) => pointee,
+            _ => return Err(Error::InvalidSpirv("variable is not a pointer")),
+        };
+        match storage {
+            STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                let (set, binding) = match (decoration.set, decoration.binding) {
+                    (Some(set), Some(binding)) => (set, binding),
+                    _ => continue,
+                };
+                let (element, count) = reflector.unwrap_array(pointee);
+                let block = decorations.get(&element).unwrap_or(&no_decorations);
+                let ty = match (storage, types.get(&element)) {
+                    (STORAGE_STORAGE_BUFFER, _) => DescriptorType::StorageBuffer,
+                    (STORAGE_UNIFORM, _) if block.buffer_block => DescriptorType::StorageBuffer,
+                    (STORAGE_UNIFORM, _) => DescriptorType::UniformBuffer,
+                    (_, Some(&Type::SampledImage)) => DescriptorType::CombinedImageSampler,
+                    (_, Some(&Type::Sampler)) => DescriptorType::Sampler,
+                    (_, Some(&Type::Image(DIM_BUFFER, 2))) => DescriptorType::StorageTexelBuffer,
+                    (_, Some(&Type::Image(DIM_BUFFER, _))) => DescriptorType::UniformTexelBuffer,
+                    (_, Some(&Type::Image(DIM_SUBPASS_DATA, _))) => DescriptorType::InputAttachment,
+                    (_, Some(&Type::Image(_, 2))) => DescriptorType::StorageImage,
+                    (_, Some(&Type::Image(_, _))) => DescriptorType::SampledImage,
+                    _ => return Err(Error::InvalidSpirv("unsupported type of descriptor")),
+                };
+                bindings.push((
+                    set as usize,
+                    DescriptorSetLayoutBinding {
+                        binding: binding as usize,
+                        ty,
+                        count: count as usize,
+                        stage_flags: stages,
+                    },
+                ));
+            }
+            STORAGE_PUSH_CONSTANT => {
+                push_constants = Some(reflector.size(pointee)?);
+            }
+            STORAGE_INPUT if stages.contains(ShaderStageFlags::VERTEX) && !decoration.built_in => {
+                let location = match decoration.location {
+                    Some(location) => location,
+                    None => continue,
+                };
+                let (ty, components) = match types.get(&pointee) {
+                    Some(&Type::Scalar(ty, _)) => (ty, 1),
+                    Some(&Type::Vector(component, count)) => match types.get(&component) {
+                        Some(&Type::Scalar(ty, _)) => (ty, count),
+                        _ => return Err(Error::InvalidSpirv("unsupported type of vertex input")),
+                    },
+                    _ => return Err(Error::InvalidSpirv("unsupported type of vertex input")),
+                };
+                inputs.push(VertexInput {
+                    location,
+                    ty,
+                    components,
+                });
+            }
+            _ => {}
+        }
+    }
+    inputs.sort_by_key(|input| input.location);
+
+    Ok(ShaderReflection {
+        stages,
+        bindings,
+        push_constants,
+        inputs,
+    })
+}
+
+/// Merge reflections of all shaders of a pipeline.
+/// Bindings used by several stages are merged with union of stage flags.
+pub fn reflect_pipeline(shaders: &[ShaderReflection]) -> Result<PipelineReflection, Error> {
+    let mut pipeline = PipelineReflection::default();
+    for shader in shaders {
+        for &(set, ref binding) in &shader.bindings {
+            while pipeline.sets.len() <= set {
+                pipeline.sets.push(Vec::new());
+            }
+            let bindings = &mut pipeline.sets[set];
+            match bindings.iter_mut().find(|b| b.binding == binding.binding) {
+                Some(existing) => {
+                    if existing.ty != binding.ty || existing.count != binding.count {
+                        return Err(Error::BindingMismatch {
+                            set,
+                            binding: binding.binding,
+                        });
+                    }
+                    existing.stage_flags |= binding.stage_flags;
+                }
+                None => bindings.push(binding.clone()),
+            }
+        }
+        if let Some(size) = shader.push_constants {
+            pipeline.push_constants.push((shader.stages, 0..size));
+        }
+        if shader.stages.contains(ShaderStageFlags::VERTEX) {
+            pipeline.inputs = shader.inputs.clone();
+        }
+    }
+    Ok(pipeline)
+}
+
+impl PipelineReflection {
+    /// Get or create pipeline layout matching reflected sets and push constants.
+    pub fn layout<B>(&self, cache: &mut LayoutCache<B>, device: &B::Device) -> PipelineLayoutId
+    where
+        B: Backend,
+    {
+        let sets = self.sets
+            .iter()
+            .map(|bindings| cache.set_layout(device, bindings))
+            .collect::<Vec<_>>();
+        cache.pipeline_layout(device, &sets, &self.push_constants)
+    }
+
+    /// Check that vertex buffers bound with `formats` provide all inputs of the vertex shader.
+    /// Attribute locations are assigned sequentially across formats as `GraphicsPipelineBuilder` does.
+    /// Attributes must fit into the stride of their format and have components of the input type.
+    /// Components of formats `input_type` doesn't know aren't checked.
+    pub fn validate_vertex_formats(&self, formats: &[&VertexFormat]) -> Result<(), Error> {
+        let mut attributes = Vec::new();
+        for format in formats {
+            for attribute in &format.attributes {
+                let size = attribute.format.surface_desc().bits as u32 / 8;
+                if attribute.offset + size > format.stride {
+                    return Err(Error::InvalidArgument("Vertex attribute doesn't fit into stride of its format"));
+                }
+                attributes.push(attribute.format);
+            }
+        }
+        for input in &self.inputs {
+            let format = match attributes.get(input.location as usize) {
+                Some(&format) => format,
+                None => {
+                    return Err(Error::VertexInputMismatch {
+                        location: input.location,
+                    })
+                }
+            };
+            match input_type(format) {
+                Some(ty) if ty != input.ty => {
+                    return Err(Error::VertexInputMismatch {
+                        location: input.location,
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get type of components vertex shader reads from attribute of the `format`.
+/// Normalized and scaled formats are read as floats.
+fn input_type(format: Format) -> Option<InputType> {
+    use hal::format::ChannelType;
+
+    match format.base_format().1 {
+        ChannelType::Float | ChannelType::Ufloat | ChannelType::Unorm | ChannelType::Inorm
+        | ChannelType::Srgb | ChannelType::Uscaled | ChannelType::Iscaled => Some(InputType::Float),
+        ChannelType::Int => Some(InputType::Int),
+        ChannelType::Uint => Some(InputType::Uint),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+struct Reflector<'a> {
+    types: &'a HashMap<u32, Type>,
+    constants: &'a HashMap<u32, u32>,
+    member_offsets: &'a HashMap<(u32, u32), u32>,
+    matrix_strides: &'a HashMap<(u32, u32), u32>,
+    array_strides: &'a HashMap<u32, u32>,
+}
+
+impl<'a> Reflector<'a> {
+    /// Get element type and number of descriptors.
+    fn unwrap_array(&self, ty: u32) -> (u32, u32) {
+        match self.types.get(&ty) {
+            Some(&Type::Array(element, length)) => {
+                let (element, count) = self.unwrap_array(element);
+                (element, count * self.constants.get(&length).cloned().unwrap_or(1))
+            }
+            _ => (ty, 1),
+        }
+    }
+
+    /// Get size of the type laid out with explicit offsets.
+    fn size(&self, ty: u32) -> Result<u32, Error> {
+        Ok(match self.types.get(&ty) {
+            Some(&Type::Scalar(_, size)) => size,
+            Some(&Type::Vector(component, count)) => self.size(component)? * count,
+            Some(&Type::Matrix(column, count)) => self.size(column)? * count,
+            Some(&Type::Array(element, length)) => {
+                let stride = match self.array_strides.get(&ty) {
+                    Some(&stride) => stride,
+                    None => self.size(element)?,
+                };
+                stride * self.constants.get(&length).cloned().unwrap_or(1)
+            }
+            Some(&Type::RuntimeArray(_)) => 0,
+            Some(&Type::Struct(ref members)) => {
+                let mut end = 0;
+                for (index, &member) in members.iter().enumerate() {
+                    let offset = self.member_offsets
+                        .get(&(ty, index as u32))
+                        .cloned()
+                        .unwrap_or(end);
+                    let size = match (self.types.get(&member), self.matrix_strides.get(&(ty, index as u32))) {
+                        (Some(&Type::Matrix(_, columns)), Some(&stride)) => stride * columns,
+                        _ => self.size(member)?,
+                    };
+                    end = end.max(offset + size);
+                }
+                end
+            }
+            _ => return Err(Error::InvalidSpirv("unsupported type in block")),
+        })
+    }
+}
+
+#[test]
+fn fixture_vertex_shader_is_reflected() {
+    // Words of the module are assembled by hand. Instruction is opcode followed by its operands.
+    let module = |instructions: &[&[u32]]| {
+        let mut words = vec![SPIRV_MAGIC, 0x0001_0000, 0, 64, 0];
+        for instruction in instructions {
+            words.push((instruction.len() as u32) << 16 | instruction[0]);
+            words.extend_from_slice(&instruction[1..]);
+        }
+        words
+            .iter()
+            .flat_map(|&word| vec![word as u8, (word >> 8) as u8, (word >> 16) as u8, (word >> 24) as u8])
+            .collect::<Vec<u8>>()
+    };
+    let code = module(&[
+        &[OP_ENTRY_POINT, 0, 63],
+        // float, vec2, vec3, vec4, uint and constants 3 and 4.
+        &[OP_TYPE_FLOAT, 1, 32],
+        &[OP_TYPE_VECTOR, 2, 1, 2],
+        &[OP_TYPE_VECTOR, 3, 1, 3],
+        &[OP_TYPE_VECTOR, 4, 1, 4],
+        &[OP_TYPE_INT, 5, 32, 0],
+        &[OP_CONSTANT, 5, 6, 3],
+        &[OP_CONSTANT, 5, 7, 4],
+        // Inputs at locations 1 and 0 and built-in input.
+        &[OP_TYPE_POINTER, 10, STORAGE_INPUT, 2],
+        &[OP_TYPE_POINTER, 11, STORAGE_INPUT, 3],
+        &[OP_TYPE_POINTER, 12, STORAGE_INPUT, 4],
+        &[OP_VARIABLE, 10, 13, STORAGE_INPUT],
+        &[OP_VARIABLE, 11, 14, STORAGE_INPUT],
+        &[OP_VARIABLE, 12, 15, STORAGE_INPUT],
+        &[OP_DECORATE, 13, DECORATION_LOCATION, 1],
+        &[OP_DECORATE, 14, DECORATION_LOCATION, 0],
+        &[OP_DECORATE, 15, DECORATION_BUILT_IN, 0],
+        // Uniform block at set 0 binding 1.
+        &[OP_TYPE_STRUCT, 20, 4],
+        &[OP_DECORATE, 20, DECORATION_BLOCK],
+        &[OP_TYPE_POINTER, 21, STORAGE_UNIFORM, 20],
+        &[OP_VARIABLE, 21, 22, STORAGE_UNIFORM],
+        &[OP_DECORATE, 22, DECORATION_DESCRIPTOR_SET, 0],
+        &[OP_DECORATE, 22, DECORATION_BINDING, 1],
+        // Array of 4 combined image samplers at set 1 binding 0.
+        &[OP_TYPE_IMAGE, 30, 1, 1, 0, 0, 0, 1, 0],
+        &[OP_TYPE_SAMPLED_IMAGE, 31, 30],
+        &[OP_TYPE_ARRAY, 32, 31, 7],
+        &[OP_TYPE_POINTER, 33, STORAGE_UNIFORM_CONSTANT, 32],
+        &[OP_VARIABLE, 33, 34, STORAGE_UNIFORM_CONSTANT],
+        &[OP_DECORATE, 34, DECORATION_DESCRIPTOR_SET, 1],
+        &[OP_DECORATE, 34, DECORATION_BINDING, 0],
+        // Push constants `{ mat4 transform; float weights[3]; }` with std140 array stride.
+        &[OP_TYPE_MATRIX, 40, 4, 4],
+        &[OP_TYPE_ARRAY, 41, 1, 6],
+        &[OP_DECORATE, 41, DECORATION_ARRAY_STRIDE, 16],
+        &[OP_TYPE_STRUCT, 42, 40, 41],
+        &[OP_MEMBER_DECORATE, 42, 0, DECORATION_OFFSET, 0],
+        &[OP_MEMBER_DECORATE, 42, 0, DECORATION_MATRIX_STRIDE, 16],
+        &[OP_MEMBER_DECORATE, 42, 1, DECORATION_OFFSET, 64],
+        &[OP_TYPE_POINTER, 43, STORAGE_PUSH_CONSTANT, 42],
+        &[OP_VARIABLE, 43, 44, STORAGE_PUSH_CONSTANT],
+    ]);
+
+    let reflection = reflect_shader(&code).unwrap();
+    assert_eq!(reflection.stages, ShaderStageFlags::VERTEX);
+    assert_eq!(
+        reflection.inputs,
+        vec![
+            VertexInput {
+                location: 0,
+                ty: InputType::Float,
+                components: 3,
+            },
+            VertexInput {
+                location: 1,
+                ty: InputType::Float,
+                components: 2,
+            },
+        ]
+    );
+    let bindings = reflection
+        .bindings
+        .iter()
+        .map(|&(set, ref binding)| (set, binding.binding, binding.ty, binding.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        bindings,
+        vec![
+            (0, 1, DescriptorType::UniformBuffer, 1),
+            (1, 0, DescriptorType::CombinedImageSampler, 4),
+        ]
+    );
+    // Array of 3 floats takes 48 bytes with stride 16.
+    assert_eq!(reflection.push_constants, Some(112));
+
+    assert!(reflect_shader(&code[..16]).is_err());
+    let mut corrupted = code.clone();
+    corrupted[0] = 0;
+    assert!(reflect_shader(&corrupted).is_err());
+}
+
+#[test]
+fn vertex_formats_are_checked_against_inputs() {
+    use hal::pso::Element;
+
+    let reflection = PipelineReflection {
+        inputs: vec![
+            VertexInput {
+                location: 0,
+                ty: InputType::Float,
+                components: 3,
+            },
+            VertexInput {
+                location: 1,
+                ty: InputType::Uint,
+                components: 1,
+            },
+        ],
+        ..PipelineReflection::default()
+    };
+    let format = |stride, attributes: &[(Format, u32)]| VertexFormat {
+        stride,
+        attributes: attributes
+            .iter()
+            .map(|&(format, offset)| Element { format, offset })
+            .collect(),
+    };
+    let position = format(12, &[(Format::Rgb32Float, 0)]);
+    let index = format(4, &[(Format::R32Uint, 0)]);
+    assert!(reflection.validate_vertex_formats(&[&position, &index]).is_ok());
+
+    // Location 1 isn't provided.
+    assert!(reflection.validate_vertex_formats(&[&position]).is_err());
+    // Location 1 is provided with float components.
+    let weight = format(4, &[(Format::R32Float, 0)]);
+    assert!(reflection.validate_vertex_formats(&[&position, &weight]).is_err());
+    // Attribute doesn't fit into the stride.
+    let short = format(8, &[(Format::Rgb32Float, 0)]);
+    assert!(reflection.validate_vertex_formats(&[&short, &index]).is_err());
+}
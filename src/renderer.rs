@@ -1,25 +1,53 @@
 
 use std::collections::{HashMap, VecDeque};
 use std::marker::PhantomData;
+use std::mem::replace;
 
-use hal::{Backend, Device as HalDevice};
-use hal::command::{Rect, Viewport};
-use hal::image::Kind;
-use hal::pool::{CommandPool, CommandPoolCreateFlags};
-use hal::queue::{General, QueueGroup, CommandQueue, RawCommandQueue, RawSubmission, Supports};
+use std::time::{Duration, Instant};
+
+use hal::{Backend, Device as HalDevice, MemoryTypeId};
+use hal::command::{BufferImageCopy, CommandBufferFlags, RawCommandBuffer, RawLevel, Rect, Viewport};
+use hal::format::{Aspects, Format};
+use hal::image::{Access, ImageLayout, Kind, NumSamples, Offset, SubresourceLayers, SubresourceRange,
+                 Usage as ImageUsage};
+use hal::memory::{Barrier, Properties};
+use hal::pool::{CommandPool, CommandPoolCreateFlags, RawCommandPool};
+use hal::pso::PipelineStage;
+use hal::queue::{Compute, General, QueueFamilyId, QueueGroup, CommandQueue, RawCommandQueue, RawSubmission, Supports,
+                 Transfer};
 use hal::window::{Backbuffer, FrameSync, Surface, Swapchain, SwapchainConfig, Frame as SurfaceFrame};
 
+use mem::Block;
+
 #[cfg(feature = "gfx-backend-metal")]
 use metal;
 
 use Error;
+use attachment::Attachments;
+#[cfg(feature = "renderdoc")]
+use capture::FrameCapture;
+use cpu_stats::{measure, CpuFrameStats, CpuFrameTimes};
+use external::ExternalSubmits;
 use factory::Factory;
+use frame::FrameGuard;
+use instrument;
+use lost::host_execution_error;
+use pacing::FramePacer;
+use stuck::{StuckTarget, StuckWork};
+use submit::{QueueRole, ROLES, Submitter};
+use telemetry;
+use texture::{kind_extent, kind_layers};
 
 pub trait Render<B: Backend, T> {
-    fn render<C>(&mut self, &mut CommandQueue<B, C>, &mut CommandPool<B, C>, &Backbuffer<B>, SurfaceFrame,
-    &B::Semaphore, &B::Semaphore, Viewport, &B::Fence, &mut Factory<B>, data: &mut T)
+    fn render<C>(&mut self, &mut CommandQueue<B, C>, &mut CommandPool<B, C>, &Backbuffer<B>, &Attachments<B>,
+    SurfaceFrame, &B::Semaphore, &B::Semaphore, Viewport, &B::Fence, &mut Factory<B>, data: &mut T)
     where
         C: Supports<General>;
+
+    /// Called after swapchain of the target was recreated with new `backbuffer`
+    /// and managed `attachments` were recreated to match it.
+    /// Views and framebuffers created for old backbuffer and attachments must be recreated.
+    fn resize(&mut self, _backbuffer: &Backbuffer<B>, _attachments: &Attachments<B>, _kind: Kind, _factory: &mut Factory<B>) {}
 }
 
 
@@ -31,6 +59,12 @@ pub struct Renderer<B: Backend, R> {
     queues_usage: Vec<usize>,
     targets: HashMap<TargetId, Target<B, R>>,
     resources: Resources<B>,
+    pacer: FramePacer,
+    cpu_stats: CpuFrameStats,
+    frames_in_flight: Option<u64>,
+    external: ExternalSubmits<B>,
+    #[cfg(feature = "renderdoc")]
+    capture: FrameCapture,
     counter: u64,
 }
 
@@ -45,6 +79,149 @@ where
         config: SwapchainConfig,
         device: &B::Device,
     ) -> TargetId {
+        let kind = surface.kind();
+        let (swapchain, backbuffer) = device.create_swapchain(&mut surface, config.clone());
+        let output = Output::Surface {
+            surface,
+            config,
+            pending_surface: None,
+            swapchain,
+        };
+        self.insert_target(output, kind, backbuffer)
+    }
+
+    /// Creates new render target backed by device images instead of surface.
+    /// Frames are rendered into `images` color images used round-robin.
+    /// Images are created with `COLOR_ATTACHMENT` and `TRANSFER_SRC` usage
+    /// and are passed to renders as `Backbuffer::Images`.
+    pub fn add_headless_target(
+        &mut self,
+        kind: Kind,
+        format: Format,
+        images: usize,
+        factory: &mut Factory<B>,
+    ) -> Result<TargetId, Error> {
+        assert!(images > 0);
+        let device: &B::Device = &**factory;
+        let mut unbound = Vec::with_capacity(images);
+        for _ in 0..images {
+            let image = device
+                .create_image(kind, 1, format, ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC)
+                .map_err(|err| Error::creation("headless target image", err))?;
+            let requirements = device.get_image_requirements(&image);
+            unbound.push((image, requirements));
+        }
+
+        let type_mask = unbound
+            .iter()
+            .fold(!0, |mask, &(_, ref requirements)| mask & requirements.type_mask);
+        let memory_type = factory
+            .memory_properties()
+            .memory_types
+            .iter()
+            .enumerate()
+            .find(|&(index, ty)| {
+                type_mask & (1 << index) != 0 && ty.properties.contains(Properties::DEVICE_LOCAL)
+            })
+            .map(|(index, _)| MemoryTypeId(index))
+            .ok_or(Error::NoMemoryType(Properties::DEVICE_LOCAL))?;
+
+        let mut offsets = Vec::with_capacity(images);
+        let mut size = 0;
+        for &(_, ref requirements) in &unbound {
+            let offset = (size + requirements.alignment - 1) / requirements.alignment * requirements.alignment;
+            offsets.push(offset);
+            size = offset + requirements.size;
+        }
+        let memory = device
+            .allocate_memory(memory_type, size)
+            .map_err(|_| Error::OutOfMemory { what: "headless target" })?;
+
+        let mut bound = Vec::with_capacity(images);
+        for ((image, _), offset) in unbound.into_iter().zip(offsets) {
+            bound.push(
+                device
+                    .bind_image_memory(&memory, offset, image)
+                    .map_err(|err| Error::creation("headless target image", err))?,
+            );
+        }
+
+        let output = Output::Headless {
+            format,
+            memory,
+            images,
+            next: 0,
+        };
+        Ok(self.insert_target(output, kind, Backbuffer::Images(bound)))
+    }
+
+    /// Creates new render target backed by images owned by external swapchain, like swapchain of OpenXR session.
+    /// Renderer doesn't acquire or present them. Image to render into is picked with `Renderer::set_external_image`
+    /// for each frame and the frame is skipped if none is picked.
+    /// Images are passed to renders as `Backbuffer::Images`. Renders must leave them in `layout`,
+    /// which is the layout external swapchain hands them in. See `Renderer::external_layout`.
+    /// Images are dropped without being destroyed when target is removed.
+    pub fn add_external_target(
+        &mut self,
+        images: Vec<B::Image>,
+        kind: Kind,
+        format: Format,
+        layout: ImageLayout,
+    ) -> TargetId {
+        assert!(!images.is_empty());
+        let output = Output::External {
+            format,
+            layout,
+            images: images.len(),
+            index: None,
+        };
+        self.insert_target(output, kind, Backbuffer::Images(images))
+    }
+
+    /// Pick image of external target to render the next frame into,
+    /// e.g. the one acquired from OpenXR swapchain. Image must be ready for rendering when the frame is submitted.
+    pub fn set_external_image(&mut self, id: TargetId, index: usize) -> Result<(), Error> {
+        let ref mut target = *self.targets
+            .get_mut(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        match target.output {
+            Output::External {
+                images,
+                index: ref mut next,
+                ..
+            } => {
+                if index >= images {
+                    return Err(Error::IndexOutOfRange {
+                        what: "external image",
+                        index,
+                        len: images,
+                    });
+                }
+                *next = Some(index);
+                Ok(())
+            }
+            _ => Err(Error::WrongTargetKind {
+                target: id,
+                expected: "external",
+            }),
+        }
+    }
+
+    /// Get layout images of external target are handed in and must be left in by renders.
+    pub fn external_layout(&self, id: TargetId) -> Result<ImageLayout, Error> {
+        let ref target = *self.targets
+            .get(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        match target.output {
+            Output::External { layout, .. } => Ok(layout),
+            _ => Err(Error::WrongTargetKind {
+                target: id,
+                expected: "external",
+            }),
+        }
+    }
+
+    fn insert_target(&mut self, output: Output<B>, kind: Kind, backbuffer: Backbuffer<B>) -> TargetId {
         self.counter += 1;
         let id = TargetId(self.counter);
         debug_assert!(self.targets.get(&id).is_none());
@@ -56,12 +233,13 @@ where
             .map(|(i, _)| i)
             .expect("There are some queues");
         self.queues_usage[queue] += 1;
-        let (swapchain, backbuffer) = device.create_swapchain(&mut surface, config);
         let target = Target {
             queue,
-            surface,
-            swapchain,
+            output,
+            kind,
             backbuffer,
+            attachments: Attachments::new(),
+            resized: false,
             active: None,
             renders: Vec::new(),
             frames: VecDeque::new(),
@@ -71,12 +249,217 @@ where
         id
     }
 
-    /// Remove render
-    pub fn remove_target(&mut self, _id: TargetId) {
-        unimplemented!()
+    /// Remove render target.
+    /// Waits for frames of the target to complete and destroys its swapchain or images.
+    /// Returns surface (if target isn't headless) and renders of the target.
+    pub fn remove_target(
+        &mut self,
+        id: TargetId,
+        factory: &mut Factory<B>,
+    ) -> Result<(Option<B::Surface>, Vec<R>), Error> {
+        let mut target = self.targets
+            .remove(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        if let Err(err) = target.reclaim_jobs(factory, &mut self.resources) {
+            self.targets.insert(id, target);
+            return Err(err);
+        }
+        self.queues_usage[target.queue] -= 1;
+        let Target {
+            output,
+            backbuffer,
+            attachments,
+            renders,
+            ..
+        } = target;
+        attachments.dispose(factory);
+        match output {
+            Output::Surface {
+                surface, swapchain, ..
+            } => {
+                factory.destroy_swapchain(swapchain);
+                Ok((Some(surface), renders))
+            }
+            Output::Headless { memory, .. } => {
+                if let Backbuffer::Images(images) = backbuffer {
+                    for image in images {
+                        factory.destroy_raw_image(image);
+                    }
+                }
+                factory.free_raw_memory(memory);
+                Ok((None, renders))
+            }
+            Output::External { .. } => Ok((None, renders)),
+        }
+    }
+
+    /// Remove all targets and destroy queues, command pools and semaphores of the renderer.
+    /// Waits for queues to become idle unless device is lost.
+    /// Returns surfaces and renders of removed targets.
+    pub fn dispose(mut self, factory: &mut Factory<B>) -> Vec<(Option<B::Surface>, Vec<R>)> {
+        let ids = self.targets.keys().cloned().collect::<Vec<_>>();
+        let mut removed = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.remove_target(id, factory) {
+                Ok(target) => removed.push(target),
+                Err(err) => error!("Failed to remove target {:?}: {}", id, err),
+            }
+        }
+        if !factory.is_lost() {
+            let result = factory
+                .wait_idle()
+                .map_err(host_execution_error);
+            if let Err(err) = factory.check_lost(result) {
+                error!("{}", err);
+            }
+        }
+        let Resources { submitter, pools } = self.resources;
+        for pool in pools {
+            factory.destroy_command_pool(pool.into_raw());
+        }
+        submitter.dispose(factory);
+        self.external.dispose(factory);
+        removed
+    }
+
+    /// Copy texels of the image of headless target to the host.
+    /// Waits for all frames of the target to complete.
+    /// Fails for block-compressed formats and targets with several layers.
+    ///
+    /// # Parameters
+    ///
+    /// `id`        - headless target.
+    /// `index`     - index of the image. Same as `Frame::id` the image was rendered with.
+    /// `layout`    - layout renders leave the image in. Image is returned to it after the copy.
+    ///
+    pub fn read_headless(
+        &mut self,
+        id: TargetId,
+        index: usize,
+        layout: ImageLayout,
+        factory: &mut Factory<B>,
+    ) -> Result<Vec<u8>, Error> {
+        let ref target = *self.targets
+            .get(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        let format = match target.output {
+            Output::Headless { format, .. } | Output::External { format, .. } => format,
+            Output::Surface { .. } => {
+                return Err(Error::WrongTargetKind {
+                    target: id,
+                    expected: "headless",
+                })
+            }
+        };
+        let image = match target.backbuffer {
+            Backbuffer::Images(ref images) => images
+                .get(index)
+                .ok_or(Error::IndexOutOfRange {
+                    what: "headless image",
+                    index,
+                    len: images.len(),
+                })?,
+            Backbuffer::Framebuffer(_) => unreachable!("Headless targets are backed by images"),
+        };
+        let desc = format.surface_desc();
+        if desc.dim != (1, 1) {
+            // Block-compressed texels can't be copied as rows of `bits / 8` bytes.
+            return Err(Error::UnsupportedFormat(format));
+        }
+        if kind_layers(target.kind) != 1 {
+            return Err(Error::Unsupported("Readback of layered headless targets"));
+        }
+        let extent = kind_extent(target.kind);
+        let size = (extent.width as u64)
+            .checked_mul(extent.height as u64)
+            .and_then(|texels| texels.checked_mul(extent.depth as u64))
+            .and_then(|texels| texels.checked_mul(desc.bits as u64 / 8))
+            .ok_or(Error::InvalidArgument("Readback size of headless target overflows"))?;
+
+        let family = self.resources.submitter.graphics().family();
+        let ref mut queue = self.resources.submitter.graphics_mut().queues[target.queue];
+        let result = queue
+            .wait_idle()
+            .map_err(host_execution_error);
+        factory.check_lost(result)?;
+
+        let buffer = factory.buffer().readback().size(size).build()?;
+        let mut pool = factory.create_command_pool(family, CommandPoolCreateFlags::TRANSIENT);
+        let mut cbuf = pool.allocate(1, RawLevel::Primary).remove(0);
+        let range = SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..1,
+        };
+        cbuf.begin(CommandBufferFlags::ONE_TIME_SUBMIT);
+        cbuf.pipeline_barrier(
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::TRANSFER,
+            Some(Barrier::Image {
+                states: (Access::COLOR_ATTACHMENT_WRITE, layout)..(Access::TRANSFER_READ, ImageLayout::TransferSrcOptimal),
+                target: image,
+                range: range.clone(),
+            }),
+        );
+        cbuf.copy_image_to_buffer(
+            image,
+            ImageLayout::TransferSrcOptimal,
+            buffer.raw(),
+            Some(BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: 0,
+                buffer_height: 0,
+                image_layers: SubresourceLayers {
+                    aspects: Aspects::COLOR,
+                    level: 0,
+                    layers: 0..1,
+                },
+                image_offset: Offset { x: 0, y: 0, z: 0 },
+                image_extent: extent,
+            }),
+        );
+        cbuf.pipeline_barrier(
+            PipelineStage::TRANSFER..PipelineStage::COLOR_ATTACHMENT_OUTPUT,
+            Some(Barrier::Image {
+                states: (Access::TRANSFER_READ, ImageLayout::TransferSrcOptimal)..(Access::COLOR_ATTACHMENT_WRITE, layout),
+                target: image,
+                range,
+            }),
+        );
+        cbuf.finish();
+
+        let fence = factory.acquire_fence();
+        unsafe {
+            queue.as_mut().submit_raw(
+                RawSubmission {
+                    cmd_buffers: Some(&cbuf),
+                    wait_semaphores: &[],
+                    signal_semaphores: &[],
+                },
+                Some(&fence),
+            );
+        }
+        let completed = factory.wait_for_fence(&fence, !0);
+        factory.release_fence(fence);
+        pool.free(vec![cbuf]);
+        factory.destroy_command_pool(pool);
+        if !completed {
+            factory.destroy_buffer(buffer);
+            // Waiting without timeout fails only if the device is lost.
+            return Err(Error::DeviceLost);
+        }
+
+        let data = factory.read_buffer(&buffer, 0..size, |data| data.to_vec());
+        factory.destroy_buffer(buffer);
+        data
+    }
+
+    /// Get ids of all targets.
+    pub fn targets(&self) -> Vec<TargetId> {
+        self.targets.keys().cloned().collect()
     }
 
     /// Add graph to the render
+    /// First render added to the target becomes active.
     pub fn add_render(
         &mut self,
         id: TargetId,
@@ -84,13 +467,182 @@ where
     ) -> Result<(), Error> {
         let ref mut target = *self.targets
             .get_mut(&id)
-            .ok_or(format!("No render with id {:#?}", id))?;
+            .ok_or(Error::UnknownTarget(id))?;
         target.renders.push(render);
+        if target.active.is_none() {
+            target.active = Some(target.renders.len() - 1);
+        }
+        Ok(())
+    }
+
+    /// Choose render the target draws with.
+    /// `None` stops drawing to the target.
+    pub fn set_active_render(&mut self, id: TargetId, index: Option<usize>) -> Result<(), Error> {
+        let ref mut target = *self.targets
+            .get_mut(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        if let Some(index) = index {
+            if index >= target.renders.len() {
+                return Err(Error::IndexOutOfRange {
+                    what: "render",
+                    index,
+                    len: target.renders.len(),
+                });
+            }
+        }
+        target.active = index;
+        Ok(())
+    }
+
+    /// Let renderer create depth-stencil attachment for the target and recreate it when target is resized.
+    /// Attachment gets first of `formats` supported by the device. Empty `formats` remove the attachment.
+    /// Returns picked format. Renders are notified with `Render::resize` before the next frame.
+    pub fn set_depth_stencil(
+        &mut self,
+        id: TargetId,
+        formats: &[Format],
+        factory: &mut Factory<B>,
+    ) -> Result<Option<Format>, Error> {
+        let ref mut target = *self.targets
+            .get_mut(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        let format = target.attachments.set_depth_formats(formats, target.kind, factory)?;
+        target.resized = true;
+        Ok(format)
+    }
+
+    /// Let renderer create multisampled color attachment with `samples` per texel for the target.
+    /// Depth-stencil attachment gets the same number of samples. Single sample disables multisampling.
+    /// Renders draw into `Attachments::color` and resolve it into backbuffer with `Attachments::resolve`.
+    /// Renders are notified with `Render::resize` before the next frame.
+    pub fn set_samples(&mut self, id: TargetId, samples: NumSamples, factory: &mut Factory<B>) -> Result<(), Error> {
+        let ref mut target = *self.targets
+            .get_mut(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        let format = match target.output {
+            Output::Surface { ref config, .. } => config.color_format,
+            Output::Headless { format, .. } | Output::External { format, .. } => format,
+        };
+        target.attachments.set_samples(samples, format, target.kind, factory)?;
+        target.resized = true;
+        Ok(())
+    }
+
+    /// Let renderer create attachments with a layer per view for the target, e.g. two views for stereo rendering.
+    /// Views split the backbuffer width equally. Single view disables it.
+    /// Renders draw all views into layers of `Attachments::color` in a framebuffer created with `Attachments::extent`,
+    /// selecting layer themselves, e.g. by instancing with layer index written by vertex or geometry shader,
+    /// and place them into backbuffer with `Attachments::resolve`.
+    /// Renders are notified with `Render::resize` before the next frame.
+    pub fn set_views(&mut self, id: TargetId, views: u32, factory: &mut Factory<B>) -> Result<(), Error> {
+        if views > u16::max_value() as u32 {
+            return Err(Error::InvalidArgument("Attachments can't have more than 65535 views"));
+        }
+        let ref mut target = *self.targets
+            .get_mut(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        let format = match target.output {
+            Output::Surface { ref config, .. } => config.color_format,
+            Output::Headless { format, .. } | Output::External { format, .. } => format,
+        };
+        target.attachments.set_views(views, format, target.kind, factory)?;
+        target.resized = true;
         Ok(())
     }
 
+    /// Check if the target presents frames to a surface rather than rendering headless or into external images.
+    pub fn presents(&self, id: TargetId) -> Result<bool, Error> {
+        let ref target = *self.targets
+            .get(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        match target.output {
+            Output::Surface { .. } => Ok(true),
+            Output::Headless { .. } | Output::External { .. } => Ok(false),
+        }
+    }
+
+    /// Replace surface of the target, e.g. with one created for resized window.
+    /// Swapchain is recreated for new surface at the beginning of the next frame and old surface is dropped.
+    pub fn replace_surface(&mut self, id: TargetId, surface: B::Surface) -> Result<(), Error> {
+        let ref mut target = *self.targets
+            .get_mut(&id)
+            .ok_or(Error::UnknownTarget(id))?;
+        match target.output {
+            Output::Surface {
+                ref mut pending_surface,
+                ..
+            } => {
+                *pending_surface = Some(surface);
+                Ok(())
+            }
+            Output::Headless { .. } | Output::External { .. } => Err(Error::WrongTargetKind {
+                target: id,
+                expected: "presenting to surface",
+            }),
+        }
+    }
+
+    /// Get frame pacer limiting frame rate of all targets.
+    pub fn pacer(&self) -> &FramePacer {
+        &self.pacer
+    }
+
+    /// Get frame pacer mutably to change frame rate limit.
+    pub fn pacer_mut(&mut self) -> &mut FramePacer {
+        &mut self.pacer
+    }
+
+    /// Get CPU times of latest frames.
+    pub fn cpu_stats(&self) -> &CpuFrameStats {
+        &self.cpu_stats
+    }
+
+    /// Get CPU times of latest frames mutably to change the window or clear them.
+    pub fn cpu_stats_mut(&mut self) -> &mut CpuFrameStats {
+        &mut self.cpu_stats
+    }
+
+    /// Limit number of frames the device may lag behind.
+    /// `Renderer::run` waits for frame `frames` before current one to complete before starting new one.
+    /// If `None` then it is limited only by number of swapchain images.
+    pub fn set_frames_in_flight(&mut self, frames: Option<u64>) {
+        assert_ne!(frames, Some(0), "At least one frame must be in flight");
+        self.frames_in_flight = frames;
+    }
+
+    /// Get limit of frames in flight.
+    pub fn frames_in_flight(&self) -> Option<u64> {
+        self.frames_in_flight
+    }
+
     /// Create new render system providing it with general queue group and surfaces to draw onto
     pub fn new(group: QueueGroup<B, General>) -> Self
+    where
+        R: Send + Sync,
+    {
+        Self::with_queues(group, None, None)
+    }
+
+    /// Create new render system providing it with general queue group which last queue runs compute submissions.
+    /// It is used for `Factory::compute` and compute passes of graphs when device has no separate compute family
+    /// or resources can't be transferred to it. Requires at least two queues in the group.
+    pub fn with_general_compute(group: QueueGroup<B, General>) -> Result<Self, Error>
+    where
+        R: Send + Sync,
+    {
+        let mut renderer = Self::with_queues(group, None, None);
+        renderer.resources.submitter.reserve_general_compute()?;
+        renderer.queues_usage.pop();
+        Ok(renderer)
+    }
+
+    /// Create new render system providing it with general queue group
+    /// and optional compute and transfer queue groups chosen according to `Factory::topology`.
+    pub fn with_queues(
+        group: QueueGroup<B, General>,
+        compute: Option<QueueGroup<B, Compute>>,
+        transfer: Option<QueueGroup<B, Transfer>>,
+    ) -> Self
     where
         R: Send + Sync,
     {
@@ -101,71 +653,277 @@ where
             autorelease: AutoreleasePool::new(),
             queues_usage: vec![0; group.queues.len()],
             targets: HashMap::new(),
+            pacer: FramePacer::new(),
+            cpu_stats: CpuFrameStats::default(),
+            frames_in_flight: None,
+            external: ExternalSubmits::new(),
+            #[cfg(feature = "renderdoc")]
+            capture: FrameCapture::new(),
             counter: 0,
             resources: Resources {
-                group,
+                submitter: Submitter::new(group, compute, transfer),
                 pools: Vec::new(),
-                fences: Vec::new(),
-                semaphores: Vec::new(),
             },
         }
     }
 
-    pub fn run<T>(&mut self, data: &mut T, factory: &mut Factory<B>)
+    /// Submit uploads and draw a frame for each target.
+    /// Fails with `Error::DeviceLost` when device loss is detected. See `recover`.
+    pub fn run<T>(&mut self, data: &mut T, factory: &mut Factory<B>) -> Result<(), Error>
     where
         B: Backend,
         R: Render<B, T>,
     {
-        self.poll_uploads(factory);
+        if factory.is_lost() {
+            return Err(Error::DeviceLost);
+        }
+        self.pacer.wait();
+        let start = Instant::now();
+        let mut times = CpuFrameTimes::default();
+        if let Some(frames) = self.frames_in_flight {
+            let current = factory.current();
+            if current >= frames {
+                measure(&mut times.fence_wait, || factory.wait_frame(current - frames))?;
+            }
+        }
+        self.check_queue_families(factory)?;
+        #[cfg(feature = "renderdoc")]
+        self.capture.begin();
+        let mut frame = factory.begin_frame();
+        let _span = instrument::frame(frame.index());
 
-        // Run targets
-        for target in self.targets.values_mut() {
-            target.run(factory, &mut self.resources, data);
+        // Run targets. Frame is finished even if submission fails.
+        let mut result = self.submit_uploads(factory);
+        if result.is_ok() {
+            for target in self.targets.values_mut() {
+                result = target.run(factory, &mut self.resources, &mut frame, &mut times, data);
+                if result.is_err() {
+                    break;
+                }
+            }
         }
 
-        // walk over frames and find earliest
-        let earliest = self.targets
-            .values()
-            .filter_map(|target| target.frames.front())
-            .map(|f| f.started)
-            .min()
-            .unwrap();
+        // Signal external semaphores and fences after all targets.
+        self.external
+            .flush(self.resources.submitter.queue(QueueRole::Graphics), &mut frame, factory);
+
+        if !frame.has_fences() {
+            // No target submitted this frame. Signal fence after uploads and computes.
+            let fence = frame.add_fence(factory.acquire_fence());
+            unsafe {
+                self.resources.submitter.queue(QueueRole::Graphics).submit_raw(
+                    RawSubmission {
+                        cmd_buffers: None::<&B::CommandBuffer>,
+                        wait_semaphores: &[],
+                        signal_semaphores: &[],
+                    },
+                    Some(fence),
+                );
+            }
+        }
 
         unsafe {
-            // cleanup after finished jobs.
-            factory.advance(earliest);
+            // All submissions of the frame signal its fences.
+            // Cleanup after finished frames.
+            factory.finish_frame(frame);
         }
+        #[cfg(feature = "renderdoc")]
+        self.capture.end();
 
         self.autorelease.reset();
+        times.total = start.elapsed();
+        telemetry::frame_time(times.total);
+        self.cpu_stats.push(times);
+        result
+    }
+
+    /// Submit all uploads recorded by the `Factory` and wait for them to complete
+    /// without running the frame.
+    /// Uploads go to the queue `QueueRole::Transfer` resolves to, the same one frames submit them to.
+    /// Ownership acquisitions recorded for graphics queue are submitted with the next frame.
+    /// Fails if uploads are recorded for family the renderer has no queue of.
+    pub fn wait_uploads_idle(&mut self, factory: &mut Factory<B>) -> Result<(), Error> {
+        self.check_queue_family(QueueRole::Transfer, factory.topology().upload_family())?;
+        factory.wait_uploads_idle(self.resources.submitter.queue(QueueRole::Transfer))
+    }
+
+    /// Wait for all frames run by the renderer to complete, but no longer than `timeout`.
+    /// Work submitted outside of frames isn't waited for.
+    /// Fails with `Error::WaitTimeout` reporting frames, queues, subsystems and renders
+    /// which work may be incomplete if frames don't complete in time.
+    pub fn wait_idle_timeout(&mut self, factory: &mut Factory<B>, timeout: Duration) -> Result<(), Error> {
+        if factory.is_lost() {
+            return Err(Error::DeviceLost);
+        }
+        let current = factory.current();
+        if current == 0 || factory.wait_frame_timeout(current - 1, timeout)? {
+            return Ok(());
+        }
+        let work = self.stuck_work(factory);
+        error!("Timed out waiting for device to become idle: {}", work);
+        Err(Error::WaitTimeout(work))
+    }
+
+    /// Report work of the `Factory` and the renderer that may be incomplete.
+    pub fn stuck_work(&self, factory: &Factory<B>) -> StuckWork {
+        let ongoing = factory.ongoing();
+        let mut work = factory.stuck_work();
+        let ref submitter = self.resources.submitter;
+        for &role in ROLES.iter() {
+            if submitter.resolve(role) != role {
+                continue;
+            }
+            match submitter.last_submitted(role) {
+                Some(frame) if frame >= ongoing => work.queues.push((role, frame)),
+                _ => {}
+            }
+        }
+        for (&id, target) in &self.targets {
+            for frame in &target.frames {
+                if frame.started >= ongoing {
+                    work.targets.push(StuckTarget {
+                        target: id,
+                        render: frame.render,
+                        frame: frame.started,
+                    });
+                }
+            }
+        }
+        work.targets.sort_by_key(|target| (target.frame, target.target));
+        work
+    }
+
+    /// Capture the next frame with RenderDoc, from its first submission to its last one.
+    /// Fails if application isn't running under RenderDoc.
+    #[cfg(feature = "renderdoc")]
+    pub fn capture_next_frame(&mut self) -> Result<(), Error> {
+        self.capture.request()
+    }
+
+    /// Let the next frame wait for the semaphore signaled by external producer before rendering at `stages`.
+    /// It is given back by `Renderer::retire_external` once the frame is complete.
+    pub fn wait_external(&mut self, semaphore: B::Semaphore, stages: PipelineStage) {
+        self.external.wait(semaphore, stages);
+    }
+
+    /// Let the next frame signal the semaphore for external consumer after rendering all targets.
+    /// It is given back by `Renderer::retire_external` once the frame is complete.
+    pub fn signal_external(&mut self, semaphore: B::Semaphore) {
+        self.external.signal(semaphore);
+    }
+
+    /// Let the next frame signal the fence for external consumer after rendering all targets.
+    /// It is given back by `Renderer::retire_external` once the frame is complete.
+    pub fn signal_external_fence(&mut self, fence: B::Fence) {
+        self.external.signal_fence(fence);
+    }
+
+    /// Take semaphores and fences passed to `Renderer::wait_external`, `Renderer::signal_external`
+    /// and `Renderer::signal_external_fence` which frames are complete.
+    /// They can be passed to the renderer again or destroyed.
+    pub fn retire_external(&mut self, factory: &Factory<B>) -> (Vec<B::Semaphore>, Vec<B::Fence>) {
+        self.external.retire(factory.frames())
+    }
+
+    /// Get submitter owning queues of the renderer.
+    pub fn submitter(&mut self) -> &mut Submitter<B> {
+        &mut self.resources.submitter
     }
 
-    fn poll_uploads(&mut self, factory: &mut Factory<B>)
+    /// Check that command buffers `Factory` records can be submitted to queues of the renderer.
+    /// Fails if uploads or compute commands are recorded for family the renderer has no queue of.
+    fn check_queue_families(&self, factory: &Factory<B>) -> Result<(), Error>
     where
         B: Backend,
     {
-        if let Some((cbuf, _)) = factory.uploads() {
-            if self.resources.group.queues.len() > 1 {
-                unimplemented!("Upload in multiqueue environment is not supported yet");
-            }
-            unsafe {
-                self.resources.group.queues[0].as_mut().submit_raw(
-                    RawSubmission {
-                        cmd_buffers: Some(cbuf),
-                        wait_semaphores: &[],
-                        signal_semaphores: &[],
-                    },
-                    None,
-                );
-            }
+        self.check_queue_family(QueueRole::Transfer, factory.topology().upload_family())?;
+        self.check_queue_family(QueueRole::Compute, factory.topology().compute_family())
+    }
+
+    /// Check that submissions of `role` go to queue of `family` command buffers are recorded for.
+    fn check_queue_family(&self, role: QueueRole, family: QueueFamilyId) -> Result<(), Error>
+    where
+        B: Backend,
+    {
+        if self.resources.submitter.family(role) != family {
+            return Err(Error::QueueFamilyMismatch {
+                expected: family,
+                found: self.resources.submitter.family(role),
+            });
         }
+        Ok(())
     }
+
+    /// Submit uploads and then computes recorded by the `Factory` with single submission per queue.
+    /// Frame rendering on general queue waits for both.
+    fn submit_uploads(&mut self, factory: &mut Factory<B>) -> Result<(), Error>
+    where
+        B: Backend,
+    {
+        let _span = instrument::submit("uploads");
+        let consumers = PipelineStage::DRAW_INDIRECT | PipelineStage::VERTEX_INPUT | PipelineStage::VERTEX_SHADER
+            | PipelineStage::FRAGMENT_SHADER | PipelineStage::COMPUTE_SHADER | PipelineStage::TRANSFER;
+        // Targets may render on any queue of general group.
+        let mut followers = self.targets.values().map(|target| target.queue).collect::<Vec<_>>();
+        followers.sort();
+        followers.dedup();
+        let mut schedule = self.resources.submitter.schedule(&**factory, factory.frames());
+        for queue in followers {
+            schedule.follow_graphics(queue, consumers);
+        }
+        let (uploads, acquires, compute) = factory.submissions();
+        schedule.submit(QueueRole::Transfer, uploads.into_iter().map(|cbuf| &*cbuf));
+        schedule.submit(QueueRole::Graphics, acquires.map(|cbuf| &*cbuf));
+        schedule.submit(QueueRole::Compute, compute.map(|cbuf| &*cbuf));
+        schedule.depend(
+            QueueRole::Compute,
+            QueueRole::Transfer,
+            PipelineStage::COMPUTE_SHADER | PipelineStage::TRANSFER,
+        );
+        schedule.depend(QueueRole::Graphics, QueueRole::Transfer, consumers);
+        schedule.depend(QueueRole::Graphics, QueueRole::Compute, consumers);
+        for &(ref semaphore, stages) in self.external.waits() {
+            schedule.wait(QueueRole::Graphics, semaphore, stages);
+        }
+        schedule.flush()
+    }
+}
+
+/// Where frames of the target go.
+enum Output<B: Backend> {
+    /// Frames are presented to the surface.
+    Surface {
+        surface: B::Surface,
+        config: SwapchainConfig,
+        pending_surface: Option<B::Surface>,
+        swapchain: B::Swapchain,
+    },
+
+    /// Frames are rendered into images bound to `memory`. Images are used round-robin.
+    Headless {
+        format: Format,
+        memory: B::Memory,
+        images: usize,
+        next: usize,
+    },
+
+    /// Frames are rendered into images of external swapchain.
+    /// Image of the next frame is picked by `index`.
+    External {
+        format: Format,
+        layout: ImageLayout,
+        images: usize,
+        index: Option<usize>,
+    },
 }
 
 struct Target<B: Backend, R> {
     queue: usize,
-    surface: B::Surface,
-    swapchain: B::Swapchain,
+    output: Output<B>,
+    kind: Kind,
     backbuffer: Backbuffer<B>,
+    attachments: Attachments<B>,
+    resized: bool,
     active: Option<usize>,
     renders: Vec<R>,
     frames: VecDeque<Frame>,
@@ -176,29 +934,91 @@ impl<B, R> Target<B, R>
 where
     B: Backend,
 {
-    fn run<T>(&mut self, factory: &mut Factory<B>, resources: &mut Resources<B>, data: &mut T)
+    fn run<T>(
+        &mut self,
+        factory: &mut Factory<B>,
+        resources: &mut Resources<B>,
+        current: &mut FrameGuard<B>,
+        times: &mut CpuFrameTimes,
+        data: &mut T,
+    ) -> Result<(), Error>
     where
         R: Render<B, T>,
     {
         if let Some(active) = self.active {
+            // Recreate swapchain if surface was resized or replaced.
+            // This is the safe point since the frame isn't acquired yet.
+            // Acquisition and presentation don't report out-of-date swapchain, so compare surface size instead.
+            let resized = match self.output {
+                Output::Surface {
+                    ref surface,
+                    ref pending_surface,
+                    ..
+                } => {
+                    let kind = pending_surface.as_ref().unwrap_or(surface).kind();
+                    if kind != self.kind || pending_surface.is_some() {
+                        Some(kind)
+                    } else {
+                        None
+                    }
+                }
+                Output::Headless { .. } | Output::External { .. } => None,
+            };
+            if let Some(kind) = resized {
+                self.recreate(factory, resources, kind)?;
+            }
+
+            // Notify renders about new backbuffer or attachments.
+            if self.resized {
+                self.resized = false;
+                for render in &mut self.renders {
+                    render.resize(&self.backbuffer, &self.attachments, self.kind, factory);
+                }
+            }
+
+            // External swapchain may have no image for this frame.
+            let external = match self.output {
+                Output::External { ref mut index, .. } => match index.take() {
+                    Some(index) => Some(index),
+                    None => return Ok(()),
+                },
+                _ => None,
+            };
+
             // Get fresh semaphore.
-            let acquire = resources.semaphores
-                .pop()
-                .unwrap_or_else(|| factory.create_semaphore());
+            let acquire = factory.acquire_semaphore();
 
             // Start frame acquisition.
-            let surface_frame = self.swapchain.acquire_frame(FrameSync::Semaphore(&acquire));
+            let surface_frame = match self.output {
+                Output::Surface { ref mut swapchain, .. } => {
+                    measure(&mut times.acquire, || swapchain.acquire_frame(FrameSync::Semaphore(&acquire)))
+                }
+                Output::Headless {
+                    images,
+                    ref mut next,
+                    ..
+                } => {
+                    signal_acquired(resources.submitter.graphics_mut().queues[self.queue].as_mut(), &acquire);
+                    let index = *next;
+                    *next = (index + 1) % images;
+                    SurfaceFrame::new(index)
+                }
+                Output::External { .. } => {
+                    // External swapchain synchronizes image acquisition itself.
+                    signal_acquired(resources.submitter.graphics_mut().queues[self.queue].as_mut(), &acquire);
+                    SurfaceFrame::new(external.unwrap())
+                }
+            };
             let frame = Frame {
                 index: surface_frame.id(),
-                started: factory.current(),
+                started: current.index(),
+                render: active,
             };
 
             // Grow job vector.
             while frame.index >= self.jobs.len() {
                 self.jobs.push(Job {
-                    release: resources.semaphores
-                        .pop()
-                        .unwrap_or_else(|| factory.create_semaphore()),
+                    release: factory.acquire_semaphore(),
                     payload: None,
                 });
             }
@@ -209,24 +1029,28 @@ where
                 let ref mut job = self.jobs[f.index];
 
                 if let Some(Payload {
-                    fence,
                     mut pool,
-                    acquire,
+                    acquire: acquired,
                     ..
                 }) = job.payload.take()
                 {
-                    // Wait for job to finish.
-                    if !factory.wait_for_fence(&fence, !0) {
-                        panic!("Device lost or something");
+                    // Wait for frame of the job to finish.
+                    if let Err(err) = measure(&mut times.fence_wait, || factory.wait_frame(f.started)) {
+                        // Keep the job to be reclaimed when target is removed.
+                        job.payload = Some(Payload {
+                            acquire: acquired,
+                            pool,
+                        });
+                        self.frames.push_front(f);
+                        factory.release_semaphore(acquire);
+                        return Err(err);
                     }
-                    // reset fence and pool
-                    factory.reset_fence(&fence);
+                    // reset pool
                     pool.reset();
 
-                    // Reclaim fence, pool and acquisition semaphore
-                    resources.fences.push(fence);
+                    // Reclaim pool. Return acquisition semaphore to the pool of the factory.
                     resources.pools.push(pool);
-                    resources.semaphores.push(acquire);
+                    factory.release_semaphore(acquired);
                 }
 
                 // ... until the job associated with current frame
@@ -237,37 +1061,58 @@ where
 
             let ref mut job = self.jobs[frame.index];
 
-            let fence = resources.fences
-                .pop()
-                .unwrap_or_else(|| factory.create_fence(false));
+            let fence = factory.acquire_fence();
             let mut pool = resources.pools.pop().unwrap_or_else(|| {
-                factory.create_command_pool_typed(&resources.group, CommandPoolCreateFlags::TRANSIENT, 1)
+                factory.create_command_pool_typed(resources.submitter.graphics(), CommandPoolCreateFlags::TRANSIENT, 1)
             });
 
             // Get all required resources.
             let ref mut render = self.renders[active];
-            let ref mut queue = resources.group.queues[self.queue];
+            let ref mut queue = resources.submitter.graphics_mut().queues[self.queue];
 
             // Record and submit commands to draw frame.
-            render.render(
-                queue,
-                &mut pool,
-                &self.backbuffer,
-                surface_frame,
-                &acquire,
-                &job.release,
-                viewport(self.surface.kind()),
-                &fence,
-                factory,
-                data,
-            );
+            // Fence of the job is signaled by the frame.
+            let fence = current.add_fence(fence);
+            let recording = Instant::now();
+            {
+                let _span = instrument::submit("render");
+                render.render(
+                    queue,
+                    &mut pool,
+                    &self.backbuffer,
+                    &self.attachments,
+                    surface_frame,
+                    &acquire,
+                    &job.release,
+                    viewport(self.kind),
+                    fence,
+                    factory,
+                    data,
+                );
+            }
+            times.recording += recording.elapsed();
 
-            // Setup presenting.
-            queue.present(Some(&mut self.swapchain), Some(&job.release));
+            match self.output {
+                Output::Surface { ref mut swapchain, .. } => {
+                    // Setup presenting.
+                    let _span = instrument::present();
+                    measure(&mut times.present, || queue.present(Some(swapchain), Some(&job.release)));
+                }
+                Output::Headless { .. } | Output::External { .. } => unsafe {
+                    // Nothing presents the image. Wait for release semaphore to unsignal it.
+                    queue.as_mut().submit_raw(
+                        RawSubmission {
+                            cmd_buffers: None::<&B::CommandBuffer>,
+                            wait_semaphores: &[(&job.release, PipelineStage::BOTTOM_OF_PIPE)],
+                            signal_semaphores: &[],
+                        },
+                        None,
+                    );
+                },
+            }
 
             // Save job resources.
             job.payload = Some(Payload {
-                fence,
                 acquire,
                 pool,
             });
@@ -276,32 +1121,75 @@ where
             self.frames.push_back(frame);
         } else if !self.jobs.is_empty() {
             // Target wants to stop processing.
-            // Wait for associated queue to become idle.
-            resources.group.queues[self.queue]
+            self.reclaim_jobs(factory, resources)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for all jobs of the target and reclaim their resources.
+    /// Jobs are reclaimed without waiting if device is lost.
+    fn reclaim_jobs(&mut self, factory: &mut Factory<B>, resources: &mut Resources<B>) -> Result<(), Error> {
+        // Wait for associated queue to become idle.
+        if !factory.is_lost() {
+            let result = resources.submitter.graphics_mut().queues[self.queue]
                 .wait_idle()
-                .expect("Device lost or something");
+                .map_err(host_execution_error);
+            if let Err(err) = factory.check_lost(result) {
+                if !factory.is_lost() {
+                    return Err(err);
+                }
+            }
+        }
+        self.frames.clear();
 
-            // Get all jobs
-            for Job { release, payload } in self.jobs.drain(..) {
-                if let Some(Payload {
-                    fence,
-                    mut pool,
-                    acquire,
-                    ..
-                }) = payload
-                {
-                    // reset fence and pool
-                    factory.reset_fence(&fence);
-                    pool.reset();
+        // Get all jobs
+        for Job { release, payload } in self.jobs.drain(..) {
+            if let Some(Payload {
+                mut pool,
+                acquire,
+                ..
+            }) = payload
+            {
+                // reset pool
+                pool.reset();
 
-                    // Reclaim fence, pool and semaphores
-                    resources.fences.push(fence);
-                    resources.pools.push(pool);
-                    resources.semaphores.push(acquire);
-                    resources.semaphores.push(release);
-                }
+                // Reclaim pool. Return semaphores to the pool of the factory.
+                resources.pools.push(pool);
+                factory.release_semaphore(acquire);
             }
+            factory.release_semaphore(release);
         }
+        Ok(())
+    }
+
+    /// Recreate swapchain and attachments after surface changed its size or was replaced.
+    /// Renders are notified before next frame so they can recreate resources that depend on backbuffer.
+    fn recreate(&mut self, factory: &mut Factory<B>, resources: &mut Resources<B>, kind: Kind) -> Result<(), Error> {
+        self.reclaim_jobs(factory, resources)?;
+
+        if let Output::Surface {
+            ref mut surface,
+            ref config,
+            ref mut pending_surface,
+            ref mut swapchain,
+        } = self.output
+        {
+            let old_surface = pending_surface.take().map(|next| replace(surface, next));
+            let (new, backbuffer) = factory.create_swapchain(surface, config.clone());
+            let old = replace(swapchain, new);
+            factory.destroy_swapchain(old);
+            // Old surface outlives its swapchain.
+            drop(old_surface);
+            self.backbuffer = backbuffer;
+        }
+        info!("Swapchain recreated for {:?}", kind);
+        telemetry::swapchain_recreated();
+
+        // Kind is updated only after attachments are recreated so failed recreation is retried next frame.
+        self.attachments.recreate(kind, factory)?;
+        self.kind = kind;
+        self.resized = true;
+        Ok(())
     }
 }
 
@@ -309,6 +1197,7 @@ where
 struct Frame {
     index: usize,
     started: u64,
+    render: usize,
 }
 
 struct Job<B: Backend> {
@@ -318,10 +1207,23 @@ struct Job<B: Backend> {
 
 struct Payload<B: Backend> {
     acquire: B::Semaphore,
-    fence: B::Fence,
     pool: CommandPool<B, General>,
 }
 
+/// Signal acquisition semaphore of the frame which image needn't be waited for.
+fn signal_acquired<B: Backend>(queue: &mut B::CommandQueue, acquire: &B::Semaphore) {
+    unsafe {
+        queue.submit_raw(
+            RawSubmission {
+                cmd_buffers: None::<&B::CommandBuffer>,
+                wait_semaphores: &[],
+                signal_semaphores: &[acquire],
+            },
+            None,
+        );
+    }
+}
+
 fn viewport(kind: Kind) -> Viewport {
     match kind {
         Kind::D2(w, h, _) | Kind::D2Array(w, h, _, _) => Viewport {
@@ -334,10 +1236,8 @@ fn viewport(kind: Kind) -> Viewport {
 
 
 struct Resources<B: Backend> {
-    group: QueueGroup<B, General>,
+    submitter: Submitter<B>,
     pools: Vec<CommandPool<B, General>>,
-    fences: Vec<B::Fence>,
-    semaphores: Vec<B::Semaphore>,
 }
 
 struct AutoreleasePool<B> {
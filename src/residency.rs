@@ -0,0 +1,251 @@
+//! `ResidencyManager` tracks when streamable resources were used last
+//! and evicts least recently used ones when memory budget is exceeded.
+//! 
+
+use std::collections::{HashMap, VecDeque};
+
+use hal::Backend;
+
+use mem::Block;
+
+use factory::{BufferHandle, Factory, ImageHandle};
+
+/// Tracked buffer or image.
+#[derive(Debug)]
+enum Resource<B: Backend> {
+    Buffer(BufferHandle<B>),
+    Image(ImageHandle<B>),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    heap: Option<usize>,
+    size: u64,
+    used: u64,
+}
+
+/// Resources evicted by `ResidencyManager::evict`.
+/// Their handles don't resolve anymore. Caller should stream them again when needed.
+#[derive(Debug)]
+pub struct Evicted<B: Backend> {
+    /// Evicted buffers.
+    pub buffers: Vec<BufferHandle<B>>,
+
+    /// Evicted images.
+    pub images: Vec<ImageHandle<B>>,
+}
+
+impl<B> Evicted<B>
+where
+    B: Backend,
+{
+    /// Check if nothing was evicted.
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty() && self.images.is_empty()
+    }
+}
+
+/// Tracker of streamable buffers and images stored in `Factory` by handles.
+/// 
+/// Resources are stamped with the current frame of the `Factory` when touched.
+/// Resources are accounted in the heap of memory type the allocator picked, the same way `Factory::memory_usage` does.
+/// Memory of evicted resources stays accounted until the frame they were evicted in retires,
+/// so evicted bytes are remembered until `ResidencyManager::clear` is called with later frame.
+#[derive(Debug)]
+pub struct ResidencyManager<B: Backend> {
+    buffers: HashMap<BufferHandle<B>, Entry>,
+    images: HashMap<ImageHandle<B>, Entry>,
+    evicted: VecDeque<(u64, Option<usize>, u64)>,
+}
+
+impl<B> ResidencyManager<B>
+where
+    B: Backend,
+{
+    /// Create empty tracker.
+    pub fn new() -> Self {
+        ResidencyManager {
+            buffers: HashMap::new(),
+            images: HashMap::new(),
+            evicted: VecDeque::new(),
+        }
+    }
+
+    /// Start tracking buffer.
+    /// Returns `false` if handle doesn't resolve.
+    pub fn track_buffer(&mut self, factory: &mut Factory<B>, handle: BufferHandle<B>) -> bool {
+        let (heap, size) = match factory.get_buffer(handle) {
+            Some(buffer) => (
+                factory.memory_heap(buffer.block()),
+                buffer.range().end - buffer.range().start,
+            ),
+            None => return false,
+        };
+        let entry = new_entry(factory, heap, size);
+        self.buffers.insert(handle, entry);
+        true
+    }
+
+    /// Start tracking image.
+    /// Returns `false` if handle doesn't resolve.
+    pub fn track_image(&mut self, factory: &mut Factory<B>, handle: ImageHandle<B>) -> bool {
+        let (heap, size) = match factory.get_image(handle) {
+            Some(image) => (
+                factory.memory_heap(image.block()),
+                image.range().end - image.range().start,
+            ),
+            None => return false,
+        };
+        let entry = new_entry(factory, heap, size);
+        self.images.insert(handle, entry);
+        true
+    }
+
+    /// Stop tracking buffer. It will never be evicted.
+    pub fn untrack_buffer(&mut self, handle: BufferHandle<B>) {
+        self.buffers.remove(&handle);
+    }
+
+    /// Stop tracking image. It will never be evicted.
+    pub fn untrack_image(&mut self, handle: ImageHandle<B>) {
+        self.images.remove(&handle);
+    }
+
+    /// Mark buffer as used by the current frame.
+    pub fn touch_buffer(&mut self, factory: &mut Factory<B>, handle: BufferHandle<B>) {
+        if let Some(entry) = self.buffers.get_mut(&handle) {
+            entry.used = factory.current();
+        }
+    }
+
+    /// Mark image as used by the current frame.
+    pub fn touch_image(&mut self, factory: &mut Factory<B>, handle: ImageHandle<B>) {
+        if let Some(entry) = self.images.get_mut(&handle) {
+            entry.used = factory.current();
+        }
+    }
+
+    /// Get number of tracked resources.
+    pub fn len(&self) -> usize {
+        self.buffers.len() + self.images.len()
+    }
+
+    /// Evict least recently used resources from heaps that exceed their budget.
+    /// Buffers and images are ordered together by the frame they were used last.
+    /// Resources used by the current frame are never evicted.
+    /// Evicted resources are destroyed through the `Factory` after commands referencing them complete.
+    pub fn evict(&mut self, factory: &mut Factory<B>) -> Evicted<B> {
+        let current = factory.current();
+        let mut excess = factory
+            .memory_usage()
+            .iter()
+            .map(|usage| match usage.budget {
+                Some(budget) if usage.allocated > budget => usage.allocated - budget,
+                _ => 0,
+            })
+            .collect::<Vec<_>>();
+        for &(_, heap, size) in &self.evicted {
+            if let Some(heap) = heap {
+                excess[heap] = excess[heap].saturating_sub(size);
+            }
+        }
+
+        let picked = {
+            let buffers = self.buffers
+                .iter()
+                .map(|(&handle, entry)| (Resource::Buffer(handle), entry));
+            let images = self.images
+                .iter()
+                .map(|(&handle, entry)| (Resource::Image(handle), entry));
+            pick_lru(buffers.chain(images), &mut excess, current)
+        };
+
+        let mut evicted = Evicted {
+            buffers: Vec::new(),
+            images: Vec::new(),
+        };
+        for resource in picked {
+            let entry = match resource {
+                Resource::Buffer(handle) => {
+                    let entry = self.buffers.remove(&handle).expect("Picked from tracked");
+                    factory.destroy_buffer_handle(handle);
+                    evicted.buffers.push(handle);
+                    entry
+                }
+                Resource::Image(handle) => {
+                    let entry = self.images.remove(&handle).expect("Picked from tracked");
+                    factory.destroy_image_handle(handle);
+                    evicted.images.push(handle);
+                    entry
+                }
+            };
+            self.evicted.push_back((current, entry.heap, entry.size));
+        }
+        evicted
+    }
+
+    /// Forget evicted resources destroyed before `ongoing` frame.
+    pub fn clear(&mut self, ongoing: u64) {
+        while self.evicted.front().map_or(false, |&(frame, _, _)| frame < ongoing) {
+            self.evicted.pop_front();
+        }
+    }
+}
+
+fn new_entry<B: Backend>(factory: &mut Factory<B>, heap: Option<usize>, size: u64) -> Entry {
+    Entry {
+        heap,
+        size,
+        used: factory.current(),
+    }
+}
+
+/// Pick least recently used entries not used by `current` frame until `excess` of their heaps is covered.
+fn pick_lru<'a, K, I>(entries: I, excess: &mut [u64], current: u64) -> Vec<K>
+where
+    I: IntoIterator<Item = (K, &'a Entry)>,
+{
+    let mut candidates = entries
+        .into_iter()
+        .filter(|&(_, entry)| entry.used < current)
+        .filter_map(|(key, entry)| entry.heap.map(|heap| (key, heap, entry)))
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|&(_, _, entry)| entry.used);
+
+    let mut picked = Vec::new();
+    for (key, heap, entry) in candidates {
+        if excess[heap] > 0 {
+            excess[heap] = excess[heap].saturating_sub(entry.size);
+            picked.push(key);
+        }
+    }
+    picked
+}
+
+#[test]
+fn buffers_and_images_share_lru_order() {
+    let entry = |heap, size, used| Entry {
+        heap: Some(heap),
+        size,
+        used,
+    };
+    let entries = vec![
+        ("buffer-old", entry(0, 64, 3)),
+        ("image-older", entry(0, 64, 1)),
+        ("buffer-current", entry(0, 64, 5)),
+        ("image-other-heap", entry(1, 64, 0)),
+        ("buffer-oldest", entry(0, 32, 0)),
+    ];
+
+    // Oldest resources of the heap are picked regardless of their kind until excess is covered.
+    let mut excess = vec![96, 0];
+    let picked = pick_lru(entries.iter().map(|&(key, ref entry)| (key, entry)), &mut excess, 5);
+    assert_eq!(picked, vec!["buffer-oldest", "image-older"]);
+    assert_eq!(excess, vec![0, 0]);
+
+    // Resources used by the current frame are never picked.
+    let mut excess = vec![1024, 0];
+    let picked = pick_lru(entries.iter().map(|&(key, ref entry)| (key, entry)), &mut excess, 5);
+    assert_eq!(picked, vec!["buffer-oldest", "image-older", "buffer-old"]);
+    assert_eq!(excess, vec![1024 - 160, 0]);
+}
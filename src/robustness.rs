@@ -0,0 +1,72 @@
+//! Opt-in robustness mode for applications that prefer not crashing on malformed content over peak performance.
+//! When enabled the `Factory` will:
+//! 1. Pad buffer allocations so that slightly out-of-bounds access hits owned memory.
+//! 2. Validate ranges bound to descriptors against buffer sizes.
+//! Robust buffer access of the device is not enabled, gfx-hal revision this crate depends on
+//! opens devices without optional features.
+//! 
+
+use std::ops::Range;
+
+use Error;
+
+/// Robustness configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Robustness {
+    /// Bytes appended to every buffer created by the `Factory`.
+    pub padding: u64,
+
+    /// Check ranges bound to descriptors against buffer sizes.
+    pub validate_ranges: bool,
+}
+
+impl Default for Robustness {
+    fn default() -> Self {
+        Robustness {
+            padding: 256,
+            validate_ranges: true,
+        }
+    }
+}
+
+impl Robustness {
+    /// Size of the buffer to allocate to fit `size` bytes with padding.
+    pub fn padded_size(&self, size: u64) -> u64 {
+        size + self.padding
+    }
+
+    /// Check that `range` fits into buffer of `size` bytes excluding padding.
+    pub fn check_range(&self, size: u64, range: Range<u64>) -> Result<(), Error> {
+        if !self.validate_ranges {
+            return Ok(());
+        }
+        if range.start > range.end || range.end > size {
+            Err(Error::OutOfBounds {
+                offset: range.start,
+                len: range.end.saturating_sub(range.start),
+                size,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn ranges_are_checked_against_size_without_padding() {
+    let robustness = Robustness::default();
+    assert_eq!(robustness.padded_size(1024), 1280);
+    assert!(robustness.check_range(1024, 0..1024).is_ok());
+    assert!(robustness.check_range(1024, 1024..1024).is_ok());
+    match robustness.check_range(1024, 1000..1100) {
+        Err(Error::OutOfBounds { offset, len, size }) => assert_eq!((offset, len, size), (1000, 100, 1024)),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert!(robustness.check_range(1024, 16..8).is_err());
+
+    let unchecked = Robustness {
+        validate_ranges: false,
+        ..Robustness::default()
+    };
+    assert!(unchecked.check_range(1024, 0..4096).is_ok());
+}
@@ -0,0 +1,151 @@
+//! `RendererConfig` maps user-editable graphics settings onto knobs of the crate.
+//! It can be loaded from TOML with `config-toml` feature or RON with `config-ron` feature.
+//! Backend and staging threshold are used at init only,
+//! other settings can be re-applied at runtime with `RendererConfig::apply`.
+//!
+
+#[cfg(any(feature = "config-toml", feature = "config-ron"))]
+use std::fs::File;
+#[cfg(any(feature = "config-toml", feature = "config-ron"))]
+use std::io::Read;
+#[cfg(any(feature = "config-toml", feature = "config-ron"))]
+use std::path::Path;
+
+use hal::Backend;
+use hal::image::NumSamples;
+
+#[cfg(feature = "config-ron")]
+use ron;
+#[cfg(feature = "config-toml")]
+use toml;
+
+use Error;
+use dynamic::{BackendKind, BackendPreference};
+use factory::Factory;
+use init::Config;
+use renderer::Renderer;
+
+/// Soft budget of memory heap. See `Factory::set_memory_budget`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct HeapBudget {
+    /// Index of the memory heap.
+    pub heap: usize,
+
+    /// Budget of the heap in bytes.
+    pub bytes: u64,
+}
+
+/// Graphics settings of the application.
+/// Fields missing in loaded file take default values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize", serde(default))]
+pub struct RendererConfig {
+    /// Backends to try in order. If `None` then `BackendPreference::default` is used.
+    /// Used at init only.
+    pub backends: Option<Vec<BackendKind>>,
+
+    /// Samples per texel of color and depth-stencil attachments of targets. See `Renderer::set_samples`.
+    pub msaa: NumSamples,
+
+    /// Maximum number of frames the device lags behind. See `Renderer::set_frames_in_flight`.
+    pub frames_in_flight: Option<u64>,
+
+    /// Soft budgets of memory heaps. Heaps not listed keep their budgets.
+    pub memory_budgets: Vec<HeapBudget>,
+
+    /// Uploads of this size or smaller are written through command buffer directly.
+    /// If `None` then threshold is picked according to memory topology of the device.
+    /// Used at init only.
+    pub staging_threshold: Option<usize>,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        RendererConfig {
+            backends: None,
+            msaa: 1,
+            frames_in_flight: None,
+            memory_budgets: Vec::new(),
+            staging_threshold: None,
+        }
+    }
+}
+
+impl RendererConfig {
+    /// Parse config from TOML.
+    #[cfg(feature = "config-toml")]
+    pub fn from_toml(text: &str) -> Result<Self, Error> {
+        toml::from_str(text).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    /// Parse config from RON.
+    #[cfg(feature = "config-ron")]
+    pub fn from_ron(text: &str) -> Result<Self, Error> {
+        ron::de::from_str(text).map_err(|err| Error::Config(err.to_string()))
+    }
+
+    /// Load config from file. Format is picked by extension, `toml` or `ron`.
+    #[cfg(any(feature = "config-toml", feature = "config-ron"))]
+    pub fn load<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            #[cfg(feature = "config-toml")]
+            Some("toml") => Self::from_toml(&text),
+            #[cfg(feature = "config-ron")]
+            Some("ron") => Self::from_ron(&text),
+            _ => Err(Error::Config(format!("Unknown format of {}", path.display()))),
+        }
+    }
+
+    /// Get order of backends to pass to `init_dynamic`.
+    pub fn backend_preference(&self) -> BackendPreference {
+        self.backends
+            .clone()
+            .map_or_else(BackendPreference::default, BackendPreference)
+    }
+
+    /// Get configuration to init `Factory` and `Renderer` with.
+    pub fn init_config(&self) -> Config {
+        Config {
+            staging_threshold: self.staging_threshold,
+            frames_in_flight: self.frames_in_flight,
+            ..Config::default()
+        }
+    }
+
+    /// Apply settings that can change at runtime.
+    /// Call it after targets are added and each time settings change.
+    /// Samples are applied to all targets presenting to surfaces.
+    /// Swapchains are recreated at the beginning of the next frame.
+    /// Fails with `Error::Config` without applying anything if budget is set for heap the device doesn't have.
+    pub fn apply<B, R>(&self, renderer: &mut Renderer<B, R>, factory: &mut Factory<B>) -> Result<(), Error>
+    where
+        B: Backend,
+    {
+        let heaps = factory.memory_usage().len();
+        if let Some(budget) = self.memory_budgets.iter().find(|budget| budget.heap >= heaps) {
+            return Err(Error::Config(format!(
+                "Budget is set for memory heap {} but device has {} heaps",
+                budget.heap, heaps
+            )));
+        }
+        renderer.set_frames_in_flight(self.frames_in_flight);
+        for budget in &self.memory_budgets {
+            factory.set_memory_budget(budget.heap, Some(budget.bytes))?;
+        }
+        for id in renderer.targets() {
+            if !renderer.presents(id)? {
+                continue;
+            }
+            renderer.set_samples(id, self.msaa, factory)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! `ShaderManager` loads SPIR-V shader modules from files and reloads them when files change.
+//! 
+
+use std::collections::HashMap;
+use std::fs::{metadata, File};
+use std::io::Read;
+use std::mem::replace;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use hal::{Backend, Device};
+
+use Error;
+use factory::Factory;
+
+/// Magic number SPIR-V binaries start with.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Identifier of shader in `ShaderManager`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderId(usize);
+
+#[derive(Debug)]
+struct Shader<B: Backend> {
+    path: PathBuf,
+    module: B::ShaderModule,
+    modified: Option<SystemTime>,
+    generation: u64,
+}
+
+/// Loads shader modules from SPIR-V files and recreates them when files are modified.
+/// 
+/// Call `poll` at a safe point between frames. It returns shaders which were reloaded.
+/// Pipelines created with those shaders should be rebuilt by the caller.
+/// `generation` can be stored with pipeline to detect stale ones.
+#[derive(Debug)]
+pub struct ShaderManager<B: Backend> {
+    shaders: Vec<Shader<B>>,
+    paths: HashMap<PathBuf, ShaderId>,
+}
+
+impl<B> ShaderManager<B>
+where
+    B: Backend,
+{
+    /// Create empty manager.
+    pub fn new() -> Self {
+        ShaderManager {
+            shaders: Vec::new(),
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Load shader module from SPIR-V file.
+    /// Loading the same path twice returns the same shader.
+    pub fn load<P>(&mut self, factory: &Factory<B>, path: P) -> Result<ShaderId, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        if let Some(&id) = self.paths.get(&path) {
+            return Ok(id);
+        }
+
+        let modified = modified(&path);
+        let module = create_module(factory, &path)?;
+        let id = ShaderId(self.shaders.len());
+        self.shaders.push(Shader {
+            path: path.clone(),
+            module,
+            modified,
+            generation: 0,
+        });
+        self.paths.insert(path, id);
+        Ok(id)
+    }
+
+    /// Get shader module.
+    pub fn module(&self, id: ShaderId) -> &B::ShaderModule {
+        &self.shaders[id.0].module
+    }
+
+    /// Get number of times the shader was reloaded.
+    pub fn generation(&self, id: ShaderId) -> u64 {
+        self.shaders[id.0].generation
+    }
+
+    /// Get path the shader was loaded from.
+    pub fn path(&self, id: ShaderId) -> &Path {
+        &self.shaders[id.0].path
+    }
+
+    /// Reload shaders which files were modified since last load.
+    /// Shaders failing to reload keep previous module and error is logged.
+    /// Returns reloaded shaders.
+    pub fn poll(&mut self, factory: &Factory<B>) -> Vec<ShaderId> {
+        let mut reloaded = Vec::new();
+        for (index, shader) in self.shaders.iter_mut().enumerate() {
+            let modified = modified(&shader.path);
+            if modified.is_none() || modified == shader.modified {
+                continue;
+            }
+            shader.modified = modified;
+            match create_module(factory, &shader.path) {
+                Ok(module) => {
+                    info!("Shader {:?} reloaded", shader.path);
+                    // Modules are not referenced by pipelines after creation.
+                    let old = replace(&mut shader.module, module);
+                    factory.destroy_shader_module(old);
+                    shader.generation += 1;
+                    reloaded.push(ShaderId(index));
+                }
+                Err(err) => {
+                    error!("Failed to reload shader {:?}: {}", shader.path, err);
+                }
+            }
+        }
+        reloaded
+    }
+
+    /// Destroy all shader modules.
+    pub fn dispose(self, factory: &Factory<B>) {
+        for shader in self.shaders {
+            factory.destroy_shader_module(shader.module);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn create_module<B>(factory: &Factory<B>, path: &Path) -> Result<B::ShaderModule, Error>
+where
+    B: Backend,
+{
+    let mut code = Vec::new();
+    File::open(path).and_then(|mut file| file.read_to_end(&mut code))?;
+    if code.len() < 4 || code.len() % 4 != 0 {
+        return Err(Error::InvalidSpirv("size is not multiple of 4"));
+    }
+    let magic = code[0] as u32 | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24;
+    if magic != SPIRV_MAGIC {
+        return Err(Error::InvalidSpirv("magic number doesn't match"));
+    }
+    let module = factory
+        .create_shader_module(&code)
+        .map_err(|err| Error::creation(format!("shader module {:?}", path), err))?;
+    Ok(module)
+}
@@ -0,0 +1,15 @@
+#version 450
+
+// Triangle covering the whole viewport without vertex buffers.
+// Draw with 3 vertices. `uv` is (0, 0) at the top-left corner and (1, 1) at the bottom-right one.
+
+layout(location = 0) out vec2 uv;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+void main() {
+    uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
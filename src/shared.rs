@@ -0,0 +1,132 @@
+//! `Shared` reference-counted resources.
+//! When the last reference is dropped resource is sent back to the `Factory` that shared it,
+//! which destroys it after commands of the current frame complete.
+//! Resources can be shared freely across the application without threading them back to `Factory::destroy_*`.
+//!
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use hal::Backend;
+
+use escape::{Escape, Terminal};
+use factory::{RelevantBuffer, RelevantImage};
+use leak::LiveResource;
+
+/// Reference-counted resource destroyed by the `Factory` after the last reference is dropped.
+/// Created with `Factory::share_*` methods.
+#[derive(Debug)]
+pub struct Shared<T> {
+    inner: Arc<Escape<T>>,
+    tracked: Option<Arc<LiveResource>>,
+}
+
+impl<T> Shared<T> {
+    pub(crate) fn new(escape: Escape<T>) -> Self {
+        Shared {
+            inner: Arc::new(escape),
+            tracked: None,
+        }
+    }
+
+    /// Share resource registered in the `LeakTracker`.
+    /// Token is kept alive until the last reference is dropped.
+    pub(crate) fn tracked(escape: Escape<T>, tracked: Arc<LiveResource>) -> Self {
+        Shared {
+            inner: Arc::new(escape),
+            tracked: Some(tracked),
+        }
+    }
+
+    /// Check if both references point to the same resource.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        Arc::ptr_eq(&this.inner, &other.inner)
+    }
+
+    /// Get number of references to the resource.
+    pub fn strong_count(this: &Self) -> usize {
+        Arc::strong_count(&this.inner)
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Shared {
+            inner: self.inner.clone(),
+            tracked: self.tracked.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &**self.inner
+    }
+}
+
+/// Shared buffer. See `Factory::share_buffer`.
+pub type SharedBuffer<B: Backend> = Shared<RelevantBuffer<B>>;
+
+/// Shared image. See `Factory::share_image`.
+pub type SharedImage<B: Backend> = Shared<RelevantImage<B>>;
+
+/// Shared image view. See `Factory::share_image_view`.
+pub type SharedImageView<B: Backend> = Shared<B::ImageView>;
+
+/// Shared sampler. See `Factory::share_sampler`.
+pub type SharedSampler<B: Backend> = Shared<B::Sampler>;
+
+/// Shared render pass. See `Factory::share_render_pass`.
+pub type SharedRenderPass<B: Backend> = Shared<B::RenderPass>;
+
+/// Shared framebuffer. See `Factory::share_framebuffer`.
+pub type SharedFramebuffer<B: Backend> = Shared<B::Framebuffer>;
+
+/// Shared graphics pipeline. See `Factory::share_graphics_pipeline`.
+pub type SharedGraphicsPipeline<B: Backend> = Shared<B::GraphicsPipeline>;
+
+/// Shared compute pipeline. See `Factory::share_compute_pipeline`.
+pub type SharedComputePipeline<B: Backend> = Shared<B::ComputePipeline>;
+
+/// Terminals receiving shared objects other than buffers and images when their last reference is dropped.
+/// Buffers and images are returned to the same terminals `Buffer` and `Image` use.
+#[derive(Debug)]
+pub(crate) struct SharedTerminals<B: Backend> {
+    pub(crate) views: Terminal<B::ImageView>,
+    pub(crate) samplers: Terminal<B::Sampler>,
+    pub(crate) render_passes: Terminal<B::RenderPass>,
+    pub(crate) framebuffers: Terminal<B::Framebuffer>,
+    pub(crate) graphics_pipelines: Terminal<B::GraphicsPipeline>,
+    pub(crate) compute_pipelines: Terminal<B::ComputePipeline>,
+}
+
+impl<B> SharedTerminals<B>
+where
+    B: Backend,
+{
+    pub(crate) fn new() -> Self {
+        SharedTerminals {
+            views: Terminal::new(),
+            samplers: Terminal::new(),
+            render_passes: Terminal::new(),
+            framebuffers: Terminal::new(),
+            graphics_pipelines: Terminal::new(),
+            compute_pipelines: Terminal::new(),
+        }
+    }
+}
+
+#[test]
+fn shared_resource_is_tracked_until_last_reference_is_dropped() {
+    use leak::LeakTracker;
+
+    let mut leaks = LeakTracker::new();
+    let terminal = Terminal::new();
+    let shared = Shared::tracked(terminal.escape(1u32), leaks.track("Buffer of 4 bytes".to_string()));
+    let other = shared.clone();
+    drop(shared);
+    assert_eq!(leaks.leaks().len(), 1);
+    drop(other);
+    assert!(leaks.leaks().is_empty());
+}
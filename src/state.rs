@@ -0,0 +1,339 @@
+//! State tracking of `Buffer`s and `Image`s created by `Factory`.
+//! Each resource remembers access, layout, stages and queue family its last recorded use left it in,
+//! so `Factory::transition_to` generates barriers to the next use instead of them being written by hand.
+//! Tracking assumes commands are executed in order they are recorded.
+//! Barriers of `hal` can't transfer ownership between queue families, so resource stays owned by
+//! the family that used it first and use on another family is rejected.
+//!
+
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut, Range};
+
+use hal::Backend;
+use hal::buffer::{Access as BufferAccess, State as BufferState};
+use hal::command::RawCommandBuffer;
+use hal::format::{Aspects, Format};
+use hal::image::{Access as ImageAccess, ImageLayout, Kind, Level, State as ImageState, SubresourceRange};
+use hal::memory::Barrier;
+use hal::pso::PipelineStage;
+use hal::queue::QueueFamilyId;
+
+use Error;
+use attachment::depth_stencil_aspects;
+use factory::{Buffer, Image};
+use texture::kind_layers;
+
+/// State the last recorded use left the resource in.
+#[derive(Clone, Debug)]
+pub struct Tracking<S> {
+    state: S,
+    stages: PipelineStage,
+    family: Option<QueueFamilyId>,
+}
+
+impl<S> Tracking<S>
+where
+    S: TrackedState,
+{
+    pub(crate) fn new(state: S) -> Self {
+        Tracking {
+            state,
+            stages: PipelineStage::TOP_OF_PIPE,
+            family: None,
+        }
+    }
+
+    /// Get access and layout of the last use.
+    /// Accesses of consecutive reads are accumulated.
+    pub fn state(&self) -> S {
+        self.state
+    }
+
+    /// Get stages of the last use.
+    /// Stages of consecutive reads are accumulated.
+    pub fn stages(&self) -> PipelineStage {
+        self.stages
+    }
+
+    /// Get queue family owning the resource. `None` until the resource is used on a queue.
+    pub fn family(&self) -> Option<QueueFamilyId> {
+        self.family
+    }
+
+    /// Set state after barriers recorded by hand on queue of `family`.
+    pub fn assume(&mut self, state: S, stages: PipelineStage, family: QueueFamilyId) {
+        self.state = state;
+        self.stages = stages;
+        self.family = Some(family);
+    }
+
+    /// Update state for use in `state` at `stages` on queue of `family`.
+    /// Returns barrier to record before the use, if any.
+    fn transition(&mut self, state: S, stages: PipelineStage, family: QueueFamilyId) -> Result<Option<Step<S>>, Error> {
+        match self.family {
+            Some(owner) if owner != family => {
+                return Err(Error::QueueFamilyMismatch {
+                    expected: owner,
+                    found: family,
+                });
+            }
+            _ => {}
+        }
+        self.family = Some(family);
+
+        if self.state == self.state.released() && !self.state.changes_layout(&state) {
+            // Nothing to wait for.
+            self.state = state;
+            self.stages = stages;
+            return Ok(None);
+        }
+        if !self.state.is_write() && !state.is_write() && !self.state.changes_layout(&state) {
+            // Reads don't depend on each other. Next write waits for all of them.
+            self.state = self.state.merge(state);
+            self.stages |= stages;
+            return Ok(None);
+        }
+        let step = Step {
+            states: self.state..state,
+            stages: self.stages..stages,
+        };
+        self.state = state;
+        self.stages = stages;
+        Ok(Some(step))
+    }
+}
+
+/// Tracked state of `Image` along with its subresources barriers cover.
+#[derive(Clone, Debug)]
+pub struct ImageTracking {
+    tracking: Tracking<ImageState>,
+    range: SubresourceRange,
+    format: Format,
+}
+
+impl ImageTracking {
+    pub(crate) fn new(kind: Kind, levels: Level, format: Format) -> Self {
+        ImageTracking {
+            tracking: Tracking::new((ImageAccess::empty(), ImageLayout::Undefined)),
+            range: SubresourceRange {
+                aspects: format_aspects(format),
+                levels: 0..levels,
+                layers: 0..kind_layers(kind),
+            },
+            format,
+        }
+    }
+
+    /// Get layout of the last use.
+    pub fn layout(&self) -> ImageLayout {
+        self.tracking.state.1
+    }
+
+    /// Get all subresources of the image.
+    pub fn range(&self) -> SubresourceRange {
+        self.range.clone()
+    }
+
+    /// Get format the image was created with.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+}
+
+impl Deref for ImageTracking {
+    type Target = Tracking<ImageState>;
+
+    fn deref(&self) -> &Tracking<ImageState> {
+        &self.tracking
+    }
+}
+
+impl DerefMut for ImageTracking {
+    fn deref_mut(&mut self) -> &mut Tracking<ImageState> {
+        &mut self.tracking
+    }
+}
+
+/// Tracked state of `Buffer`.
+pub type BufferTracking = Tracking<BufferState>;
+
+/// State of buffer or image the tracking works with.
+pub trait TrackedState: Copy + Debug + PartialEq {
+    /// Check if the access writes the resource.
+    fn is_write(&self) -> bool;
+
+    /// Check if transition to `other` changes layout.
+    fn changes_layout(&self, other: &Self) -> bool;
+
+    /// Combine accesses of two reads.
+    fn merge(self, other: Self) -> Self;
+
+    /// Get state with no access in the same layout.
+    fn released(&self) -> Self;
+}
+
+impl TrackedState for BufferState {
+    fn is_write(&self) -> bool {
+        self.intersects(
+            BufferAccess::SHADER_WRITE | BufferAccess::TRANSFER_WRITE | BufferAccess::HOST_WRITE
+                | BufferAccess::MEMORY_WRITE,
+        )
+    }
+
+    fn changes_layout(&self, _other: &Self) -> bool {
+        false
+    }
+
+    fn merge(self, other: Self) -> Self {
+        self | other
+    }
+
+    fn released(&self) -> Self {
+        BufferAccess::empty()
+    }
+}
+
+impl TrackedState for ImageState {
+    fn is_write(&self) -> bool {
+        self.0.intersects(
+            ImageAccess::SHADER_WRITE | ImageAccess::COLOR_ATTACHMENT_WRITE
+                | ImageAccess::DEPTH_STENCIL_ATTACHMENT_WRITE | ImageAccess::TRANSFER_WRITE
+                | ImageAccess::HOST_WRITE | ImageAccess::MEMORY_WRITE,
+        )
+    }
+
+    fn changes_layout(&self, other: &Self) -> bool {
+        self.1 != other.1
+    }
+
+    fn merge(self, other: Self) -> Self {
+        (self.0 | other.0, self.1)
+    }
+
+    fn released(&self) -> Self {
+        (ImageAccess::empty(), self.1)
+    }
+}
+
+/// Get all aspects of the format.
+pub(crate) fn format_aspects(format: Format) -> Aspects {
+    match format {
+        Format::D16Unorm
+        | Format::X8D24Unorm
+        | Format::D32Float
+        | Format::S8Uint
+        | Format::D16UnormS8Uint
+        | Format::D24UnormS8Uint
+        | Format::D32FloatS8Uint => depth_stencil_aspects(format),
+        _ => Aspects::COLOR,
+    }
+}
+
+/// Barrier computed from tracked state.
+struct Step<S> {
+    states: Range<S>,
+    stages: Range<PipelineStage>,
+}
+
+/// Resource which state is tracked. Implemented for `Buffer` and `Image`.
+/// See `Factory::transition_to`.
+pub trait Tracked<B: Backend> {
+    /// State resource is used in. `buffer::State` for buffers and `image::State` for images.
+    type State: TrackedState;
+
+    /// Get queue family owning the resource.
+    fn family(&self) -> Option<QueueFamilyId>;
+
+    /// Record barriers from tracked state to `state` used at `stages` on queue of `family`.
+    /// Fails if the resource is owned by another family.
+    fn transition(
+        &mut self,
+        cbuf: &mut B::CommandBuffer,
+        state: Self::State,
+        stages: PipelineStage,
+        family: QueueFamilyId,
+    ) -> Result<(), Error>;
+}
+
+impl<B> Tracked<B> for Buffer<B>
+where
+    B: Backend,
+{
+    type State = BufferState;
+
+    fn family(&self) -> Option<QueueFamilyId> {
+        self.state().family()
+    }
+
+    fn transition(
+        &mut self,
+        cbuf: &mut B::CommandBuffer,
+        state: BufferState,
+        stages: PipelineStage,
+        family: QueueFamilyId,
+    ) -> Result<(), Error> {
+        if let Some(step) = self.state_mut().transition(state, stages, family)? {
+            cbuf.pipeline_barrier(
+                step.stages,
+                Some(Barrier::Buffer {
+                    states: step.states,
+                    target: self.raw(),
+                }),
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<B> Tracked<B> for Image<B>
+where
+    B: Backend,
+{
+    type State = ImageState;
+
+    fn family(&self) -> Option<QueueFamilyId> {
+        self.state().family()
+    }
+
+    fn transition(
+        &mut self,
+        cbuf: &mut B::CommandBuffer,
+        state: ImageState,
+        stages: PipelineStage,
+        family: QueueFamilyId,
+    ) -> Result<(), Error> {
+        let range = self.state().range();
+        if let Some(step) = self.state_mut().transition(state, stages, family)? {
+            cbuf.pipeline_barrier(
+                step.stages,
+                Some(Barrier::Image {
+                    states: step.states,
+                    target: self.raw(),
+                    range,
+                }),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn consecutive_reads_share_barrier() {
+    let mut tracking = Tracking::new(BufferAccess::empty());
+    let family = QueueFamilyId(0);
+    assert!(tracking.transition(BufferAccess::TRANSFER_WRITE, PipelineStage::TRANSFER, family).unwrap().is_none());
+    let step = tracking.transition(BufferAccess::SHADER_READ, PipelineStage::VERTEX_SHADER, family).unwrap().unwrap();
+    assert_eq!(step.states, BufferAccess::TRANSFER_WRITE..BufferAccess::SHADER_READ);
+    assert!(tracking.transition(BufferAccess::SHADER_READ, PipelineStage::FRAGMENT_SHADER, family).unwrap().is_none());
+    let step = tracking.transition(BufferAccess::TRANSFER_WRITE, PipelineStage::TRANSFER, family).unwrap().unwrap();
+    assert_eq!(step.stages.start, PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER);
+}
+
+#[test]
+fn use_on_another_family_is_rejected() {
+    let mut tracking = Tracking::new((ImageAccess::empty(), ImageLayout::Undefined));
+    let state = (ImageAccess::SHADER_READ, ImageLayout::ShaderReadOnlyOptimal);
+    tracking.transition(state, PipelineStage::FRAGMENT_SHADER, QueueFamilyId(0)).unwrap();
+    assert!(tracking.transition(state, PipelineStage::COMPUTE_SHADER, QueueFamilyId(1)).is_err());
+    assert_eq!(tracking.family(), Some(QueueFamilyId(0)));
+}
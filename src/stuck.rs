@@ -0,0 +1,110 @@
+//! Report of work that didn't complete in time.
+//! `Renderer::wait_idle_timeout` collects it when frames don't complete before timeout,
+//! so hangs on shutdown can be traced to frames, queues and subsystems that submitted the commands.
+//!
+
+use std::fmt;
+
+use renderer::TargetId;
+use submit::QueueRole;
+
+/// Frame which fences aren't signaled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StuckFrame {
+    /// Index of the frame.
+    pub index: u64,
+
+    /// Number of fences the frame is complete with.
+    pub fences: usize,
+
+    /// Number of those fences not signaled yet.
+    pub unsignaled: usize,
+}
+
+/// Frame of the target which commands may be incomplete.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StuckTarget {
+    /// Target the frame was drawn for.
+    pub target: TargetId,
+
+    /// Index of the render that recorded commands of the frame.
+    pub render: usize,
+
+    /// Index of the frame.
+    pub frame: u64,
+}
+
+/// Work submitted to the device that may be incomplete.
+/// Lists are sorted by frame index.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StuckWork {
+    /// Frames which fences aren't signaled.
+    pub frames: Vec<StuckFrame>,
+
+    /// Last frames submissions to queues were made with, for frames that aren't complete.
+    pub queues: Vec<(QueueRole, u64)>,
+
+    /// Frames of uploads made through the `Factory` that aren't retired.
+    pub uploads: Vec<u64>,
+
+    /// Frames of uploads made by recorders of the `Factory` that aren't retired.
+    pub recorders: Vec<u64>,
+
+    /// Frames of acquisitions of uploaded resources by general family that aren't retired.
+    pub acquires: Vec<u64>,
+
+    /// Frames of compute commands recorded by the `Factory` that aren't retired.
+    pub computes: Vec<u64>,
+
+    /// Frames of targets that may be incomplete.
+    pub targets: Vec<StuckTarget>,
+}
+
+impl StuckWork {
+    /// Check if nothing is reported.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty() && self.queues.is_empty() && self.uploads.is_empty() && self.recorders.is_empty()
+            && self.acquires.is_empty() && self.computes.is_empty() && self.targets.is_empty()
+    }
+}
+
+impl fmt::Display for StuckWork {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(fmt, "no outstanding work");
+        }
+        let mut sep = "";
+        for frame in &self.frames {
+            write!(
+                fmt,
+                "{}frame {} ({} of {} fences unsignaled)",
+                sep, frame.index, frame.unsignaled, frame.fences
+            )?;
+            sep = ", ";
+        }
+        for &(role, frame) in &self.queues {
+            write!(fmt, "{}{:?} queue submitted frame {}", sep, role, frame)?;
+            sep = ", ";
+        }
+        for &(name, frames) in &[
+            ("uploads", &self.uploads),
+            ("recorder uploads", &self.recorders),
+            ("acquisitions", &self.acquires),
+            ("computes", &self.computes),
+        ] {
+            if !frames.is_empty() {
+                write!(fmt, "{}{} of frames {:?}", sep, name, frames)?;
+                sep = ", ";
+            }
+        }
+        for target in &self.targets {
+            write!(
+                fmt,
+                "{}render {} of target {:?} for frame {}",
+                sep, target.render, target.target, target.frame
+            )?;
+            sep = ", ";
+        }
+        Ok(())
+    }
+}
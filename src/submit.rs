@@ -0,0 +1,400 @@
+//! Submission scheduler. `Submitter` owns queues of the device.
+//! Command buffers collected into `Schedule` during the frame are submitted with single submission per queue,
+//! and queues are synchronized with semaphores `Submitter` manages.
+//!
+
+use std::collections::VecDeque;
+
+use hal::{Backend, Device};
+use hal::pso::PipelineStage;
+use hal::queue::{Compute, General, QueueFamilyId, QueueGroup, RawCommandQueue, RawSubmission, Transfer};
+
+use Error;
+use frame::Frames;
+
+/// Role of the queue submissions are scheduled to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueueRole {
+    /// General queue. It presents frames and takes submissions of missing queues.
+    Graphics = 0,
+
+    /// Async compute queue.
+    Compute = 1,
+
+    /// Dedicated transfer queue.
+    Transfer = 2,
+}
+
+pub(crate) const ROLES: [QueueRole; 3] = [QueueRole::Graphics, QueueRole::Compute, QueueRole::Transfer];
+
+/// Owner of the device queues.
+/// Compute and transfer submissions go to graphics queue if there is no queue for them.
+pub struct Submitter<B: Backend> {
+    graphics: QueueGroup<B, General>,
+    compute: Option<QueueGroup<B, Compute>>,
+    transfer: Option<QueueGroup<B, Transfer>>,
+    general_compute: bool,
+    free: Vec<B::Semaphore>,
+    used: VecDeque<(Vec<B::Semaphore>, u64)>,
+    submitted: [Option<u64>; 3],
+}
+
+impl<B> Submitter<B>
+where
+    B: Backend,
+{
+    /// Create submitter owning queue groups.
+    pub fn new(
+        graphics: QueueGroup<B, General>,
+        compute: Option<QueueGroup<B, Compute>>,
+        transfer: Option<QueueGroup<B, Transfer>>,
+    ) -> Self {
+        Submitter {
+            graphics,
+            compute,
+            transfer,
+            general_compute: false,
+            free: Vec::new(),
+            used: VecDeque::new(),
+            submitted: [None; 3],
+        }
+    }
+
+    /// Dedicate the last queue of general group to compute submissions.
+    /// Resources stay in the same family, so compute and graphics queues share them without ownership transfers.
+    /// Fails if there is compute queue group or general group has single queue.
+    pub fn reserve_general_compute(&mut self) -> Result<(), Error> {
+        if self.compute.is_some() || self.graphics.queues.len() < 2 {
+            return Err(Error::Unsupported("Async compute on general queue without spare queue or with compute queue"));
+        }
+        self.general_compute = true;
+        Ok(())
+    }
+
+    /// Get general queue group.
+    pub fn graphics(&self) -> &QueueGroup<B, General> {
+        &self.graphics
+    }
+
+    /// Get general queue group mutably.
+    pub fn graphics_mut(&mut self) -> &mut QueueGroup<B, General> {
+        &mut self.graphics
+    }
+
+    /// Get role of the queue submissions of `role` go to.
+    pub fn resolve(&self, role: QueueRole) -> QueueRole {
+        match role {
+            QueueRole::Compute if self.compute.is_some() || self.general_compute => QueueRole::Compute,
+            QueueRole::Transfer if self.transfer.is_some() => QueueRole::Transfer,
+            _ => QueueRole::Graphics,
+        }
+    }
+
+    /// Get queue family command buffers submitted to `role` must be allocated from.
+    pub fn family(&self, role: QueueRole) -> QueueFamilyId {
+        match self.resolve(role) {
+            QueueRole::Graphics => self.graphics.family(),
+            QueueRole::Compute => self.compute
+                .as_ref()
+                .map_or(self.graphics.family(), |compute| compute.family()),
+            QueueRole::Transfer => self.transfer.as_ref().unwrap().family(),
+        }
+    }
+
+    /// Get raw queue of the role.
+    pub fn queue(&mut self, role: QueueRole) -> &mut B::CommandQueue {
+        match self.resolve(role) {
+            QueueRole::Graphics => self.graphics.queues[0].as_mut(),
+            QueueRole::Compute => match self.compute {
+                Some(ref mut compute) => compute.queues[0].as_mut(),
+                None => self.graphics.queues.last_mut().unwrap().as_mut(),
+            },
+            QueueRole::Transfer => self.transfer.as_mut().unwrap().queues[0].as_mut(),
+        }
+    }
+
+    /// Get index of the last frame `Schedule::flush` submitted to the queue of the role with.
+    pub fn last_submitted(&self, role: QueueRole) -> Option<u64> {
+        self.submitted[self.resolve(role) as usize]
+    }
+
+    /// Start collecting submissions of current frame of `frames`.
+    /// Semaphores used by complete frames are reused.
+    pub fn schedule<'a>(&'a mut self, device: &B::Device, frames: &Frames<B>) -> Schedule<'a, B> {
+        while self.used
+            .front()
+            .map_or(false, |&(_, frame)| frames.is_complete(frame))
+        {
+            let (semaphores, _) = self.used.pop_front().unwrap();
+            self.free.extend(semaphores);
+        }
+        // Enough for a dependency between each pair of queues and for each queue following general one.
+        while self.free.len() < ROLES.len() * (ROLES.len() - 1) + self.graphics.queues.len() {
+            self.free.push(device.create_semaphore());
+        }
+        Schedule {
+            frame: frames.current(),
+            submitter: self,
+            submits: ROLES.iter().map(|_| Submit::new()).collect(),
+            dependencies: Vec::new(),
+            followers: Vec::new(),
+        }
+    }
+
+    /// Destroy semaphores. Queues must be idle.
+    pub fn dispose(self, device: &B::Device) {
+        for (semaphores, _) in self.used {
+            for semaphore in semaphores {
+                device.destroy_semaphore(semaphore);
+            }
+        }
+        for semaphore in self.free {
+            device.destroy_semaphore(semaphore);
+        }
+    }
+}
+
+/// Submissions to queues collected during the frame.
+/// They are submitted with `Schedule::flush`.
+pub struct Schedule<'a, B: Backend + 'a> {
+    frame: u64,
+    submitter: &'a mut Submitter<B>,
+    submits: Vec<Submit<'a, B>>,
+    dependencies: Vec<(QueueRole, QueueRole, PipelineStage)>,
+    followers: Vec<(usize, PipelineStage)>,
+}
+
+impl<'a, B> Schedule<'a, B>
+where
+    B: Backend,
+{
+    /// Add command buffers to the submission of the queue.
+    /// They are submitted in order they are added.
+    pub fn submit<I>(&mut self, role: QueueRole, cbufs: I)
+    where
+        I: IntoIterator<Item = &'a B::CommandBuffer>,
+    {
+        let role = self.submitter.resolve(role);
+        self.submits[role as usize].cbufs.extend(cbufs);
+    }
+
+    /// Let submission of the queue wait for the semaphore.
+    pub fn wait(&mut self, role: QueueRole, semaphore: &'a B::Semaphore, stages: PipelineStage) {
+        let role = self.submitter.resolve(role);
+        self.submits[role as usize].waits.push((semaphore, stages));
+    }
+
+    /// Let submission of the queue signal the semaphore.
+    pub fn signal(&mut self, role: QueueRole, semaphore: &'a B::Semaphore) {
+        let role = self.submitter.resolve(role);
+        self.submits[role as usize].signals.push(semaphore);
+    }
+
+    /// Let submission of the queue signal the fence.
+    /// Panics if submission already has fence.
+    pub fn fence(&mut self, role: QueueRole, fence: &'a B::Fence) {
+        let role = self.submitter.resolve(role);
+        let ref mut submit = self.submits[role as usize];
+        assert!(submit.fence.is_none(), "Submission to {:?} queue already has fence", role);
+        submit.fence = Some(fence);
+    }
+
+    /// Let submission of the queue wait for submission of queue `on` at `stages`.
+    /// Dependency between roles resolved to the same queue is dropped.
+    /// Command buffers submitted to the same queue must synchronize with barriers.
+    pub fn depend(&mut self, role: QueueRole, on: QueueRole, stages: PipelineStage) {
+        self.dependencies.push((role, on, stages));
+    }
+
+    /// Let queue `index` of general group wait at `stages` for submission of `QueueRole::Graphics`,
+    /// and so for everything it depends on.
+    /// Commands submitted to that queue after the flush are ordered after them.
+    /// Queue `QueueRole::Graphics` resolves to needs no waiting.
+    pub fn follow_graphics(&mut self, index: usize, stages: PipelineStage) {
+        self.followers.push((index, stages));
+    }
+
+    /// Submit all collected submissions. Queues are submitted to in order of their dependencies.
+    pub fn flush(self) -> Result<(), Error> {
+        let Schedule {
+            frame,
+            submitter,
+            submits,
+            dependencies,
+            followers,
+        } = self;
+
+        let dependencies = dependencies
+            .into_iter()
+            .map(|(role, on, stages)| (submitter.resolve(on) as usize, submitter.resolve(role) as usize, stages))
+            .collect::<Vec<_>>();
+        let plan = plan(&dependencies, &followers)?;
+
+        let semaphores = (0..plan.edges.len() + plan.followers.len())
+            .map(|_| {
+                submitter
+                    .free
+                    .pop()
+                    .expect("Semaphores are reserved by `Submitter::schedule`")
+            })
+            .collect::<Vec<_>>();
+        let (edge_semaphores, follower_semaphores) = semaphores.split_at(plan.edges.len());
+
+        for &index in &plan.order {
+            let ref submit = submits[index];
+            let mut waits = submit.waits.clone();
+            let mut signals = submit.signals.clone();
+            for (&(from, to, stages), semaphore) in plan.edges.iter().zip(edge_semaphores) {
+                if to == index {
+                    waits.push((semaphore, stages));
+                }
+                if from == index {
+                    signals.push(semaphore);
+                }
+            }
+            if index == QueueRole::Graphics as usize {
+                signals.extend(follower_semaphores);
+            }
+            if submit.cbufs.is_empty() && waits.is_empty() && signals.is_empty() && submit.fence.is_none() {
+                continue;
+            }
+            unsafe {
+                submitter.queue(ROLES[index]).submit_raw(
+                    RawSubmission {
+                        cmd_buffers: submit.cbufs.iter().cloned(),
+                        wait_semaphores: &waits,
+                        signal_semaphores: &signals,
+                    },
+                    submit.fence,
+                );
+            }
+            submitter.submitted[index] = Some(frame);
+        }
+
+        for (&(queue, stages), semaphore) in plan.followers.iter().zip(follower_semaphores) {
+            unsafe {
+                // Wait of submission orders all commands submitted later to the queue.
+                submitter.graphics.queues[queue].as_mut().submit_raw(
+                    RawSubmission {
+                        cmd_buffers: None::<&B::CommandBuffer>,
+                        wait_semaphores: &[(semaphore, stages)],
+                        signal_semaphores: &[],
+                    },
+                    None,
+                );
+            }
+        }
+
+        submitter.used.push_back((semaphores, frame));
+        Ok(())
+    }
+}
+
+/// Order of submissions and semaphores between them.
+#[derive(Debug, PartialEq, Eq)]
+struct Plan {
+    /// Roles in order their submissions are made.
+    order: Vec<usize>,
+
+    /// Semaphores between submissions of roles `(from, to, stages)`.
+    edges: Vec<(usize, usize, PipelineStage)>,
+
+    /// Queues of general group waiting for submission of `QueueRole::Graphics` `(queue, stages)`.
+    followers: Vec<(usize, PipelineStage)>,
+}
+
+/// Plan submissions for `dependencies` between resolved roles `(from, to, stages)`
+/// and `followers` of general queue.
+/// Dependencies and followers of the same queues are merged.
+fn plan(dependencies: &[(usize, usize, PipelineStage)], followers: &[(usize, PipelineStage)]) -> Result<Plan, Error> {
+    fn merge<K: PartialEq>(merged: &mut Vec<(K, PipelineStage)>, key: K, stages: PipelineStage) {
+        match merged.iter().position(|&(ref other, _)| *other == key) {
+            Some(index) => merged[index].1 |= stages,
+            None => merged.push((key, stages)),
+        }
+    }
+
+    let mut merged = Vec::new();
+    for &(from, to, stages) in dependencies {
+        if to != from {
+            merge(&mut merged, (from, to), stages);
+        }
+    }
+    let edges = merged
+        .into_iter()
+        .map(|((from, to), stages)| (from, to, stages))
+        .collect::<Vec<_>>();
+
+    // Queue 0 of general group is the one `QueueRole::Graphics` resolves to.
+    let mut merged = Vec::new();
+    for &(queue, stages) in followers {
+        if queue != 0 {
+            merge(&mut merged, queue, stages);
+        }
+    }
+
+    // Signal must be submitted before wait.
+    let mut order = Vec::with_capacity(ROLES.len());
+    while order.len() < ROLES.len() {
+        let next = (0..ROLES.len()).find(|&index| {
+            !order.contains(&index) && edges.iter().all(|&(from, to, _)| to != index || order.contains(&from))
+        });
+        match next {
+            Some(index) => order.push(index),
+            None => return Err(Error::InvalidArgument("Dependencies between queues form a cycle")),
+        }
+    }
+
+    Ok(Plan {
+        order,
+        edges,
+        followers: merged,
+    })
+}
+
+#[test]
+fn second_general_queue_follows_graphics() {
+    let consumers = PipelineStage::VERTEX_SHADER | PipelineStage::FRAGMENT_SHADER;
+    let (graphics, transfer) = (QueueRole::Graphics as usize, QueueRole::Transfer as usize);
+    // Targets render on both queues of general group.
+    let plan = plan(
+        &[(transfer, graphics, consumers)],
+        &[(0, consumers), (1, PipelineStage::VERTEX_SHADER), (1, PipelineStage::FRAGMENT_SHADER)],
+    ).unwrap();
+    assert_eq!(plan.edges, vec![(transfer, graphics, consumers)]);
+    assert_eq!(plan.followers, vec![(1, consumers)]);
+    let position = |role| plan.order.iter().position(|&index| index == role).unwrap();
+    assert!(position(transfer) < position(graphics));
+}
+
+#[test]
+fn cyclic_dependencies_are_rejected() {
+    let stages = PipelineStage::TRANSFER;
+    let (graphics, compute) = (QueueRole::Graphics as usize, QueueRole::Compute as usize);
+    assert!(plan(&[(graphics, compute, stages), (compute, graphics, stages)], &[]).is_err());
+    // Dependencies resolved to the same queue are dropped.
+    let plan = plan(&[(graphics, graphics, stages)], &[]).unwrap();
+    assert!(plan.edges.is_empty());
+}
+
+/// Submission to single queue.
+struct Submit<'a, B: Backend + 'a> {
+    cbufs: Vec<&'a B::CommandBuffer>,
+    waits: Vec<(&'a B::Semaphore, PipelineStage)>,
+    signals: Vec<&'a B::Semaphore>,
+    fence: Option<&'a B::Fence>,
+}
+
+impl<'a, B> Submit<'a, B>
+where
+    B: Backend,
+{
+    fn new() -> Self {
+        Submit {
+            cbufs: Vec::new(),
+            waits: Vec::new(),
+            signals: Vec::new(),
+            fence: None,
+        }
+    }
+}
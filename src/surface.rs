@@ -0,0 +1,93 @@
+//! Negotiation of surface format and color space from user preferences.
+//! 
+
+use hal::Backend;
+use hal::format::{ChannelType, Format};
+
+use factory::Factory;
+
+/// Color space presentation engine interprets swapchain images in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// sRGB primaries with sRGB transfer function.
+    SrgbNonLinear,
+
+    /// sRGB primaries with linear transfer function and values outside `[0, 1]` (scRGB).
+    ExtendedSrgbLinear,
+
+    /// BT.2020 primaries with ST 2084 (PQ) transfer function.
+    Hdr10St2084,
+}
+
+/// Format of swapchain images with color space they are presented in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SurfaceFormat {
+    /// Format of swapchain images.
+    pub format: Format,
+
+    /// Color space of swapchain images.
+    pub color_space: ColorSpace,
+}
+
+impl SurfaceFormat {
+    /// sRGB non-linear color space with given format.
+    pub fn srgb(format: Format) -> Self {
+        SurfaceFormat {
+            format,
+            color_space: ColorSpace::SrgbNonLinear,
+        }
+    }
+
+    /// Check if shaders must apply sRGB transfer function themselves before writing to the images.
+    /// It's the case when format is not sRGB but images are presented in sRGB non-linear color space.
+    pub fn needs_manual_srgb_encoding(&self) -> bool {
+        self.color_space == ColorSpace::SrgbNonLinear && self.format.base_format().1 != ChannelType::Srgb
+    }
+
+    /// Check if images are presented in HDR color space.
+    pub fn is_hdr(&self) -> bool {
+        self.color_space != ColorSpace::SrgbNonLinear
+    }
+}
+
+impl<B> Factory<B>
+where
+    B: Backend,
+{
+    /// Get formats the surface supports.
+    /// gfx-hal revision this crate depends on doesn't report color spaces,
+    /// so all formats are reported in sRGB non-linear color space.
+    /// Returns `None` if surface has no preferences.
+    pub fn surface_formats(&self, surface: &B::Surface) -> Option<Vec<SurfaceFormat>> {
+        self.capabilities_and_formats(surface)
+            .1
+            .map(|formats| formats.into_iter().map(SurfaceFormat::srgb).collect())
+    }
+
+    /// Pick first of the `preferences` the surface supports.
+    /// Falls back to the first format supported by the surface with warning.
+    /// Returns `None` if surface supports none of formats and has no preferences.
+    pub fn pick_surface_format(&self, surface: &B::Surface, preferences: &[SurfaceFormat]) -> Option<SurfaceFormat> {
+        let picked = match self.surface_formats(surface) {
+            Some(supported) => preferences
+                .iter()
+                .cloned()
+                .find(|preference| supported.contains(preference))
+                .or_else(|| {
+                    let fallback = supported.first().cloned();
+                    warn!(
+                        "None of preferred surface formats {:?} is supported. Falling back to {:?}",
+                        preferences,
+                        fallback
+                    );
+                    fallback
+                }),
+            None => preferences
+                .iter()
+                .cloned()
+                .find(|preference| preference.color_space == ColorSpace::SrgbNonLinear),
+        };
+        info!("Surface format picked: {:?}", picked);
+        picked
+    }
+}
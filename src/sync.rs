@@ -0,0 +1,110 @@
+//! Pool of semaphores and fences recycled with frames.
+//! Semaphores and fences released to the pool are reused after the frame they were released in is complete,
+//! so submissions don't create and destroy them every frame.
+//!
+
+use std::collections::VecDeque;
+
+use hal::{Backend, Device};
+
+/// Pool of semaphores and fences.
+/// `Factory` owns one and `Renderer` takes semaphores and fences for its frames from it.
+#[derive(Debug)]
+pub struct SyncPool<B: Backend> {
+    semaphores: Vec<B::Semaphore>,
+    fences: Vec<B::Fence>,
+    released: VecDeque<(SyncItem<B>, u64)>,
+}
+
+impl<B> SyncPool<B>
+where
+    B: Backend,
+{
+    /// Create empty pool.
+    pub fn new() -> Self {
+        SyncPool {
+            semaphores: Vec::new(),
+            fences: Vec::new(),
+            released: VecDeque::new(),
+        }
+    }
+
+    /// Get free semaphore or create new one.
+    pub fn acquire_semaphore(&mut self, device: &B::Device) -> B::Semaphore {
+        self.semaphores
+            .pop()
+            .unwrap_or_else(|| device.create_semaphore())
+    }
+
+    /// Get free unsignaled fence or create new one.
+    pub fn acquire_fence(&mut self, device: &B::Device) -> B::Fence {
+        self.fences
+            .pop()
+            .unwrap_or_else(|| device.create_fence(false))
+    }
+
+    /// Return semaphore to the pool.
+    /// It will be reused after commands associated with `frame` complete.
+    /// Nothing submitted after those commands may wait for the semaphore.
+    /// Frames must be released in non-decreasing order.
+    pub fn release_semaphore(&mut self, semaphore: B::Semaphore, frame: u64) {
+        self.released.push_back((SyncItem::Semaphore(semaphore), frame));
+    }
+
+    /// Return fence to the pool.
+    /// It will be reset and reused after commands associated with `frame` complete.
+    /// Fence must be signaled by then if it was submitted.
+    pub fn release_fence(&mut self, fence: B::Fence, frame: u64) {
+        self.released.push_back((SyncItem::Fence(fence), frame));
+    }
+
+    /// Reset signaled fence and make it available right away.
+    pub fn recycle_fence(&mut self, device: &B::Device, fence: B::Fence) {
+        device.reset_fence(&fence);
+        self.fences.push(fence);
+    }
+
+    /// Make semaphores and fences released before `ongoing` frame available again.
+    pub fn clear(&mut self, device: &B::Device, ongoing: u64) {
+        while self.released
+            .front()
+            .map_or(false, |&(_, frame)| frame < ongoing)
+        {
+            match self.released.pop_front().unwrap().0 {
+                SyncItem::Semaphore(semaphore) => self.semaphores.push(semaphore),
+                SyncItem::Fence(fence) => {
+                    device.reset_fence(&fence);
+                    self.fences.push(fence);
+                }
+            }
+        }
+    }
+
+    /// Get number of semaphores and fences ready to be reused.
+    pub fn free(&self) -> (usize, usize) {
+        (self.semaphores.len(), self.fences.len())
+    }
+
+    /// Destroy all semaphores and fences.
+    /// Device must be idle.
+    pub fn dispose(self, device: &B::Device) {
+        for (item, _) in self.released {
+            match item {
+                SyncItem::Semaphore(semaphore) => device.destroy_semaphore(semaphore),
+                SyncItem::Fence(fence) => device.destroy_fence(fence),
+            }
+        }
+        for semaphore in self.semaphores {
+            device.destroy_semaphore(semaphore);
+        }
+        for fence in self.fences {
+            device.destroy_fence(fence);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SyncItem<B: Backend> {
+    Semaphore(B::Semaphore),
+    Fence(B::Fence),
+}
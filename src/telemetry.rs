@@ -0,0 +1,51 @@
+//! Counters and gauges emitted through the `metrics` facade.
+//! Applications install any `metrics` recorder to ship them to their telemetry.
+//! Enabled with `metrics` feature. Without it all functions here are no-ops.
+//!
+//! Emitted metrics:
+//!
+//! * `gfx_render.uploaded_bytes` - counter of bytes uploaded through the `Factory`.
+//! * `gfx_render.staging_bytes_in_flight` - gauge of bytes of staging buffers not yet retired.
+//! * `gfx_render.allocations_per_frame` - gauge of buffers and images created during the last frame.
+//! * `gfx_render.frame_time` - timing of `Renderer::run` in nanoseconds.
+//! * `gfx_render.swapchain_recreations` - counter of swapchain recreations.
+//!
+
+use std::time::Duration;
+
+/// Count bytes uploaded through the `Factory`.
+#[cfg(feature = "metrics")]
+pub(crate) fn uploaded(bytes: u64) {
+    counter!("gfx_render.uploaded_bytes", bytes);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn uploaded(_bytes: u64) {}
+
+/// Report state of the `Factory` at the end of the frame.
+#[cfg(feature = "metrics")]
+pub(crate) fn frame_finished(allocations: u64, staging_bytes: u64) {
+    gauge!("gfx_render.allocations_per_frame", allocations as i64);
+    gauge!("gfx_render.staging_bytes_in_flight", staging_bytes as i64);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn frame_finished(_allocations: u64, _staging_bytes: u64) {}
+
+/// Report CPU time of `Renderer::run`.
+#[cfg(feature = "metrics")]
+pub(crate) fn frame_time(time: Duration) {
+    timing!("gfx_render.frame_time", time.as_secs() * 1_000_000_000 + time.subsec_nanos() as u64);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn frame_time(_time: Duration) {}
+
+/// Count swapchain recreation.
+#[cfg(feature = "metrics")]
+pub(crate) fn swapchain_recreated() {
+    counter!("gfx_render.swapchain_recreations", 1);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn swapchain_recreated() {}
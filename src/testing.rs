@@ -0,0 +1,120 @@
+//! Harness for testing frame-driven logic without GPU.
+//! `FrameDriver` advances `Frames` with fences of empty backend signaled by the test,
+//! so uploads, deferred destruction and other systems keyed off frames can be checked on CI machines.
+//! Enabled with `testing` feature. Device of empty backend must not be called.
+//!
+
+use hal::Backend;
+
+use empty;
+use frame::Frames;
+
+/// Backend for tests. All its objects are plain values.
+pub type TestBackend = empty::Backend;
+
+/// Frame counter advanced by the test instead of the device.
+#[derive(Debug)]
+pub struct FrameDriver<B: Backend = TestBackend> {
+    frames: Frames<B>,
+    completed: Option<u64>,
+}
+
+impl<B> FrameDriver<B>
+where
+    B: Backend,
+    B::Fence: Default,
+{
+    /// Create driver starting from frame 0.
+    pub fn new() -> Self {
+        FrameDriver {
+            frames: Frames::new(),
+            completed: None,
+        }
+    }
+
+    /// Get frame counter to pass to systems under test.
+    pub fn frames(&self) -> &Frames<B> {
+        &self.frames
+    }
+
+    /// Get frame counter mutably.
+    pub fn frames_mut(&mut self) -> &mut Frames<B> {
+        &mut self.frames
+    }
+
+    /// Finish current frame with single fence. Returns index of the frame.
+    pub fn submit(&mut self) -> u64 {
+        let mut frame = self.frames.begin();
+        let index = frame.index();
+        frame.add_fence(B::Fence::default());
+        unsafe {
+            // Fence is signaled only by `complete`.
+            self.frames.finish(frame);
+        }
+        index
+    }
+
+    /// Finish `count` frames. Returns index of the last one.
+    pub fn submit_many(&mut self, count: u64) -> Option<u64> {
+        (0..count).map(|_| self.submit()).last()
+    }
+
+    /// Signal fences of frames up to `index` and let counter find them.
+    /// Returns number of fences signaled.
+    pub fn complete(&mut self, index: u64) -> usize {
+        assert!(index < self.frames.current(), "Frame {} isn't submitted", index);
+        if self.completed.map_or(true, |completed| completed < index) {
+            self.completed = Some(index);
+        }
+        self.poll()
+    }
+
+    /// Let counter find signaled frames. Returns number of fences signaled since last poll.
+    pub fn poll(&mut self) -> usize {
+        let completed = self.completed;
+        self.frames
+            .poll_with(|index, _| completed.map_or(false, |completed| index <= completed))
+            .len()
+    }
+
+    /// Assert that commands of the frame are complete.
+    pub fn assert_complete(&self, index: u64) {
+        assert!(
+            self.frames.is_complete(index),
+            "Frame {} is expected complete, but frames from {} are ongoing",
+            index,
+            self.frames.ongoing()
+        );
+    }
+
+    /// Assert that commands of the frame may be incomplete.
+    pub fn assert_ongoing(&self, index: u64) {
+        assert!(
+            !self.frames.is_complete(index),
+            "Frame {} is expected ongoing, but frames before {} are complete",
+            index,
+            self.frames.ongoing()
+        );
+    }
+}
+
+/// Assert that items are released in expected order.
+pub fn assert_released<T>(released: &[T], expected: &[T])
+where
+    T: PartialEq + ::std::fmt::Debug,
+{
+    assert_eq!(released, expected, "Released items differ from expected");
+}
+
+#[test]
+fn frames_complete_in_order() {
+    let mut driver = FrameDriver::<TestBackend>::new();
+    driver.submit_many(3);
+    driver.assert_ongoing(0);
+    assert_eq!(driver.complete(1), 2);
+    driver.assert_complete(1);
+    driver.assert_ongoing(2);
+    assert_eq!(driver.poll(), 0);
+    assert_eq!(driver.complete(2), 1);
+    driver.assert_complete(2);
+}
@@ -0,0 +1,405 @@
+//! `Texture` bundles image with its default view, optional sampler and current layout.
+//! 
+
+use std::ops::Range;
+
+use hal::{Backend, Device};
+use hal::command::BufferImageCopy;
+use hal::device::Extent;
+use hal::format::{Aspects, Format, Swizzle};
+use hal::image::{Access, ImageLayout, Kind, Layer, Level, Offset, SamplerInfo, SubresourceLayers,
+                 SubresourceRange, Usage as ImageUsage};
+use hal::memory::Properties;
+use hal::pso::PipelineStage;
+
+use Error;
+use factory::{Factory, Image};
+use state::ImageTracking;
+use upload::UploadId;
+
+/// Alignment of regions packed into single staging buffer. Multiple of 4 and of power-of-two texel and block sizes.
+pub(crate) const REGION_ALIGNMENT: usize = 16;
+
+/// Image with default view, optional sampler and tracked layout.
+#[derive(Debug)]
+pub struct Texture<B: Backend> {
+    image: Image<B>,
+    view: B::ImageView,
+    sampler: Option<B::Sampler>,
+    kind: Kind,
+    levels: Level,
+    format: Format,
+    layout: ImageLayout,
+    rollback: Option<(UploadId, ImageTracking, ImageLayout)>,
+}
+
+impl<B> Texture<B>
+where
+    B: Backend,
+{
+    /// Get image of the texture.
+    pub fn image(&self) -> &Image<B> {
+        &self.image
+    }
+
+    /// Get default view of the whole image.
+    pub fn view(&self) -> &B::ImageView {
+        &self.view
+    }
+
+    /// Get sampler if texture was created with one.
+    pub fn sampler(&self) -> Option<&B::Sampler> {
+        self.sampler.as_ref()
+    }
+
+    /// Get dimensions of the texture.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Get number of mip-levels.
+    pub fn levels(&self) -> Level {
+        self.levels
+    }
+
+    /// Get format of the texture.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Get layout the image will be in after all recorded commands complete.
+    pub fn layout(&self) -> ImageLayout {
+        self.layout
+    }
+
+    /// Update tracked layout after recording layout transition.
+    pub fn set_layout(&mut self, layout: ImageLayout) {
+        self.layout = layout;
+    }
+
+    /// Roll back tracked state of the texture to one before uploads with tag `id`
+    /// after they are cancelled with `Factory::cancel_upload`.
+    /// Returns `false` if the texture wasn't uploaded with the tag or was uploaded with another tag since.
+    pub fn roll_back(&mut self, id: UploadId) -> bool {
+        match self.rollback.take() {
+            Some((tag, state, layout)) if tag == id => {
+                *self.image.state_mut() = state;
+                self.layout = layout;
+                true
+            }
+            rollback => {
+                self.rollback = rollback;
+                false
+            }
+        }
+    }
+
+    /// Remember state of the texture before uploads with `tag` so they can be rolled back.
+    fn prepare_rollback(&mut self, tag: Option<UploadId>) {
+        match tag {
+            Some(id) => {
+                if self.rollback.as_ref().map(|&(tag, _, _)| tag) != Some(id) {
+                    self.rollback = Some((id, self.image.state().clone(), self.layout));
+                }
+            }
+            None => self.rollback = None,
+        }
+    }
+
+    /// Get range of all subresources of the texture.
+    pub fn range(&self) -> SubresourceRange {
+        SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: 0..self.levels,
+            layers: 0..kind_layers(self.kind),
+        }
+    }
+}
+
+impl<B> Factory<B>
+where
+    B: Backend,
+{
+    /// Create `Texture` with single mip-level and upload data to it.
+    /// Texture will be in `ShaderReadOnlyOptimal` layout after upload completes.
+    /// 
+    /// # Parameters
+    /// 
+    /// `kind`      - texture dimensions.
+    /// `format`    - format of the texture. It must be color format.
+    /// `data`      - texels of all layers tightly packed.
+    /// `sampler`   - parameters of the sampler to create with the texture.
+    /// 
+    pub fn create_texture(
+        &mut self,
+        kind: Kind,
+        format: Format,
+        data: &[u8],
+        sampler: Option<SamplerInfo>,
+    ) -> Result<Texture<B>, Error> {
+        let region = BufferImageCopy {
+            buffer_offset: 0,
+            buffer_width: 0,
+            buffer_height: 0,
+            image_layers: SubresourceLayers {
+                aspects: Aspects::COLOR,
+                level: 0,
+                layers: 0..kind_layers(kind),
+            },
+            image_offset: Offset { x: 0, y: 0, z: 0 },
+            image_extent: kind_extent(kind),
+        };
+        self.create_texture_from_regions(kind, 1, &[format], data, &[region], sampler)
+    }
+
+    /// Create `Texture` with a mip-level per element of `levels` and upload data to them.
+    /// Texture will be in `ShaderReadOnlyOptimal` layout after upload completes.
+    /// 
+    /// # Parameters
+    /// 
+    /// `kind`      - texture dimensions. Dimensions of each next mip-level are halved.
+    /// `format`    - format of the texture. It must be color format.
+    /// `levels`    - texels of all layers tightly packed for each mip-level starting from the first.
+    /// `sampler`   - parameters of the sampler to create with the texture.
+    /// 
+    pub fn create_texture_with_mips(
+        &mut self,
+        kind: Kind,
+        format: Format,
+        levels: &[&[u8]],
+        sampler: Option<SamplerInfo>,
+    ) -> Result<Texture<B>, Error> {
+        assert!(!levels.is_empty());
+        let layers = kind_layers(kind);
+        let mut data = Vec::new();
+        let mut regions = Vec::with_capacity(levels.len());
+        for (level, texels) in levels.iter().enumerate() {
+            // Offsets must be multiples of texel size.
+            let offset = (data.len() + REGION_ALIGNMENT - 1) / REGION_ALIGNMENT * REGION_ALIGNMENT;
+            data.resize(offset, 0);
+            data.extend_from_slice(texels);
+            regions.push(BufferImageCopy {
+                buffer_offset: offset as u64,
+                buffer_width: 0,
+                buffer_height: 0,
+                image_layers: SubresourceLayers {
+                    aspects: Aspects::COLOR,
+                    level: level as Level,
+                    layers: 0..layers,
+                },
+                image_offset: Offset { x: 0, y: 0, z: 0 },
+                image_extent: level_extent(kind_extent(kind), level as Level),
+            });
+        }
+        self.create_texture_from_regions(kind, levels.len() as Level, &[format], &data, &regions, sampler)
+    }
+
+    /// Create `Texture` and upload regions of its mip-levels and layers from single staging buffer.
+    /// Texture will be in `ShaderReadOnlyOptimal` layout after upload completes.
+    /// 
+    /// # Parameters
+    /// 
+    /// `kind`      - texture dimensions.
+    /// `levels`    - number of mip-levels.
+    /// `formats`   - preferred format of the texture followed by formats to fall back to if it can't be sampled.
+    ///               All of them must be color formats with the same texel layout.
+    /// `data`      - texels of all regions.
+    /// `regions`   - regions of the texture and offsets of their texels in `data`.
+    ///               Offsets must be multiples of 4 and of texel block size of the format.
+    /// `sampler`   - parameters of the sampler to create with the texture.
+    /// 
+    pub fn create_texture_from_regions(
+        &mut self,
+        kind: Kind,
+        levels: Level,
+        formats: &[Format],
+        data: &[u8],
+        regions: &[BufferImageCopy],
+        sampler: Option<SamplerInfo>,
+    ) -> Result<Texture<B>, Error> {
+        let usage = ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST;
+        let format = self.pick_format(formats, usage, false)
+            .ok_or_else(|| Error::NoSupportedFormat(formats.to_vec()))?;
+        if Some(&format) != formats.first() {
+            info!("Format {:?} is picked instead of {:?}", format, formats[0]);
+        }
+        let mut image = self.create_image(kind, levels, format, Properties::DEVICE_LOCAL, usage)?;
+        let range = SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: 0..levels,
+            layers: 0..kind_layers(kind),
+        };
+        let rollback = self.upload_tag()
+            .map(|id| (id, image.state().clone(), ImageLayout::Undefined));
+
+        // Regions are always copied through staging buffer after the image is transitioned for transfer.
+        self.upload_image_regions(&mut image, ImageLayout::TransferDstOptimal, data, regions)?;
+        self.upload_transition_to(
+            &mut image,
+            (Access::SHADER_READ, ImageLayout::ShaderReadOnlyOptimal),
+            sampling_stages(),
+        )?;
+
+        let view = self.create_image_view(image.raw(), format, Swizzle::NO, range)
+            .map_err(|err| Error::creation("image view", err))?;
+        let sampler = sampler.map(|info| self.create_sampler(info));
+
+        Ok(Texture {
+            image,
+            view,
+            sampler,
+            kind,
+            levels,
+            format,
+            layout: ImageLayout::ShaderReadOnlyOptimal,
+            rollback,
+        })
+    }
+
+    /// Create `Texture` with single mip-level without data.
+    /// Texture will be in `ShaderReadOnlyOptimal` layout after commands recorded for uploads complete.
+    /// Parts of the texture can be filled with `Factory::upload_texture_region`.
+    /// 
+    /// # Parameters
+    /// 
+    /// `kind`      - texture dimensions.
+    /// `format`    - format of the texture. It must be color format.
+    /// `sampler`   - parameters of the sampler to create with the texture.
+    /// 
+    pub fn create_empty_texture(
+        &mut self,
+        kind: Kind,
+        format: Format,
+        sampler: Option<SamplerInfo>,
+    ) -> Result<Texture<B>, Error> {
+        let mut image = self.create_image(
+            kind,
+            1,
+            format,
+            Properties::DEVICE_LOCAL,
+            ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+        )?;
+        let range = SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: 0..1,
+            layers: 0..kind_layers(kind),
+        };
+        let rollback = self.upload_tag()
+            .map(|id| (id, image.state().clone(), ImageLayout::Undefined));
+        self.upload_transition_to(
+            &mut image,
+            (Access::SHADER_READ, ImageLayout::ShaderReadOnlyOptimal),
+            sampling_stages(),
+        )?;
+
+        let view = self.create_image_view(image.raw(), format, Swizzle::NO, range)
+            .map_err(|err| Error::creation("image view", err))?;
+        let sampler = sampler.map(|info| self.create_sampler(info));
+
+        Ok(Texture {
+            image,
+            view,
+            sampler,
+            kind,
+            levels: 1,
+            format,
+            layout: ImageLayout::ShaderReadOnlyOptimal,
+            rollback,
+        })
+    }
+
+    /// Upload texels to the region of the first mip-level of the texture in `ShaderReadOnlyOptimal` layout.
+    /// Texture is transitioned to `TransferDstOptimal` for the copy and back.
+    /// Fails if the texture is in another layout.
+    pub fn upload_texture_region(
+        &mut self,
+        texture: &mut Texture<B>,
+        layers: Range<Layer>,
+        offset: Offset,
+        extent: Extent,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        if texture.layout != ImageLayout::ShaderReadOnlyOptimal {
+            return Err(Error::InvalidArgument("Texture regions can be uploaded only in `ShaderReadOnlyOptimal` layout"));
+        }
+        let tag = self.upload_tag();
+        texture.prepare_rollback(tag);
+        let region = BufferImageCopy {
+            buffer_offset: 0,
+            buffer_width: 0,
+            buffer_height: 0,
+            image_layers: SubresourceLayers {
+                aspects: Aspects::COLOR,
+                level: 0,
+                layers,
+            },
+            image_offset: offset,
+            image_extent: extent,
+        };
+        // Region is always copied through staging buffer after the image is transitioned for transfer.
+        let result = self.upload_image_regions(&mut texture.image, ImageLayout::TransferDstOptimal, data, &[region]);
+        self.upload_transition_to(
+            &mut texture.image,
+            (Access::SHADER_READ, ImageLayout::ShaderReadOnlyOptimal),
+            sampling_stages(),
+        )?;
+        result
+    }
+
+    /// Destroy `Texture`.
+    /// Factory will destroy image, view and sampler after all commands referencing them will complete.
+    pub fn destroy_texture(&mut self, texture: Texture<B>) {
+        let Texture {
+            image,
+            view,
+            sampler,
+            ..
+        } = texture;
+        self.destroy_image_view(view);
+        if let Some(sampler) = sampler {
+            self.destroy_sampler(sampler);
+        }
+        self.destroy_image(image);
+    }
+}
+
+/// Get shader stages textures can be sampled at.
+/// Uploads make textures visible to all of them as the `Factory` doesn't know which will sample the texture.
+pub(crate) fn sampling_stages() -> PipelineStage {
+    PipelineStage::VERTEX_SHADER | PipelineStage::HULL_SHADER | PipelineStage::DOMAIN_SHADER
+        | PipelineStage::GEOMETRY_SHADER | PipelineStage::FRAGMENT_SHADER | PipelineStage::COMPUTE_SHADER
+}
+
+/// Get number of array layers of the image.
+pub(crate) fn kind_layers(kind: Kind) -> Layer {
+    match kind {
+        Kind::D1Array(_, layers) | Kind::D2Array(_, _, layers, _) => layers,
+        Kind::Cube(_) => 6,
+        Kind::CubeArray(_, cubes) => cubes * 6,
+        _ => 1,
+    }
+}
+
+/// Get extent of the mip-level of the image which first level has `extent`.
+pub(crate) fn level_extent(extent: Extent, level: Level) -> Extent {
+    Extent {
+        width: (extent.width >> level).max(1),
+        height: (extent.height >> level).max(1),
+        depth: (extent.depth >> level).max(1),
+    }
+}
+
+/// Get extent of single layer of the image.
+pub(crate) fn kind_extent(kind: Kind) -> Extent {
+    let (width, height, depth) = match kind {
+        Kind::D1(width) | Kind::D1Array(width, _) => (width, 1, 1),
+        Kind::D2(width, height, _) | Kind::D2Array(width, height, _, _) => (width, height, 1),
+        Kind::D3(width, height, depth) => (width, height, depth),
+        Kind::Cube(size) | Kind::CubeArray(size, _) => (size, size, 1),
+    };
+    Extent {
+        width: width as u32,
+        height: height as u32,
+        depth: depth as u32,
+    }
+}
@@ -0,0 +1,102 @@
+//! `TimestampPool` wraps `hal` query pool of timestamp queries.
+//! Graph timing and profiler use a pool per frame and read it once the frame is complete,
+//! so reading results never waits for the device.
+//! Ticks are converted to nanoseconds with `Factory::timestamp_period`.
+//!
+
+use std::ops::Range;
+use std::slice::from_raw_parts_mut;
+use std::time::Duration;
+
+use hal::{Backend, Device};
+use hal::command::RawCommandBuffer;
+use hal::pso::PipelineStage;
+use hal::query::{Query, QueryType, ResultFlags};
+
+use Error;
+
+/// Pool of timestamp queries.
+#[derive(Debug)]
+pub(crate) struct TimestampPool<B: Backend> {
+    pool: B::QueryPool,
+    count: u32,
+}
+
+impl<B> TimestampPool<B>
+where
+    B: Backend,
+{
+    /// Create pool of `count` timestamp queries.
+    pub(crate) fn new(device: &B::Device, count: u32) -> Result<Self, Error> {
+        let pool = device
+            .create_query_pool(QueryType::Timestamp, count)
+            .map_err(|err| Error::creation(format!("pool of {} timestamp queries", count), err))?;
+        Ok(TimestampPool { pool, count })
+    }
+
+    /// Get number of queries in the pool.
+    pub(crate) fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Record reset of `queries`.
+    /// Command buffer must be in recording state outside of render pass.
+    pub(crate) fn reset(&self, cbuf: &mut B::CommandBuffer, queries: Range<u32>) {
+        debug_assert!(queries.end <= self.count);
+        cbuf.reset_query_pool(&self.pool, queries);
+    }
+
+    /// Record timestamp write after all previous commands reach `stage`.
+    /// Query must be reset before.
+    pub(crate) fn write(&self, cbuf: &mut B::CommandBuffer, stage: PipelineStage, query: u32) {
+        debug_assert!(query < self.count);
+        cbuf.write_timestamp(
+            stage,
+            Query {
+                pool: &self.pool,
+                id: query,
+            },
+        );
+    }
+
+    /// Read timestamps of `queries` into `results` without waiting.
+    /// Returns `false` if some results are not available.
+    pub(crate) fn read(&self, device: &B::Device, queries: Range<u32>, results: &mut [u64]) -> bool {
+        assert_eq!(results.len(), (queries.end - queries.start) as usize);
+        let data = unsafe {
+            // Safe due to `u64` has no invalid bit patterns and slice covers exactly `results`.
+            from_raw_parts_mut(results.as_mut_ptr() as *mut u8, results.len() * 8)
+        };
+        match device.get_query_pool_results(&self.pool, queries, data, 8, ResultFlags::BITS_64) {
+            Ok(available) => available,
+            Err(err) => {
+                warn!("Failed to read timestamp queries: {:?}", err);
+                false
+            }
+        }
+    }
+
+    /// Destroy the pool. Commands writing its queries must be complete.
+    pub(crate) fn dispose(self, device: &B::Device) {
+        device.destroy_query_pool(self.pool);
+    }
+}
+
+/// Convert ticks between `start` and `end` timestamps to nanoseconds.
+pub(crate) fn elapsed_nanos(start: u64, end: u64, period: f32) -> u64 {
+    (end.saturating_sub(start) as f64 * period as f64) as u64
+}
+
+/// Convert nanoseconds to `Duration`.
+pub(crate) fn nanos_to_duration(nanos: u64) -> Duration {
+    Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+}
+
+#[test]
+fn ticks_are_scaled_by_period() {
+    assert_eq!(elapsed_nanos(100, 300, 1.0), 200);
+    assert_eq!(elapsed_nanos(100, 300, 52.08), 10416);
+    // Reordered timestamps never underflow.
+    assert_eq!(elapsed_nanos(300, 100, 1.0), 0);
+    assert_eq!(nanos_to_duration(1_500_000_000), Duration::new(1, 500_000_000));
+}
@@ -0,0 +1,173 @@
+//! Queue families picked at initialization.
+//! `QueuePolicy` tells which dedicated queues to look for and `QueueTopology` reports what was chosen,
+//! so `Factory` and `Renderer` know which families to record uploads and compute for.
+//! Resources can't be transferred between families, so `init` uses general family only
+//! and runs async compute on a spare queue of it.
+//!
+
+use hal::queue::{QueueFamily, QueueFamilyId, QueueType};
+
+use Error;
+
+/// Policy of picking queue families at initialization.
+/// Dedicated families are used only if device has them, otherwise work goes to general family.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueuePolicy {
+    /// Record uploads made through `Factory` for dedicated transfer family.
+    /// Resources can't be transferred between families, so `init` ignores it.
+    pub dedicated_transfer: bool,
+
+    /// Submit commands of `Factory::compute` and async compute passes of graphs to a separate queue
+    /// of general family, if the family has more than one queue. See `Renderer::with_general_compute`.
+    pub async_compute: bool,
+
+    /// Priorities of the queues created.
+    pub priorities: QueuePriorities,
+}
+
+impl QueuePolicy {
+    /// Use single general family for everything.
+    pub fn single() -> Self {
+        QueuePolicy {
+            dedicated_transfer: false,
+            async_compute: false,
+            priorities: QueuePriorities::default(),
+        }
+    }
+
+    /// Prefer dedicated transfer family for uploads and separate queue for compute.
+    pub fn dedicated() -> Self {
+        QueuePolicy {
+            dedicated_transfer: true,
+            async_compute: true,
+            priorities: QueuePriorities::default(),
+        }
+    }
+}
+
+impl Default for QueuePolicy {
+    fn default() -> Self {
+        QueuePolicy::single()
+    }
+}
+
+/// Priorities of queues in range from `0.0` to `1.0`.
+/// Higher priority queues may get more execution time on platforms that honor priorities.
+/// Priorities matter only between queues of the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueuePriorities {
+    /// Priority of general queue. Frames are rendered and presented on it.
+    pub graphics: f32,
+
+    /// Priority of async compute queue.
+    pub compute: f32,
+
+    /// Priority of dedicated transfer queue.
+    /// Lower than `graphics` by default so streaming uploads don't delay presentation.
+    pub transfer: f32,
+}
+
+impl QueuePriorities {
+    /// Check that all priorities are in range from `0.0` to `1.0`.
+    pub fn check(&self) -> Result<(), Error> {
+        for &priority in &[self.graphics, self.compute, self.transfer] {
+            if !(priority >= 0.0 && priority <= 1.0) {
+                return Err(Error::InvalidArgument("Queue priority is out of range 0.0 .. 1.0"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for QueuePriorities {
+    fn default() -> Self {
+        QueuePriorities {
+            graphics: 1.0,
+            compute: 1.0,
+            transfer: 0.5,
+        }
+    }
+}
+
+/// Queue families chosen for the device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueTopology {
+    graphics: QueueFamilyId,
+    compute: Option<QueueFamilyId>,
+    transfer: Option<QueueFamilyId>,
+}
+
+impl QueueTopology {
+    /// Topology with single general family.
+    pub fn single(graphics: QueueFamilyId) -> Self {
+        QueueTopology {
+            graphics,
+            compute: None,
+            transfer: None,
+        }
+    }
+
+    /// Topology with optional separate compute and transfer families.
+    /// Families equal to `graphics` are ignored.
+    pub fn new(graphics: QueueFamilyId, compute: Option<QueueFamilyId>, transfer: Option<QueueFamilyId>) -> Self {
+        let separate = |family: Option<QueueFamilyId>| match family {
+            Some(family) if family != graphics => Some(family),
+            _ => None,
+        };
+        QueueTopology {
+            graphics,
+            compute: separate(compute),
+            transfer: separate(transfer),
+        }
+    }
+
+    /// Get general family. Frames are rendered and presented on its queue.
+    pub fn graphics(&self) -> QueueFamilyId {
+        self.graphics
+    }
+
+    /// Get separate compute family if any.
+    pub fn compute(&self) -> Option<QueueFamilyId> {
+        self.compute
+    }
+
+    /// Get dedicated transfer family if any.
+    pub fn transfer(&self) -> Option<QueueFamilyId> {
+        self.transfer
+    }
+
+    /// Get family uploads are recorded for.
+    pub fn upload_family(&self) -> QueueFamilyId {
+        self.transfer.unwrap_or(self.graphics)
+    }
+
+    /// Get family commands of `Factory::compute` are recorded for.
+    pub fn compute_family(&self) -> QueueFamilyId {
+        self.compute.unwrap_or(self.graphics)
+    }
+
+    /// Check if everything goes to general family.
+    pub fn is_single(&self) -> bool {
+        self.compute.is_none() && self.transfer.is_none()
+    }
+}
+
+/// Pick general family according to `policy`.
+/// Returns index of general family in `families` and whether its second queue runs async compute.
+pub(crate) fn select_families<Q>(families: &[Q], policy: &QueuePolicy) -> Result<(usize, bool), Error>
+where
+    Q: QueueFamily,
+{
+    let graphics = families
+        .iter()
+        .position(|family| family.queue_type() == QueueType::General)
+        .ok_or(Error::Unsupported("Devices without General queue family"))?;
+    if policy.dedicated_transfer {
+        warn!("Queue family ownership transfers are not supported. Uploads are recorded for general family");
+    }
+    let compute = policy.async_compute && families[graphics].max_queues() > 1;
+    if policy.async_compute && !compute {
+        warn!("General queue family has single queue. Compute is submitted to graphics queue");
+    }
+    Ok((graphics, compute))
+}
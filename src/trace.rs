@@ -0,0 +1,279 @@
+//! Recording of operations performed through `Factory` and render graph into serializable `Trace`
+//! and replaying it against another device, so driver bugs can be reproduced without the application.
+//! Resources are recorded with their creation parameters and uploaded data.
+//! Commands recorded by passes themselves aren't captured, only pass boundaries are.
+//! Enabled with `trace` feature.
+//!
+
+use std::collections::HashMap;
+
+use hal::Backend;
+use hal::buffer::Usage as BufferUsage;
+use hal::device::Extent;
+use hal::format::{Aspects, Format};
+use hal::image::{Access as ImageAccess, ImageLayout, Kind, Layer, Level, Offset, SubresourceLayers, SubresourceRange,
+                 Usage as ImageUsage};
+use hal::memory::Properties;
+use hal::pso::PipelineStage;
+
+use Error;
+use factory::{Buffer, Factory, Image};
+
+/// Operation performed through `Factory`.
+/// Resources are referred by ids assigned at creation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TraceOp {
+    /// `Factory::create_buffer`.
+    CreateBuffer {
+        id: u64,
+        size: u64,
+        properties: Properties,
+        usage: BufferUsage,
+    },
+
+    /// `Factory::create_image`.
+    CreateImage {
+        id: u64,
+        kind: Kind,
+        levels: Level,
+        format: Format,
+        properties: Properties,
+        usage: ImageUsage,
+    },
+
+    /// `Factory::upload_buffer`.
+    UploadBuffer { id: u64, offset: u64, data: Vec<u8> },
+
+    /// `Factory::upload_image`.
+    UploadImage {
+        id: u64,
+        layout: ImageLayout,
+        aspects: Aspects,
+        level: Level,
+        layers: (Layer, Layer),
+        offset: (i32, i32, i32),
+        extent: (u32, u32, u32),
+        data: Vec<u8>,
+    },
+
+    /// `Factory::transition_image`.
+    TransitionImage {
+        id: u64,
+        aspects: Aspects,
+        levels: (Level, Level),
+        layers: (Layer, Layer),
+        stages: (PipelineStage, PipelineStage),
+        states: ((ImageAccess, ImageLayout), (ImageAccess, ImageLayout)),
+    },
+
+    /// `Factory::destroy_buffer`.
+    DestroyBuffer { id: u64 },
+
+    /// `Factory::destroy_image`.
+    DestroyImage { id: u64 },
+
+    /// Render graph starts recording commands of the pass.
+    BeginPass { name: String },
+
+    /// Render graph finishes recording commands of the pass.
+    EndPass { name: String },
+
+    /// Frame is finished.
+    Frame { index: u64 },
+}
+
+/// Operations recorded between `Factory::start_trace` and `Factory::stop_trace`.
+/// Serialize it with any `serde` format.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Trace {
+    /// Operations in order they were performed.
+    pub ops: Vec<TraceOp>,
+}
+
+/// Recorder owned by `Factory`. Assigns ids to resources even when not recording.
+#[derive(Debug)]
+pub(crate) struct TraceRecorder {
+    next_id: u64,
+    trace: Option<Trace>,
+}
+
+impl TraceRecorder {
+    pub(crate) fn new() -> Self {
+        TraceRecorder {
+            next_id: 0,
+            trace: None,
+        }
+    }
+
+    pub(crate) fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub(crate) fn start(&mut self) {
+        self.trace = Some(Trace::default());
+    }
+
+    pub(crate) fn stop(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Record operation built by `op` if recording.
+    pub(crate) fn record<F>(&mut self, op: F)
+    where
+        F: FnOnce() -> TraceOp,
+    {
+        if let Some(ref mut trace) = self.trace {
+            trace.ops.push(op());
+        }
+    }
+}
+
+/// Replay the trace against the device of the `factory`.
+/// Resources created by the trace and not destroyed by it are destroyed at the end.
+/// Buffers and images created before the trace was started are missing, so operations on them are skipped.
+///
+/// # Parameters
+///
+/// `factory`   - factory of the device to replay on.
+/// `trace`     - recorded operations.
+/// `frame`     - called at frame boundaries. It must submit uploads and finish the frame, e.g. with `Renderer::run`.
+///
+pub fn replay_trace<B, F>(factory: &mut Factory<B>, trace: &Trace, mut frame: F) -> Result<(), Error>
+where
+    B: Backend,
+    F: FnMut(&mut Factory<B>) -> Result<(), Error>,
+{
+    let mut buffers: HashMap<u64, Buffer<B>> = HashMap::new();
+    let mut images: HashMap<u64, Image<B>> = HashMap::new();
+    let mut result = Ok(());
+    for op in &trace.ops {
+        result = replay_op(factory, op, &mut buffers, &mut images, &mut frame);
+        if result.is_err() {
+            break;
+        }
+    }
+    for (_, buffer) in buffers {
+        factory.destroy_buffer(buffer);
+    }
+    for (_, image) in images {
+        factory.destroy_image(image);
+    }
+    result
+}
+
+fn replay_op<B, F>(
+    factory: &mut Factory<B>,
+    op: &TraceOp,
+    buffers: &mut HashMap<u64, Buffer<B>>,
+    images: &mut HashMap<u64, Image<B>>,
+    frame: &mut F,
+) -> Result<(), Error>
+where
+    B: Backend,
+    F: FnMut(&mut Factory<B>) -> Result<(), Error>,
+{
+    match *op {
+        TraceOp::CreateBuffer {
+            id,
+            size,
+            properties,
+            usage,
+        } => {
+            let buffer = factory.create_buffer(size, properties, usage)?;
+            buffers.insert(id, buffer);
+        }
+        TraceOp::CreateImage {
+            id,
+            kind,
+            levels,
+            format,
+            properties,
+            usage,
+        } => {
+            let image = factory.create_image(kind, levels, format, properties, usage)?;
+            images.insert(id, image);
+        }
+        TraceOp::UploadBuffer {
+            id,
+            offset,
+            ref data,
+        } => match buffers.get_mut(&id) {
+            Some(buffer) => factory.upload_buffer(buffer, offset, data)?,
+            None => warn!("Buffer {} of the trace is missing. Upload is skipped", id),
+        },
+        TraceOp::UploadImage {
+            id,
+            layout,
+            aspects,
+            level,
+            layers,
+            offset,
+            extent,
+            ref data,
+        } => match images.get_mut(&id) {
+            Some(image) => factory.upload_image(
+                image,
+                layout,
+                SubresourceLayers {
+                    aspects,
+                    level,
+                    layers: layers.0..layers.1,
+                },
+                Offset {
+                    x: offset.0,
+                    y: offset.1,
+                    z: offset.2,
+                },
+                Extent {
+                    width: extent.0,
+                    height: extent.1,
+                    depth: extent.2,
+                },
+                data,
+            )?,
+            None => warn!("Image {} of the trace is missing. Upload is skipped", id),
+        },
+        TraceOp::TransitionImage {
+            id,
+            aspects,
+            levels,
+            layers,
+            stages,
+            states,
+        } => match images.get_mut(&id) {
+            Some(image) => factory.transition_image(
+                image,
+                SubresourceRange {
+                    aspects,
+                    levels: levels.0..levels.1,
+                    layers: layers.0..layers.1,
+                },
+                stages.0..stages.1,
+                states.0..states.1,
+            ),
+            None => warn!("Image {} of the trace is missing. Transition is skipped", id),
+        },
+        TraceOp::DestroyBuffer { id } => {
+            if let Some(buffer) = buffers.remove(&id) {
+                factory.destroy_buffer(buffer);
+            }
+        }
+        TraceOp::DestroyImage { id } => {
+            if let Some(image) = images.remove(&id) {
+                factory.destroy_image(image);
+            }
+        }
+        TraceOp::BeginPass { ref name } => debug!("Pass {} begins", name),
+        TraceOp::EndPass { ref name } => debug!("Pass {} ends", name),
+        TraceOp::Frame { index } => {
+            debug!("Frame {} of the trace is finished", index);
+            frame(factory)?;
+        }
+    }
+    Ok(())
+}
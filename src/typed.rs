@@ -0,0 +1,454 @@
+//! `TypedBuffer` and `TypedImage` wrap `Buffer` and `Image` with element type and format.
+//! Uploads, copies and descriptor writes through them are checked at compile time,
+//! so data of one type can't be uploaded into resource of another.
+//!
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::Range;
+use std::slice::from_raw_parts;
+
+use hal::Backend;
+use hal::buffer::Usage as BufferUsage;
+use hal::command::{BufferCopy, BufferImageCopy, RawCommandBuffer};
+use hal::device::Extent;
+use hal::format::{Aspects, Format, Swizzle};
+use hal::image::{ImageLayout, Kind, Layer, Level, Offset, SubresourceLayers, SubresourceRange,
+                 Usage as ImageUsage};
+use hal::memory::Properties;
+
+use Error;
+use factory::{Buffer, Factory, Image};
+use texture::kind_layers;
+use upload::UploadRecorder;
+
+/// Plain data which can be viewed as bytes.
+///
+/// # Safety
+///
+/// Type must have no padding bytes, no pointers and every bit pattern must be its valid value.
+/// `#[repr(C)]` structs of `Pod` fields without padding between them can implement it.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(
+            unsafe impl Pod for $ty {}
+        )*
+    };
+}
+
+macro_rules! impl_pod_arrays {
+    ($($len:expr),*) => {
+        $(
+            unsafe impl<T: Pod> Pod for [T; $len] {}
+        )*
+    };
+}
+
+impl_pod!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+impl_pod_arrays!(1, 2, 3, 4, 8, 9, 12, 16, 32);
+
+/// Format of the image known at compile time along with type of its texels.
+pub trait TexelFormat: Copy + Debug + Send + Sync + 'static {
+    /// Format of the image.
+    const FORMAT: Format;
+
+    /// Single texel of the format.
+    type Texel: Pod;
+}
+
+macro_rules! texel_formats {
+    ($($name:ident: $texel:ty,)*) => {
+        $(
+            #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+            pub struct $name;
+
+            impl super::TexelFormat for $name {
+                const FORMAT: Format = Format::$name;
+                type Texel = $texel;
+            }
+        )*
+    };
+}
+
+/// Formats usable with `TypedImage`.
+/// Each one is `TexelFormat` of the `Format` variant of the same name.
+/// Half-float texels are stored as raw `u16` bits.
+pub mod formats {
+    use hal::format::Format;
+
+    texel_formats! {
+        R8Unorm: u8,
+        Rg8Unorm: [u8; 2],
+        Rgba8Unorm: [u8; 4],
+        Rgba8Srgb: [u8; 4],
+        Bgra8Unorm: [u8; 4],
+        Bgra8Srgb: [u8; 4],
+        R16Float: u16,
+        Rg16Float: [u16; 2],
+        Rgba16Float: [u16; 4],
+        R32Uint: u32,
+        R32Float: f32,
+        Rg32Float: [f32; 2],
+        Rgba32Float: [f32; 4],
+    }
+}
+
+/// `Buffer` holding elements of type `T`.
+#[derive(Debug)]
+pub struct TypedBuffer<B: Backend, T> {
+    buffer: Buffer<B>,
+    len: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<B, T> TypedBuffer<B, T>
+where
+    B: Backend,
+    T: Pod,
+{
+    /// Create buffer for `len` elements.
+    ///
+    /// # Parameters
+    ///
+    /// `len`           - number of elements.
+    /// `properties`    - memory properties required for buffer.
+    /// `usage`         - how buffer is supposed to be used.
+    ///
+    pub fn create(
+        factory: &Factory<B>,
+        len: usize,
+        properties: Properties,
+        usage: BufferUsage,
+    ) -> Result<Self, Error> {
+        let buffer = factory.create_buffer((len * size_of::<T>()) as u64, properties, usage)?;
+        Ok(TypedBuffer {
+            buffer,
+            len,
+            marker: PhantomData,
+        })
+    }
+
+    /// Wrap buffer created by the `Factory`.
+    ///
+    /// # Safety
+    ///
+    /// Buffer must be at least `len * size_of::<T>()` bytes large
+    /// and its content must be valid elements of type `T`.
+    pub unsafe fn from_buffer(buffer: Buffer<B>, len: usize) -> Self {
+        TypedBuffer {
+            buffer,
+            len,
+            marker: PhantomData,
+        }
+    }
+
+    /// Get number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if buffer holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get raw buffer.
+    pub fn raw(&self) -> &B::Buffer {
+        self.buffer.raw()
+    }
+
+    /// Get untyped buffer.
+    pub fn buffer(&self) -> &Buffer<B> {
+        &self.buffer
+    }
+
+    /// Get untyped buffer to transition or upload it through the `Factory`.
+    pub fn buffer_mut(&mut self) -> &mut Buffer<B> {
+        &mut self.buffer
+    }
+
+    /// Unwrap untyped buffer.
+    pub fn into_buffer(self) -> Buffer<B> {
+        self.buffer
+    }
+
+    /// Upload elements to the buffer starting from element `first`.
+    /// See `Factory::upload_buffer`.
+    pub fn upload(&mut self, factory: &mut Factory<B>, first: usize, data: &[T]) -> Result<(), Error> {
+        self.check_range(first..first + data.len())?;
+        factory.upload_buffer(&mut self.buffer, (first * size_of::<T>()) as u64, as_bytes(data))
+    }
+
+    /// Upload elements to the buffer starting from element `first` recording commands into the `recorder`.
+    /// See `Factory::upload_buffer_with`.
+    pub fn upload_with(
+        &mut self,
+        factory: &Factory<B>,
+        recorder: &mut UploadRecorder<B>,
+        first: usize,
+        data: &[T],
+    ) -> Result<(), Error> {
+        self.check_range(first..first + data.len())?;
+        factory.upload_buffer_with(recorder, &mut self.buffer, (first * size_of::<T>()) as u64, as_bytes(data))
+    }
+
+    /// Record copy of `range` of elements into `dst` starting from element `dst_first`.
+    /// Caller must record barriers for both buffers.
+    pub fn copy_to(
+        &self,
+        cbuf: &mut B::CommandBuffer,
+        dst: &TypedBuffer<B, T>,
+        range: Range<usize>,
+        dst_first: usize,
+    ) -> Result<(), Error> {
+        self.check_range(range.clone())?;
+        dst.check_range(dst_first..dst_first + range.end - range.start)?;
+        cbuf.copy_buffer(
+            self.raw(),
+            dst.raw(),
+            Some(BufferCopy {
+                src: (range.start * size_of::<T>()) as u64,
+                dst: (dst_first * size_of::<T>()) as u64,
+                size: ((range.end - range.start) * size_of::<T>()) as u64,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Get buffer and byte range of `range` of elements
+    /// for `DescriptorWrite::UniformBuffer` and `DescriptorWrite::StorageBuffer`.
+    pub fn descriptor(&self, range: Range<usize>) -> Result<(&B::Buffer, Range<u64>), Error> {
+        self.check_range(range.clone())?;
+        let start = (range.start * size_of::<T>()) as u64;
+        let end = (range.end * size_of::<T>()) as u64;
+        Ok((self.raw(), start..end))
+    }
+
+    fn check_range(&self, range: Range<usize>) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::OutOfBounds {
+                offset: (range.start * size_of::<T>()) as u64,
+                len: (range.end.saturating_sub(range.start) * size_of::<T>()) as u64,
+                size: (self.len * size_of::<T>()) as u64,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Color `Image` of format `F`.
+#[derive(Debug)]
+pub struct TypedImage<B: Backend, F> {
+    image: Image<B>,
+    kind: Kind,
+    levels: Level,
+    marker: PhantomData<fn() -> F>,
+}
+
+impl<B, F> TypedImage<B, F>
+where
+    B: Backend,
+    F: TexelFormat,
+{
+    /// Create image of format `F`.
+    ///
+    /// # Parameters
+    ///
+    /// `kind`          - image dimensions.
+    /// `levels`        - number of mip-levels.
+    /// `properties`    - memory properties required for image.
+    /// `usage`         - how image is supposed to be used.
+    ///
+    pub fn create(
+        factory: &Factory<B>,
+        kind: Kind,
+        levels: Level,
+        properties: Properties,
+        usage: ImageUsage,
+    ) -> Result<Self, Error> {
+        let image = factory.create_image(kind, levels, F::FORMAT, properties, usage)?;
+        Ok(TypedImage {
+            image,
+            kind,
+            levels,
+            marker: PhantomData,
+        })
+    }
+
+    /// Wrap image created by the `Factory`.
+    ///
+    /// # Safety
+    ///
+    /// Image must be created with format `F::FORMAT`, `kind` and `levels`.
+    pub unsafe fn from_image(image: Image<B>, kind: Kind, levels: Level) -> Self {
+        TypedImage {
+            image,
+            kind,
+            levels,
+            marker: PhantomData,
+        }
+    }
+
+    /// Get dimensions of the image.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Get number of mip-levels.
+    pub fn levels(&self) -> Level {
+        self.levels
+    }
+
+    /// Get format of the image.
+    pub fn format(&self) -> Format {
+        F::FORMAT
+    }
+
+    /// Get raw image.
+    pub fn raw(&self) -> &B::Image {
+        self.image.raw()
+    }
+
+    /// Get untyped image.
+    pub fn image(&self) -> &Image<B> {
+        &self.image
+    }
+
+    /// Get untyped image to transition or upload it through the `Factory`.
+    pub fn image_mut(&mut self) -> &mut Image<B> {
+        &mut self.image
+    }
+
+    /// Unwrap untyped image.
+    pub fn into_image(self) -> Image<B> {
+        self.image
+    }
+
+    /// Upload texels to sub-region of the image.
+    /// See `Factory::upload_image`.
+    ///
+    /// # Parameters
+    ///
+    /// `layout`    - layout in which `Image` is during command execution. It must be either `General` or `TransferDstOptimal`
+    /// `level`     - mip-level to upload.
+    /// `layers`    - array layers to upload.
+    /// `offset`    - offsets in texels of the sub-region.
+    /// `extent`    - size in texels of the sub-region.
+    /// `data`      - texels of the sub-region, one per texel of each layer.
+    ///
+    pub fn upload(
+        &mut self,
+        factory: &mut Factory<B>,
+        layout: ImageLayout,
+        level: Level,
+        layers: Range<Layer>,
+        offset: Offset,
+        extent: Extent,
+        data: &[F::Texel],
+    ) -> Result<(), Error> {
+        let layers = self.check_region(level, layers, extent, data.len())?;
+        factory.upload_image(&mut self.image, layout, layers, offset, extent, as_bytes(data))
+    }
+
+    /// Upload texels to sub-region of the image recording commands into the `recorder`.
+    /// See `TypedImage::upload` and `Factory::upload_image_with`.
+    pub fn upload_with(
+        &mut self,
+        factory: &Factory<B>,
+        recorder: &mut UploadRecorder<B>,
+        layout: ImageLayout,
+        level: Level,
+        layers: Range<Layer>,
+        offset: Offset,
+        extent: Extent,
+        data: &[F::Texel],
+    ) -> Result<(), Error> {
+        let layers = self.check_region(level, layers, extent, data.len())?;
+        factory.upload_image_with(recorder, &mut self.image, layout, layers, offset, extent, as_bytes(data))
+    }
+
+    /// Record copy of texels tightly packed in `src` starting from element `first` into sub-region of the image.
+    /// Caller must record barriers for both resources.
+    pub fn copy_from_buffer(
+        &self,
+        cbuf: &mut B::CommandBuffer,
+        src: &TypedBuffer<B, F::Texel>,
+        first: usize,
+        layout: ImageLayout,
+        level: Level,
+        layers: Range<Layer>,
+        offset: Offset,
+        extent: Extent,
+    ) -> Result<(), Error> {
+        let count = texel_count(&layers, extent);
+        src.check_range(first..first + count)?;
+        let layers = self.check_region(level, layers, extent, count)?;
+        cbuf.copy_buffer_to_image(
+            src.raw(),
+            self.raw(),
+            layout,
+            Some(BufferImageCopy {
+                buffer_offset: (first * size_of::<F::Texel>()) as u64,
+                buffer_width: 0,
+                buffer_height: 0,
+                image_layers: layers,
+                image_offset: offset,
+                image_extent: extent,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Create view of all mip-levels and layers of the image with format `F`.
+    /// View must be destroyed with `Factory::destroy_image_view`.
+    pub fn create_view(&self, factory: &Factory<B>) -> Result<B::ImageView, Error> {
+        let range = SubresourceRange {
+            aspects: Aspects::COLOR,
+            levels: 0..self.levels,
+            layers: 0..kind_layers(self.kind),
+        };
+        factory
+            .create_image_view(self.raw(), F::FORMAT, Swizzle::NO, range)
+            .map_err(|err| Error::creation("view of typed image", err))
+    }
+
+    fn check_region(
+        &self,
+        level: Level,
+        layers: Range<Layer>,
+        extent: Extent,
+        texels: usize,
+    ) -> Result<SubresourceLayers, Error> {
+        if level >= self.levels || layers.start >= layers.end || layers.end > kind_layers(self.kind) {
+            return Err(Error::InvalidArgument("Level or layers are out of the image"));
+        }
+        let expected = texel_count(&layers, extent);
+        if texels != expected {
+            return Err(Error::SizeMismatch {
+                what: "texels",
+                expected: expected as u64,
+                actual: texels as u64,
+            });
+        }
+        Ok(SubresourceLayers {
+            aspects: Aspects::COLOR,
+            level,
+            layers,
+        })
+    }
+}
+
+fn texel_count(layers: &Range<Layer>, extent: Extent) -> usize {
+    (layers.end - layers.start) as usize * extent.width as usize * extent.height as usize * extent.depth as usize
+}
+
+/// View plain data as bytes.
+pub(crate) fn as_bytes<T: Pod>(data: &[T]) -> &[u8] {
+    unsafe {
+        // Safe due to `Pod` types have no padding bytes.
+        from_raw_parts(data.as_ptr() as *const u8, data.len() * size_of::<T>())
+    }
+}
@@ -0,0 +1,142 @@
+//! `UniformRing` sub-allocates per-frame uniform data from persistently mapped buffer.
+//! Buffer gets memory object of its own, so keeping it mapped never conflicts with mappings of other resources.
+//! 
+
+use std::mem::size_of;
+use std::ptr::copy_nonoverlapping;
+use std::slice::from_raw_parts;
+
+use hal::{Backend, Device};
+use hal::buffer::Usage as BufferUsage;
+use hal::memory::Properties;
+
+use Error;
+use factory::{DedicatedBuffer, Factory};
+
+/// Ring of per-frame regions in persistently mapped `CPU_VISIBLE | COHERENT` uniform buffer.
+/// Allocations return `(buffer, offset)` pairs aligned to `min_uniform_buffer_offset_alignment`
+/// so they can be used with dynamic uniform descriptors.
+/// 
+/// Region of the frame is reused after `frames` calls to `advance`.
+/// Hence `frames` must be no less than number of frames that can be in flight simultaneously.
+#[derive(Debug)]
+pub struct UniformRing<B: Backend> {
+    buffer: DedicatedBuffer<B>,
+    ptr: *mut u8,
+    alignment: u64,
+    region: u64,
+    frames: u64,
+    frame: u64,
+    offset: u64,
+}
+
+// `ptr` points into memory owned by `buffer`.
+unsafe impl<B> Send for UniformRing<B>
+where
+    B: Backend,
+{
+}
+unsafe impl<B> Sync for UniformRing<B>
+where
+    B: Backend,
+{
+}
+
+impl<B> UniformRing<B>
+where
+    B: Backend,
+{
+    /// Create new ring.
+    /// 
+    /// # Parameters
+    /// 
+    /// `factory`   - factory to allocate buffer from.
+    /// `region`    - size of per-frame region in bytes.
+    /// `frames`    - number of regions.
+    /// 
+    pub fn new(factory: &mut Factory<B>, region: u64, frames: u64) -> Result<Self, Error> {
+        assert_ne!(frames, 0);
+        let alignment = factory.limits().min_uniform_buffer_offset_alignment.max(1);
+        let region = align(region, alignment);
+        let buffer = factory.create_dedicated_buffer(
+            region * frames,
+            Properties::CPU_VISIBLE | Properties::COHERENT,
+            BufferUsage::UNIFORM,
+        )?;
+        let ptr = match factory.map_memory(buffer.memory(), 0..buffer.size()) {
+            Ok(ptr) => ptr,
+            Err(err) => {
+                factory.destroy_dedicated_buffer(buffer);
+                return Err(Error::MappingFailed(err));
+            }
+        };
+
+        Ok(UniformRing {
+            buffer,
+            ptr,
+            alignment,
+            region,
+            frames,
+            frame: 0,
+            offset: 0,
+        })
+    }
+
+    /// Get buffer of the ring.
+    pub fn buffer(&self) -> &B::Buffer {
+        self.buffer.raw()
+    }
+
+    /// Get size of per-frame region.
+    pub fn region_size(&self) -> u64 {
+        self.region
+    }
+
+    /// Get bytes left in current region.
+    pub fn remaining(&self) -> u64 {
+        self.region - self.offset
+    }
+
+    /// Start writing into region of the `frame`.
+    /// Region of the `frame` must not be used by the device anymore.
+    pub fn advance(&mut self, frame: u64) {
+        self.frame = frame % self.frames;
+        self.offset = 0;
+    }
+
+    /// Copy `data` into current region.
+    /// Returns buffer and offset to bind with dynamic descriptor.
+    pub fn write(&mut self, data: &[u8]) -> Result<(&B::Buffer, u64), Error> {
+        let size = data.len() as u64;
+        if size > self.remaining() {
+            return Err(Error::Exhausted("Uniform ring region of the frame"));
+        }
+        let offset = self.frame * self.region + self.offset;
+        unsafe {
+            // `offset + size` is in bounds of mapped range.
+            copy_nonoverlapping(data.as_ptr(), self.ptr.offset(offset as isize), data.len());
+        }
+        self.offset = align(self.offset + size, self.alignment).min(self.region);
+        Ok((self.buffer.raw(), offset))
+    }
+
+    /// Copy `value` into current region.
+    pub fn push<T: Copy>(&mut self, value: &T) -> Result<(&B::Buffer, u64), Error> {
+        let bytes = unsafe {
+            // Safe due to `T` is plain data.
+            from_raw_parts(value as *const T as *const u8, size_of::<T>())
+        };
+        self.write(bytes)
+    }
+
+    /// Dispose of the ring.
+    /// Factory will destroy buffer after all commands referencing it will complete.
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        factory.unmap_memory(self.buffer.memory());
+        factory.destroy_dedicated_buffer(self.buffer);
+    }
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    (value + alignment - 1) / alignment * alignment
+}
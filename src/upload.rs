@@ -1,33 +1,397 @@
 use std::borrow::{Borrow, BorrowMut};
+use std::cmp::max;
 use std::collections::VecDeque;
-use std::slice::from_raw_parts_mut;
+use std::vec::Drain;
+use std::mem::replace;
+use std::ops::{Deref, DerefMut, Range};
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+use std::sync::{Arc, Mutex};
 
 use hal::{Backend, Device};
 use hal::buffer::Usage as BufferUsage;
 use hal::command::{BufferCopy, BufferImageCopy, CommandBufferFlags, RawCommandBuffer,
                    RawLevel};
 use hal::device::Extent;
-use hal::image::{ImageLayout, Offset, SubresourceLayers};
-use hal::mapping::Error as MappingError;
-use hal::memory::Properties;
+use hal::format::{Aspects, Format};
+use hal::image::{ImageLayout, Layer, Level, Offset, State as ImageState, SubresourceLayers,
+                 SubresourceRange};
+use hal::memory::{Barrier, Properties};
+use hal::pso::PipelineStage;
+use hal::MemoryProperties;
 use hal::pool::{CommandPoolCreateFlags, RawCommandPool};
-use hal::queue::QueueFamilyId;
+use hal::queue::{QueueFamilyId, RawCommandQueue, RawSubmission};
 
 use mem::{Block, Factory, Item, SmartAllocator, SmartBlock, Type};
 
 use Error;
+use escape::{Escape, Terminal};
+use frame::Frames;
+use debug::{DebugMarkers, DebugObject};
+use lost::host_execution_error;
 
 type SmartBuffer<B: Backend> = Item<B::Buffer, SmartBlock<B::Memory>>;
 type SmartImage<B: Backend> = Item<B::Image, SmartBlock<B::Memory>>;
 
+/// Maximum size of data written by single `update_buffer` command.
+const MAX_UPDATE_BUFFER_SIZE: usize = 65536;
+
+/// Required alignment of offset and size of `update_buffer` command.
+const UPDATE_BUFFER_ALIGNMENT: u64 = 4;
+
+/// Staging threshold for devices where all device-local memory is cpu-visible.
+/// Buffers there are usually written through mapping so threshold only affects rare device-local only buffers.
+const UMA_STAGING_THRESHOLD: usize = MAX_UPDATE_BUFFER_SIZE;
+
+/// Staging threshold for devices with small cpu-visible window into device-local memory.
+const BAR_STAGING_THRESHOLD: usize = 32 * 1024;
+
+/// Staging threshold for devices where device-local memory is accessible only through transfers.
+const DISCRETE_STAGING_THRESHOLD: usize = 16 * 1024;
+
+/// Size of chunks of staging arena `UploadRecorder` sub-allocates from.
+/// Data bigger than a chunk is copied through staging buffer of its own.
+pub const STAGING_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Alignment of buffer data in staging arena.
+const BUFFER_STAGING_ALIGNMENT: u64 = 4;
+
+/// Alignment of image data in staging arena.
+/// Multiple of 4 and of texel block size of any format as copies to images require.
+const IMAGE_STAGING_ALIGNMENT: u64 = 96;
+
+/// Pick staging threshold suitable for memory topology of the device.
+///
+/// 1. If all device-local memory types are cpu-visible (integrated GPUs) then inline updates are used up to their limit.
+/// 2. If some device-local memory type is cpu-visible (PCIe BAR window) then moderate threshold is used.
+/// 3. Otherwise all data goes through PCIe and staging is preferred for anything but small updates.
+pub fn tune_staging_threshold(properties: &MemoryProperties) -> usize {
+    let mut device_local = properties
+        .memory_types
+        .iter()
+        .filter(|ty| ty.properties.contains(Properties::DEVICE_LOCAL))
+        .peekable();
+
+    if device_local.peek().is_none() {
+        // Software implementation or something weird.
+        return DISCRETE_STAGING_THRESHOLD;
+    }
+
+    let (visible, total) = device_local.fold((0, 0), |(visible, total), ty| {
+        if ty.properties.contains(Properties::CPU_VISIBLE) {
+            (visible + 1, total + 1)
+        } else {
+            (visible, total + 1)
+        }
+    });
+
+    if visible == total {
+        UMA_STAGING_THRESHOLD
+    } else if visible > 0 {
+        BAR_STAGING_THRESHOLD
+    } else {
+        DISCRETE_STAGING_THRESHOLD
+    }
+}
+
+/// Command pool with command buffers allocated from it.
+/// Whole pool is reset at once when frame it was used in retires.
+#[derive(Debug)]
+struct UploadPool<B: Backend> {
+    pool: B::CommandPool,
+    cbufs: Vec<B::CommandBuffer>,
+    used: usize,
+    priority: UploadPriority,
+    tag: Option<UploadId>,
+    bytes: u64,
+    staging: Vec<Staging<B>>,
+    chunks: Vec<StagingChunk<B>>,
+    resources: Vec<ResourceKey>,
+}
+
+impl<B> UploadPool<B>
+where
+    B: Backend,
+{
+    fn new(device: &B::Device, family: QueueFamilyId) -> Self {
+        UploadPool {
+            pool: device.create_command_pool(family, CommandPoolCreateFlags::TRANSIENT),
+            cbufs: Vec::new(),
+            used: 0,
+            priority: UploadPriority::Normal,
+            tag: None,
+            bytes: 0,
+            staging: Vec::new(),
+            chunks: Vec::new(),
+            resources: Vec::new(),
+        }
+    }
+
+    /// Get index of the command buffer not yet used since last reset.
+    fn acquire(&mut self) -> usize {
+        if self.used == self.cbufs.len() {
+            let cbuf = self.pool.allocate(1, RawLevel::Primary).remove(0);
+            self.cbufs.push(cbuf);
+        }
+        self.used += 1;
+        self.used - 1
+    }
+
+    /// Reset all command buffers allocated from the pool.
+    fn reset(&mut self) {
+        self.pool.reset();
+        self.used = 0;
+        self.bytes = 0;
+        self.resources.clear();
+    }
+}
+
+/// Staging buffer kept alive until commands copying from it complete.
+#[derive(Debug)]
+struct Staging<B: Backend> {
+    buffer: SmartBuffer<B>,
+    frame: u64,
+}
+
+/// Chunk of staging arena filled linearly with data of uploads recorded into single command buffer.
+/// It is reused once that command buffer retires.
+#[derive(Debug)]
+struct StagingChunk<B: Backend> {
+    buffer: SmartBuffer<B>,
+    coherent: bool,
+    used: u64,
+}
+
+/// Information about staging buffer owned by `Upload`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StagingInfo {
+    /// Index of the frame staging buffer was recorded for.
+    pub frame: u64,
+
+    /// Size of the staging buffer in bytes.
+    pub size: u64,
+}
+
+/// Counters of staging buffers created by `Upload`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StagingStats {
+    /// Number of staging buffers created.
+    pub created: u64,
+
+    /// Total size of staging buffers created.
+    pub created_bytes: u64,
+
+    /// Number of staging buffers retired.
+    pub retired: u64,
+
+    /// Total size of staging buffers retired.
+    pub retired_bytes: u64,
+}
+
+impl StagingStats {
+    /// Number of staging buffers not yet retired.
+    pub fn outstanding(&self) -> u64 {
+        self.created - self.retired
+    }
+
+    /// Total size of staging buffers not yet retired.
+    pub fn outstanding_bytes(&self) -> u64 {
+        self.created_bytes - self.retired_bytes
+    }
+
+    pub(crate) fn merge(self, other: Self) -> Self {
+        StagingStats {
+            created: self.created + other.created,
+            created_bytes: self.created_bytes + other.created_bytes,
+            retired: self.retired + other.retired,
+            retired_bytes: self.retired_bytes + other.retired_bytes,
+        }
+    }
+}
+
+/// Priority of the uploads.
+/// Uploads of each priority are recorded into separate command buffer.
+/// Command buffers are submitted in order of priority so that `Immediate` uploads start before `Normal`
+/// and `Normal` before `Background`.
+///
+/// Priority orders submission only. Command buffers of all priorities are submitted to the same queue
+/// in one batch without semaphores between them, so their execution may overlap and complete in any order.
+/// Dependencies between uploads rely on pipeline barriers, which wait for commands submitted earlier to the queue.
+/// `Factory` records them from tracked state of the resources; users of `Upload` must record them by hand.
+/// Upload to the resource that has uploads of lower priority not yet submitted, e.g. held back by budget,
+/// is recorded with their priority so uploads to the same resource are submitted in order they are recorded.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UploadPriority {
+    /// Uploads required by commands of the frame. Like per-frame uniforms.
+    Immediate,
+
+    /// Regular uploads.
+    Normal,
+
+    /// Bulk streaming uploads. Those can be deferred to later frames with `Upload::defer_background`.
+    Background,
+}
+
+impl Default for UploadPriority {
+    fn default() -> Self {
+        UploadPriority::Normal
+    }
+}
+
+const PRIORITIES: [UploadPriority; 3] = [
+    UploadPriority::Immediate,
+    UploadPriority::Normal,
+    UploadPriority::Background,
+];
+
+/// Tag of the uploads that allows to cancel them before submission.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadId(pub u64);
+
+/// Identity of the resource uploads are recorded for.
+/// Memory and offset of the block resource is bound to are unique among live resources.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct ResourceKey(usize, u64);
+
+impl ResourceKey {
+    pub(crate) fn of<T>(block: &T) -> Self
+    where
+        T: Block,
+    {
+        ResourceKey(block.memory() as *const _ as usize, block.range().start)
+    }
+}
+
+/// Recording state of uploads of single priority.
+#[derive(Debug)]
+struct Lane<B: Backend> {
+    pool: Option<UploadPool<B>>,
+    cbuf: Option<usize>,
+    staging: Vec<Staging<B>>,
+    chunks: Vec<StagingChunk<B>>,
+    tag: Option<UploadId>,
+    bytes: u64,
+    resources: Vec<ResourceKey>,
+    pending: VecDeque<UploadPool<B>>,
+}
+
+impl<B> Lane<B>
+where
+    B: Backend,
+{
+    fn new() -> Self {
+        Lane {
+            pool: None,
+            cbuf: None,
+            staging: Vec::new(),
+            chunks: Vec::new(),
+            tag: None,
+            bytes: 0,
+            resources: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Check if recorded or pending command buffers reference the resource.
+    fn references(&self, key: ResourceKey) -> bool {
+        self.resources.contains(&key) || self.pending.iter().any(|pool| pool.resources.contains(&key))
+    }
+
+    /// Finish recording command buffer and enqueue it for submission.
+    fn finish(&mut self) {
+        if let Some(index) = self.cbuf.take() {
+            let mut pool = self.pool.take().expect("Pool exists while command buffer is recorded");
+            pool.cbufs[index].finish();
+            pool.staging.extend(self.staging.drain(..));
+            pool.chunks.extend(self.chunks.drain(..));
+            pool.bytes = replace(&mut self.bytes, 0);
+            pool.tag = self.tag.take();
+            pool.resources = replace(&mut self.resources, Vec::new());
+            self.pending.push_back(pool);
+        }
+    }
+
+    /// Take recorded and pending command buffers with specified tag.
+    fn cancel(&mut self, id: UploadId) -> Vec<UploadPool<B>> {
+        let mut cancelled = Vec::new();
+        if self.cbuf.is_some() && self.tag == Some(id) {
+            self.cbuf = None;
+            self.tag = None;
+            self.bytes = 0;
+            self.resources.clear();
+            let mut pool = self.pool.take().expect("Pool exists while command buffer is recorded");
+            pool.staging.extend(self.staging.drain(..));
+            pool.chunks.extend(self.chunks.drain(..));
+            cancelled.push(pool);
+        }
+        let (keep, cancel): (VecDeque<_>, VecDeque<_>) = self.pending
+            .drain(..)
+            .partition(|pool| pool.tag != Some(id));
+        self.pending = keep;
+        cancelled.extend(cancel);
+        cancelled
+    }
+}
+
+/// Get index of the lane to record upload of `priority` to the resource into.
+/// It is the lane of the lowest priority among `priority` and lanes with unsubmitted uploads to the resource.
+fn lane_index<B>(lanes: &[Lane<B>], priority: UploadPriority, key: Option<ResourceKey>) -> usize
+where
+    B: Backend,
+{
+    key.and_then(|key| lanes.iter().rposition(|lane| lane.references(key)))
+        .map_or(priority as usize, |index| max(index, priority as usize))
+}
+
+/// Get number of pending command buffers of each lane to submit with the frame
+/// given sizes of their uploads in order of recording.
+/// Lanes are submitted in order of priority until `budget` is exhausted.
+/// `Immediate` uploads are never held back and are not accounted.
+fn schedule(pending: &[Vec<u64>], budget: Option<u64>) -> Vec<usize> {
+    let mut submitted = 0;
+    let mut exhausted = false;
+    pending
+        .iter()
+        .zip(PRIORITIES.iter())
+        .map(|(bytes, &priority)| {
+            if priority == UploadPriority::Immediate {
+                return bytes.len();
+            }
+            let mut count = 0;
+            for &bytes in bytes {
+                let over = budget.map_or(false, |budget| submitted > 0 && submitted + bytes > budget);
+                if exhausted || over {
+                    // Lanes of lower priority must not overtake held back uploads.
+                    exhausted = true;
+                    break;
+                }
+                submitted += bytes;
+                count += 1;
+            }
+            count
+        })
+        .collect()
+}
+
+/// Records uploads into command buffers.
+/// `Upload` owns staging buffers it creates. They are yielded by `Upload::drain_retired`
+/// after frame they were recorded for retires and must be destroyed with the allocator they were created from.
 #[derive(Debug)]
 pub struct Upload<B: Backend> {
     staging_threshold: usize,
     family: QueueFamilyId,
-    pool: Option<B::CommandPool>,
-    cbuf: Option<B::CommandBuffer>,
-    free: Vec<B::CommandBuffer>,
-    used: VecDeque<(B::CommandBuffer, u64)>,
+    frame: u64,
+    priority: UploadPriority,
+    tag: Option<UploadId>,
+    defer_background: bool,
+    budget: Option<u64>,
+    debug: Option<Arc<DebugMarkers<B>>>,
+    lanes: Vec<Lane<B>>,
+    lane: usize,
+    free: Vec<UploadPool<B>>,
+    used: VecDeque<(UploadPool<B>, u64)>,
+    chunks: Vec<StagingChunk<B>>,
+    retired: Vec<SmartBuffer<B>>,
+    stats: StagingStats,
 }
 
 impl<B> Upload<B>
@@ -38,10 +402,19 @@ where
         Upload {
             staging_threshold,
             family,
-            pool: None,
-            cbuf: None,
+            frame: 0,
+            priority: UploadPriority::Normal,
+            tag: None,
+            defer_background: false,
+            budget: None,
+            debug: None,
+            lanes: PRIORITIES.iter().map(|_| Lane::new()).collect(),
+            lane: UploadPriority::Normal as usize,
             free: Vec::new(),
             used: VecDeque::new(),
+            chunks: Vec::new(),
+            retired: Vec::new(),
+            stats: StagingStats::default(),
         }
     }
 
@@ -52,12 +425,13 @@ where
         buffer: &mut SmartBuffer<B>,
         offset: u64,
         data: &[u8],
-    ) -> Result<Option<SmartBuffer<B>>, Error> {
+    ) -> Result<(), Error> {
         if buffer.size() < offset + data.len() as u64 {
-            return Err(Error::with_chain(
-                MappingError::OutOfBounds,
-                "Buffer upload failed",
-            ));
+            return Err(Error::OutOfBounds {
+                offset,
+                len: data.len() as u64,
+                size: buffer.size(),
+            });
         }
         let props = allocator.properties(buffer.block());
         if props.contains(Properties::CPU_VISIBLE) {
@@ -71,7 +445,7 @@ where
                     data,
                 );
             }
-            Ok(None)
+            Ok(())
         } else {
             self.upload_device_local_buffer(device, allocator, buffer, offset, data)
         }
@@ -87,15 +461,15 @@ where
         layers: SubresourceLayers,
         offset: Offset,
         extent: Extent,
-    ) -> Result<SmartBuffer<B>, Error> {
-        let staging = allocator
-            .create_buffer(
-                device,
-                (Type::ShortLived, Properties::CPU_VISIBLE),
-                data.len() as u64,
-                BufferUsage::TRANSFER_SRC,
-            )
-            .map_err(|err| Error::with_chain(err, "Failed to create staging buffer"))?;
+    ) -> Result<(), Error> {
+        if layers.aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+            return Err(Error::InvalidArgument(
+                "Depth and stencil aspects can't be uploaded with single copy. Use `upload_depth_stencil_image`",
+            ));
+        }
+        // Images are created with optimal tiling, so their layout is opaque to the host
+        // even if memory is cpu-visible, as on UMA devices. Always copy through staging.
+        let staging = create_staging_buffer(device, allocator, data.len() as u64)?;
         let props = allocator.properties(staging.block());
         unsafe {
             // Safe due to block is allocated with `CPU_VISIBLE` property.
@@ -107,7 +481,8 @@ where
                 data,
             );
         }
-        self.get_command_buffer(device).copy_buffer_to_image(
+        let key = ResourceKey::of(image.block());
+        self.get_command_buffer(device, data.len() as u64, Some(key)).copy_buffer_to_image(
             staging.borrow(),
             image.borrow_mut(),
             layout,
@@ -120,48 +495,743 @@ where
                 image_extent: extent,
             }),
         );
-        Ok(staging)
+        self.keep_staging(staging);
+        Ok(())
     }
 
-    pub fn uploads(&mut self, frame: u64) -> Option<(&mut B::CommandBuffer, QueueFamilyId)> {
-        if let Some(mut cbuf) = self.cbuf.take() {
-            cbuf.finish();
-            self.used.push_back((cbuf, frame));
-            Some((&mut self.used.back_mut().unwrap().0, self.family))
-        } else {
-            None
+    /// Upload depth and/or stencil data to the image of depth-stencil format.
+    /// Data for each aspect is tightly packed in the format backends require for buffer-image copies:
+    /// depth of `D16` formats is 2 bytes per texel, depth of `D24` and `D32` formats is 4 bytes per texel
+    /// (lower 24 bits are used for `D24`), stencil is 1 byte per texel.
+    /// Both aspects are placed into single staging buffer and copied with separate regions.
+    pub fn upload_depth_stencil_image(
+        &mut self,
+        device: &B::Device,
+        allocator: &mut SmartAllocator<B>,
+        image: &mut SmartImage<B>,
+        format: Format,
+        layout: ImageLayout,
+        level: Level,
+        layers: Range<Layer>,
+        offset: Offset,
+        extent: Extent,
+        depth: Option<&[u8]>,
+        stencil: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let texels = extent.width as u64 * extent.height as u64 * extent.depth as u64
+            * (layers.end - layers.start) as u64;
+
+        let mut regions = Vec::new();
+        let mut size = 0;
+        for &(aspect, data) in &[(Aspects::DEPTH, depth), (Aspects::STENCIL, stencil)] {
+            if let Some(data) = data {
+                let texel_size = aspect_texel_size(format, aspect)
+                    .ok_or(Error::UnsupportedFormat(format))?;
+                if data.len() as u64 != texels * texel_size {
+                    return Err(Error::SizeMismatch {
+                        what: "bytes of depth or stencil data",
+                        expected: texels * texel_size,
+                        actual: data.len() as u64,
+                    });
+                }
+                // Buffer offset must be multiple of 4 for depth-stencil copies.
+                size = (size + 3) & !3;
+                regions.push((
+                    data,
+                    BufferImageCopy {
+                        buffer_offset: size,
+                        buffer_width: 0,
+                        buffer_height: 0,
+                        image_layers: SubresourceLayers {
+                            aspects: aspect,
+                            level,
+                            layers: layers.clone(),
+                        },
+                        image_offset: offset,
+                        image_extent: extent,
+                    },
+                ));
+                size += data.len() as u64;
+            }
+        }
+
+        if regions.is_empty() {
+            return Err(Error::InvalidArgument("Neither depth nor stencil data provided"));
+        }
+
+        let staging = create_staging_buffer(device, allocator, size)?;
+        let props = allocator.properties(staging.block());
+        for &(data, ref region) in &regions {
+            unsafe {
+                // Safe due to block is allocated with `CPU_VISIBLE` property.
+                update_cpu_visible_block::<B>(
+                    device,
+                    props.contains(Properties::COHERENT),
+                    staging.block(),
+                    region.buffer_offset,
+                    data,
+                );
+            }
+        }
+        let key = ResourceKey::of(image.block());
+        self.get_command_buffer(device, size, Some(key)).copy_buffer_to_image(
+            staging.borrow(),
+            image.borrow_mut(),
+            layout,
+            regions.into_iter().map(|(_, region)| region),
+        );
+        self.keep_staging(staging);
+        Ok(())
+    }
+
+    /// Upload multiple regions of the image, e.g. all mip-levels and layers, from single staging buffer.
+    /// `data` is copied into staging buffer as is and regions refer to it by `buffer_offset`.
+    /// Offsets must be multiples of 4 and of texel block size of the format.
+    pub fn upload_image_regions<I>(
+        &mut self,
+        device: &B::Device,
+        allocator: &mut SmartAllocator<B>,
+        image: &mut SmartImage<B>,
+        layout: ImageLayout,
+        data: &[u8],
+        regions: I,
+    ) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = BufferImageCopy>,
+    {
+        let staging = create_staging_buffer(device, allocator, data.len() as u64)?;
+        let props = allocator.properties(staging.block());
+        unsafe {
+            // Safe due to block is allocated with `CPU_VISIBLE` property.
+            update_cpu_visible_block::<B>(
+                device,
+                props.contains(Properties::COHERENT),
+                staging.block(),
+                0,
+                data,
+            );
+        }
+        let key = ResourceKey::of(image.block());
+        self.get_command_buffer(device, data.len() as u64, Some(key)).copy_buffer_to_image(
+            staging.borrow(),
+            image.borrow_mut(),
+            layout,
+            regions,
+        );
+        self.keep_staging(staging);
+        Ok(())
+    }
+
+    /// Set priority of subsequent uploads.
+    /// Returns previous priority.
+    pub fn set_priority(&mut self, priority: UploadPriority) -> UploadPriority {
+        replace(&mut self.priority, priority)
+    }
+
+    /// Set tag of subsequent uploads.
+    /// Uploads with tag are recorded into separate command buffers so they can be cancelled with `Upload::cancel`
+    /// until submitted.
+    /// Returns previous tag.
+    pub fn set_tag(&mut self, tag: Option<UploadId>) -> Option<UploadId> {
+        replace(&mut self.tag, tag)
+    }
+
+    /// Get tag of subsequent uploads.
+    pub fn tag(&self) -> Option<UploadId> {
+        self.tag
+    }
+
+    /// Cancel uploads with specified tag that are not yet submitted.
+    /// Their staging buffers are retired immediately.
+    /// Returns `true` if any uploads were cancelled.
+    pub fn cancel(&mut self, id: UploadId) -> bool {
+        let cancelled = self.lanes
+            .iter_mut()
+            .flat_map(|lane| lane.cancel(id))
+            .collect::<Vec<_>>();
+        let any = !cancelled.is_empty();
+        for pool in cancelled {
+            self.retire(pool);
+        }
+        any
+    }
+
+    /// Keep `Background` uploads recording instead of submitting them with next frame.
+    /// Use it to spread bulk uploads over several frames when frame is under pressure.
+    pub fn defer_background(&mut self, defer: bool) {
+        self.defer_background = defer;
+    }
+
+    /// Get command buffer of current priority to record commands ordered with uploads to the resource.
+    pub(crate) fn command_buffer(&mut self, device: &B::Device, key: Option<ResourceKey>) -> &mut B::CommandBuffer {
+        self.get_command_buffer(device, 0, key)
+    }
+
+    /// Check if uploads that are not yet submitted reference the resource.
+    /// Such resource must not be destroyed until they are submitted.
+    pub(crate) fn references(&self, key: ResourceKey) -> bool {
+        self.lanes.iter().any(|lane| lane.references(key))
+    }
+
+    /// Record layout transition of the image.
+    /// It is recorded into command buffer of current priority so it is ordered with uploads of the same priority
+    /// and after uploads to the image of lower priority that are not yet submitted.
+    pub fn transition_image(
+        &mut self,
+        device: &B::Device,
+        image: &SmartImage<B>,
+        range: SubresourceRange,
+        stages: Range<PipelineStage>,
+        states: Range<ImageState>,
+    ) {
+        let key = ResourceKey::of(image.block());
+        self.get_command_buffer(device, 0, Some(key)).pipeline_barrier(
+            stages,
+            Some(Barrier::Image {
+                states,
+                target: image.borrow(),
+                range,
+            }),
+        );
+    }
+
+    /// Record copy to the buffer from caller-provided staging buffer.
+    /// Staging buffer must be cpu-visible and created with `TRANSFER_SRC` usage.
+    /// `Upload` takes ownership of the staging buffer and retires it with the frame.
+    pub fn upload_buffer_from_staging(
+        &mut self,
+        device: &B::Device,
+        buffer: &mut SmartBuffer<B>,
+        offset: u64,
+        staging: SmartBuffer<B>,
+        range: Range<u64>,
+    ) -> Result<(), Error> {
+        if range.start > range.end || range.end > staging.size() {
+            return Err(Error::OutOfBounds {
+                offset: range.start,
+                len: range.end.saturating_sub(range.start),
+                size: staging.size(),
+            });
         }
+        if buffer.size() < offset + (range.end - range.start) {
+            return Err(Error::OutOfBounds {
+                offset,
+                len: range.end - range.start,
+                size: buffer.size(),
+            });
+        }
+        let key = ResourceKey::of(buffer.block());
+        self.get_command_buffer(device, range.end - range.start, Some(key)).copy_buffer(
+            staging.borrow(),
+            (&*buffer).borrow(),
+            Some(BufferCopy {
+                src: range.start,
+                dst: offset,
+                size: range.end - range.start,
+            }),
+        );
+        self.keep_staging(staging);
+        Ok(())
     }
 
-    pub fn clear(&mut self, ongoing: u64) {
-        while let Some((mut cbuf, frame)) = self.used.pop_front() {
+    /// Record copy to the image from caller-provided staging buffer.
+    /// Staging buffer must be cpu-visible and created with `TRANSFER_SRC` usage.
+    /// Texels must be tightly packed starting from `staging_offset`.
+    /// Fails with `Error::OutOfBounds` if staging buffer is too small for texels of `format`.
+    /// `Upload` takes ownership of the staging buffer and retires it with the frame.
+    pub fn upload_image_from_staging(
+        &mut self,
+        device: &B::Device,
+        image: &mut SmartImage<B>,
+        format: Format,
+        layout: ImageLayout,
+        layers: SubresourceLayers,
+        offset: Offset,
+        extent: Extent,
+        staging: SmartBuffer<B>,
+        staging_offset: u64,
+    ) -> Result<(), Error> {
+        if layers.aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+            return Err(Error::InvalidArgument("Depth and stencil aspects can't be uploaded with single copy"));
+        }
+        let bytes = check_staging_range(staging.size(), staging_offset, &layers, extent, format)?;
+        let key = ResourceKey::of(image.block());
+        self.get_command_buffer(device, bytes, Some(key)).copy_buffer_to_image(
+            staging.borrow(),
+            image.borrow_mut(),
+            layout,
+            Some(BufferImageCopy {
+                buffer_offset: staging_offset,
+                buffer_width: 0,
+                buffer_height: 0,
+                image_layers: layers,
+                image_offset: offset,
+                image_extent: extent,
+            }),
+        );
+        self.keep_staging(staging);
+        Ok(())
+    }
+
+    /// Name command pools and command buffers created for uploads.
+    pub fn set_debug_markers(&mut self, debug: Option<Arc<DebugMarkers<B>>>) {
+        self.debug = debug;
+    }
+
+    /// Set maximum number of bytes submitted per frame.
+    /// Uploads beyond the budget are recorded into separate command buffers and submitted
+    /// with subsequent frames in order of priority and then in order of recording.
+    /// `Immediate` uploads are never held back and are not accounted.
+    /// Single upload bigger than the budget is submitted alone.
+    pub fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    /// Get number of bytes recorded but held back by the budget.
+    pub fn pending_bytes(&self) -> u64 {
+        self.lanes
+            .iter()
+            .flat_map(|lane| lane.pending.iter())
+            .map(|pool| pool.bytes)
+            .sum()
+    }
+
+    /// Fetch command buffers with uploads recorded in order of priority.
+    /// They must be submitted with current frame of `frames`.
+    pub fn uploads(&mut self, frames: &Frames<B>) -> Vec<(UploadPriority, &mut B::CommandBuffer)> {
+        let frame = frames.current();
+        let defer_background = self.defer_background;
+        for (lane, &priority) in self.lanes.iter_mut().zip(PRIORITIES.iter()) {
+            if !defer_background || priority != UploadPriority::Background {
+                lane.finish();
+            }
+        }
+        let pending = self.lanes
+            .iter()
+            .map(|lane| lane.pending.iter().map(|pool| pool.bytes).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let counts = schedule(&pending, self.budget);
+        for (lane, count) in self.lanes.iter_mut().zip(counts) {
+            for pool in lane.pending.drain(..count) {
+                self.used.push_back((pool, frame));
+            }
+        }
+        self.frame = frame + 1;
+        self.used
+            .iter_mut()
+            .filter(|&&mut (_, f)| f == frame)
+            .map(|&mut (ref mut pool, _)| (pool.priority, &mut pool.cbufs[pool.used - 1]))
+            .collect()
+    }
+
+    /// Finish and submit all recorded uploads including ones held back by budget or deferred,
+    /// then wait for the queue to become idle and retire everything.
+    /// Use it on loading screens or before taking resource snapshots.
+    ///
+    /// # Parameters
+    ///
+    /// `queue`     - queue of the family uploads are recorded for.
+    ///
+    pub fn wait_idle(&mut self, queue: &mut B::CommandQueue) -> Result<(), Error> {
+        let frame = self.frame;
+        for lane in &mut self.lanes {
+            lane.finish();
+            for pool in lane.pending.drain(..) {
+                self.used.push_back((pool, frame));
+            }
+        }
+        unsafe {
+            queue.submit_raw(
+                RawSubmission {
+                    cmd_buffers: self.used
+                        .iter()
+                        .filter(|&&(_, f)| f == frame)
+                        .map(|&(ref pool, _)| &pool.cbufs[pool.used - 1]),
+                    wait_semaphores: &[],
+                    signal_semaphores: &[],
+                },
+                None,
+            );
+        }
+        queue
+            .wait_idle()
+            .map_err(host_execution_error)?;
+        // Frame isn't advanced as nothing recorded for it is outstanding anymore.
+        while let Some((pool, _)) = self.used.pop_front() {
+            self.retire(pool);
+        }
+        Ok(())
+    }
+
+    /// Reset command buffers and retire staging buffers of complete frames.
+    pub fn clear(&mut self, frames: &Frames<B>) {
+        let ongoing = frames.ongoing();
+        while let Some((mut pool, frame)) = self.used.pop_front() {
             if frame >= ongoing {
-                self.used.push_front((cbuf, ongoing));
+                self.used.push_front((pool, frame));
                 break;
             }
-            cbuf.reset(true);
-            self.free.push(cbuf);
+            self.retire(pool);
         }
+        if self.frame < ongoing {
+            self.frame = ongoing;
+        }
+    }
+
+    /// Check if there are no recorded or pending command buffers.
+    pub fn is_idle(&self) -> bool {
+        self.lanes
+            .iter()
+            .all(|lane| lane.cbuf.is_none() && lane.pending.is_empty()) && self.used.is_empty()
     }
 
-    fn get_command_buffer<'a>(&'a mut self, device: &B::Device) -> &'a mut B::CommandBuffer {
+    /// Get frames of submitted command buffers that aren't retired.
+    pub fn outstanding_frames(&self) -> Vec<u64> {
+        let mut frames = self.used.iter().map(|&(_, frame)| frame).collect::<Vec<_>>();
+        frames.dedup();
+        frames
+    }
+
+    /// Get staging buffers which frames are retired.
+    /// They must be destroyed with the allocator they were created from.
+    pub fn drain_retired(&mut self) -> Drain<SmartBuffer<B>> {
+        self.retired.drain(..)
+    }
+
+    /// Get counters of staging buffers created and retired.
+    pub fn staging_stats(&self) -> StagingStats {
+        self.stats
+    }
+
+    /// List staging buffers that were recorded for frame `current - age` or earlier and not yet retired.
+    /// Those are likely kept alive by frames that were never cleared.
+    pub fn orphaned_staging(&self, current: u64, age: u64) -> Vec<StagingInfo> {
+        self.used
+            .iter()
+            .flat_map(|&(ref pool, _)| pool.staging.iter())
+            .chain(
+                self.lanes
+                    .iter()
+                    .flat_map(|lane| lane.pending.iter())
+                    .flat_map(|pool| pool.staging.iter()),
+            )
+            .chain(self.lanes.iter().flat_map(|lane| lane.staging.iter()))
+            .filter(|staging| staging.frame + age <= current)
+            .map(|staging| StagingInfo {
+                frame: staging.frame,
+                size: staging.buffer.size(),
+            })
+            .collect()
+    }
+
+    /// Retire all submitted frames recorded for frame `current - age` or earlier regardless of `ongoing` frame.
+    ///
+    /// # Safety
+    ///
+    /// Caller must wait for device to become idle before calling this function.
+    pub unsafe fn force_retire(&mut self, current: u64, age: u64) {
+        while let Some((pool, frame)) = self.used.pop_front() {
+            if frame + age > current {
+                self.used.push_front((pool, frame));
+                break;
+            }
+            warn!("Force retiring {} staging buffers of frame {}", pool.staging.len(), frame);
+            self.retire(pool);
+        }
+    }
+
+    /// Destroy command pools and take all staging buffers including ones of pending uploads.
+    /// Staging buffers must be destroyed with the allocator they were created from.
+    ///
+    /// # Safety
+    ///
+    /// Device must be idle or lost.
+    pub unsafe fn dispose(self, device: &B::Device) -> Vec<SmartBuffer<B>> {
+        let Upload {
+            lanes,
+            free,
+            used,
+            chunks,
+            mut retired,
+            ..
+        } = self;
+        let mut pools = free;
+        pools.extend(used.into_iter().map(|(pool, _)| pool));
+        retired.extend(chunks.into_iter().map(|chunk| chunk.buffer));
+        for lane in lanes {
+            retired.extend(lane.staging.into_iter().map(|staging| staging.buffer));
+            retired.extend(lane.chunks.into_iter().map(|chunk| chunk.buffer));
+            pools.extend(lane.pool);
+            pools.extend(lane.pending);
+        }
+        for pool in pools {
+            retired.extend(pool.staging.into_iter().map(|staging| staging.buffer));
+            retired.extend(pool.chunks.into_iter().map(|chunk| chunk.buffer));
+            device.destroy_command_pool(pool.pool);
+        }
+        retired
+    }
+
+    fn retire(&mut self, mut pool: UploadPool<B>) {
+        pool.reset();
+        for Staging { buffer, .. } in pool.staging.drain(..) {
+            self.stats.retired += 1;
+            self.stats.retired_bytes += buffer.size();
+            self.retired.push(buffer);
+        }
+        self.chunks.extend(pool.chunks.drain(..).map(|mut chunk| {
+            chunk.used = 0;
+            chunk
+        }));
+        self.free.push(pool);
+    }
+
+    fn keep_staging(&mut self, buffer: SmartBuffer<B>) {
+        self.stats.created += 1;
+        self.stats.created_bytes += buffer.size();
+        self.lanes[self.lane].staging.push(Staging {
+            buffer,
+            frame: self.frame,
+        });
+    }
+
+    /// Get command buffer to record upload of `bytes` size to the resource.
+    /// Uploads are recorded into the lane of current priority unless the resource has unsubmitted uploads
+    /// in the lane of lower priority. Then they are recorded into that lane,
+    /// so uploads to the same resource are submitted in order they are recorded.
+    fn get_command_buffer<'a>(
+        &'a mut self,
+        device: &B::Device,
+        bytes: u64,
+        key: Option<ResourceKey>,
+    ) -> &'a mut B::CommandBuffer {
         let Upload {
             family,
-            ref mut pool,
+            priority,
+            tag,
+            budget,
+            ref debug,
+            ref mut lanes,
+            ref mut lane,
             ref mut free,
-            ref mut cbuf,
             ..
         } = *self;
-        cbuf.get_or_insert_with(|| {
-            let mut cbuf = free.pop().unwrap_or_else(|| {
-                let pool = pool.get_or_insert_with(|| {
-                    device.create_command_pool(family, CommandPoolCreateFlags::empty())
-                });
-                pool.allocate(1, RawLevel::Primary).remove(0)
+        *lane = lane_index(lanes, priority, key);
+        let priority = PRIORITIES[*lane];
+        let lane = &mut lanes[*lane];
+        if lane.cbuf.is_some() && lane.tag != tag {
+            // Uploads with different tags are recorded separately to be cancelable.
+            lane.finish();
+        }
+        lane.tag = tag;
+        if let Some(budget) = budget {
+            if lane.bytes > 0 && lane.bytes + bytes > budget {
+                // Start new command buffer so that uploads can be submitted within budget.
+                lane.finish();
+            }
+        }
+        lane.bytes += bytes;
+        if let Some(key) = key {
+            if !lane.resources.contains(&key) {
+                lane.resources.push(key);
+            }
+        }
+        let Lane {
+            ref mut pool,
+            ref mut cbuf,
+            ..
+        } = *lane;
+        let pool = pool.get_or_insert_with(|| {
+            let mut pool = free.pop().unwrap_or_else(|| {
+                let pool = UploadPool::new(device, family);
+                if let Some(ref debug) = *debug {
+                    debug.set_name(device, DebugObject::CommandPool(&pool.pool), "upload pool");
+                }
+                pool
             });
-            cbuf.begin(CommandBufferFlags::empty());
-            cbuf
-        })
+            pool.priority = priority;
+            pool
+        });
+        let index = *cbuf.get_or_insert_with(|| {
+            let index = pool.acquire();
+            if let Some(ref debug) = *debug {
+                let name = format!("upload {:?}", priority);
+                debug.set_name(device, DebugObject::CommandBuffer(&pool.cbufs[index]), &name);
+            }
+            pool.cbufs[index].begin(CommandBufferFlags::ONE_TIME_SUBMIT);
+            index
+        });
+        &mut pool.cbufs[index]
+    }
+
+    /// Write data into the staging arena and record copy to the buffer.
+    /// Data must not be bigger than `STAGING_CHUNK_SIZE`.
+    ///
+    /// # Parameters
+    ///
+    /// `create`    - creates cpu-visible chunk of `STAGING_CHUNK_SIZE` bytes with `TRANSFER_SRC` usage
+    ///               and tells if its memory is coherent. Called only if no chunk can fit the data.
+    ///
+    pub(crate) fn upload_buffer_from_arena<F>(
+        &mut self,
+        device: &B::Device,
+        buffer: &mut SmartBuffer<B>,
+        offset: u64,
+        data: &[u8],
+        create: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce() -> Result<(SmartBuffer<B>, bool), Error>,
+    {
+        if buffer.size() < offset + data.len() as u64 {
+            return Err(Error::OutOfBounds {
+                offset,
+                len: data.len() as u64,
+                size: buffer.size(),
+            });
+        }
+        let key = ResourceKey::of(buffer.block());
+        self.get_command_buffer(device, data.len() as u64, Some(key));
+        let src = self.write_arena(device, BUFFER_STAGING_ALIGNMENT, data, create)?;
+        let (cbuf, staging) = self.arena_command_buffer();
+        cbuf.copy_buffer(
+            staging.borrow(),
+            (&*buffer).borrow(),
+            Some(BufferCopy {
+                src,
+                dst: offset,
+                size: data.len() as u64,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Write texels into the staging arena and record copy to the image.
+    /// Data must not be bigger than `STAGING_CHUNK_SIZE`.
+    /// See `Upload::upload_buffer_from_arena`.
+    pub(crate) fn upload_image_from_arena<F>(
+        &mut self,
+        device: &B::Device,
+        image: &mut SmartImage<B>,
+        layout: ImageLayout,
+        layers: SubresourceLayers,
+        offset: Offset,
+        extent: Extent,
+        data: &[u8],
+        create: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce() -> Result<(SmartBuffer<B>, bool), Error>,
+    {
+        if layers.aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+            return Err(Error::InvalidArgument("Depth and stencil aspects can't be uploaded with single copy"));
+        }
+        let key = ResourceKey::of(image.block());
+        self.get_command_buffer(device, data.len() as u64, Some(key));
+        let buffer_offset = self.write_arena(device, IMAGE_STAGING_ALIGNMENT, data, create)?;
+        let (cbuf, staging) = self.arena_command_buffer();
+        cbuf.copy_buffer_to_image(
+            staging.borrow(),
+            image.borrow_mut(),
+            layout,
+            Some(BufferImageCopy {
+                buffer_offset,
+                buffer_width: 0,
+                buffer_height: 0,
+                image_layers: layers,
+                image_offset: offset,
+                image_extent: extent,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Write data into the last staging chunk of the current lane at offset aligned to `alignment`.
+    /// New chunk is taken from retired ones or created with `create` if data doesn't fit.
+    /// Returns offset of the data in the chunk.
+    fn write_arena<F>(&mut self, device: &B::Device, alignment: u64, data: &[u8], create: F) -> Result<u64, Error>
+    where
+        F: FnOnce() -> Result<(SmartBuffer<B>, bool), Error>,
+    {
+        let size = data.len() as u64;
+        assert!(size <= STAGING_CHUNK_SIZE);
+        let Upload {
+            lane,
+            ref mut lanes,
+            ref mut chunks,
+            ref mut stats,
+            ..
+        } = *self;
+        let ref mut lane = lanes[lane];
+        let fits = lane.chunks
+            .last()
+            .map_or(false, |chunk| align_up(chunk.used, alignment) + size <= chunk.buffer.size());
+        if !fits {
+            let chunk = match chunks.pop() {
+                Some(chunk) => chunk,
+                None => {
+                    let (buffer, coherent) = create()?;
+                    stats.created += 1;
+                    stats.created_bytes += buffer.size();
+                    StagingChunk {
+                        buffer,
+                        coherent,
+                        used: 0,
+                    }
+                }
+            };
+            lane.chunks.push(chunk);
+        }
+        let chunk = lane.chunks.last_mut().expect("Chunk is pushed above");
+        let offset = align_up(chunk.used, alignment);
+        unsafe {
+            // Safe due to chunks are created cpu-visible.
+            update_cpu_visible_block::<B>(device, chunk.coherent, chunk.buffer.block(), offset, data);
+        }
+        chunk.used = offset + size;
+        Ok(offset)
+    }
+
+    /// Get command buffer of the current lane and its last staging chunk.
+    fn arena_command_buffer(&mut self) -> (&mut B::CommandBuffer, &SmartBuffer<B>) {
+        let Lane {
+            ref mut pool,
+            cbuf,
+            ref chunks,
+            ..
+        } = self.lanes[self.lane];
+        let pool = pool.as_mut().expect("Pool exists while command buffer is recorded");
+        let cbuf = cbuf.expect("Command buffer is begun");
+        let chunk = chunks.last().expect("Data is written into the chunk");
+        (&mut pool.cbufs[cbuf], &chunk.buffer)
+    }
+
+    /// Write data through command buffer directly if it is small enough and aligned for `update_buffer`.
+    /// Returns `false` if data must be copied through staging buffer instead.
+    pub(crate) fn update_buffer(
+        &mut self,
+        device: &B::Device,
+        buffer: &mut SmartBuffer<B>,
+        offset: u64,
+        data: &[u8],
+    ) -> bool {
+        // `update_buffer` requires offset and size to be multiple of 4.
+        let aligned = offset % UPDATE_BUFFER_ALIGNMENT == 0
+            && data.len() as u64 % UPDATE_BUFFER_ALIGNMENT == 0;
+        if !aligned || data.len() > self.staging_threshold {
+            return false;
+        }
+        let key = ResourceKey::of(buffer.block());
+        let cbuf = self.get_command_buffer(device, data.len() as u64, Some(key));
+        // `update_buffer` can't write more than 65536 bytes at once.
+        for (index, chunk) in data.chunks(MAX_UPDATE_BUFFER_SIZE).enumerate() {
+            cbuf.update_buffer(
+                (&*buffer).borrow(),
+                offset + (index * MAX_UPDATE_BUFFER_SIZE) as u64,
+                chunk,
+            );
+        }
+        true
     }
 
     fn upload_device_local_buffer(
@@ -171,20 +1241,11 @@ where
         buffer: &mut SmartBuffer<B>,
         offset: u64,
         data: &[u8],
-    ) -> Result<Option<SmartBuffer<B>>, Error> {
-        if data.len() <= self.staging_threshold {
-            self.get_command_buffer(device)
-                .update_buffer((&*buffer).borrow(), offset, data);
-            Ok(None)
+    ) -> Result<(), Error> {
+        if self.update_buffer(device, buffer, offset, data) {
+            Ok(())
         } else {
-            let staging = allocator
-                .create_buffer(
-                    device,
-                    (Type::ShortLived, Properties::CPU_VISIBLE),
-                    data.len() as u64,
-                    BufferUsage::TRANSFER_SRC,
-                )
-                .map_err(|err| Error::with_chain(err, "Failed to create staging buffer"))?;
+            let staging = create_staging_buffer(device, allocator, data.len() as u64)?;
             let props = allocator.properties(staging.block());
             unsafe {
                 // Safe due to block is allocated with `CPU_VISIBLE` property.
@@ -196,7 +1257,8 @@ where
                     data,
                 );
             }
-            self.get_command_buffer(device).copy_buffer(
+            let key = ResourceKey::of(buffer.block());
+            self.get_command_buffer(device, data.len() as u64, Some(key)).copy_buffer(
                 staging.borrow(),
                 (&*buffer).borrow(),
                 Some(BufferCopy {
@@ -205,11 +1267,262 @@ where
                     size: data.len() as u64,
                 }),
             );
-            Ok(Some(staging))
+            self.keep_staging(staging);
+            Ok(())
+        }
+    }
+}
+
+/// Upload recorder handed out by `UploadGroup`.
+/// It will be returned to the group when dropped.
+#[derive(Debug)]
+pub struct UploadRecorder<B: Backend> {
+    inner: Escape<Upload<B>>,
+}
+
+impl<B> Deref for UploadRecorder<B>
+where
+    B: Backend,
+{
+    type Target = Upload<B>;
+    fn deref(&self) -> &Upload<B> {
+        &*self.inner
+    }
+}
+
+impl<B> DerefMut for UploadRecorder<B>
+where
+    B: Backend,
+{
+    fn deref_mut(&mut self) -> &mut Upload<B> {
+        &mut *self.inner
+    }
+}
+
+/// Group of upload recorders that can be used from different threads simultaneously.
+/// Each recorder owns its own command pools and staging arena so recording requires no synchronization.
+/// Recorders are returned to the group when dropped and their command buffers
+/// are collected by `UploadGroup::uploads`.
+#[derive(Debug)]
+pub struct UploadGroup<B: Backend> {
+    staging_threshold: usize,
+    family: QueueFamilyId,
+    idle: Mutex<Vec<Upload<B>>>,
+    returned: Terminal<Upload<B>>,
+    active: Vec<Upload<B>>,
+    retired: Vec<SmartBuffer<B>>,
+    stats: StagingStats,
+}
+
+impl<B> UploadGroup<B>
+where
+    B: Backend,
+{
+    /// Create group of recorders recording uploads for the queue `family`.
+    pub fn new(staging_threshold: usize, family: QueueFamilyId) -> Self {
+        UploadGroup {
+            staging_threshold,
+            family,
+            idle: Mutex::new(Vec::new()),
+            returned: Terminal::new(),
+            active: Vec::new(),
+            retired: Vec::new(),
+            stats: StagingStats::default(),
+        }
+    }
+
+    /// Get recorder that can be sent to another thread.
+    /// Commands it records will be submitted with the first `uploads` call after it is dropped.
+    /// Resources uploaded with the recorder must not be destroyed before it is dropped.
+    pub fn recorder(&self) -> UploadRecorder<B> {
+        let upload = self.idle
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| Upload::new(self.staging_threshold, self.family));
+        UploadRecorder {
+            inner: self.returned.escape(upload),
         }
     }
+
+    /// Fetch command buffers with uploads recorded by all returned recorders.
+    /// They must be submitted with current frame of `frames`.
+    pub fn uploads(&mut self, frames: &Frames<B>) -> Vec<(UploadPriority, &mut B::CommandBuffer)> {
+        self.active.extend(self.returned.drain());
+        self.active
+            .iter_mut()
+            .flat_map(|upload| upload.uploads(frames))
+            .collect()
+    }
+
+    /// Check if uploads of returned recorders that are not yet submitted reference the resource.
+    pub(crate) fn references(&mut self, key: ResourceKey) -> bool {
+        self.active.extend(self.returned.drain());
+        self.active.iter().any(|upload| upload.references(key))
+    }
+
+    /// Queue family uploads are recorded for.
+    pub fn family(&self) -> QueueFamilyId {
+        self.family
+    }
+
+    /// Reset command buffers and retire staging buffers of complete frames.
+    /// Recorders with nothing left in flight become idle and keep their staging arenas for reuse.
+    pub fn clear(&mut self, frames: &Frames<B>) {
+        let mut idle = self.idle.lock().unwrap();
+        for mut upload in replace(&mut self.active, Vec::new()) {
+            upload.clear(frames);
+            self.retired.extend(upload.drain_retired());
+            if upload.is_idle() {
+                // Keep counters of recorders going idle.
+                self.stats = self.stats.merge(upload.staging_stats());
+                upload.stats = StagingStats::default();
+                idle.push(upload);
+            } else {
+                self.active.push(upload);
+            }
+        }
+    }
+
+    /// Submit uploads of all returned recorders and wait for the queue to become idle.
+    /// See `Upload::wait_idle`.
+    pub fn wait_idle(&mut self, queue: &mut B::CommandQueue) -> Result<(), Error> {
+        self.active.extend(self.returned.drain());
+        for upload in &mut self.active {
+            upload.wait_idle(queue)?;
+            self.retired.extend(upload.drain_retired());
+        }
+        Ok(())
+    }
+
+    /// Get frames of submitted command buffers of recorders that aren't retired.
+    pub fn outstanding_frames(&self) -> Vec<u64> {
+        let mut frames = self.active
+            .iter()
+            .flat_map(|upload| upload.outstanding_frames())
+            .collect::<Vec<_>>();
+        frames.sort();
+        frames.dedup();
+        frames
+    }
+
+    /// Get staging buffers which frames are retired.
+    pub fn drain_retired(&mut self) -> Drain<SmartBuffer<B>> {
+        self.retired.drain(..)
+    }
+
+    /// Get counters of staging buffers created and retired by recorders.
+    /// Recorders that are not returned yet are not accounted.
+    pub fn staging_stats(&self) -> StagingStats {
+        self.active
+            .iter()
+            .fold(self.stats, |stats, upload| stats.merge(upload.staging_stats()))
+    }
+
+    /// List staging buffers of returned recorders that were recorded for frame `current - age` or earlier and not yet retired.
+    pub fn orphaned_staging(&self, current: u64, age: u64) -> Vec<StagingInfo> {
+        self.active
+            .iter()
+            .flat_map(|upload| upload.orphaned_staging(current, age))
+            .collect()
+    }
+
+    /// Destroy command pools of idle and returned recorders and take all their staging buffers.
+    /// Recorders that are not returned yet are leaked.
+    ///
+    /// # Safety
+    ///
+    /// Device must be idle or lost.
+    pub unsafe fn dispose(mut self, device: &B::Device) -> Vec<SmartBuffer<B>> {
+        self.active.extend(self.returned.drain());
+        let mut retired = self.retired;
+        let idle = self.idle.into_inner().unwrap();
+        for upload in self.active.into_iter().chain(idle) {
+            retired.extend(upload.dispose(device));
+        }
+        retired
+    }
+
+    /// Retire all submitted frames of returned recorders recorded for frame `current - age` or earlier.
+    ///
+    /// # Safety
+    ///
+    /// Caller must wait for device to become idle before calling this function.
+    pub unsafe fn force_retire(&mut self, current: u64, age: u64) {
+        for upload in &mut self.active {
+            upload.force_retire(current, age);
+            self.retired.extend(upload.drain_retired());
+        }
+    }
+}
+
+/// Round `offset` up to multiple of `alignment`.
+fn align_up(offset: u64, alignment: u64) -> u64 {
+    (offset + alignment - 1) / alignment * alignment
+}
+
+/// Create cpu-visible buffer to copy data from.
+pub(crate) fn create_staging_buffer<B: Backend>(
+    device: &B::Device,
+    allocator: &mut SmartAllocator<B>,
+    size: u64,
+) -> Result<SmartBuffer<B>, Error> {
+    allocator
+        .create_buffer(
+            device,
+            (Type::ShortLived, Properties::CPU_VISIBLE),
+            size,
+            BufferUsage::TRANSFER_SRC,
+        )
+        .map_err(|err| Error::allocation("staging buffer", err))
+}
+
+/// Size in bytes of single texel of the aspect in buffer-image copies.
+/// Get number of bytes tightly packed texels of `format` take in the region.
+/// Block-compressed formats take whole blocks.
+fn required_bytes(layers: &SubresourceLayers, extent: Extent, format: Format) -> u64 {
+    let desc = format.surface_desc();
+    let (block_width, block_height) = (desc.dim.0 as u64, desc.dim.1 as u64);
+    let blocks_x = (extent.width as u64 + block_width - 1) / block_width;
+    let blocks_y = (extent.height as u64 + block_height - 1) / block_height;
+    let layer_count = (layers.layers.end - layers.layers.start) as u64;
+    blocks_x * blocks_y * extent.depth as u64 * layer_count * (desc.bits as u64 / 8)
+}
+
+/// Check that texels of the region fit into staging buffer of `size` bytes after `staging_offset`.
+/// Returns number of bytes they take.
+fn check_staging_range(
+    size: u64,
+    staging_offset: u64,
+    layers: &SubresourceLayers,
+    extent: Extent,
+    format: Format,
+) -> Result<u64, Error> {
+    let required = required_bytes(layers, extent, format);
+    if staging_offset.checked_add(required).map_or(true, |end| end > size) {
+        return Err(Error::OutOfBounds {
+            offset: staging_offset,
+            len: required,
+            size,
+        });
+    }
+    Ok(required)
 }
 
+fn aspect_texel_size(format: Format, aspect: Aspects) -> Option<u64> {
+    match (format, aspect) {
+        (Format::D16Unorm, Aspects::DEPTH) | (Format::D16UnormS8Uint, Aspects::DEPTH) => Some(2),
+        (Format::X8D24Unorm, Aspects::DEPTH)
+        | (Format::D24UnormS8Uint, Aspects::DEPTH)
+        | (Format::D32Float, Aspects::DEPTH)
+        | (Format::D32FloatS8Uint, Aspects::DEPTH) => Some(4),
+        (Format::S8Uint, Aspects::STENCIL)
+        | (Format::D16UnormS8Uint, Aspects::STENCIL)
+        | (Format::D24UnormS8Uint, Aspects::STENCIL)
+        | (Format::D32FloatS8Uint, Aspects::STENCIL) => Some(1),
+        _ => None,
+    }
+}
 
 /// Update cpu-visible block.
 /// 
@@ -225,8 +1538,30 @@ pub unsafe fn update_cpu_visible_block<B: Backend>(
     offset: u64,
     data: &[u8],
 ) {
-    let start = block.range().start + offset;
-    let end = start + data.len() as u64;
+    write_cpu_visible_block::<B, _>(device, coherent, block, offset..offset + data.len() as u64, |slice| {
+        slice.copy_from_slice(data)
+    });
+}
+
+/// Map range of cpu-visible block and let `f` write into it.
+/// 
+/// # Safety
+/// 
+/// Caller must be sure that memory of the block has `CPU_VISIBLE` property.
+/// `coherent` argument must be set to `true` only if memory of the block has `COHERENT` property.
+/// 
+pub unsafe fn write_cpu_visible_block<B, F>(
+    device: &B::Device,
+    coherent: bool,
+    block: &SmartBlock<B::Memory>,
+    range: Range<u64>,
+    f: F,
+) where
+    B: Backend,
+    F: FnOnce(&mut [u8]),
+{
+    let start = block.range().start + range.start;
+    let end = block.range().start + range.end;
     let range = start..end;
     debug_assert!(
         end <= block.range().end,
@@ -238,9 +1573,135 @@ pub unsafe fn update_cpu_visible_block<B: Backend>(
     if !coherent {
         device.invalidate_mapped_memory_ranges(Some((block.memory(), range.clone())));
     }
-    let slice = from_raw_parts_mut(ptr, data.len());
-    slice.copy_from_slice(data);
+    let slice = from_raw_parts_mut(ptr, (end - start) as usize);
+    f(slice);
     if !coherent {
         device.flush_mapped_memory_ranges(Some((block.memory(), range)));
     }
-}
\ No newline at end of file
+}
+
+/// Map range of cpu-visible block and let `f` read from it.
+/// Non-coherent memory is invalidated first, so writes of complete device commands are visible.
+/// 
+/// # Safety
+/// 
+/// Caller must be sure that memory of the block has `CPU_VISIBLE` property.
+/// `coherent` argument must be set to `true` only if memory of the block has `COHERENT` property.
+/// 
+pub unsafe fn read_cpu_visible_block<B, F, T>(
+    device: &B::Device,
+    coherent: bool,
+    block: &SmartBlock<B::Memory>,
+    range: Range<u64>,
+    f: F,
+) -> Result<T, Error>
+where
+    B: Backend,
+    F: FnOnce(&[u8]) -> T,
+{
+    let start = block.range().start + range.start;
+    let end = block.range().start + range.end;
+    let range = start..end;
+    debug_assert!(end <= block.range().end, "Checked by caller");
+    let ptr = device.map_memory(block.memory(), range.clone())?;
+    if !coherent {
+        device.invalidate_mapped_memory_ranges(Some((block.memory(), range)));
+    }
+    let result = f(from_raw_parts(ptr, (end - start) as usize));
+    device.unmap_memory(block.memory());
+    Ok(result)
+}
+
+#[test]
+fn clear_follows_complete_frames() {
+    use hal::queue::QueueFamilyId;
+    use testing::{FrameDriver, TestBackend};
+
+    let mut driver = FrameDriver::<TestBackend>::new();
+    let mut upload = Upload::<TestBackend>::new(0, QueueFamilyId(0));
+    driver.submit_many(3);
+    upload.clear(driver.frames());
+    assert_eq!(upload.frame, 0);
+    driver.complete(1);
+    upload.clear(driver.frames());
+    assert_eq!(upload.frame, 2);
+    assert!(upload.is_idle());
+    assert!(upload.outstanding_frames().is_empty());
+}
+
+#[test]
+fn uploads_follow_unsubmitted_uploads_to_same_resource() {
+    use testing::TestBackend;
+
+    let mut lanes = PRIORITIES.iter().map(|_| Lane::<TestBackend>::new()).collect::<Vec<_>>();
+    let background = ResourceKey(1, 0);
+    let normal = ResourceKey(1, 256);
+    lanes[UploadPriority::Background as usize].resources.push(background);
+    lanes[UploadPriority::Normal as usize].resources.push(normal);
+
+    // Uploads to resources without pending uploads keep their priority.
+    assert_eq!(lane_index(&lanes, UploadPriority::Immediate, None), 0);
+    assert_eq!(lane_index(&lanes, UploadPriority::Immediate, Some(ResourceKey(2, 0))), 0);
+
+    // Uploads can't overtake uploads to the same resource recorded with lower priority.
+    assert_eq!(lane_index(&lanes, UploadPriority::Immediate, Some(background)), 2);
+    assert_eq!(lane_index(&lanes, UploadPriority::Normal, Some(normal)), 1);
+    assert_eq!(lane_index(&lanes, UploadPriority::Immediate, Some(normal)), 1);
+    assert_eq!(lane_index(&lanes, UploadPriority::Background, Some(normal)), 2);
+}
+
+#[test]
+fn schedule_submits_in_order_of_priority_within_budget() {
+    // Sizes of pending uploads of `Immediate`, `Normal` and `Background` lanes.
+    let pending = vec![vec![64, 64], vec![32, 32, 32], vec![16]];
+
+    // Without budget everything is submitted.
+    assert_eq!(schedule(&pending, None), vec![2, 3, 1]);
+
+    // `Immediate` uploads ignore budget, the rest are submitted in order until it is exhausted.
+    assert_eq!(schedule(&pending, Some(64)), vec![2, 2, 0]);
+
+    // `Background` upload fits after `Normal` ones are held back, but must not overtake them.
+    assert_eq!(schedule(&pending, Some(50)), vec![2, 1, 0]);
+
+    // Single upload bigger than the budget is submitted alone.
+    assert_eq!(schedule(&[vec![], vec![128, 8], vec![]], Some(64)), vec![0, 1, 0]);
+}
+
+#[test]
+fn arena_offsets_are_aligned() {
+    assert_eq!(align_up(0, IMAGE_STAGING_ALIGNMENT), 0);
+    assert_eq!(align_up(1, IMAGE_STAGING_ALIGNMENT), 96);
+    assert_eq!(align_up(96, IMAGE_STAGING_ALIGNMENT), 96);
+    assert_eq!(align_up(6, BUFFER_STAGING_ALIGNMENT), 8);
+    // Any texel block size divides image alignment.
+    for &block in &[1, 2, 3, 4, 6, 8, 12, 16, 24, 32] {
+        assert_eq!(IMAGE_STAGING_ALIGNMENT % block, 0);
+    }
+}
+
+#[test]
+fn staging_too_small_for_image_region_is_out_of_bounds() {
+    let layers = SubresourceLayers {
+        aspects: Aspects::COLOR,
+        level: 0,
+        layers: 0..2,
+    };
+    let extent = Extent {
+        width: 4,
+        height: 4,
+        depth: 1,
+    };
+    // 4x4 texels of 4 bytes in 2 layers.
+    assert_eq!(check_staging_range(128, 0, &layers, extent, Format::Rgba8Unorm).unwrap(), 128);
+    assert_eq!(check_staging_range(256, 128, &layers, extent, Format::Rgba8Unorm).unwrap(), 128);
+    match check_staging_range(256, 192, &layers, extent, Format::Rgba8Unorm) {
+        Err(Error::OutOfBounds { offset, len, size }) => assert_eq!((offset, len, size), (192, 128, 256)),
+        other => panic!("Unexpected result {:?}", other),
+    }
+    assert!(check_staging_range(256, !0, &layers, extent, Format::Rgba8Unorm).is_err());
+
+    // Single 4x4 block of 8 bytes per layer.
+    assert_eq!(check_staging_range(16, 0, &layers, extent, Format::Bc1RgbaUnorm).unwrap(), 16);
+    assert!(check_staging_range(15, 0, &layers, extent, Format::Bc1RgbaUnorm).is_err());
+}
@@ -0,0 +1,278 @@
+//! `VirtualTexture` streams pages of texture too large to be resident into fixed-size page cache.
+//! 
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use hal::Backend;
+use hal::device::Extent;
+use hal::format::Format;
+use hal::image::{AaMode, Kind, Offset, SamplerInfo};
+
+use Error;
+use factory::{Buffer, Factory};
+use texture::Texture;
+use upload::UploadPriority;
+
+/// Indirection table entry of the page that is not resident.
+pub const PAGE_NOT_RESIDENT: u32 = !0;
+
+/// Backing store of virtual texture pages.
+pub trait PageSource {
+    /// Read texels of the page at (`x`, `y`) in pages into `data`.
+    /// `data` is cleared before the call and must receive `page_size * page_size` tightly packed texels.
+    fn read_page(&mut self, x: u32, y: u32, data: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    page: Option<u32>,
+    used: u64,
+}
+
+/// Single-level virtual texture backed by page cache texture and indirection table.
+///
+/// Shaders read indirection table buffer with one `u32` per virtual page in row-major order.
+/// Entry is either `PAGE_NOT_RESIDENT` or cache slot coordinates packed as `x | y << 16`.
+/// Pages are uploaded with `Background` priority, so they are subject to the upload budget
+/// set with `Factory::set_upload_budget`.
+#[derive(Debug)]
+pub struct VirtualTexture<B: Backend> {
+    cache: Texture<B>,
+    indirection: Buffer<B>,
+    table: Vec<u32>,
+    format: Format,
+    texel_size: u32,
+    page_size: u32,
+    pages: (u32, u32),
+    cache_pages: u32,
+    slots: Vec<Slot>,
+    resident: HashMap<u32, u32>,
+    requests: VecDeque<u32>,
+    requested: HashSet<u32>,
+    data: Vec<u8>,
+}
+
+impl<B> VirtualTexture<B>
+where
+    B: Backend,
+{
+    /// Create virtual texture with empty page cache.
+    /// 
+    /// # Parameters
+    /// 
+    /// `format`        - format of the texture. It must be color format.
+    /// `pages`         - width and height of the virtual texture in pages.
+    /// `page_size`     - width and height of the page in texels.
+    /// `cache_pages`   - width and height of the page cache in pages.
+    /// `sampler`       - parameters of sampler created with page cache.
+    /// 
+    pub fn new(
+        factory: &mut Factory<B>,
+        format: Format,
+        pages: (u32, u32),
+        page_size: u32,
+        cache_pages: u32,
+        sampler: Option<SamplerInfo>,
+    ) -> Result<Self, Error> {
+        let cache_size = page_size * cache_pages;
+        if cache_size > u16::max_value() as u32 {
+            return Err(Error::InvalidArgument("Page cache is larger than 65535x65535 texels"));
+        }
+        let cache = factory.create_empty_texture(
+            Kind::D2(cache_size as u16, cache_size as u16, AaMode::Single),
+            format,
+            sampler,
+        )?;
+
+        let table = vec![PAGE_NOT_RESIDENT; (pages.0 * pages.1) as usize];
+        let mut indirection = factory
+            .buffer()
+            .storage()
+            .device_local()
+            .size(table.len() as u64 * 4)
+            .name("virtual texture indirection")
+            .build()?;
+        factory.upload_buffer(&mut indirection, 0, &table_bytes(&table))?;
+
+        Ok(VirtualTexture {
+            cache,
+            indirection,
+            table,
+            format,
+            texel_size: format.surface_desc().bits as u32 / 8,
+            page_size,
+            pages,
+            cache_pages,
+            slots: vec![Slot { page: None, used: 0 }; (cache_pages * cache_pages) as usize],
+            resident: HashMap::new(),
+            requests: VecDeque::new(),
+            requested: HashSet::new(),
+            data: Vec::new(),
+        })
+    }
+
+    /// Get page cache texture.
+    pub fn cache(&self) -> &Texture<B> {
+        &self.cache
+    }
+
+    /// Get indirection table buffer.
+    pub fn indirection(&self) -> &Buffer<B> {
+        &self.indirection
+    }
+
+    /// Get width and height of the page in texels.
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// Get width and height of the virtual texture in pages.
+    pub fn pages(&self) -> (u32, u32) {
+        self.pages
+    }
+
+    /// Get number of resident pages.
+    pub fn resident_pages(&self) -> usize {
+        self.resident.len()
+    }
+
+    /// Check if page at (`x`, `y`) is resident.
+    pub fn is_resident(&self, x: u32, y: u32) -> bool {
+        self.resident.contains_key(&(y * self.pages.0 + x))
+    }
+
+    /// Mark page at (`x`, `y`) as used by the `frame`.
+    /// Pages that are not resident are queued for streaming.
+    pub fn request(&mut self, x: u32, y: u32, frame: u64) {
+        assert!(x < self.pages.0 && y < self.pages.1);
+        let page = y * self.pages.0 + x;
+        match self.resident.get(&page) {
+            Some(&slot) => self.slots[slot as usize].used = frame,
+            None => {
+                if self.requested.insert(page) {
+                    self.requests.push_back(page);
+                }
+            }
+        }
+    }
+
+    /// Stream in up to `max_pages` requested pages in order of requests.
+    /// Evicts least recently used pages not used by the `frame`.
+    /// Returns number of pages streamed in.
+    pub fn update<S>(
+        &mut self,
+        factory: &mut Factory<B>,
+        source: &mut S,
+        frame: u64,
+        max_pages: usize,
+    ) -> Result<usize, Error>
+    where
+        S: PageSource,
+    {
+        let priority = factory.set_upload_priority(UploadPriority::Background);
+        let result = self.stream(factory, source, frame, max_pages);
+        factory.set_upload_priority(priority);
+        result
+    }
+
+    fn stream<S>(
+        &mut self,
+        factory: &mut Factory<B>,
+        source: &mut S,
+        frame: u64,
+        max_pages: usize,
+    ) -> Result<usize, Error>
+    where
+        S: PageSource,
+    {
+        let mut streamed = 0;
+        while streamed < max_pages {
+            let slot = match self.pick_slot(frame) {
+                Some(slot) => slot,
+                None => break,
+            };
+            let page = match self.requests.pop_front() {
+                Some(page) => page,
+                None => break,
+            };
+            self.requested.remove(&page);
+
+            self.data.clear();
+            source.read_page(page % self.pages.0, page / self.pages.0, &mut self.data)?;
+            let expected = (self.page_size * self.page_size * self.texel_size) as usize;
+            if self.data.len() != expected {
+                return Err(Error::SizeMismatch {
+                    what: "page data bytes",
+                    expected: expected as u64,
+                    actual: self.data.len() as u64,
+                });
+            }
+
+            if let Some(evicted) = self.slots[slot as usize].page.take() {
+                self.resident.remove(&evicted);
+                self.table[evicted as usize] = PAGE_NOT_RESIDENT;
+            }
+
+            let (x, y) = (slot % self.cache_pages, slot / self.cache_pages);
+            factory.upload_texture_region(
+                &mut self.cache,
+                0..1,
+                Offset {
+                    x: (x * self.page_size) as i32,
+                    y: (y * self.page_size) as i32,
+                    z: 0,
+                },
+                Extent {
+                    width: self.page_size,
+                    height: self.page_size,
+                    depth: 1,
+                },
+                &self.data,
+            )?;
+
+            self.slots[slot as usize] = Slot {
+                page: Some(page),
+                used: frame,
+            };
+            self.resident.insert(page, slot);
+            self.table[page as usize] = x | y << 16;
+            streamed += 1;
+        }
+
+        if streamed > 0 {
+            factory.upload_buffer(&mut self.indirection, 0, &table_bytes(&self.table))?;
+        }
+        Ok(streamed)
+    }
+
+    /// Find free slot or least recently used one not used by the `frame`.
+    fn pick_slot(&self, frame: u64) -> Option<u32> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|&(_, slot)| slot.page.is_none() || slot.used < frame)
+            .min_by_key(|&(_, slot)| slot.page.map(|_| slot.used + 1).unwrap_or(0))
+            .map(|(index, _)| index as u32)
+    }
+
+    /// Destroy page cache and indirection table.
+    /// Factory will destroy them after all commands referencing them will complete.
+    pub fn dispose(self, factory: &mut Factory<B>) {
+        factory.destroy_texture(self.cache);
+        factory.destroy_buffer(self.indirection);
+    }
+}
+
+fn table_bytes(table: &[u32]) -> Vec<u8> {
+    table
+        .iter()
+        .flat_map(|&entry| {
+            vec![
+                entry as u8,
+                (entry >> 8) as u8,
+                (entry >> 16) as u8,
+                (entry >> 24) as u8,
+            ]
+        })
+        .collect()
+}
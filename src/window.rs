@@ -0,0 +1,158 @@
+//! Helpers for rendering into `winit` windows.
+//! `WindowTarget` keeps target of the `Renderer` in sync with the window:
+//! events changing size or hi-DPI factor of the window replace surface of the target.
+//!
+
+use hal::format::Format;
+use hal::window::SwapchainConfig;
+use winit::{Event, Window, WindowEvent};
+
+use Error;
+use backend::BackendEx;
+use factory::Factory;
+use init::{init_with_config, Config};
+use renderer::{Renderer, TargetId};
+use surface::SurfaceFormat;
+
+/// Init chosen backend, create `Factory` and `Renderer` and add target drawing into the window.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut events = EventsLoop::new();
+/// let window = WindowBuilder::new().build(&events)?;
+/// let (mut factory, mut renderer, mut target) = init_window::<B, _>(&window, Config::default())?;
+/// // `ClearScreen` implements `Render`.
+/// renderer.add_render(target.id(), ClearScreen::new([0.0, 0.0, 0.0, 1.0]))?;
+/// let mut running = true;
+/// while running {
+///     events.poll_events(|event| {
+///         if let Event::WindowEvent { event: WindowEvent::Closed, .. } = event {
+///             running = false;
+///         }
+///         target.handle_event(&event, &window, &mut factory, &mut renderer).unwrap();
+///     });
+///     renderer.run(&mut (), &mut factory)?;
+/// }
+/// ```
+pub fn init_window<B, R>(window: &Window, config: Config) -> Result<(Factory<B>, Renderer<B, R>, WindowTarget), Error>
+where
+    B: BackendEx,
+    R: Send + Sync + 'static,
+{
+    let (mut factory, mut renderer) = init_with_config(config)?;
+    let target = WindowTarget::new(window, &mut factory, &mut renderer)?;
+    Ok((factory, renderer, target))
+}
+
+/// Target of the `Renderer` drawing into `winit` window.
+#[derive(Clone, Copy, Debug)]
+pub struct WindowTarget {
+    id: TargetId,
+    format: SurfaceFormat,
+    size: (u32, u32),
+    hidpi_factor: f32,
+}
+
+impl WindowTarget {
+    /// Create surface for the window and add target presenting to it.
+    /// sRGB formats are preferred for swapchain images.
+    pub fn new<B, R>(window: &Window, factory: &mut Factory<B>, renderer: &mut Renderer<B, R>) -> Result<Self, Error>
+    where
+        B: BackendEx,
+    {
+        let surface = factory.create_surface(window);
+        let format = factory
+            .pick_surface_format(
+                &surface,
+                &[
+                    SurfaceFormat::srgb(Format::Bgra8Srgb),
+                    SurfaceFormat::srgb(Format::Rgba8Srgb),
+                ],
+            )
+            .ok_or(Error::NoSupportedFormat(vec![Format::Bgra8Srgb, Format::Rgba8Srgb]))?;
+        let config = SwapchainConfig::new().with_color(format.format);
+        let id = renderer.add_target(surface, config, factory);
+        Ok(WindowTarget {
+            id,
+            format,
+            size: inner_size(window),
+            hidpi_factor: window.hidpi_factor(),
+        })
+    }
+
+    /// Get id of the target in the `Renderer`.
+    pub fn id(&self) -> TargetId {
+        self.id
+    }
+
+    /// Get format of swapchain images.
+    pub fn format(&self) -> SurfaceFormat {
+        self.format
+    }
+
+    /// Get size of the window in physical pixels as of the last handled event.
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Get hi-DPI factor of the window as of the last handled event.
+    pub fn hidpi_factor(&self) -> f32 {
+        self.hidpi_factor
+    }
+
+    /// Get size of the window in logical pixels.
+    /// Layouts specified in logical pixels look the same on screens with different density.
+    pub fn logical_size(&self) -> (f32, f32) {
+        (
+            self.size.0 as f32 / self.hidpi_factor,
+            self.size.1 as f32 / self.hidpi_factor,
+        )
+    }
+
+    /// Handle event of the events loop.
+    /// If the window is resized or moved to screen with different hi-DPI factor,
+    /// surface of the target is replaced and swapchain is recreated at the beginning of the next frame.
+    /// Minimized window keeps old surface.
+    /// Returns `true` if surface is replaced.
+    pub fn handle_event<B, R>(
+        &mut self,
+        event: &Event,
+        window: &Window,
+        factory: &mut Factory<B>,
+        renderer: &mut Renderer<B, R>,
+    ) -> Result<bool, Error>
+    where
+        B: BackendEx,
+    {
+        match *event {
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(..),
+            }
+            | Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Moved(..),
+            } if window_id == window.id() => {}
+            _ => return Ok(false),
+        }
+        let size = inner_size(window);
+        let hidpi_factor = window.hidpi_factor();
+        if size == self.size && hidpi_factor == self.hidpi_factor {
+            return Ok(false);
+        }
+        self.size = size;
+        self.hidpi_factor = hidpi_factor;
+        if size.0 == 0 || size.1 == 0 {
+            return Ok(false);
+        }
+        debug!("Window resized to {:?} with hi-DPI factor {}", size, hidpi_factor);
+        renderer.replace_surface(self.id, factory.create_surface(window))?;
+        Ok(true)
+    }
+}
+
+/// Get size of the window in physical pixels. Closed window has zero size.
+fn inner_size(window: &Window) -> (u32, u32) {
+    window.get_inner_size().unwrap_or((0, 0))
+}